@@ -82,9 +82,7 @@ impl HttpClient {
             received.extend_from_slice(&buf[..bytes_read]);
         }
 
-        match core::str::from_utf8(&received) {
-            Ok(response) => HttpResponse::new(response.to_string()),
-            Err(e) => Err(Error::Network(format!("Invalid received response: {}", e))),
-        }
+        // ボディの文字コードはレンダラで判定するため、バイト列のまま渡す
+        HttpResponse::from_bytes(received)
     }
 }
@@ -1,5 +1,11 @@
+use crate::renderer::css::cascade::Origin;
+use crate::renderer::css::cssom::CssParser;
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::css::user_agent::user_agent_style_sheet;
 use crate::renderer::page::Page;
 use alloc::rc::Rc;
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 
@@ -7,15 +13,24 @@ use core::cell::RefCell;
 pub struct Browser {
     active_page_index: usize,
     pages: Vec<Rc<RefCell<Page>>>,
+    /// これまでに開いたページのURL。リンクが訪問済みかどうかの判定に使う
+    visited_urls: Vec<String>,
+    /// ユーザーが指定したスタイルシート。作者のスタイルシートより優先度は低いが、
+    /// 重要な宣言は作者のものより優先される
+    user_style_sheet: Option<Rc<StyleSheet>>,
 }
 
 impl Browser {
     pub fn new() -> Rc<RefCell<Self>> {
-        let mut page = Page::new();
+        // ユーザーエージェントのスタイルシートは起動時に一度だけパースし、
+        // すべてのページで共有する
+        let mut page = Page::new(Rc::new(user_agent_style_sheet()));
 
         let browser = Rc::new(RefCell::new(Self {
             active_page_index: 0,
             pages: Vec::new(),
+            visited_urls: Vec::new(),
+            user_style_sheet: None,
         }));
 
         page.set_browser(Rc::downgrade(&browser));
@@ -27,4 +42,29 @@ impl Browser {
     pub fn current_page(&self) -> Rc<RefCell<Page>> {
         self.pages[self.active_page_index].clone()
     }
+
+    /// `url`を訪問済みとして記録する
+    pub fn add_visited_url(&mut self, url: String) {
+        if !self.is_visited(&url) {
+            self.visited_urls.push(url);
+        }
+    }
+
+    /// https://html.spec.whatwg.org/multipage/semantics-other.html#selector-visited
+    pub fn is_visited(&self, url: &str) -> bool {
+        self.visited_urls.iter().any(|u| u == url)
+    }
+
+    /// ユーザーのスタイルシートを設定する。次にページを描画するときから適用される。
+    /// 表示中のページに反映するには`Page::restyle`を呼ぶ
+    /// https://drafts.csswg.org/css-cascade/#cascade-origin-user
+    pub fn set_user_style_sheet(&mut self, css: String) {
+        let mut sheet = CssParser::new(CssTokenizer::new(css)).parse_stylesheet();
+        sheet.set_origin(Origin::User);
+        self.user_style_sheet = Some(Rc::new(sheet));
+    }
+
+    pub fn user_style_sheet(&self) -> Option<Rc<StyleSheet>> {
+        self.user_style_sheet.clone()
+    }
 }
@@ -3,6 +3,8 @@ pub static LIGHTGREY: u32 = 0xd3d3d3;
 pub static GREY: u32 = 0x808080;
 pub static DARKGREY: u32 = 0x5a5a5a;
 pub static BLACK: u32 = 0x000000;
+pub static BLUE: u32 = 0x0000ff;
+pub static LIGHTBLUE: u32 = 0xadd8e6;
 
 pub static ADDRESSBAR_HEIGHT: i64 = 20;
 
@@ -16,7 +18,11 @@ pub static WINDOW_PADDING: i64 = 5;
 // noliライブラリに定義されている定数
 pub static TITLE_BAR_HEIGHT: i64 = 24;
 
-pub static TOOLBAR_HEIGHT: i64 = 26;
+// ページのタイトルを表示するタブの高さ
+pub static TAB_HEIGHT: i64 = 20;
+pub static TAB_WIDTH: i64 = 200;
+
+pub static TOOLBAR_HEIGHT: i64 = TAB_HEIGHT + 26;
 
 pub static CONTENT_AREA_WIDTH: i64 = WINDOW_WIDTH - WINDOW_PADDING * 2;
 pub static CONTENT_AREA_HEIGHT: i64 =
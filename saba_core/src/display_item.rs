@@ -1,7 +1,10 @@
+use crate::constants::{CHAR_HEIGHT_WITH_PADDING, CHAR_WIDTH};
+use crate::image::Image;
 use crate::renderer::layout::{
-    computed_style::ComputedStyle,
+    computed_style::{ComputedStyle, FontSize},
     layout_object::{LayoutPoint, LayoutSize},
 };
+use alloc::rc::Rc;
 use alloc::string::String;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -16,4 +19,153 @@ pub enum DisplayItem {
         style: ComputedStyle,
         layout_point: LayoutPoint,
     },
+    Image {
+        image: Rc<Image>,
+        style: ComputedStyle,
+        layout_point: LayoutPoint,
+        layout_size: LayoutSize,
+    },
+    /// フォーカスされている要素の周りに描く枠線
+    FocusRing {
+        layout_point: LayoutPoint,
+        layout_size: LayoutSize,
+    },
+    /// 選択されているテキストの背景
+    Highlight {
+        layout_point: LayoutPoint,
+        layout_size: LayoutSize,
+    },
+}
+
+impl DisplayItem {
+    /// 入れ子の文書の表示項目を、親の文書の`offset`の位置に移動し、`size`の範囲の
+    /// 外側を切り取る。範囲の中に描画するものがない場合はNoneを返す
+    pub fn clip(&self, offset: LayoutPoint, size: LayoutSize) -> Option<DisplayItem> {
+        let translate = |x: i64, y: i64| LayoutPoint::new(offset.x() + x, offset.y() + y);
+
+        match self {
+            DisplayItem::Rect {
+                style,
+                layout_point,
+                layout_size,
+            } => {
+                let (x0, y0, x1, y1) = intersect(*layout_point, *layout_size, size)?;
+                Some(DisplayItem::Rect {
+                    style: style.clone(),
+                    layout_point: translate(x0, y0),
+                    layout_size: LayoutSize::new(x1 - x0, y1 - y0),
+                })
+            }
+            DisplayItem::Text {
+                text,
+                style,
+                layout_point,
+            } => {
+                let ratio = match style.font_size() {
+                    FontSize::Medium => 1,
+                    FontSize::XLarge => 2,
+                    FontSize::XXLarge => 3,
+                };
+                let char_width = CHAR_WIDTH * ratio;
+
+                // 文字の一部だけを描画することはできないので、はみ出す行や文字は
+                // 描画しない
+                let y = layout_point.y();
+                if y < 0 || y + CHAR_HEIGHT_WITH_PADDING * ratio > size.height() {
+                    return None;
+                }
+                let mut x = layout_point.x();
+                let mut first_x = None;
+                let mut visible = String::new();
+                for c in text.chars() {
+                    if x >= 0 && x + char_width <= size.width() {
+                        first_x.get_or_insert(x);
+                        visible.push(c);
+                    }
+                    x += char_width;
+                }
+
+                Some(DisplayItem::Text {
+                    text: visible,
+                    style: style.clone(),
+                    layout_point: translate(first_x?, y),
+                })
+            }
+            DisplayItem::Image {
+                image,
+                style,
+                layout_point,
+                layout_size,
+            } => {
+                let (x0, y0, x1, y1) = intersect(*layout_point, *layout_size, size)?;
+                if x1 - x0 == layout_size.width() && y1 - y0 == layout_size.height() {
+                    return Some(DisplayItem::Image {
+                        image: image.clone(),
+                        style: style.clone(),
+                        layout_point: translate(x0, y0),
+                        layout_size: *layout_size,
+                    });
+                }
+
+                // はみ出す場合は、見えている部分を描画する大きさで切り出す
+                let mut clipped = Image::new((x1 - x0) as usize, (y1 - y0) as usize);
+                for y in y0..y1 {
+                    let image_y =
+                        (y - layout_point.y()) * image.height() as i64 / layout_size.height();
+                    for x in x0..x1 {
+                        let image_x =
+                            (x - layout_point.x()) * image.width() as i64 / layout_size.width();
+                        clipped.set_pixel(
+                            (x - x0) as usize,
+                            (y - y0) as usize,
+                            image.pixel(image_x as usize, image_y as usize),
+                        );
+                    }
+                }
+                Some(DisplayItem::Image {
+                    image: Rc::new(clipped),
+                    style: style.clone(),
+                    layout_point: translate(x0, y0),
+                    layout_size: LayoutSize::new(x1 - x0, y1 - y0),
+                })
+            }
+            DisplayItem::FocusRing {
+                layout_point,
+                layout_size,
+            } => {
+                let (x0, y0, x1, y1) = intersect(*layout_point, *layout_size, size)?;
+                Some(DisplayItem::FocusRing {
+                    layout_point: translate(x0, y0),
+                    layout_size: LayoutSize::new(x1 - x0, y1 - y0),
+                })
+            }
+            DisplayItem::Highlight {
+                layout_point,
+                layout_size,
+            } => {
+                let (x0, y0, x1, y1) = intersect(*layout_point, *layout_size, size)?;
+                Some(DisplayItem::Highlight {
+                    layout_point: translate(x0, y0),
+                    layout_size: LayoutSize::new(x1 - x0, y1 - y0),
+                })
+            }
+        }
+    }
+}
+
+/// `point`と`size`で表される矩形と、原点から`clip`の範囲の矩形が重なる部分を
+/// (左, 上, 右, 下) で返す
+fn intersect(
+    point: LayoutPoint,
+    size: LayoutSize,
+    clip: LayoutSize,
+) -> Option<(i64, i64, i64, i64)> {
+    let x0 = point.x().max(0);
+    let y0 = point.y().max(0);
+    let x1 = (point.x() + size.width()).min(clip.width());
+    let y1 = (point.y() + size.height()).min(clip.height());
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+    Some((x0, y0, x1, y1))
 }
@@ -0,0 +1,399 @@
+use crate::encoding::jis0208::INDEX_JIS0208;
+use alloc::string::String;
+
+/// デコードに失敗したバイト列の代わりに出力する文字
+const REPLACEMENT_CHARACTER: char = '\u{FFFD}';
+
+/// https://encoding.spec.whatwg.org/#utf-8-decoder
+pub fn decode_utf8(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// https://encoding.spec.whatwg.org/#shared-utf-16-decoder
+pub fn decode_utf16(bytes: &[u8], is_big_endian: bool) -> String {
+    let units = bytes.chunks(2).map(|pair| {
+        if pair.len() < 2 {
+            // 奇数個のバイトが残った場合はエラーとして扱う
+            return None;
+        }
+        if is_big_endian {
+            Some(u16::from_be_bytes([pair[0], pair[1]]))
+        } else {
+            Some(u16::from_le_bytes([pair[0], pair[1]]))
+        }
+    });
+
+    let mut result = String::new();
+    let mut lead_surrogate: Option<u16> = None;
+    for unit in units {
+        let unit = match unit {
+            Some(u) => u,
+            None => {
+                result.push(REPLACEMENT_CHARACTER);
+                continue;
+            }
+        };
+
+        if let Some(lead) = lead_surrogate.take() {
+            if (0xDC00..=0xDFFF).contains(&unit) {
+                let c = 0x10000 + ((lead as u32 - 0xD800) << 10) + (unit as u32 - 0xDC00);
+                result.push(char::from_u32(c).unwrap_or(REPLACEMENT_CHARACTER));
+                continue;
+            }
+            // 後続のサロゲートがない場合はエラー。現在のコードユニットは
+            // 通常どおり処理する
+            result.push(REPLACEMENT_CHARACTER);
+        }
+
+        match unit {
+            0xD800..=0xDBFF => lead_surrogate = Some(unit),
+            0xDC00..=0xDFFF => result.push(REPLACEMENT_CHARACTER),
+            _ => result.push(char::from_u32(unit as u32).unwrap_or(REPLACEMENT_CHARACTER)),
+        }
+    }
+
+    if lead_surrogate.is_some() {
+        result.push(REPLACEMENT_CHARACTER);
+    }
+
+    result
+}
+
+/// 0x80から0x9Fまでのバイトに対応するコードポイント。それ以外のバイトは
+/// ISO-8859-1と同じく、バイトの値がそのままコードポイントになる
+/// https://encoding.spec.whatwg.org/index-windows-1252.txt
+static WINDOWS_1252_HIGH: [u16; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x008D, 0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+/// https://encoding.spec.whatwg.org/#single-byte-decoder
+pub fn decode_windows_1252(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    for b in bytes {
+        let code_point = match b {
+            0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize] as u32,
+            _ => *b as u32,
+        };
+        result.push(char::from_u32(code_point).unwrap_or(REPLACEMENT_CHARACTER));
+    }
+    result
+}
+
+/// https://encoding.spec.whatwg.org/#index-code-point
+fn jis0208_code_point(pointer: usize) -> Option<char> {
+    match INDEX_JIS0208.get(pointer) {
+        Some(0) | None => None,
+        Some(c) => char::from_u32(*c as u32),
+    }
+}
+
+/// https://encoding.spec.whatwg.org/#shift_jis-decoder
+pub fn decode_shift_jis(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    let mut lead: u8 = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let byte = bytes[i];
+        i += 1;
+
+        if lead != 0 {
+            let offset = if byte < 0x7F { 0x40 } else { 0x41 };
+            let lead_offset = if lead < 0xA0 { 0x81 } else { 0xC1 };
+            let l = lead;
+            lead = 0;
+
+            if matches!(byte, 0x40..=0x7E | 0x80..=0xFC) {
+                let pointer = (l - lead_offset) as usize * 188 + (byte - offset) as usize;
+                // 外字の領域は私用領域のコードポイントに対応させる
+                if (8836..=10715).contains(&pointer) {
+                    result.push(
+                        char::from_u32(0xE000 + pointer as u32 - 8836)
+                            .unwrap_or(REPLACEMENT_CHARACTER),
+                    );
+                    continue;
+                }
+                if let Some(c) = jis0208_code_point(pointer) {
+                    result.push(c);
+                    continue;
+                }
+            }
+
+            // ASCIIのバイトは、次の文字として読み直す
+            if byte.is_ascii() {
+                i -= 1;
+            }
+            result.push(REPLACEMENT_CHARACTER);
+            continue;
+        }
+
+        match byte {
+            0x00..=0x80 => result.push(byte as char),
+            // 半角カタカナ
+            0xA1..=0xDF => result
+                .push(char::from_u32(0xFF61 - 0xA1 + byte as u32).unwrap_or(REPLACEMENT_CHARACTER)),
+            0x81..=0x9F | 0xE0..=0xFC => lead = byte,
+            _ => result.push(REPLACEMENT_CHARACTER),
+        }
+    }
+
+    if lead != 0 {
+        result.push(REPLACEMENT_CHARACTER);
+    }
+
+    result
+}
+
+/// https://encoding.spec.whatwg.org/#euc-jp-decoder
+/// 本書では補助漢字 (JIS X 0212) の表を持たないため、0x8Fから始まる文字は
+/// デコードに失敗したものとして扱う
+pub fn decode_euc_jp(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    let mut lead: u8 = 0;
+    let mut jis0212 = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let byte = bytes[i];
+        i += 1;
+
+        if lead == 0x8E && (0xA1..=0xDF).contains(&byte) {
+            lead = 0;
+            result
+                .push(char::from_u32(0xFF61 - 0xA1 + byte as u32).unwrap_or(REPLACEMENT_CHARACTER));
+            continue;
+        }
+
+        if lead == 0x8F && (0xA1..=0xFE).contains(&byte) {
+            jis0212 = true;
+            lead = byte;
+            continue;
+        }
+
+        if lead != 0 {
+            let l = lead;
+            lead = 0;
+            let is_jis0212 = jis0212;
+            jis0212 = false;
+
+            if (0xA1..=0xFE).contains(&l) && (0xA1..=0xFE).contains(&byte) && !is_jis0212 {
+                let pointer = (l - 0xA1) as usize * 94 + (byte - 0xA1) as usize;
+                if let Some(c) = jis0208_code_point(pointer) {
+                    result.push(c);
+                    continue;
+                }
+            }
+
+            if byte.is_ascii() {
+                i -= 1;
+            }
+            result.push(REPLACEMENT_CHARACTER);
+            continue;
+        }
+
+        match byte {
+            0x00..=0x7F => result.push(byte as char),
+            0x8E | 0x8F | 0xA1..=0xFE => lead = byte,
+            _ => result.push(REPLACEMENT_CHARACTER),
+        }
+    }
+
+    if lead != 0 {
+        result.push(REPLACEMENT_CHARACTER);
+    }
+
+    result
+}
+
+/// https://encoding.spec.whatwg.org/#iso-2022-jp-decoder-state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Iso2022JpState {
+    Ascii,
+    Roman,
+    Katakana,
+    LeadByte,
+    TrailByte,
+    EscapeStart,
+    Escape,
+}
+
+/// https://encoding.spec.whatwg.org/#iso-2022-jp-decoder
+pub fn decode_iso_2022_jp(bytes: &[u8]) -> String {
+    let mut result = String::new();
+    let mut state = Iso2022JpState::Ascii;
+    let mut output_state = Iso2022JpState::Ascii;
+    let mut lead: u8 = 0;
+    let mut output = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let byte = bytes[i];
+        i += 1;
+
+        match state {
+            Iso2022JpState::Ascii | Iso2022JpState::Roman => {
+                if byte == 0x1B {
+                    state = Iso2022JpState::EscapeStart;
+                    continue;
+                }
+                output = false;
+                match byte {
+                    0x0E | 0x0F | 0x80..=0xFF => result.push(REPLACEMENT_CHARACTER),
+                    // JIS X 0201 ローマ字では、円記号とオーバーラインが異なる
+                    0x5C if state == Iso2022JpState::Roman => result.push('\u{00A5}'),
+                    0x7E if state == Iso2022JpState::Roman => result.push('\u{203E}'),
+                    _ => result.push(byte as char),
+                }
+            }
+            Iso2022JpState::Katakana => {
+                if byte == 0x1B {
+                    state = Iso2022JpState::EscapeStart;
+                    continue;
+                }
+                output = false;
+                match byte {
+                    0x21..=0x5F => result.push(
+                        char::from_u32(0xFF61 - 0x21 + byte as u32)
+                            .unwrap_or(REPLACEMENT_CHARACTER),
+                    ),
+                    _ => result.push(REPLACEMENT_CHARACTER),
+                }
+            }
+            Iso2022JpState::LeadByte => {
+                if byte == 0x1B {
+                    state = Iso2022JpState::EscapeStart;
+                    continue;
+                }
+                output = false;
+                match byte {
+                    0x21..=0x7E => {
+                        lead = byte;
+                        state = Iso2022JpState::TrailByte;
+                    }
+                    _ => result.push(REPLACEMENT_CHARACTER),
+                }
+            }
+            Iso2022JpState::TrailByte => {
+                if byte == 0x1B {
+                    state = Iso2022JpState::EscapeStart;
+                    result.push(REPLACEMENT_CHARACTER);
+                    continue;
+                }
+                state = Iso2022JpState::LeadByte;
+                match byte {
+                    0x21..=0x7E => {
+                        let pointer = (lead - 0x21) as usize * 94 + (byte - 0x21) as usize;
+                        result.push(jis0208_code_point(pointer).unwrap_or(REPLACEMENT_CHARACTER));
+                    }
+                    _ => result.push(REPLACEMENT_CHARACTER),
+                }
+            }
+            Iso2022JpState::EscapeStart => {
+                if byte == 0x24 || byte == 0x28 {
+                    lead = byte;
+                    state = Iso2022JpState::Escape;
+                    continue;
+                }
+                // バイトを読み直す
+                i -= 1;
+                output = false;
+                state = output_state;
+                result.push(REPLACEMENT_CHARACTER);
+            }
+            Iso2022JpState::Escape => {
+                let l = lead;
+                lead = 0;
+                let next_state = match (l, byte) {
+                    (0x28, 0x42) => Some(Iso2022JpState::Ascii),
+                    (0x28, 0x4A) => Some(Iso2022JpState::Roman),
+                    (0x28, 0x49) => Some(Iso2022JpState::Katakana),
+                    (0x24, 0x40) | (0x24, 0x42) => Some(Iso2022JpState::LeadByte),
+                    _ => None,
+                };
+
+                match next_state {
+                    Some(s) => {
+                        state = s;
+                        output_state = s;
+                        // 文字を出力しないまま、エスケープシーケンスが続いた場合はエラー
+                        let previous_output = output;
+                        output = true;
+                        if previous_output {
+                            result.push(REPLACEMENT_CHARACTER);
+                        }
+                    }
+                    None => {
+                        // leadとbyteを読み直す
+                        i -= 2;
+                        output = false;
+                        state = output_state;
+                        result.push(REPLACEMENT_CHARACTER);
+                    }
+                }
+            }
+        }
+    }
+
+    // 入力の終わりで、読みかけの文字がある場合はエラー
+    match state {
+        Iso2022JpState::TrailByte | Iso2022JpState::EscapeStart | Iso2022JpState::Escape => {
+            result.push(REPLACEMENT_CHARACTER)
+        }
+        _ => {}
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shift_jis() {
+        // "あいう ABC ｱ"
+        let bytes = [
+            0x82, 0xA0, 0x82, 0xA2, 0x82, 0xA4, 0x20, 0x41, 0x42, 0x43, 0x20, 0xB1,
+        ];
+        assert_eq!("あいう ABC ｱ", decode_shift_jis(&bytes));
+        // 2バイト目がASCIIの不正な文字は、ASCIIの文字として読み直す
+        assert_eq!("\u{FFFD}\"", decode_shift_jis(&[0x82, 0x22]));
+        assert_eq!("\u{FFFD}", decode_shift_jis(&[0x82]));
+        // 波ダッシュ
+        assert_eq!("\u{301C}", decode_shift_jis(&[0x81, 0x60]));
+    }
+
+    #[test]
+    fn test_euc_jp() {
+        // "日本語ｱ"
+        let bytes = [0xC6, 0xFC, 0xCB, 0xDC, 0xB8, 0xEC, 0x8E, 0xB1];
+        assert_eq!("日本語ｱ", decode_euc_jp(&bytes));
+    }
+
+    #[test]
+    fn test_iso_2022_jp() {
+        // "a日本b"
+        let bytes = [
+            0x61, 0x1B, 0x24, 0x42, 0x46, 0x7C, 0x4B, 0x5C, 0x1B, 0x28, 0x42, 0x62,
+        ];
+        assert_eq!("a日本b", decode_iso_2022_jp(&bytes));
+        // 終了していないエスケープシーケンス
+        assert_eq!("a\u{FFFD}", decode_iso_2022_jp(&[0x61, 0x1B]));
+    }
+
+    #[test]
+    fn test_windows_1252() {
+        assert_eq!("€é\u{81}", decode_windows_1252(&[0x80, 0xE9, 0x81]));
+    }
+
+    #[test]
+    fn test_utf16() {
+        assert_eq!(
+            "a😀",
+            decode_utf16(&[0x61, 0x00, 0x3D, 0xD8, 0x00, 0xDE], false)
+        );
+        assert_eq!("a\u{FFFD}", decode_utf16(&[0x00, 0x61, 0xD8, 0x3D], true));
+    }
+}
@@ -0,0 +1,100 @@
+pub mod decoder;
+pub mod jis0208;
+pub mod sniffer;
+
+use crate::encoding::decoder::decode_euc_jp;
+use crate::encoding::decoder::decode_iso_2022_jp;
+use crate::encoding::decoder::decode_shift_jis;
+use crate::encoding::decoder::decode_utf16;
+use crate::encoding::decoder::decode_utf8;
+use crate::encoding::decoder::decode_windows_1252;
+use alloc::string::String;
+use core::fmt::Display;
+use core::fmt::Formatter;
+
+/// https://encoding.spec.whatwg.org/#names-and-labels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Be,
+    Utf16Le,
+    ShiftJis,
+    EucJp,
+    Iso2022Jp,
+    Windows1252,
+}
+
+impl Encoding {
+    /// ラベルから文字コードを取得する。サポートしていないラベルの場合はNoneを返す
+    /// https://encoding.spec.whatwg.org/#concept-encoding-get
+    pub fn from_label(label: &str) -> Option<Self> {
+        let label = label
+            .trim_matches(|c: char| c.is_ascii_whitespace())
+            .to_ascii_lowercase();
+
+        match label.as_str() {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8"
+            | "x-unicode20utf8" => Some(Encoding::Utf8),
+            "unicodefffe" | "utf-16be" => Some(Encoding::Utf16Be),
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16"
+            | "utf-16le" => Some(Encoding::Utf16Le),
+            "csshiftjis" | "ms932" | "ms_kanji" | "shift-jis" | "shift_jis" | "sjis"
+            | "windows-31j" | "x-sjis" => Some(Encoding::ShiftJis),
+            "cseucpkdfmtjapanese" | "euc-jp" | "x-euc-jp" => Some(Encoding::EucJp),
+            "csiso2022jp" | "iso-2022-jp" => Some(Encoding::Iso2022Jp),
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819"
+            | "iso-8859-1" | "iso-ir-100" | "iso8859-1" | "iso88591" | "iso_8859-1"
+            | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii" | "windows-1252" | "x-cp1252" => {
+                Some(Encoding::Windows1252)
+            }
+            _ => None,
+        }
+    }
+
+    /// https://encoding.spec.whatwg.org/#concept-encoding-decode
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => decode_utf8(bytes),
+            Encoding::Utf16Be => decode_utf16(bytes, /*is_big_endian*/ true),
+            Encoding::Utf16Le => decode_utf16(bytes, /*is_big_endian*/ false),
+            Encoding::ShiftJis => decode_shift_jis(bytes),
+            Encoding::EucJp => decode_euc_jp(bytes),
+            Encoding::Iso2022Jp => decode_iso_2022_jp(bytes),
+            Encoding::Windows1252 => decode_windows_1252(bytes),
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        let s = match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::ShiftJis => "Shift_JIS",
+            Encoding::EucJp => "EUC-JP",
+            Encoding::Iso2022Jp => "ISO-2022-JP",
+            Encoding::Windows1252 => "windows-1252",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_label() {
+        assert_eq!(
+            Some(Encoding::ShiftJis),
+            Encoding::from_label(" Shift_JIS\n")
+        );
+        assert_eq!(Some(Encoding::Utf8), Encoding::from_label("utf8"));
+        assert_eq!(
+            Some(Encoding::Windows1252),
+            Encoding::from_label("ISO-8859-1")
+        );
+        assert_eq!(None, Encoding::from_label("unknown"));
+    }
+}
@@ -0,0 +1,348 @@
+use crate::encoding::Encoding;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::str;
+
+/// プリスキャンで読むバイト数の上限
+const PRESCAN_LIMIT: usize = 1024;
+
+/// どの方法でも文字コードが決まらなかった場合に使う文字コード。日本語の
+/// ロケールでの既定値に合わせる
+/// https://html.spec.whatwg.org/multipage/parsing.html#determining-the-character-encoding
+const DEFAULT_ENCODING: Encoding = Encoding::ShiftJis;
+
+/// バイト列の文字コードを判定し、文字列にデコードする。BOMは取り除く。
+/// `content_type`にはHTTPのContent-Typeヘッダの値を渡す
+pub fn decode_html(bytes: &[u8], content_type: Option<String>) -> String {
+    if let Some((encoding, bom_len)) = sniff_bom(bytes) {
+        return encoding.decode(&bytes[bom_len..]);
+    }
+
+    sniff_encoding(bytes, content_type).decode(bytes)
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#encoding-sniffing-algorithm
+pub fn sniff_encoding(bytes: &[u8], content_type: Option<String>) -> Encoding {
+    // 1. BOM
+    if let Some((encoding, _)) = sniff_bom(bytes) {
+        return encoding;
+    }
+
+    // 2. トランスポート層 (HTTPヘッダ) で指定された文字コード
+    if let Some(content_type) = content_type {
+        if let Some(encoding) = extract_encoding_from_meta(content_type.as_bytes()) {
+            return encoding;
+        }
+    }
+
+    // 3. <meta>要素のプリスキャン
+    let end = bytes.len().min(PRESCAN_LIMIT);
+    if let Some(encoding) = prescan(&bytes[..end]) {
+        return encoding;
+    }
+
+    // 4. 自動判定。UTF-8として正しいバイト列の場合は、UTF-8とみなす
+    if str::from_utf8(bytes).is_ok() {
+        return Encoding::Utf8;
+    }
+
+    // 5. 既定値
+    DEFAULT_ENCODING
+}
+
+/// BOMから文字コードとBOMのバイト数を求める
+/// https://encoding.spec.whatwg.org/#bom-sniff
+fn sniff_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return Some((Encoding::Utf8, 3));
+    }
+    if bytes.starts_with(&[0xFE, 0xFF]) {
+        return Some((Encoding::Utf16Be, 2));
+    }
+    if bytes.starts_with(&[0xFF, 0xFE]) {
+        return Some((Encoding::Utf16Le, 2));
+    }
+    None
+}
+
+fn is_space(b: u8) -> bool {
+    matches!(b, 0x09 | 0x0A | 0x0C | 0x0D | 0x20)
+}
+
+/// `bytes`の`pos`の位置が、大文字と小文字を区別せずに`pattern`で始まるかどうか
+fn starts_with_ignore_case(bytes: &[u8], pos: usize, pattern: &[u8]) -> bool {
+    match bytes.get(pos..pos + pattern.len()) {
+        Some(s) => s.eq_ignore_ascii_case(pattern),
+        None => false,
+    }
+}
+
+/// `pos`以降で最初に`pattern`が現れる位置を返す
+fn find(bytes: &[u8], pos: usize, pattern: &[u8]) -> Option<usize> {
+    (pos..bytes.len()).find(|&i| starts_with_ignore_case(bytes, i, pattern))
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding
+fn prescan(bytes: &[u8]) -> Option<Encoding> {
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        // コメントを読み飛ばす
+        if starts_with_ignore_case(bytes, pos, b"<!--") {
+            pos = find(bytes, pos + 2, b"-->")? + 3;
+            continue;
+        }
+
+        // <meta>要素
+        if starts_with_ignore_case(bytes, pos, b"<meta")
+            && bytes
+                .get(pos + 5)
+                .is_some_and(|b| is_space(*b) || *b == b'/')
+        {
+            pos += 5;
+            if let Some(encoding) = prescan_meta(bytes, &mut pos)? {
+                return Some(encoding);
+            }
+            continue;
+        }
+
+        // そのほかのタグは、属性を読み飛ばす
+        if bytes[pos] == b'<'
+            && (bytes.get(pos + 1).is_some_and(|b| b.is_ascii_alphabetic())
+                || (bytes.get(pos + 1) == Some(&b'/')
+                    && bytes.get(pos + 2).is_some_and(|b| b.is_ascii_alphabetic())))
+        {
+            while pos < bytes.len() && !is_space(bytes[pos]) && bytes[pos] != b'>' {
+                pos += 1;
+            }
+            while get_attribute(bytes, &mut pos)?.is_some() {}
+            pos += 1;
+            continue;
+        }
+
+        if starts_with_ignore_case(bytes, pos, b"<!")
+            || starts_with_ignore_case(bytes, pos, b"</")
+            || starts_with_ignore_case(bytes, pos, b"<?")
+        {
+            pos = find(bytes, pos + 1, b">")? + 1;
+            continue;
+        }
+
+        pos += 1;
+    }
+
+    None
+}
+
+/// <meta>要素の属性から文字コードを求める。入力の終わりに達した場合はNoneを返し、
+/// 文字コードが見つからなかった場合はSome(None)を返す
+fn prescan_meta(bytes: &[u8], pos: &mut usize) -> Option<Option<Encoding>> {
+    let mut attribute_names: Vec<Vec<u8>> = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma: Option<bool> = None;
+    let mut charset: Option<Encoding> = None;
+
+    while let Some((name, value)) = get_attribute(bytes, pos)? {
+        if attribute_names.contains(&name) {
+            continue;
+        }
+        attribute_names.push(name.clone());
+
+        match name.as_slice() {
+            b"http-equiv" => {
+                if value == b"content-type" {
+                    got_pragma = true;
+                }
+            }
+            b"content" => {
+                if charset.is_none() {
+                    if let Some(encoding) = extract_encoding_from_meta(&value) {
+                        charset = Some(encoding);
+                        need_pragma = Some(true);
+                    }
+                }
+            }
+            b"charset" => {
+                charset = str::from_utf8(&value).ok().and_then(Encoding::from_label);
+                need_pragma = Some(false);
+            }
+            _ => {}
+        }
+    }
+    *pos += 1;
+
+    match need_pragma {
+        None => return Some(None),
+        Some(true) if !got_pragma => return Some(None),
+        _ => {}
+    }
+
+    // <meta>で指定されたUTF-16は、実際にはASCII互換の文字コードであるため、
+    // UTF-8として扱う
+    Some(charset.map(|encoding| match encoding {
+        Encoding::Utf16Be | Encoding::Utf16Le => Encoding::Utf8,
+        e => e,
+    }))
+}
+
+/// 属性の名前と値を小文字にして返す。属性がない場合はSome(None)、入力の
+/// 終わりに達した場合はNoneを返す
+/// https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing
+#[allow(clippy::type_complexity)]
+fn get_attribute(bytes: &[u8], pos: &mut usize) -> Option<Option<(Vec<u8>, Vec<u8>)>> {
+    while is_space(*bytes.get(*pos)?) || bytes[*pos] == b'/' {
+        *pos += 1;
+    }
+
+    if bytes[*pos] == b'>' {
+        return Some(None);
+    }
+
+    let mut name = Vec::new();
+    let mut value = Vec::new();
+
+    // 属性の名前
+    loop {
+        let b = *bytes.get(*pos)?;
+        if b == b'=' && !name.is_empty() {
+            *pos += 1;
+            break;
+        }
+        if is_space(b) {
+            while is_space(*bytes.get(*pos)?) {
+                *pos += 1;
+            }
+            if bytes[*pos] != b'=' {
+                return Some(Some((name, value)));
+            }
+            *pos += 1;
+            break;
+        }
+        if b == b'/' || b == b'>' {
+            return Some(Some((name, value)));
+        }
+        name.push(b.to_ascii_lowercase());
+        *pos += 1;
+    }
+
+    // 属性の値
+    while is_space(*bytes.get(*pos)?) {
+        *pos += 1;
+    }
+
+    let b = bytes[*pos];
+    if b == b'"' || b == b'\'' {
+        loop {
+            *pos += 1;
+            let c = *bytes.get(*pos)?;
+            if c == b {
+                *pos += 1;
+                return Some(Some((name, value)));
+            }
+            value.push(c.to_ascii_lowercase());
+        }
+    }
+
+    if b == b'>' {
+        return Some(Some((name, value)));
+    }
+
+    loop {
+        let c = *bytes.get(*pos)?;
+        if is_space(c) || c == b'>' {
+            return Some(Some((name, value)));
+        }
+        value.push(c.to_ascii_lowercase());
+        *pos += 1;
+    }
+}
+
+/// "text/html; charset=Shift_JIS"のような文字列から文字コードを取り出す。
+/// HTTPのContent-Typeヘッダにも使う
+/// https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element
+fn extract_encoding_from_meta(content: &[u8]) -> Option<Encoding> {
+    let mut pos = 0;
+
+    loop {
+        pos = find(content, pos, b"charset")? + 7;
+        while content.get(pos).is_some_and(|b| is_space(*b)) {
+            pos += 1;
+        }
+        if content.get(pos) == Some(&b'=') {
+            pos += 1;
+            break;
+        }
+    }
+
+    while content.get(pos).is_some_and(|b| is_space(*b)) {
+        pos += 1;
+    }
+
+    let label = match content.get(pos)? {
+        q @ (b'"' | b'\'') => {
+            let start = pos + 1;
+            let end = (start..content.len()).find(|&i| content[i] == *q)?;
+            &content[start..end]
+        }
+        _ => {
+            let start = pos;
+            let end = (start..content.len())
+                .find(|&i| is_space(content[i]) || content[i] == b';')
+                .unwrap_or(content.len());
+            &content[start..end]
+        }
+    };
+
+    Encoding::from_label(str::from_utf8(label).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::string::ToString;
+
+    #[test]
+    fn test_bom() {
+        let bytes = [0xEF, 0xBB, 0xBF, 0x61];
+        assert_eq!(
+            Encoding::Utf8,
+            sniff_encoding(&bytes, Some("text/html; charset=Shift_JIS".to_string()))
+        );
+        assert_eq!("a", decode_html(&bytes, None));
+    }
+
+    #[test]
+    fn test_transport_charset() {
+        let html = b"<meta charset=\"euc-jp\">";
+        assert_eq!(
+            Encoding::ShiftJis,
+            sniff_encoding(html, Some("text/html; charset=\"Shift_JIS\"".to_string()))
+        );
+        assert_eq!(
+            Encoding::EucJp,
+            sniff_encoding(html, Some("text/html".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_prescan() {
+        let html = b"<!-- <meta charset=utf-8> --><html><head><title a='>'>t</title>\
+<META http-equiv=\"Content-Type\" content=\"text/html; charset=EUC-JP\"></head>";
+        assert_eq!(Encoding::EucJp, sniff_encoding(html, None));
+
+        // http-equivがない場合、content属性の文字コードは使わない
+        let html = b"<meta content=\"text/html; charset=EUC-JP\">";
+        assert_eq!(Encoding::Utf8, sniff_encoding(html, None));
+
+        let html = b"<meta charset=utf-16le>";
+        assert_eq!(Encoding::Utf8, sniff_encoding(html, None));
+    }
+
+    #[test]
+    fn test_fallback() {
+        // "<p>あ</p>" in Shift_JIS
+        let bytes = [0x3C, 0x70, 0x3E, 0x82, 0xA0, 0x3C, 0x2F, 0x70, 0x3E];
+        assert_eq!(Encoding::ShiftJis, sniff_encoding(&bytes, None));
+        assert_eq!("<p>あ</p>", decode_html(&bytes, None));
+    }
+}
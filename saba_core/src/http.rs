@@ -11,6 +11,7 @@ pub struct HttpResponse {
     reason: String,
     headers: Vec<Header>,
     body: String,
+    body_bytes: Vec<u8>,
 }
 
 #[derive(Debug, Clone)]
@@ -63,9 +64,44 @@ impl HttpResponse {
             reason: statuses[2].to_string(),
             headers,
             body: body.to_string(),
+            body_bytes: body.as_bytes().to_vec(),
         })
     }
 
+    /// 受信したバイト列からレスポンスを作成する。ヘッダはASCIIとして解釈し、
+    /// ボディはデコードせずにバイト列のまま保持する
+    pub fn from_bytes(raw_response: Vec<u8>) -> Result<Self, Error> {
+        // ヘッダとボディを区切る空行を探す
+        let mut body_start = None;
+        for i in 0..raw_response.len() {
+            if raw_response[i] != b'\n' {
+                continue;
+            }
+            if raw_response.get(i + 1) == Some(&b'\n') {
+                body_start = Some((i, i + 2));
+                break;
+            }
+            if raw_response.get(i + 1..i + 3) == Some(b"\r\n") {
+                body_start = Some((i, i + 3));
+                break;
+            }
+        }
+
+        let (head_end, body_start) = match body_start {
+            Some(b) => b,
+            None => return Self::new(String::from_utf8_lossy(&raw_response).into_owned()),
+        };
+
+        let mut head = String::from_utf8_lossy(&raw_response[..head_end]).into_owned();
+        head.push_str("\n\n");
+        let mut response = Self::new(head)?;
+
+        let body = &raw_response[body_start..];
+        response.body = String::from_utf8_lossy(body).into_owned();
+        response.body_bytes = body.to_vec();
+        Ok(response)
+    }
+
     pub fn version(&self) -> String {
         self.version.clone()
     }
@@ -86,9 +122,15 @@ impl HttpResponse {
         self.body.clone()
     }
 
+    /// デコードする前のボディ
+    pub fn body_bytes(&self) -> Vec<u8> {
+        self.body_bytes.clone()
+    }
+
     pub fn header_value(&self, name: &str) -> Result<String, String> {
+        // ヘッダの名前は大文字と小文字を区別しない
         for h in &self.headers {
-            if h.name == name {
+            if h.name.eq_ignore_ascii_case(name) {
                 return Ok(h.value.clone());
             }
         }
@@ -100,6 +142,7 @@ impl HttpResponse {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec;
     #[test]
     fn test_status_line_only() {
         let raw = "HTTP/1.1 200 OK\n\n".to_string();
@@ -145,6 +188,19 @@ mod tests {
         assert_eq!(res.body(), "body message".to_string());
     }
 
+    #[test]
+    fn test_from_bytes() {
+        let mut raw = b"HTTP/1.1 200 OK\r\ncontent-type: text/html\r\n\r\n".to_vec();
+        raw.extend_from_slice(&[0x82, 0xA0]);
+        let res = HttpResponse::from_bytes(raw).expect("failed to parse http response");
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(
+            res.header_value("Content-Type"),
+            Ok("text/html".to_string())
+        );
+        assert_eq!(res.body_bytes(), vec![0x82, 0xA0]);
+    }
+
     #[test]
     fn test_invalid() {
         let raw = "HTTP/1.1 200 OK".to_string();
@@ -0,0 +1,176 @@
+use crate::error::Error;
+use crate::image::argb;
+use crate::image::checked_pixel_count;
+use crate::image::Image;
+use alloc::format;
+use alloc::vec::Vec;
+
+fn invalid(message: &str) -> Error {
+    Error::UnexpectedInput(format!("invalid bmp: {}", message))
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Result<u16, Error> {
+    match data.get(pos..pos + 2) {
+        Some(b) => Ok(u16::from_le_bytes([b[0], b[1]])),
+        None => Err(invalid("unexpected end of data")),
+    }
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32, Error> {
+    match data.get(pos..pos + 4) {
+        Some(b) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(invalid("unexpected end of data")),
+    }
+}
+
+/// 圧縮されていない、1/4/8/24/32ビットのBMPをデコードする
+/// https://learn.microsoft.com/en-us/windows/win32/gdi/bitmap-storage
+pub fn decode_bmp(data: &[u8]) -> Result<Image, Error> {
+    if !data.starts_with(b"BM") {
+        return Err(invalid("wrong signature"));
+    }
+
+    // BITMAPFILEHEADER
+    let pixel_offset = read_u32(data, 10)? as usize;
+
+    // BITMAPINFOHEADER
+    let header_size = read_u32(data, 14)? as usize;
+    if header_size < 40 {
+        return Err(invalid("unsupported header"));
+    }
+    let width = read_u32(data, 18)? as i32;
+    let raw_height = read_u32(data, 22)? as i32;
+    let bit_count = read_u16(data, 28)?;
+    let compression = read_u32(data, 30)?;
+    let colors_used = read_u32(data, 46)? as usize;
+
+    // BI_RGBのみサポートする
+    if compression != 0 {
+        return Err(invalid("unsupported compression"));
+    }
+    if width < 0 {
+        return Err(invalid("negative width"));
+    }
+    let width = width as usize;
+    // 高さが負の場合は、上の行から順に並んでいる
+    let is_top_down = raw_height < 0;
+    let height = raw_height.unsigned_abs() as usize;
+    checked_pixel_count(width, height)?;
+
+    // カラーテーブルは (青, 緑, 赤, 予約) の4バイトずつ並ぶ
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    if bit_count <= 8 {
+        let count = if colors_used == 0 {
+            1 << bit_count
+        } else {
+            colors_used
+        };
+        let start = 14 + header_size;
+        palette = match data.get(start..start + count * 4) {
+            Some(t) => t.chunks_exact(4).map(|c| [c[2], c[1], c[0]]).collect(),
+            None => return Err(invalid("unexpected end of data")),
+        };
+    }
+
+    // 各行は4バイト境界に揃えられている
+    let stride = (width * bit_count as usize).div_ceil(32) * 4;
+    // 画像を確保する前に、ヘッダの大きさに見合うデータがあるかを確かめる
+    let end = stride
+        .checked_mul(height)
+        .and_then(|size| size.checked_add(pixel_offset));
+    if end.map_or(true, |end| end > data.len()) {
+        return Err(invalid("image data is too short"));
+    }
+    let mut image = Image::new(width, height);
+
+    for row in 0..height {
+        let start = pixel_offset + row * stride;
+        let line = match data.get(start..start + stride) {
+            Some(l) => l,
+            None => return Err(invalid("image data is too short")),
+        };
+        let y = if is_top_down { row } else { height - 1 - row };
+
+        for x in 0..width {
+            let pixel = match bit_count {
+                1 | 4 | 8 => {
+                    let bit = x * bit_count as usize;
+                    let shift = 8 - bit_count as usize - bit % 8;
+                    let index = (line[bit / 8] >> shift) & ((1u16 << bit_count) - 1) as u8;
+                    match palette.get(index as usize) {
+                        Some(c) => argb(255, c[0], c[1], c[2]),
+                        None => return Err(invalid("palette index out of range")),
+                    }
+                }
+                24 => argb(255, line[x * 3 + 2], line[x * 3 + 1], line[x * 3]),
+                // BI_RGBの32ビットでは、4バイト目は使われない
+                32 => argb(255, line[x * 4 + 2], line[x * 4 + 1], line[x * 4]),
+                _ => return Err(invalid("unsupported bit count")),
+            };
+            image.set_pixel(x, y, pixel);
+        }
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn create_bmp(
+        width: i32,
+        height: i32,
+        bit_count: u16,
+        palette: &[u8],
+        pixels: &[u8],
+    ) -> Vec<u8> {
+        let pixel_offset = 54 + palette.len() as u32;
+        let mut data = vec![b'B', b'M'];
+        data.extend_from_slice(&(pixel_offset + pixels.len() as u32).to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(&pixel_offset.to_le_bytes());
+        data.extend_from_slice(&40u32.to_le_bytes());
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&bit_count.to_le_bytes());
+        data.extend_from_slice(&[0; 24]);
+        data.extend_from_slice(palette);
+        data.extend_from_slice(pixels);
+        data
+    }
+
+    #[test]
+    fn test_bottom_up_24bit() {
+        // 2x2ピクセル。下の行から並び、各行は4バイト境界までパディングされる
+        let pixels = [
+            0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, //
+            0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00,
+        ];
+        let image = decode_bmp(&create_bmp(2, 2, 24, &[], &pixels)).expect("failed to decode");
+        assert_eq!(0xFFFF0000, image.pixel(0, 0));
+        assert_eq!(0xFFFFFFFF, image.pixel(1, 0));
+        assert_eq!(0xFF0000FF, image.pixel(0, 1));
+        assert_eq!(0xFF00FF00, image.pixel(1, 1));
+    }
+
+    #[test]
+    fn test_top_down_1bit() {
+        let palette = [0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x00];
+        let pixels = [0b1010_0000, 0, 0, 0, 0b0100_0000, 0, 0, 0];
+        let image = decode_bmp(&create_bmp(3, -2, 1, &palette, &pixels)).expect("failed to decode");
+        assert_eq!(0xFFFFFFFF, image.pixel(0, 0));
+        assert_eq!(0xFF000000, image.pixel(1, 0));
+        assert_eq!(0xFFFFFFFF, image.pixel(2, 0));
+        assert_eq!(0xFFFFFFFF, image.pixel(1, 1));
+    }
+
+    #[test]
+    fn test_oversized_header() {
+        // 画像を確保する前に、大きすぎるヘッダや足りないデータを拒否する
+        assert!(decode_bmp(&create_bmp(0x7FFFFFFF, 0x7FFFFFFF, 24, &[], &[])).is_err());
+        assert!(decode_bmp(&create_bmp(1000, 1000, 24, &[], &[0; 16])).is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use crate::error::Error;
+use crate::image::argb;
+use crate::image::checked_pixel_count;
+use crate::image::Image;
+use alloc::format;
+use alloc::vec::Vec;
+
+/// LZWの符号の最大ビット数
+const MAX_CODE_SIZE: usize = 12;
+
+fn invalid(message: &str) -> Error {
+    Error::UnexpectedInput(format!("invalid gif: {}", message))
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Result<u16, Error> {
+    match data.get(pos..pos + 2) {
+        Some(b) => Ok(u16::from_le_bytes([b[0], b[1]])),
+        None => Err(invalid("unexpected end of data")),
+    }
+}
+
+fn read_color_table(data: &[u8], pos: usize, size: usize) -> Result<Vec<[u8; 3]>, Error> {
+    match data.get(pos..pos + size * 3) {
+        Some(t) => Ok(t.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect()),
+        None => Err(invalid("unexpected end of data")),
+    }
+}
+
+/// サブブロックの並びを連結して返す。2つ目の値は読み終えた位置
+fn read_sub_blocks(data: &[u8], mut pos: usize) -> Result<(Vec<u8>, usize), Error> {
+    let mut result = Vec::new();
+    loop {
+        let size = match data.get(pos) {
+            Some(s) => *s as usize,
+            None => return Err(invalid("unexpected end of data")),
+        };
+        pos += 1;
+        if size == 0 {
+            return Ok((result, pos));
+        }
+        match data.get(pos..pos + size) {
+            Some(block) => result.extend_from_slice(block),
+            None => return Err(invalid("unexpected end of data")),
+        }
+        pos += size;
+    }
+}
+
+/// 可変長のLZW符号を展開して、カラーテーブルのインデックスの列を返す
+/// https://www.w3.org/Graphics/GIF/spec-gif89a.txt (Appendix F)
+fn decode_lzw(data: &[u8], min_code_size: usize, pixel_count: usize) -> Result<Vec<u8>, Error> {
+    if !(1..MAX_CODE_SIZE).contains(&min_code_size) {
+        return Err(invalid("wrong LZW minimum code size"));
+    }
+
+    let clear_code = 1 << min_code_size;
+    let end_code = clear_code + 1;
+
+    // 各符号は (1つ前の符号, 末尾の値) の組で表す。辞書の初期状態は単一の値の符号だけを持つ
+    let mut prefixes: Vec<Option<usize>> = (0..clear_code + 2).map(|_| None).collect();
+    let mut suffixes: Vec<u8> = (0..clear_code + 2).map(|c| c as u8).collect();
+    let mut code_size = min_code_size + 1;
+    let mut previous: Option<usize> = None;
+
+    let mut output = Vec::with_capacity(pixel_count);
+    let mut bit_pos = 0;
+    let mut stack = Vec::new();
+
+    while bit_pos + code_size <= data.len() * 8 {
+        let mut code = 0;
+        for i in 0..code_size {
+            let bit = (data[(bit_pos + i) / 8] >> ((bit_pos + i) % 8)) & 1;
+            code |= (bit as usize) << i;
+        }
+        bit_pos += code_size;
+
+        if code == clear_code {
+            prefixes.truncate(clear_code + 2);
+            suffixes.truncate(clear_code + 2);
+            code_size = min_code_size + 1;
+            previous = None;
+            continue;
+        }
+        if code == end_code {
+            break;
+        }
+
+        let previous_code = match previous {
+            Some(p) => p,
+            None => {
+                // クリア符号の直後は、単一の値の符号が来る
+                if code >= clear_code {
+                    return Err(invalid("wrong LZW code"));
+                }
+                output.push(code as u8);
+                previous = Some(code);
+                continue;
+            }
+        };
+
+        // 符号の展開結果の先頭の値を求めるため、末尾から辿ってスタックに積む
+        let known = code < prefixes.len();
+        if !known && code != prefixes.len() {
+            return Err(invalid("wrong LZW code"));
+        }
+        let mut current = Some(if known { code } else { previous_code });
+        stack.clear();
+        while let Some(c) = current {
+            stack.push(suffixes[c]);
+            current = prefixes[c];
+        }
+        let first = *stack.last().expect("stack has at least one value");
+        output.extend(stack.iter().rev());
+        if !known {
+            output.push(first);
+        }
+
+        if prefixes.len() < 1 << MAX_CODE_SIZE {
+            prefixes.push(Some(previous_code));
+            suffixes.push(first);
+            if prefixes.len() == 1 << code_size && code_size < MAX_CODE_SIZE {
+                code_size += 1;
+            }
+        }
+        previous = Some(code);
+
+        if output.len() >= pixel_count {
+            break;
+        }
+    }
+
+    Ok(output)
+}
+
+/// インターレースされた画像の、`row`番目に格納された行の実際の位置を返す
+fn interlaced_row(row: usize, height: usize) -> usize {
+    // 各パスの開始行と間隔
+    let passes = [(0, 8), (4, 8), (2, 4), (1, 2)];
+    let mut row = row;
+    for (start, step) in passes {
+        let rows = if height > start {
+            (height - start).div_ceil(step)
+        } else {
+            0
+        };
+        if row < rows {
+            return start + row * step;
+        }
+        row -= rows;
+    }
+    row
+}
+
+/// 最初のフレームだけをデコードする
+/// https://www.w3.org/Graphics/GIF/spec-gif89a.txt
+pub fn decode_gif(data: &[u8]) -> Result<Image, Error> {
+    if !data.starts_with(b"GIF87a") && !data.starts_with(b"GIF89a") {
+        return Err(invalid("wrong signature"));
+    }
+
+    // Logical Screen Descriptor
+    let width = read_u16(data, 6)? as usize;
+    let height = read_u16(data, 8)? as usize;
+    checked_pixel_count(width, height)?;
+    let flags = match data.get(10) {
+        Some(f) => *f,
+        None => return Err(invalid("unexpected end of data")),
+    };
+    let mut pos = 13;
+
+    let mut global_color_table = Vec::new();
+    if flags & 0x80 != 0 {
+        let size = 1 << ((flags & 0x07) + 1);
+        global_color_table = read_color_table(data, pos, size)?;
+        pos += size * 3;
+    }
+
+    let mut transparent_index: Option<u8> = None;
+
+    loop {
+        let introducer = match data.get(pos) {
+            Some(i) => *i,
+            None => return Err(invalid("image is missing")),
+        };
+        pos += 1;
+
+        match introducer {
+            // Extension
+            0x21 => {
+                let label = match data.get(pos) {
+                    Some(l) => *l,
+                    None => return Err(invalid("unexpected end of data")),
+                };
+                let (block, next) = read_sub_blocks(data, pos + 1)?;
+                // Graphic Control Extension
+                if label == 0xF9 && block.len() >= 4 && block[0] & 0x01 != 0 {
+                    transparent_index = Some(block[3]);
+                }
+                pos = next;
+            }
+            // Image Descriptor
+            0x2C => {
+                let left = read_u16(data, pos)? as usize;
+                let top = read_u16(data, pos + 2)? as usize;
+                let frame_width = read_u16(data, pos + 4)? as usize;
+                let frame_height = read_u16(data, pos + 6)? as usize;
+                let frame_flags = match data.get(pos + 8) {
+                    Some(f) => *f,
+                    None => return Err(invalid("unexpected end of data")),
+                };
+                pos += 9;
+
+                let mut color_table = &global_color_table;
+                let local_color_table;
+                if frame_flags & 0x80 != 0 {
+                    let size = 1 << ((frame_flags & 0x07) + 1);
+                    local_color_table = read_color_table(data, pos, size)?;
+                    color_table = &local_color_table;
+                    pos += size * 3;
+                }
+                let interlaced = frame_flags & 0x40 != 0;
+
+                let min_code_size = match data.get(pos) {
+                    Some(s) => *s as usize,
+                    None => return Err(invalid("unexpected end of data")),
+                };
+                let (compressed, _) = read_sub_blocks(data, pos + 1)?;
+                let pixel_count = checked_pixel_count(frame_width, frame_height)?;
+                let indices = decode_lzw(&compressed, min_code_size, pixel_count)?;
+
+                let mut image = Image::new(width, height);
+                for (i, index) in indices.iter().enumerate() {
+                    if frame_width == 0 || i >= frame_width * frame_height {
+                        break;
+                    }
+                    if Some(*index) == transparent_index {
+                        continue;
+                    }
+                    let color = match color_table.get(*index as usize) {
+                        Some(c) => c,
+                        None => continue,
+                    };
+                    let row = if interlaced {
+                        interlaced_row(i / frame_width, frame_height)
+                    } else {
+                        i / frame_width
+                    };
+                    image.set_pixel(
+                        left + i % frame_width,
+                        top + row,
+                        argb(255, color[0], color[1], color[2]),
+                    );
+                }
+                return Ok(image);
+            }
+            // Trailer
+            0x3B => return Err(invalid("image is missing")),
+            _ => return Err(invalid("unknown block")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_frame() {
+        // 3x2ピクセルの画像。右上のピクセルは透明
+        let data = [
+            0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x03, 0x00, 0x02, 0x00, 0x81, 0x00, 0x00, 0xFF,
+            0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x21, 0xF9, 0x04,
+            0x01, 0x00, 0x00, 0x03, 0x00, 0x2C, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x02, 0x00,
+            0x00, 0x02, 0x04, 0x44, 0x26, 0x02, 0x05, 0x00, 0x3B,
+        ];
+        let image = decode_gif(&data).expect("failed to decode a gif");
+        assert_eq!(3, image.width());
+        assert_eq!(2, image.height());
+        assert_eq!(0xFFFF0000, image.pixel(0, 0));
+        assert_eq!(0xFF00FF00, image.pixel(1, 0));
+        assert_eq!(0x00000000, image.pixel(2, 0));
+        assert_eq!(0xFF0000FF, image.pixel(0, 1));
+        assert_eq!(0xFFFF0000, image.pixel(2, 1));
+    }
+
+    #[test]
+    fn test_code_size_growth() {
+        // 16x16ピクセルの画像。符号のビット数が増えていく
+        let data = [
+            0x47, 0x49, 0x46, 0x38, 0x37, 0x61, 0x10, 0x00, 0x10, 0x00, 0x81, 0x00, 0x00, 0xFF,
+            0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x2C, 0x00, 0x00,
+            0x00, 0x00, 0x10, 0x00, 0x10, 0x00, 0x00, 0x02, 0x49, 0x84, 0x83, 0xA2, 0xC3, 0x06,
+            0xF1, 0x44, 0x9C, 0x41, 0x56, 0x23, 0x47, 0x18, 0x52, 0x6C, 0x98, 0x01, 0x9C, 0x67,
+            0x8C, 0x90, 0xA8, 0x38, 0x48, 0xB5, 0x34, 0x07, 0x65, 0xC5, 0x13, 0x90, 0x6D, 0xDD,
+            0x47, 0x8B, 0x28, 0xB9, 0x9F, 0xA3, 0x9A, 0x68, 0x0D, 0x54, 0xB0, 0xE2, 0x25, 0xA7,
+            0xE1, 0xD0, 0x70, 0x21, 0x53, 0x49, 0xE1, 0x4B, 0x1D, 0x82, 0x02, 0xC6, 0xF0, 0x75,
+            0xC9, 0xCA, 0x8E, 0x35, 0xA5, 0x47, 0x83, 0xEC, 0x3D, 0x79, 0xA3, 0x02, 0x00, 0x3B,
+        ];
+        let image = decode_gif(&data).expect("failed to decode a gif");
+        let palette = [0xFFFF0000, 0xFF00FF00, 0xFF0000FF, 0xFFFFFFFF];
+        for y in 0..16 {
+            for x in 0..16 {
+                assert_eq!(palette[(x * y + x / 3) % 4], image.pixel(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn test_oversized_header() {
+        // 65535x65535ピクセルの論理画面
+        let data = [
+            0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x2C,
+            0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x02, 0x00, 0x3B,
+        ];
+        assert!(decode_gif(&data).is_err());
+    }
+
+    #[test]
+    fn test_interlaced_row() {
+        let rows: Vec<usize> = (0..10).map(|r| interlaced_row(r, 10)).collect();
+        assert_eq!(alloc::vec![0, 8, 4, 2, 6, 1, 3, 5, 7, 9], rows);
+    }
+}
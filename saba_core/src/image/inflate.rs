@@ -0,0 +1,357 @@
+use crate::error::Error;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// 符号の最大のビット長
+const MAX_BITS: usize = 15;
+
+/// 長さの符号 (257から285) に対応する基本の長さと、追加で読むビット数
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.5
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// 距離の符号 (0から29) に対応する基本の距離と、追加で読むビット数
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// 符号長の符号を読む順番
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.7
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn invalid(message: &str) -> Error {
+    Error::UnexpectedInput(format!("invalid deflate stream: {}", message))
+}
+
+/// 最下位ビットから順にビットを読む
+#[derive(Debug, Clone)]
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buf: u32,
+    bit_count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            bit_buf: 0,
+            bit_count: 0,
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, Error> {
+        while self.bit_count < n {
+            let b = match self.data.get(self.pos) {
+                Some(b) => *b,
+                None => return Err(invalid("unexpected end of data")),
+            };
+            self.pos += 1;
+            self.bit_buf |= (b as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+
+        let value = self.bit_buf & ((1u32 << n) - 1);
+        self.bit_buf >>= n;
+        self.bit_count -= n;
+        Ok(value)
+    }
+
+    /// 読みかけのバイトの残りのビットを捨てる
+    fn align_to_byte(&mut self) {
+        self.bit_buf = 0;
+        self.bit_count = 0;
+    }
+
+    fn read_bytes(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.pos + n > self.data.len() {
+            return Err(invalid("unexpected end of data"));
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+}
+
+/// 正準ハフマン符号の表
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.2
+#[derive(Debug, Clone)]
+struct Huffman {
+    /// ビット長ごとの符号の数
+    counts: [u16; MAX_BITS + 1],
+    /// 符号の順に並べたシンボル
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, Error> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for len in lengths {
+            counts[*len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // 符号が多すぎる場合は不正な表
+        let mut left: i32 = 1;
+        for count in counts.iter().skip(1) {
+            left <<= 1;
+            left -= *count as i32;
+            if left < 0 {
+                return Err(invalid("over-subscribed huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, len) in lengths.iter().enumerate() {
+            if *len != 0 {
+                symbols[offsets[*len as usize] as usize] = symbol as u16;
+                offsets[*len as usize] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, Error> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+
+        for len in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+
+        Err(invalid("invalid huffman code"))
+    }
+}
+
+/// 固定ハフマン符号の表を作成する
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.6
+fn fixed_tables() -> Result<(Huffman, Huffman), Error> {
+    let mut lengths = [0u8; 288];
+    for (i, len) in lengths.iter_mut().enumerate() {
+        *len = match i {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    let literal = Huffman::new(&lengths)?;
+    let distance = Huffman::new(&[5u8; 30])?;
+    Ok((literal, distance))
+}
+
+/// ブロックの先頭に書かれたハフマン符号の表を読む
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.7
+fn dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman), Error> {
+    let hlit = reader.bits(5)? as usize + 257;
+    let hdist = reader.bits(5)? as usize + 1;
+    let hclen = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for i in CODE_LENGTH_ORDER.iter().take(hclen) {
+        code_lengths[*i] = reader.bits(3)? as u8;
+    }
+    let code_length_table = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::new();
+    while lengths.len() < hlit + hdist {
+        let symbol = code_length_table.decode(reader)?;
+        match symbol {
+            0..=15 => lengths.push(symbol as u8),
+            16 => {
+                let previous = match lengths.last() {
+                    Some(len) => *len,
+                    None => return Err(invalid("repeat with no previous length")),
+                };
+                let count = 3 + reader.bits(2)? as usize;
+                lengths.resize(lengths.len() + count, previous);
+            }
+            17 => {
+                let count = 3 + reader.bits(3)? as usize;
+                lengths.resize(lengths.len() + count, 0);
+            }
+            _ => {
+                let count = 11 + reader.bits(7)? as usize;
+                lengths.resize(lengths.len() + count, 0);
+            }
+        }
+    }
+
+    if lengths.len() > hlit + hdist {
+        return Err(invalid("too many code lengths"));
+    }
+
+    let literal = Huffman::new(&lengths[..hlit])?;
+    let distance = Huffman::new(&lengths[hlit..])?;
+    Ok((literal, distance))
+}
+
+/// ハフマン符号で圧縮されたブロックを展開する
+/// https://www.rfc-editor.org/rfc/rfc1951#section-3.2.5
+fn inflate_block(
+    reader: &mut BitReader,
+    output: &mut Vec<u8>,
+    literal: &Huffman,
+    distance: &Huffman,
+) -> Result<(), Error> {
+    loop {
+        let symbol = literal.decode(reader)? as usize;
+        match symbol {
+            0..=255 => output.push(symbol as u8),
+            256 => return Ok(()),
+            257..=285 => {
+                let i = symbol - 257;
+                let length =
+                    LENGTH_BASE[i] as usize + reader.bits(LENGTH_EXTRA[i] as u32)? as usize;
+
+                let d = distance.decode(reader)? as usize;
+                if d >= DISTANCE_BASE.len() {
+                    return Err(invalid("invalid distance symbol"));
+                }
+                let dist =
+                    DISTANCE_BASE[d] as usize + reader.bits(DISTANCE_EXTRA[d] as u32)? as usize;
+                if dist > output.len() {
+                    return Err(invalid("distance too far back"));
+                }
+
+                // 参照先と出力が重なることがあるので、1バイトずつコピーする
+                let start = output.len() - dist;
+                for k in 0..length {
+                    let b = output[start + k];
+                    output.push(b);
+                }
+            }
+            _ => return Err(invalid("invalid literal/length symbol")),
+        }
+    }
+}
+
+/// DEFLATE形式で圧縮されたデータを展開する
+/// https://www.rfc-editor.org/rfc/rfc1951
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut reader = BitReader::new(data);
+    let mut output = Vec::new();
+
+    loop {
+        let is_final = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            // 無圧縮のブロック
+            0 => {
+                reader.align_to_byte();
+                let header = reader.read_bytes(4)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if len != !nlen {
+                    return Err(invalid("stored block length mismatch"));
+                }
+                output.extend_from_slice(reader.read_bytes(len as usize)?);
+            }
+            1 => {
+                let (literal, distance) = fixed_tables()?;
+                inflate_block(&mut reader, &mut output, &literal, &distance)?;
+            }
+            2 => {
+                let (literal, distance) = dynamic_tables(&mut reader)?;
+                inflate_block(&mut reader, &mut output, &literal, &distance)?;
+            }
+            _ => return Err(invalid("invalid block type")),
+        }
+
+        if is_final {
+            return Ok(output);
+        }
+    }
+}
+
+/// zlib形式のデータを展開する。チェックサムは検証しない
+/// https://www.rfc-editor.org/rfc/rfc1950
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 2 {
+        return Err(invalid("zlib header is too short"));
+    }
+
+    let cmf = data[0];
+    let flg = data[1];
+    if cmf & 0x0F != 8 || ((cmf as u16) << 8 | flg as u16) % 31 != 0 {
+        return Err(Error::UnexpectedInput("invalid zlib header".to_string()));
+    }
+    // プリセット辞書はサポートしない
+    if flg & 0x20 != 0 {
+        return Err(Error::UnexpectedInput(
+            "zlib preset dictionary is not supported".to_string(),
+        ));
+    }
+
+    inflate(&data[2..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_block() {
+        let data = [0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c'];
+        assert_eq!(Ok(b"abc".to_vec()), inflate(&data));
+    }
+
+    #[test]
+    fn test_fixed_huffman() {
+        // zlib.compress(b"hello hello hello")
+        let data = [
+            0x78, 0x9C, 0xCB, 0x48, 0xCD, 0xC9, 0xC9, 0x57, 0xC8, 0x40, 0x90, 0x00, 0x3A, 0x2E,
+            0x06, 0x7D,
+        ];
+        assert_eq!(Ok(b"hello hello hello".to_vec()), zlib_decompress(&data));
+    }
+
+    #[test]
+    fn test_dynamic_huffman() {
+        // 62個の"a"、39個の"b"、1個の"c"を、ハフマン符号だけで圧縮したデータ
+        let data = [
+            0x78, 0x01, 0x05, 0xC1, 0x01, 0x01, 0x00, 0x00, 0x00, 0x82, 0xA0, 0xAD, 0xD8, 0xFF,
+            0x0F, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x50, 0x55, 0x55, 0x55, 0x55,
+            0x55, 0x55, 0x55, 0x55, 0x55, 0xED, 0xCA, 0x69, 0x26, 0xD0,
+        ];
+        let mut expected = [b'a'; 102];
+        expected[62..101].fill(b'b');
+        expected[101] = b'c';
+        assert_eq!(Ok(expected.to_vec()), zlib_decompress(&data));
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        assert!(zlib_decompress(&[0x00, 0x00, 0x00]).is_err());
+    }
+}
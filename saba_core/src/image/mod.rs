@@ -0,0 +1,92 @@
+pub mod bmp;
+pub mod gif;
+pub mod inflate;
+pub mod png;
+
+use crate::error::Error;
+use crate::image::bmp::decode_bmp;
+use crate::image::gif::decode_gif;
+use crate::image::png::decode_png;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// デコードする画像のピクセル数の上限。ヘッダの大きさはネットワークから届いた
+/// 値なので、確保する前に確かめる
+const MAX_PIXELS: usize = 4096 * 4096;
+
+/// デコードした画像。各ピクセルは0xAARRGGBB形式で、左上から行ごとに並ぶ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<u32>,
+}
+
+impl Image {
+    /// 透明なピクセルで埋めた画像を作成する
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// (x, y)の位置のピクセルを返す。画像の外の場合は透明なピクセルを返す
+    pub fn pixel(&self, x: usize, y: usize) -> u32 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, argb: u32) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = argb;
+        }
+    }
+}
+
+/// 先頭のバイト列から画像の形式を判定してデコードする
+/// https://mimesniff.spec.whatwg.org/#matching-an-image-type-pattern
+pub fn decode_image(data: &[u8]) -> Result<Image, Error> {
+    if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        return decode_png(data);
+    }
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        return decode_gif(data);
+    }
+    if data.starts_with(b"BM") {
+        return decode_bmp(data);
+    }
+
+    Err(Error::UnexpectedInput(
+        "unsupported image format".to_string(),
+    ))
+}
+
+/// 画像のピクセル数を返す。上限を超える場合はエラーを返す
+fn checked_pixel_count(width: usize, height: usize) -> Result<usize, Error> {
+    match width.checked_mul(height) {
+        Some(count) if count <= MAX_PIXELS => Ok(count),
+        _ => Err(Error::UnexpectedInput(format!(
+            "image size {}x{} is too large",
+            width, height
+        ))),
+    }
+}
+
+/// アルファ値と色の成分から、0xAARRGGBB形式のピクセルを作る
+fn argb(a: u8, r: u8, g: u8, b: u8) -> u32 {
+    (a as u32) << 24 | (r as u32) << 16 | (g as u32) << 8 | b as u32
+}
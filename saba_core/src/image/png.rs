@@ -0,0 +1,370 @@
+use crate::error::Error;
+use crate::image::argb;
+use crate::image::checked_pixel_count;
+use crate::image::inflate::zlib_decompress;
+use crate::image::Image;
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// インターレース (Adam7) の各パスの開始位置と間隔 (x0, y0, dx, dy)
+/// https://www.w3.org/TR/png/#8Interlace
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+fn invalid(message: &str) -> Error {
+    Error::UnexpectedInput(format!("invalid png: {}", message))
+}
+
+/// https://www.w3.org/TR/png/#6Colour-values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorType {
+    Grayscale,
+    Truecolor,
+    Indexed,
+    GrayscaleAlpha,
+    TruecolorAlpha,
+}
+
+impl ColorType {
+    fn from_u8(value: u8) -> Result<Self, Error> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Truecolor),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::TruecolorAlpha),
+            _ => Err(invalid("unknown color type")),
+        }
+    }
+
+    fn channels(&self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Truecolor => 3,
+            ColorType::TruecolorAlpha => 4,
+        }
+    }
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32, Error> {
+    match data.get(pos..pos + 4) {
+        Some(b) => Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(invalid("unexpected end of data")),
+    }
+}
+
+/// https://www.w3.org/TR/png/#9Filter-type-4-Paeth
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// フィルタを取り除いて、元のスキャンラインに戻す
+/// https://www.w3.org/TR/png/#9Filters
+fn unfilter(filter: u8, row: &mut [u8], previous: &[u8], bpp: usize) -> Result<(), Error> {
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = previous[i];
+        let c = if i >= bpp { previous[i - bpp] } else { 0 };
+        let predictor = match filter {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            4 => paeth(a, b, c),
+            _ => return Err(invalid("unknown filter type")),
+        };
+        row[i] = row[i].wrapping_add(predictor);
+    }
+    Ok(())
+}
+
+/// スキャンラインの`index`番目のサンプルの値を返す
+fn sample(row: &[u8], index: usize, bit_depth: u8) -> u16 {
+    match bit_depth {
+        16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+        8 => row[index] as u16,
+        _ => {
+            let bit = index * bit_depth as usize;
+            let shift = 8 - bit_depth as usize - bit % 8;
+            ((row[bit / 8] >> shift) & ((1 << bit_depth) - 1)) as u16
+        }
+    }
+}
+
+/// サンプルの値を8ビットに変換する
+fn to_u8(value: u16, bit_depth: u8) -> u8 {
+    match bit_depth {
+        16 => (value >> 8) as u8,
+        8 => value as u8,
+        _ => (value as u32 * 255 / ((1u32 << bit_depth) - 1)) as u8,
+    }
+}
+
+/// https://www.w3.org/TR/png/
+pub fn decode_png(data: &[u8]) -> Result<Image, Error> {
+    if !data.starts_with(&SIGNATURE) {
+        return Err(invalid("wrong signature"));
+    }
+
+    let mut header: Option<(usize, usize, u8, ColorType, bool)> = None;
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut transparency: Vec<u8> = Vec::new();
+    let mut compressed: Vec<u8> = Vec::new();
+
+    // チャンクを順に読む
+    // https://www.w3.org/TR/png/#5Chunk-layout
+    let mut pos = SIGNATURE.len();
+    while pos < data.len() {
+        let length = read_u32(data, pos)? as usize;
+        let chunk_type = match data.get(pos + 4..pos + 8) {
+            Some(t) => t,
+            None => return Err(invalid("unexpected end of data")),
+        };
+        let chunk = match data.get(pos + 8..pos + 8 + length) {
+            Some(c) => c,
+            None => return Err(invalid("unexpected end of data")),
+        };
+        // CRCは検証しない
+        pos += 12 + length;
+
+        match chunk_type {
+            b"IHDR" => {
+                if chunk.len() < 13 {
+                    return Err(invalid("IHDR is too short"));
+                }
+                let width = read_u32(chunk, 0)? as usize;
+                let height = read_u32(chunk, 4)? as usize;
+                let bit_depth = chunk[8];
+                let color_type = ColorType::from_u8(chunk[9])?;
+                let interlaced = chunk[12] == 1;
+                if !matches!(bit_depth, 1 | 2 | 4 | 8 | 16) {
+                    return Err(invalid("unsupported bit depth"));
+                }
+                checked_pixel_count(width, height)?;
+                header = Some((width, height, bit_depth, color_type, interlaced));
+            }
+            b"PLTE" => {
+                palette = chunk.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+            }
+            b"tRNS" => transparency = chunk.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(chunk),
+            b"IEND" => break,
+            // そのほかの補助チャンクは無視する
+            _ => {}
+        }
+    }
+
+    let (width, height, bit_depth, color_type, interlaced) = match header {
+        Some(h) => h,
+        None => return Err(invalid("IHDR is missing")),
+    };
+
+    let raw = zlib_decompress(&compressed)?;
+
+    let bits_per_pixel = color_type.channels() * bit_depth as usize;
+    // フィルタで参照する、左隣のピクセルまでのバイト数
+    let bpp = (bits_per_pixel / 8).max(1);
+
+    let passes: Vec<(usize, usize, usize, usize)> = if interlaced {
+        ADAM7_PASSES.to_vec()
+    } else {
+        vec![(0, 0, 1, 1)]
+    };
+
+    // 画像を確保する前に、展開したデータがヘッダの大きさに見合うかを確かめる。
+    // 各行の先頭にはフィルタの種類を表す1バイトがある
+    let required: usize = passes
+        .iter()
+        .filter(|(x0, y0, _, _)| *x0 < width && *y0 < height)
+        .map(|(x0, y0, dx, dy)| {
+            let pass_width = (width - x0).div_ceil(*dx);
+            let pass_height = (height - y0).div_ceil(*dy);
+            (1 + (pass_width * bits_per_pixel).div_ceil(8)) * pass_height
+        })
+        .sum();
+    if raw.len() < required {
+        return Err(invalid("image data is too short"));
+    }
+    let mut image = Image::new(width, height);
+
+    let mut pos = 0;
+    for (x0, y0, dx, dy) in passes {
+        if width <= x0 || height <= y0 {
+            continue;
+        }
+        let pass_width = (width - x0).div_ceil(dx);
+        let pass_height = (height - y0).div_ceil(dy);
+        let stride = (pass_width * bits_per_pixel).div_ceil(8);
+
+        let mut previous = vec![0u8; stride];
+        for j in 0..pass_height {
+            let filter = match raw.get(pos) {
+                Some(f) => *f,
+                None => return Err(invalid("image data is too short")),
+            };
+            let mut row = match raw.get(pos + 1..pos + 1 + stride) {
+                Some(r) => r.to_vec(),
+                None => return Err(invalid("image data is too short")),
+            };
+            pos += 1 + stride;
+            unfilter(filter, &mut row, &previous, bpp)?;
+
+            for i in 0..pass_width {
+                let channels = color_type.channels();
+                let s = |c: usize| sample(&row, i * channels + c, bit_depth);
+
+                let pixel = match color_type {
+                    ColorType::Grayscale => {
+                        let v = s(0);
+                        let alpha = if transparency.len() >= 2
+                            && v == u16::from_be_bytes([transparency[0], transparency[1]])
+                        {
+                            0
+                        } else {
+                            255
+                        };
+                        let g = to_u8(v, bit_depth);
+                        argb(alpha, g, g, g)
+                    }
+                    ColorType::Truecolor => {
+                        let (r, g, b) = (s(0), s(1), s(2));
+                        let is_transparent = transparency.len() >= 6
+                            && [r, g, b]
+                                == [
+                                    u16::from_be_bytes([transparency[0], transparency[1]]),
+                                    u16::from_be_bytes([transparency[2], transparency[3]]),
+                                    u16::from_be_bytes([transparency[4], transparency[5]]),
+                                ];
+                        argb(
+                            if is_transparent { 0 } else { 255 },
+                            to_u8(r, bit_depth),
+                            to_u8(g, bit_depth),
+                            to_u8(b, bit_depth),
+                        )
+                    }
+                    ColorType::Indexed => {
+                        let index = s(0) as usize;
+                        let color = match palette.get(index) {
+                            Some(c) => c,
+                            None => return Err(invalid("palette index out of range")),
+                        };
+                        let alpha = transparency.get(index).copied().unwrap_or(255);
+                        argb(alpha, color[0], color[1], color[2])
+                    }
+                    ColorType::GrayscaleAlpha => {
+                        let g = to_u8(s(0), bit_depth);
+                        argb(to_u8(s(1), bit_depth), g, g, g)
+                    }
+                    ColorType::TruecolorAlpha => argb(
+                        to_u8(s(3), bit_depth),
+                        to_u8(s(0), bit_depth),
+                        to_u8(s(1), bit_depth),
+                        to_u8(s(2), bit_depth),
+                    ),
+                };
+
+                image.set_pixel(x0 + i * dx, y0 + j * dy, pixel);
+            }
+
+            previous = row;
+        }
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rgba() {
+        // 2x1ピクセルの画像。赤と半透明の青
+        let data = [
+            0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00,
+            0x00, 0xF4, 0x22, 0x7F, 0x8A, 0x00, 0x00, 0x00, 0x0E, 0x49, 0x44, 0x41, 0x54, 0x78,
+            0x9C, 0x63, 0xF8, 0xCF, 0xC0, 0x00, 0x42, 0x0D, 0x00, 0x0F, 0x7A, 0x03, 0x7E, 0x77,
+            0xE9, 0x7F, 0x97, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60,
+            0x82,
+        ];
+        let image = decode_png(&data).expect("failed to decode a png");
+        assert_eq!(2, image.width());
+        assert_eq!(1, image.height());
+        assert_eq!(0xFFFF0000, image.pixel(0, 0));
+        assert_eq!(0x800000FF, image.pixel(1, 0));
+    }
+
+    #[test]
+    fn test_indexed() {
+        // 3x2ピクセル、2ビットのパレット画像。2行目はUpフィルタがかかっている
+        let data = [
+            0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x02, 0x02, 0x03, 0x00, 0x00,
+            0x00, 0xE0, 0x1A, 0x8E, 0x89, 0x00, 0x00, 0x00, 0x09, 0x50, 0x4C, 0x54, 0x45, 0xFF,
+            0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0x2D, 0x4A, 0xCD, 0x8A, 0x00, 0x00,
+            0x00, 0x02, 0x74, 0x52, 0x4E, 0x53, 0xFF, 0x00, 0xE5, 0xB7, 0x30, 0x4A, 0x00, 0x00,
+            0x00, 0x0C, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9C, 0x63, 0x90, 0x60, 0xEA, 0x00, 0x00,
+            0x00, 0xD8, 0x00, 0xA3, 0xF0, 0xCC, 0x40, 0xE3, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45,
+            0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82,
+        ];
+        let image = decode_png(&data).expect("failed to decode a png");
+        assert_eq!(0xFFFF0000, image.pixel(0, 0));
+        // tRNSで透明になっている
+        assert_eq!(0x0000FF00, image.pixel(1, 0));
+        assert_eq!(0xFF0000FF, image.pixel(2, 0));
+        assert_eq!(0xFF0000FF, image.pixel(0, 1));
+        assert_eq!(0xFF0000FF, image.pixel(1, 1));
+        assert_eq!(0xFFFF0000, image.pixel(2, 1));
+    }
+
+    #[test]
+    fn test_oversized_header() {
+        let mut data = vec![0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52]);
+        data.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        data.extend_from_slice(&[0x08, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44]);
+        assert!(decode_png(&data).is_err());
+    }
+
+    #[test]
+    fn test_truncated_data() {
+        // test_rgbaの画像の幅を1000ピクセルに書き換える。展開したデータが足りない
+        let data = [
+            0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x03, 0xE8, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00,
+            0x00, 0xF4, 0x22, 0x7F, 0x8A, 0x00, 0x00, 0x00, 0x0E, 0x49, 0x44, 0x41, 0x54, 0x78,
+            0x9C, 0x63, 0xF8, 0xCF, 0xC0, 0x00, 0x42, 0x0D, 0x00, 0x0F, 0x7A, 0x03, 0x7E, 0x77,
+            0xE9, 0x7F, 0x97, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60,
+            0x82,
+        ];
+        assert!(decode_png(&data).is_err());
+    }
+
+    #[test]
+    fn test_wrong_signature() {
+        assert!(decode_png(b"GIF89a").is_err());
+    }
+}
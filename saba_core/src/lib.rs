@@ -5,8 +5,10 @@ extern crate alloc;
 pub mod browser;
 pub mod constants;
 pub mod display_item;
+pub mod encoding;
 pub mod error;
 pub mod http;
+pub mod image;
 pub mod renderer;
 pub mod url;
 pub mod utils;
@@ -0,0 +1,585 @@
+//! 支援技術に渡すためのアクセシビリティツリー。DOMツリー、スタイル、レイアウトの
+//! 結果から、各ノードの役割(role)、名前、状態、表示されている位置を計算する
+//! https://www.w3.org/TR/core-aam-1.2/
+//! https://www.w3.org/TR/html-aam-1.0/
+
+use crate::renderer::dom::api::children;
+use crate::renderer::dom::api::get_document_title;
+use crate::renderer::dom::api::get_element_by_id;
+use crate::renderer::dom::api::strip_and_collapse_ascii_whitespace;
+use crate::renderer::dom::focus::is_focusable;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::ElementKind;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::node::NodeKind;
+use crate::renderer::layout::layout_object::LayoutPoint;
+use crate::renderer::layout::layout_object::LayoutSize;
+use crate::renderer::layout::layout_view::LayoutView;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// ノードの役割
+/// https://w3c.github.io/aria/#role_definitions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Button,
+    Checkbox,
+    Document,
+    Generic,
+    Heading,
+    /// 入れ子の文書を表示する<iframe>要素
+    Iframe,
+    Image,
+    Link,
+    List,
+    ListItem,
+    Main,
+    Navigation,
+    /// 意味を持たない要素。role="none"とrole="presentation"
+    None,
+    Paragraph,
+    /// テキストノード
+    StaticText,
+    TextBox,
+}
+
+impl Role {
+    /// role属性の値から役割を返す。ARIAで定義されていない値の場合はNoneを返す
+    fn from_aria(value: &str) -> Option<Self> {
+        let role = match value.to_ascii_lowercase().as_str() {
+            "button" => Role::Button,
+            "checkbox" => Role::Checkbox,
+            "document" => Role::Document,
+            "generic" => Role::Generic,
+            "heading" => Role::Heading,
+            "img" | "image" => Role::Image,
+            "link" => Role::Link,
+            "list" => Role::List,
+            "listitem" => Role::ListItem,
+            "main" => Role::Main,
+            "navigation" => Role::Navigation,
+            "none" | "presentation" => Role::None,
+            "paragraph" => Role::Paragraph,
+            "textbox" => Role::TextBox,
+            _ => return None,
+        };
+        Some(role)
+    }
+
+    /// 子孫のテキストを名前として使う役割かどうかを返す
+    /// https://w3c.github.io/aria/#namefromcontent
+    fn is_name_from_content(&self) -> bool {
+        matches!(
+            self,
+            Role::Button | Role::Checkbox | Role::Heading | Role::Link | Role::StaticText
+        )
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Role::Button => "button",
+            Role::Checkbox => "checkbox",
+            Role::Document => "document",
+            Role::Generic => "generic",
+            Role::Heading => "heading",
+            Role::Iframe => "iframe",
+            Role::Image => "img",
+            Role::Link => "link",
+            Role::List => "list",
+            Role::ListItem => "listitem",
+            Role::Main => "main",
+            Role::Navigation => "navigation",
+            Role::None => "none",
+            Role::Paragraph => "paragraph",
+            Role::StaticText => "text",
+            Role::TextBox => "textbox",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// ノードの状態
+/// https://w3c.github.io/aria/#state_prop_def
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct States {
+    pub focusable: bool,
+    pub focused: bool,
+    /// 訪問済みのページへのリンク
+    pub visited: bool,
+    /// hidden属性またはaria-hidden="true"によって支援技術から隠されている。
+    /// 隠されたノードの子孫はツリーに含めない
+    pub hidden: bool,
+}
+
+/// アクセシビリティツリーのノード
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessibilityNode {
+    node: NodeId,
+    role: Role,
+    name: String,
+    level: Option<i64>,
+    states: States,
+    bounds: Option<(LayoutPoint, LayoutSize)>,
+    children: Vec<AccessibilityNode>,
+}
+
+impl AccessibilityNode {
+    /// 対応するDOMツリーのノード
+    pub fn node(&self) -> NodeId {
+        self.node
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// https://w3c.github.io/accname/#dfn-accessible-name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 見出しのレベル。見出し以外の場合はNone
+    pub fn level(&self) -> Option<i64> {
+        self.level
+    }
+
+    pub fn states(&self) -> States {
+        self.states
+    }
+
+    /// レイアウトツリーの中での位置と大きさ。描画されていないノードの場合はNone
+    pub fn bounds(&self) -> Option<(LayoutPoint, LayoutSize)> {
+        self.bounds
+    }
+
+    pub fn children(&self) -> &[AccessibilityNode] {
+        &self.children
+    }
+
+    /// `node`に対応するノードを、自身とその子孫から探す
+    pub fn find(&self, node: NodeId) -> Option<&AccessibilityNode> {
+        if self.node == node {
+            return Some(self);
+        }
+        self.children.iter().find_map(|c| c.find(node))
+    }
+
+    fn dump(&self, depth: usize, result: &mut String) {
+        let mut line = format!("{}{} {:?}", "  ".repeat(depth), self.role, self.name);
+        if let Some(level) = self.level {
+            line.push_str(&format!(" level={}", level));
+        }
+        for (state, name) in [
+            (self.states.focusable, "focusable"),
+            (self.states.focused, "focused"),
+            (self.states.visited, "visited"),
+            (self.states.hidden, "hidden"),
+        ] {
+            if state {
+                line.push(' ');
+                line.push_str(name);
+            }
+        }
+        if let Some((point, size)) = self.bounds {
+            line.push_str(&format!(
+                " ({}, {}, {}x{})",
+                point.x(),
+                point.y(),
+                size.width(),
+                size.height()
+            ));
+        }
+        result.push_str(&line);
+        result.push('\n');
+
+        for child in &self.children {
+            child.dump(depth + 1, result);
+        }
+    }
+}
+
+/// ドキュメント全体のアクセシビリティツリー。DOMツリーやレイアウトが変わっても
+/// 更新されないので、必要なときに作り直す
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessibilityTree {
+    root: AccessibilityNode,
+}
+
+impl AccessibilityTree {
+    /// `document`と、それをレイアウトした`layout_view`からツリーを作成する。
+    /// `is_visited`は、リンクのhref属性の値を受け取り、訪問済みかどうかを返す
+    pub fn new(
+        document: &Document,
+        layout_view: &LayoutView,
+        is_visited: &dyn Fn(&str) -> bool,
+    ) -> Self {
+        let mut bounds = BTreeMap::new();
+        for o in layout_view.layout_objects() {
+            let o = o.borrow();
+            bounds.entry(o.node()).or_insert((o.point(), o.size()));
+        }
+
+        let builder = TreeBuilder {
+            document,
+            bounds,
+            is_visited,
+        };
+        let root = document.root();
+        let mut children = Vec::new();
+        builder.build_children(root, &mut children);
+
+        Self {
+            root: AccessibilityNode {
+                node: root,
+                role: Role::Document,
+                name: strip_and_collapse_ascii_whitespace(&get_document_title(document)),
+                level: None,
+                states: States::default(),
+                bounds: None,
+                children,
+            },
+        }
+    }
+
+    pub fn root(&self) -> &AccessibilityNode {
+        &self.root
+    }
+
+    /// `node`に対応するノードを返す。ツリーに含まれないノードの場合はNone
+    pub fn find(&self, node: NodeId) -> Option<&AccessibilityNode> {
+        self.root.find(node)
+    }
+
+    /// ツリーを1行に1ノードずつ、深さに応じて字下げしたテキストにする。
+    /// 各行は`役割 "名前"`の後に、見出しのレベル、状態、位置と大きさを続ける
+    pub fn dump(&self) -> String {
+        let mut result = String::new();
+        self.root.dump(0, &mut result);
+        result
+    }
+}
+
+struct TreeBuilder<'a> {
+    document: &'a Document,
+    bounds: BTreeMap<NodeId, (LayoutPoint, LayoutSize)>,
+    is_visited: &'a dyn Fn(&str) -> bool,
+}
+
+impl TreeBuilder<'_> {
+    /// `parent`の子ノードから作ったノードを`result`に追加する
+    fn build_children(&self, parent: NodeId, result: &mut Vec<AccessibilityNode>) {
+        for child in children(self.document, parent) {
+            self.build(child, result);
+        }
+    }
+
+    /// `node`から作ったノードを`result`に追加する。名前のない汎用的な要素は
+    /// ツリーに含めず、その子を`result`に直接追加する
+    fn build(&self, node: NodeId, result: &mut Vec<AccessibilityNode>) {
+        let document = self.document;
+        let element = document.element(node);
+
+        // <html>要素はレイアウトツリーに含まれないが、描画される<body>要素を子に持つ
+        if element.map(|e| e.kind()) == Some(ElementKind::Html) {
+            self.build_children(node, result);
+            return;
+        }
+        // 描画されていないノードは含めない
+        let bounds = match self.bounds.get(&node) {
+            Some(b) => *b,
+            None => return,
+        };
+
+        let role = self.role(node);
+        let name = self.name(node, role);
+        if role == Role::StaticText && name.is_empty() {
+            return;
+        }
+
+        let hidden = element.is_some_and(|e| {
+            e.has_attribute("hidden")
+                || e.get_attribute("aria-hidden")
+                    .is_some_and(|v| v.trim().eq_ignore_ascii_case("true"))
+        });
+        if !hidden && matches!(role, Role::Generic | Role::None) && name.is_empty() {
+            self.build_children(node, result);
+            return;
+        }
+
+        let level = if role == Role::Heading {
+            let implicit = match element.map(|e| e.kind()) {
+                Some(ElementKind::H1) => 1,
+                // https://w3c.github.io/aria/#aria-level
+                _ => 2,
+            };
+            Some(
+                element
+                    .and_then(|e| e.get_attribute("aria-level"))
+                    .and_then(|v| v.trim().parse::<i64>().ok())
+                    .filter(|level| *level > 0)
+                    .unwrap_or(implicit),
+            )
+        } else {
+            None
+        };
+
+        let visited = role == Role::Link
+            && element
+                .and_then(|e| e.get_attribute("href"))
+                .is_some_and(|href| (self.is_visited)(href));
+        let states = States {
+            focusable: is_focusable(document, node),
+            focused: document.focused_element() == Some(node),
+            visited,
+            hidden,
+        };
+
+        let mut children = Vec::new();
+        if !hidden {
+            self.build_children(node, &mut children);
+        }
+
+        result.push(AccessibilityNode {
+            node,
+            role,
+            name,
+            level,
+            states,
+            bounds: Some(bounds),
+            children,
+        });
+    }
+
+    /// role属性で指定された役割か、要素の種類から決まる役割を返す。role属性には
+    /// 空白区切りで複数の値を書くことができ、最初に認識できた値を使う
+    /// https://w3c.github.io/aria/#document-handling_author-errors_roles
+    /// https://www.w3.org/TR/html-aam-1.0/#html-element-role-mappings
+    fn role(&self, node: NodeId) -> Role {
+        let element = match self.document.element(node) {
+            Some(e) => e,
+            None => return Role::StaticText,
+        };
+        if let Some(role) = element
+            .get_attribute("role")
+            .and_then(|v| v.split_ascii_whitespace().find_map(Role::from_aria))
+        {
+            return role;
+        }
+
+        // 編集可能な要素は、要素の種類に関わらずテキストボックスになる
+        if element
+            .get_attribute("contenteditable")
+            .is_some_and(|v| !v.eq_ignore_ascii_case("false"))
+        {
+            return Role::TextBox;
+        }
+
+        match element.kind() {
+            ElementKind::A if element.has_attribute("href") => Role::Link,
+            ElementKind::H1 | ElementKind::H2 => Role::Heading,
+            ElementKind::P => Role::Paragraph,
+            ElementKind::Iframe => Role::Iframe,
+            // alt属性が空の画像は装飾なので、意味を持たない
+            ElementKind::Img if element.get_attribute("alt") == Some("") => Role::None,
+            ElementKind::Img => Role::Image,
+            _ => Role::Generic,
+        }
+    }
+
+    /// https://w3c.github.io/accname/#computation-steps
+    fn name(&self, node: NodeId, role: Role) -> String {
+        let document = self.document;
+        if let NodeKind::Text(s) = document.kind(node) {
+            return strip_and_collapse_ascii_whitespace(s);
+        }
+        let element = match document.element(node) {
+            Some(e) => e,
+            None => return String::new(),
+        };
+
+        // https://w3c.github.io/aria/#aria-labelledby
+        if let Some(ids) = element.get_attribute("aria-labelledby") {
+            let names: Vec<String> = ids
+                .split_ascii_whitespace()
+                .filter_map(|id| get_element_by_id(document, Some(document.root()), id))
+                .map(|n| self.text_alternative(n))
+                .filter(|name| !name.is_empty())
+                .collect();
+            if !names.is_empty() {
+                return strip_and_collapse_ascii_whitespace(&names.join(" "));
+            }
+        }
+
+        // https://w3c.github.io/aria/#aria-label
+        if let Some(label) = element.get_attribute("aria-label") {
+            let label = strip_and_collapse_ascii_whitespace(label);
+            if !label.is_empty() {
+                return label;
+            }
+        }
+
+        if element.kind() == ElementKind::Img {
+            if let Some(alt) = element.get_attribute("alt") {
+                return strip_and_collapse_ascii_whitespace(alt);
+            }
+        }
+
+        if role.is_name_from_content() {
+            let name = strip_and_collapse_ascii_whitespace(&self.name_from_content(node));
+            if !name.is_empty() {
+                return name;
+            }
+        }
+
+        // https://www.w3.org/TR/html-aam-1.0/#att-title
+        element
+            .get_attribute("title")
+            .map(strip_and_collapse_ascii_whitespace)
+            .unwrap_or_default()
+    }
+
+    /// 他の要素の名前の一部として使うときの、`node`のテキスト
+    fn text_alternative(&self, node: NodeId) -> String {
+        let document = self.document;
+        match document.element(node) {
+            Some(element) => {
+                if let Some(label) = element.get_attribute("aria-label") {
+                    if !label.trim().is_empty() {
+                        return String::from(label);
+                    }
+                }
+                if element.kind() == ElementKind::Img {
+                    return String::from(element.get_attribute("alt").unwrap_or_default());
+                }
+                self.name_from_content(node)
+            }
+            None => match document.kind(node) {
+                NodeKind::Text(s) => s.clone(),
+                _ => String::new(),
+            },
+        }
+    }
+
+    /// 子孫のテキストを連結する。隠された要素は除き、画像は代替テキストを使う
+    /// https://w3c.github.io/accname/#step2F
+    fn name_from_content(&self, node: NodeId) -> String {
+        let mut name = String::new();
+        for child in children(self.document, node) {
+            if let Some(element) = self.document.element(child) {
+                if element.has_attribute("hidden")
+                    || element.get_attribute("aria-hidden") == Some("true")
+                {
+                    continue;
+                }
+            }
+            name.push_str(&self.text_alternative(child));
+        }
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::cssom::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+    use crate::renderer::css::user_agent::user_agent_style_sheet;
+    use crate::renderer::dom::api::get_style_content;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use alloc::string::ToString;
+
+    fn create_tree(html: &str, focused_id: Option<&str>) -> AccessibilityTree {
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let dom = window.borrow().document();
+        if let Some(id) = focused_id {
+            let node = {
+                let document = dom.borrow();
+                get_element_by_id(&document, Some(document.root()), id)
+            };
+            dom.borrow_mut().set_focused_element(node);
+        }
+        let style = get_style_content(&dom.borrow());
+        let cssom = CssParser::new(CssTokenizer::new(style)).parse_stylesheet();
+        let layout_view = LayoutView::new(dom.clone(), &[&user_agent_style_sheet(), &cssom]);
+        let document = dom.borrow();
+        AccessibilityTree::new(&document, &layout_view, &|href| href == "/visited")
+    }
+
+    /// 位置と大きさを除いたダンプ
+    fn dump_without_bounds(tree: &AccessibilityTree) -> String {
+        tree.dump()
+            .lines()
+            .map(|line| match line.find(" (") {
+                Some(i) => &line[..i],
+                None => line,
+            })
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn test_roles_and_names() {
+        let html = r#"<html><head><title>Test  page</title><style>.none{display:none;}</style>
+</head><body>
+<h1>Hello <a href="/visited">world</a></h1>
+<h2 aria-level="3">Sub</h2>
+<p class="none">not rendered</p>
+<p><a href="/x"><img src="a.png" alt="Logo"></a> <a>no href</a></p>
+<p id="label">Search</p>
+<p role="bogus button" aria-labelledby="label missing">x</p>
+<p aria-label="  Labelled   paragraph ">y</p>
+<p aria-hidden="true"><a href="/x">hidden link</a></p>
+<p contenteditable id="edit">edit</p>
+</body></html>"#;
+        let tree = create_tree(html, Some("edit"));
+        let expected = r#"document "Test page"
+  heading "Hello world" level=1
+    text "Hello"
+    link "world" focusable visited
+      text "world"
+  heading "Sub" level=3
+    text "Sub"
+  paragraph ""
+    link "Logo" focusable
+      img "Logo"
+    text "no href"
+  paragraph ""
+    text "Search"
+  button "Search"
+    text "x"
+  paragraph "Labelled paragraph"
+    text "y"
+  paragraph "" hidden
+  textbox "" focusable focused
+    text "edit""#;
+        assert_eq!(expected, dump_without_bounds(&tree));
+    }
+
+    #[test]
+    fn test_bounds() {
+        let html = "<html><head></head><body><p>hello</p><a href=x>link</a></body></html>";
+        let tree = create_tree(html, None);
+        assert_eq!(None, tree.root().bounds());
+
+        let paragraph = &tree.root().children()[0];
+        assert_eq!(Role::Paragraph, paragraph.role());
+        let link = &tree.root().children()[1];
+        assert_eq!(Role::Link, link.role());
+        let (p_point, p_size) = paragraph.bounds().expect("paragraph has no bounds");
+        let (l_point, _) = link.bounds().expect("link has no bounds");
+        assert!(p_size.width() > 0 && p_size.height() > 0);
+        assert!(p_point.y() + p_size.height() <= l_point.y());
+
+        assert_eq!(Some(link), tree.find(link.node()));
+        assert_eq!(
+            Some(Role::StaticText),
+            tree.find(link.children()[0].node()).map(|n| n.role())
+        );
+    }
+}
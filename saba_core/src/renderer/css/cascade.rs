@@ -0,0 +1,229 @@
+//! https://drafts.csswg.org/css-cascade/#cascading
+
+use crate::renderer::css::cssom::Declaration;
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::selector::Specificity;
+use crate::renderer::dom::api::get_inline_style;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::NodeId;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// スタイルシートを作成した主体
+/// https://drafts.csswg.org/css-cascade/#cascading-origins
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// ブラウザが持つ既定のスタイルシート
+    UserAgent,
+    /// ユーザーが指定したスタイルシート
+    User,
+    /// ページの作者が書いたスタイルシート
+    Author,
+}
+
+/// 出自と重要度の組み合わせの優先順位を返す。通常の宣言は作者のものが最も
+/// 優先され、重要な宣言はその逆の順になる
+/// https://drafts.csswg.org/css-cascade/#cascade-origin
+fn precedence(origin: Origin, important: bool) -> u8 {
+    match (important, origin) {
+        (false, Origin::UserAgent) => 0,
+        (false, Origin::User) => 1,
+        (false, Origin::Author) => 2,
+        (true, Origin::Author) => 3,
+        (true, Origin::User) => 4,
+        (true, Origin::UserAgent) => 5,
+    }
+}
+
+/// 宣言の優先順位。出自と重要度、style属性の宣言かどうか、詳細度の順に比べる
+type CascadeKey = (u8, bool, Specificity);
+
+/// `node`に一致するルールの宣言と、style属性の宣言をすべて集め、プロパティ
+/// ごとに最も優先される宣言を選ぶ。出自と重要度、style属性の宣言かどうか、
+/// 詳細度、出現順の順に比べる。`style_sheets`は出現順に並べて渡す。結果は
+/// プロパティ名の順に並ぶ
+/// https://drafts.csswg.org/css-cascade/#cascade-sort
+pub fn cascade(
+    document: &Document,
+    node: NodeId,
+    style_sheets: &[&StyleSheet],
+) -> Vec<Declaration> {
+    let mut winners: BTreeMap<String, (CascadeKey, Declaration)> = BTreeMap::new();
+    // 出現順に見ていくので、優先順位が同じ場合は後の宣言で置き換える
+    let mut apply = |key: CascadeKey, declaration: &Declaration| {
+        let wins = winners
+            .get(&declaration.property)
+            .map_or(true, |(k, _)| *k <= key);
+        if wins {
+            winners.insert(declaration.property.clone(), (key, declaration.clone()));
+        }
+    };
+
+    for sheet in style_sheets {
+        for rule in &sheet.rules {
+            let specificity = match rule.selector.matching_specificity(document, node) {
+                Some(s) => s,
+                None => continue,
+            };
+            for declaration in &rule.declarations {
+                let precedence = precedence(sheet.origin, declaration.important);
+                apply((precedence, false, specificity), declaration);
+            }
+        }
+    }
+
+    // style属性の宣言は作者のものとして扱い、どのセレクタの宣言よりも優先する
+    // https://drafts.csswg.org/css-cascade/#style-attr
+    for declaration in &get_inline_style(document, node) {
+        let precedence = precedence(Origin::Author, declaration.important);
+        apply((precedence, true, Specificity::default()), declaration);
+    }
+
+    winners.into_values().map(|(_, d)| d).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::cssom::ComponentValue;
+    use crate::renderer::css::cssom::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+    use crate::renderer::dom::api::get_element_by_id;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use alloc::format;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn style_sheet(css: &str, origin: Origin) -> StyleSheet {
+        let mut sheet = CssParser::new(CssTokenizer::new(css.to_string())).parse_stylesheet();
+        sheet.set_origin(origin);
+        sheet
+    }
+
+    /// `<p id=p class="a b">`に対するカスケードの結果を(プロパティ, 値)で返す
+    fn cascade_values(style_sheets: &[&StyleSheet]) -> Vec<(String, ComponentValue)> {
+        cascade_values_with_style(style_sheets, None)
+    }
+
+    /// style属性を持つ`<p id=p class="a b">`に対するカスケードの結果を返す
+    fn cascade_values_with_style(
+        style_sheets: &[&StyleSheet],
+        style: Option<&str>,
+    ) -> Vec<(String, ComponentValue)> {
+        let style = style
+            .map(|s| format!(" style=\"{}\"", s))
+            .unwrap_or_default();
+        let html = format!(
+            "<html><head></head><body><p id=p class=\"a b\"{}>x</p></body></html>",
+            style
+        );
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = window.borrow().document();
+        let document = dom.borrow();
+        let p = get_element_by_id(&document, Some(document.root()), "p").expect("no p");
+        cascade(&document, p, style_sheets)
+            .into_iter()
+            .map(|d| (d.property, d.value))
+            .collect()
+    }
+
+    fn number(property: &str, n: f64) -> (String, ComponentValue) {
+        (property.to_string(), ComponentValue::Number(n))
+    }
+
+    fn ident(property: &str, value: &str) -> (String, ComponentValue) {
+        (
+            property.to_string(),
+            ComponentValue::Ident(value.to_string()),
+        )
+    }
+
+    #[test]
+    fn test_specificity_and_order() {
+        let author = style_sheet(
+            "#p { color: blue; } p { color: red; } \
+             .a { display: block; } .b { display: inline; } \
+             p.a { width: 1; } .a.b { width: 2; } p { width: 3; }",
+            Origin::Author,
+        );
+        assert_eq!(
+            vec![
+                ident("color", "blue"),
+                ident("display", "inline"),
+                number("width", 2.0),
+            ],
+            cascade_values(&[&author])
+        );
+    }
+
+    /// https://drafts.csswg.org/css-cascade/#example-8aaaa5b1
+    #[test]
+    fn test_importance() {
+        let user = style_sheet(
+            "p { text-indent: 1 !important; } p { font-style: italic !important; } \
+             p { font-size: 18; }",
+            Origin::User,
+        );
+        let author = style_sheet(
+            "p { text-indent: 1.5 !important; } p { font-style: normal !important; \
+             font-size: 12 !important; } p { font-size: 24; }",
+            Origin::Author,
+        );
+        assert_eq!(
+            vec![
+                number("font-size", 12.0),
+                ident("font-style", "italic"),
+                number("text-indent", 1.0),
+            ],
+            cascade_values(&[&user, &author])
+        );
+    }
+
+    #[test]
+    fn test_inline_style() {
+        let user = style_sheet("p { width: 1 !important; }", Origin::User);
+        let author = style_sheet(
+            "#p.a { color: blue; display: block !important; } p { height: 3; }",
+            Origin::Author,
+        );
+
+        // style属性の宣言はIDセレクタより優先されるが、重要な宣言には負ける
+        assert_eq!(
+            vec![
+                ident("color", "red"),
+                ident("display", "block"),
+                number("height", 3.0),
+                number("width", 1.0),
+            ],
+            cascade_values_with_style(
+                &[&user, &author],
+                Some("color: red; display: inline; width: 2 !important")
+            )
+        );
+    }
+
+    #[test]
+    fn test_origins() {
+        let user_agent = style_sheet(
+            "p { display: block; color: black; } p { width: 1 !important; }",
+            Origin::UserAgent,
+        );
+        let user = style_sheet("p { color: green; width: 2; }", Origin::User);
+        let author = style_sheet(
+            "#p.a { width: 3 !important; } p { color: red; }",
+            Origin::Author,
+        );
+
+        // 作者のスタイルシートが後から追加されても、出自の順は変わらない
+        assert_eq!(
+            vec![
+                ident("color", "red"),
+                ident("display", "block"),
+                number("width", 1.0),
+            ],
+            cascade_values(&[&author, &user_agent, &user])
+        );
+    }
+}
@@ -1,20 +1,34 @@
 use crate::alloc::string::ToString;
+use crate::renderer::css::cascade::Origin;
+use crate::renderer::css::selector::parse_an_plus_b;
+use crate::renderer::css::selector::AttributeOperator;
+use crate::renderer::css::selector::AttributeSelector;
+use crate::renderer::css::selector::Combinator;
+use crate::renderer::css::selector::ComplexSelector;
+use crate::renderer::css::selector::CompoundSelector;
+use crate::renderer::css::selector::PseudoClass;
+use crate::renderer::css::selector::SelectorList;
+use crate::renderer::css::selector::SimpleSelector;
 use crate::renderer::css::token::CssToken;
 use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::diagnostics::ParseDiagnostics;
 use alloc::string::String;
 use alloc::vec::Vec;
-use core::iter::Peekable;
 
 pub type ComponentValue = CssToken;
 
 #[derive(Debug, Clone)]
 pub struct CssParser {
-    t: Peekable<CssTokenizer>,
+    t: CssTokenizer,
 }
 
 impl CssParser {
     pub fn new(t: CssTokenizer) -> Self {
-        Self { t: t.peekable() }
+        Self { t }
+    }
+
+    pub fn diagnostics(&self) -> ParseDiagnostics {
+        self.t.diagnostics()
     }
 
     pub fn parse_stylesheet(&mut self) -> StyleSheet {
@@ -27,6 +41,38 @@ impl CssParser {
         sheet
     }
 
+    /// 入力全体を、波括弧で囲まれていない宣言のリストとしてパースする。
+    /// style属性の値をパースするときに使う
+    /// https://drafts.csswg.org/css-syntax-3/#parse-a-list-of-declarations
+    /// https://drafts.csswg.org/css-style-attr/#syntax
+    pub fn parse_declaration_list(&mut self) -> Vec<Declaration> {
+        let mut declarations = Vec::new();
+        while let Some(token) = self.t.peek() {
+            match token {
+                CssToken::Ident(_) => {
+                    if let Some(declaration) = self.consume_declaration() {
+                        declarations.push(declaration);
+                    }
+                }
+                _ => {
+                    self.t.next();
+                }
+            }
+        }
+        declarations
+    }
+
+    /// 入力全体を1つのセレクタとしてパースする。サポートしていないセレクタや、
+    /// セレクタの後に余分なトークンがある場合はNoneを返す
+    /// https://drafts.csswg.org/selectors-4/#parse-a-selector
+    pub fn parse_selector(&mut self) -> Option<SelectorList> {
+        let selectors = self.consume_selector_list();
+        if selectors.selectors().is_empty() || self.t.peek().is_some() {
+            return None;
+        }
+        Some(selectors)
+    }
+
     fn consume_list_of_rules(&mut self) -> Vec<QualifiedRule> {
         // 空のベクタを作成する
         let mut rules = Vec::new();
@@ -63,7 +109,11 @@ impl CssParser {
         loop {
             let token = match self.t.peek() {
                 Some(t) => t,
-                None => return None,
+                None => {
+                    // 宣言ブロックが始まる前に入力が終わったため、パースの失敗
+                    self.t.parse_error("eof-in-qualified-rule");
+                    return None;
+                }
             };
 
             match token {
@@ -73,49 +123,290 @@ impl CssParser {
                     return Some(rule);
                 }
                 _ => {
-                    rule.set_selector(self.consume_selector());
+                    rule.set_selector(self.consume_selector_list());
                 }
             }
         }
     }
 
-    fn consume_selector(&mut self) -> Selector {
-        let token = match self.t.next() {
-            Some(t) => t,
-            None => panic!("should have a token but got None"),
-        };
+    /// 宣言ブロックの開始直前までを、カンマ区切りのセレクタのリストとして
+    /// パースする。不正なセレクタが1つでもある場合はリスト全体が無効になり、
+    /// 何にも一致しない空のリストを返す
+    /// https://drafts.csswg.org/selectors-4/#invalid
+    fn consume_selector_list(&mut self) -> SelectorList {
+        if let Some(CssToken::AtKeyword(_)) = self.t.peek() {
+            // @から始まるルールを無視するために、宣言ブロックの開始直前まで
+            // トークンを進める
+            self.skip_until_open_curly();
+            return SelectorList::default();
+        }
 
-        match token {
-            CssToken::HashToken(value) => Selector::IdSelector(value[1..].to_string()),
-            CssToken::Delim(delim) => {
-                if delim == '.' {
-                    return Selector::ClassSelector(self.consume_ident());
+        match self.consume_complex_selectors(false) {
+            Some(list) => list,
+            None => {
+                self.skip_until_open_curly();
+                SelectorList::default()
+            }
+        }
+    }
+
+    /// カンマ区切りの複雑セレクタを、宣言ブロックの開始か入力の終わりまで
+    /// パースする。`nested`がtrueの場合は`:not()`の引数なので、閉じ括弧でも
+    /// 終わる。終わりのトークンは消費しない
+    fn consume_complex_selectors(&mut self, nested: bool) -> Option<SelectorList> {
+        let mut selectors = Vec::new();
+        loop {
+            selectors.push(self.consume_complex_selector(nested)?);
+            match self.t.peek() {
+                Some(CssToken::Delim(',')) => {
+                    self.t.next();
                 }
-                panic!("Parse error: {:?} is an unexpected token.", token);
+                _ => return Some(SelectorList::new(selectors)),
             }
-            CssToken::Ident(ident) => {
-                // a:hoverのようなセレクタはタイプセレクタとして扱うため、もし
-                // コロン (:) が出てきた場合は宣言ブロックの開始直前までトークン
-                // を進める
-                if self.t.peek() == Some(&CssToken::Colon) {
-                    while self.t.peek() != Some(&CssToken::OpenCurly) {
-                        self.t.next();
-                    }
+        }
+    }
+
+    /// https://drafts.csswg.org/selectors-4/#typedef-complex-selector
+    fn consume_complex_selector(&mut self, nested: bool) -> Option<ComplexSelector> {
+        let mut compounds = Vec::new();
+        let mut combinators = Vec::new();
+
+        loop {
+            compounds.push(self.consume_compound_selector()?);
+
+            let token = self.t.peek().cloned();
+            let combinator = match token {
+                None | Some(CssToken::OpenCurly) | Some(CssToken::Delim(',')) => break,
+                Some(CssToken::CloseParenthesis) if nested => break,
+                Some(CssToken::Delim('>')) => Combinator::Child,
+                Some(CssToken::Delim('+')) => Combinator::NextSibling,
+                Some(CssToken::Delim('~')) => Combinator::SubsequentSibling,
+                // 空白文字で区切られた複合セレクタは子孫結合子でつながる
+                Some(_) if self.t.preceded_by_whitespace() => {
+                    combinators.push(Combinator::Descendant);
+                    continue;
+                }
+                Some(_) => {
+                    self.t.parse_error("unexpected-token-in-selector");
+                    return None;
                 }
-                Selector::TypeSelector(ident.to_string())
+            };
+            self.t.next();
+            combinators.push(combinator);
+        }
+
+        Some(ComplexSelector::new(compounds, combinators))
+    }
+
+    /// 空白文字を挟まずに続く単純セレクタを、複合セレクタとしてパースする。
+    /// タイプセレクタと全称セレクタは先頭にだけ書ける
+    /// https://drafts.csswg.org/selectors-4/#typedef-compound-selector
+    fn consume_compound_selector(&mut self) -> Option<CompoundSelector> {
+        let mut selectors = Vec::new();
+
+        while let Some(token) = self.t.peek().cloned() {
+            if !selectors.is_empty() && self.t.preceded_by_whitespace() {
+                break;
             }
-            CssToken::AtKeyword(_keyword) => {
-                // @から始まるルールを無視するために、宣言ブロックの開始直前まで
-                // トークンを進める
-                while self.t.peek() != Some(&CssToken::OpenCurly) {
+
+            let selector = match token {
+                CssToken::Ident(name) if selectors.is_empty() => {
+                    self.t.next();
+                    SimpleSelector::Type(name.to_ascii_lowercase())
+                }
+                CssToken::Delim('*') if selectors.is_empty() => {
+                    self.t.next();
+                    SimpleSelector::Universal
+                }
+                CssToken::HashToken(value) if value.len() > 1 => {
                     self.t.next();
+                    SimpleSelector::Id(value[1..].to_string())
                 }
-                Selector::UnknownSelector
+                CssToken::Delim('.') => {
+                    self.t.next();
+                    SimpleSelector::Class(self.consume_adjacent_ident()?)
+                }
+                CssToken::Delim('[') => {
+                    self.t.next();
+                    SimpleSelector::Attribute(self.consume_attribute_selector()?)
+                }
+                CssToken::Colon => {
+                    self.t.next();
+                    SimpleSelector::PseudoClass(self.consume_pseudo_class()?)
+                }
+                _ => break,
+            };
+            selectors.push(selector);
+        }
+
+        if selectors.is_empty() {
+            self.t.parse_error("unexpected-token-in-selector");
+            return None;
+        }
+        Some(CompoundSelector::new(selectors))
+    }
+
+    /// 直前のトークンとの間に空白文字を挟まない識別子を消費する
+    fn consume_adjacent_ident(&mut self) -> Option<String> {
+        let token = self.t.peek().cloned();
+        match token {
+            Some(CssToken::Ident(ident)) if !self.t.preceded_by_whitespace() => {
+                self.t.next();
+                Some(ident)
             }
             _ => {
+                self.t.parse_error("expected-ident");
+                None
+            }
+        }
+    }
+
+    /// `[`の後から`]`までを属性セレクタとしてパースする
+    /// https://drafts.csswg.org/selectors-4/#attribute-selectors
+    fn consume_attribute_selector(&mut self) -> Option<AttributeSelector> {
+        let name = self.consume_ident()?;
+
+        let operator = match self.t.next() {
+            Some(CssToken::Delim(']')) => return Some(AttributeSelector::new(&name, None)),
+            Some(CssToken::Delim('=')) => AttributeOperator::Equal,
+            Some(CssToken::Delim(c)) if self.t.peek() == Some(&CssToken::Delim('=')) => {
+                let operator = match c {
+                    '~' => AttributeOperator::Includes,
+                    '|' => AttributeOperator::DashMatch,
+                    '^' => AttributeOperator::Prefix,
+                    '$' => AttributeOperator::Suffix,
+                    '*' => AttributeOperator::Substring,
+                    _ => {
+                        self.t.parse_error("unexpected-token-in-attribute-selector");
+                        return None;
+                    }
+                };
                 self.t.next();
-                Selector::UnknownSelector
+                operator
+            }
+            _ => {
+                self.t.parse_error("unexpected-token-in-attribute-selector");
+                return None;
+            }
+        };
+
+        let value = match self.t.next() {
+            Some(CssToken::Ident(value)) | Some(CssToken::StringToken(value)) => value,
+            _ => {
+                self.t.parse_error("unexpected-token-in-attribute-selector");
+                return None;
             }
+        };
+
+        if self.t.next() != Some(CssToken::Delim(']')) {
+            self.t.parse_error("unexpected-token-in-attribute-selector");
+            return None;
+        }
+        Some(AttributeSelector::new(&name, Some((operator, value))))
+    }
+
+    /// `:`の後から疑似クラスをパースする。疑似要素とサポートしていない疑似クラス
+    /// はパースの失敗になる
+    /// https://drafts.csswg.org/selectors-4/#pseudo-classes
+    fn consume_pseudo_class(&mut self) -> Option<PseudoClass> {
+        let name = self.consume_adjacent_ident()?.to_ascii_lowercase();
+
+        let pseudo_class = match name.as_str() {
+            "root" => PseudoClass::Root,
+            "empty" => PseudoClass::Empty,
+            "first-child" => PseudoClass::FirstChild,
+            "last-child" => PseudoClass::LastChild,
+            "only-child" => PseudoClass::OnlyChild,
+            "link" => PseudoClass::Link,
+            "visited" => PseudoClass::Visited,
+            "hover" => PseudoClass::Hover,
+            "active" => PseudoClass::Active,
+            "focus" => PseudoClass::Focus,
+            "nth-child" | "nth-last-child" | "not" => {
+                let token = self.t.peek().cloned();
+                match token {
+                    Some(CssToken::OpenParenthesis) if !self.t.preceded_by_whitespace() => {
+                        self.t.next();
+                    }
+                    _ => {
+                        self.t.parse_error("unexpected-token-in-selector");
+                        return None;
+                    }
+                }
+
+                let pseudo_class = if name == "not" {
+                    PseudoClass::Not(self.consume_complex_selectors(true)?)
+                } else {
+                    let (a, b) = match parse_an_plus_b(&self.consume_an_plus_b_text()?) {
+                        Some(ab) => ab,
+                        None => {
+                            self.t.parse_error("invalid-an-plus-b");
+                            return None;
+                        }
+                    };
+                    if name == "nth-child" {
+                        PseudoClass::NthChild(a, b)
+                    } else {
+                        PseudoClass::NthLastChild(a, b)
+                    }
+                };
+
+                // 閉じ括弧がないまま宣言ブロックや入力の終わりに達した場合は、
+                // 宣言ブロックの"{"を消費せずにセレクタを無効にする
+                if self.t.peek() != Some(&CssToken::CloseParenthesis) {
+                    self.t.parse_error("unexpected-token-in-selector");
+                    return None;
+                }
+                self.t.next();
+                pseudo_class
+            }
+            _ => {
+                self.t.parse_error("unknown-pseudo-class");
+                return None;
+            }
+        };
+        Some(pseudo_class)
+    }
+
+    /// 閉じ括弧の直前までのトークンを、`an+b`の式の文字列に戻す。閉じ括弧は
+    /// 消費しない
+    /// https://drafts.csswg.org/css-syntax-3/#anb-microsyntax
+    fn consume_an_plus_b_text(&mut self) -> Option<String> {
+        let mut text = String::new();
+        loop {
+            let token = match self.t.peek() {
+                Some(CssToken::CloseParenthesis) => return Some(text),
+                Some(t) => t.clone(),
+                None => {
+                    self.t.parse_error("unexpected-token-in-selector");
+                    return None;
+                }
+            };
+            if self.t.preceded_by_whitespace() {
+                text.push(' ');
+            }
+            match token {
+                CssToken::Number(n) if n == (n as i64) as f64 => {
+                    text.push_str(&(n as i64).to_string())
+                }
+                CssToken::Ident(ident) => text.push_str(&ident),
+                CssToken::Delim(c) => text.push(c),
+                _ => {
+                    self.t.parse_error("invalid-an-plus-b");
+                    return None;
+                }
+            }
+            self.t.next();
+        }
+    }
+
+    /// 宣言ブロックの開始直前、または入力の終わりまでトークンを進める
+    fn skip_until_open_curly(&mut self) {
+        while let Some(token) = self.t.peek() {
+            if *token == CssToken::OpenCurly {
+                return;
+            }
+            self.t.next();
         }
     }
 
@@ -125,7 +416,11 @@ impl CssParser {
         loop {
             let token = match self.t.peek() {
                 Some(t) => t,
-                None => return declarations,
+                None => {
+                    // 宣言ブロックが閉じられる前に入力が終わったため、パースの失敗
+                    self.t.parse_error("eof-in-block");
+                    return declarations;
+                }
             };
 
             match token {
@@ -157,39 +452,80 @@ impl CssParser {
         // Declaration構造体を初期化する
         let mut declaration = Declaration::new();
         // Declaration構造体のプロパティに識別子を設定する
-        declaration.set_property(self.consume_ident());
+        declaration.set_property(self.consume_ident()?);
         // もし次のトークンがコロンでない場合、パースエラーなので、Noneを返す
-        match self.t.next() {
-            Some(token) => match token {
-                CssToken::Colon => {}
-                _ => return None,
-            },
-            None => return None,
+        match self.t.peek() {
+            Some(CssToken::Colon) => {
+                self.t.next();
+            }
+            _ => {
+                self.t.parse_error("missing-colon-in-declaration");
+                self.skip_until_end_of_declaration();
+                return None;
+            }
         }
 
         // Declaration構造体の値にコンポーネント値を設定する
-        declaration.set_value(self.consume_component_value());
+        declaration.set_value(self.consume_component_value()?);
+
+        // 最初のコンポーネント値だけを値として使う。`10px`の単位のような残りの
+        // トークンは、宣言の終わりまで読み飛ばす
+        let mut rest = Vec::new();
+        while let Some(token) = self.t.peek() {
+            if *token == CssToken::SemiColon || *token == CssToken::CloseCurly {
+                break;
+            }
+            rest.extend(self.t.next());
+        }
+
+        // 宣言の最後が"!important"の場合は、重要な宣言になる
+        // https://drafts.csswg.org/css-syntax/#consume-declaration
+        // https://drafts.csswg.org/css-cascade/#importance
+        match rest.as_slice() {
+            [.., CssToken::Delim('!'), CssToken::Ident(ident)]
+                if ident.eq_ignore_ascii_case("important") =>
+            {
+                declaration.set_important(true);
+            }
+            _ if rest.contains(&CssToken::Delim('!')) => {
+                self.t.parse_error("invalid-important");
+                return None;
+            }
+            _ => {}
+        }
         Some(declaration)
     }
 
-    fn consume_ident(&mut self) -> String {
-        let token = match self.t.next() {
-            Some(t) => t,
-            None => panic!("should have a token but got None"),
-        };
+    /// 宣言の終わりまでトークンを読み飛ばす
+    fn skip_until_end_of_declaration(&mut self) {
+        while let Some(token) = self.t.peek() {
+            if *token == CssToken::SemiColon || *token == CssToken::CloseCurly {
+                break;
+            }
+            self.t.next();
+        }
+    }
 
-        match token {
-            CssToken::Ident(ref ident) => ident.to_string(),
+    fn consume_ident(&mut self) -> Option<String> {
+        match self.t.peek() {
+            Some(CssToken::Ident(ident)) => {
+                let ident = ident.to_string();
+                self.t.next();
+                Some(ident)
+            }
             _ => {
-                panic!("Parse error: {:?} is an unexpected token.", token);
+                self.t.parse_error("expected-ident");
+                None
             }
         }
     }
 
-    fn consume_component_value(&mut self) -> ComponentValue {
-        self.t
-            .next()
-            .expect("should have a token in consume_component_value")
+    fn consume_component_value(&mut self) -> Option<ComponentValue> {
+        let value = self.t.next();
+        if value.is_none() {
+            self.t.parse_error("missing-declaration-value");
+        }
+        value
     }
 }
 
@@ -197,11 +533,20 @@ impl CssParser {
 pub struct StyleSheet {
     /// https://drafts.csswg.org/cssom/#dom-cssstylesheet-cssrules
     pub rules: Vec<QualifiedRule>,
+    /// スタイルシートを作成した主体。カスケードで宣言の優先順位を決めるのに使う
+    pub origin: Origin,
 }
 
 impl StyleSheet {
     pub fn new() -> Self {
-        Self { rules: Vec::new() }
+        Self {
+            rules: Vec::new(),
+            origin: Origin::Author,
+        }
+    }
+
+    pub fn set_origin(&mut self, origin: Origin) {
+        self.origin = origin;
     }
 
     pub fn set_rules(&mut self, rules: Vec<QualifiedRule>) {
@@ -212,7 +557,7 @@ impl StyleSheet {
 #[derive(Debug, Clone, PartialEq)]
 pub struct QualifiedRule {
     /// https://www.w3.org/TR/selectors-4/#typedef-selector-list
-    pub selector: Selector,
+    pub selector: SelectorList,
     /// https://www.w3.org/TR/css-syntax-3/#parse-a-list-of-declarations
     pub declarations: Vec<Declaration>,
 }
@@ -220,12 +565,12 @@ pub struct QualifiedRule {
 impl QualifiedRule {
     pub fn new() -> Self {
         Self {
-            selector: Selector::TypeSelector("".to_string()),
+            selector: SelectorList::default(),
             declarations: Vec::new(),
         }
     }
 
-    pub fn set_selector(&mut self, selector: Selector) {
+    pub fn set_selector(&mut self, selector: SelectorList) {
         self.selector = selector;
     }
 
@@ -234,22 +579,13 @@ impl QualifiedRule {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Selector {
-    /// https://www.w3.org/TR/selectors-4/#type-selectors
-    TypeSelector(String),
-    /// https://www.w3.org/TR/selectors-4/#class-html
-    ClassSelector(String),
-    /// https://www.w3.org/TR/selectors-4/#id-selectors
-    IdSelector(String),
-    /// パース中にエラーが起こったときに使用されるセレクタ
-    UnknownSelector,
-}
-
 #[derive(Debug, Clone, PartialEq)]
 pub struct Declaration {
     pub property: String,
     pub value: ComponentValue,
+    /// "!important"が付いているかどうか
+    /// https://drafts.csswg.org/css-cascade/#important
+    pub important: bool,
 }
 
 impl Declaration {
@@ -257,9 +593,14 @@ impl Declaration {
         Self {
             property: String::new(),
             value: ComponentValue::Ident(String::new()),
+            important: false,
         }
     }
 
+    pub fn set_important(&mut self, important: bool) {
+        self.important = important;
+    }
+
     pub fn set_property(&mut self, property: String) {
         self.property = property;
     }
@@ -274,6 +615,14 @@ mod tests {
     use super::*;
     use alloc::vec;
 
+    /// 1つの単純セレクタだけからなるセレクタのリストを作る
+    fn simple(selector: SimpleSelector) -> SelectorList {
+        SelectorList::new(vec![ComplexSelector::new(
+            vec![CompoundSelector::new(vec![selector])],
+            Vec::new(),
+        )])
+    }
+
     #[test]
     fn test_empty() {
         let style = "".to_string();
@@ -290,7 +639,7 @@ mod tests {
         let cssom = CssParser::new(t).parse_stylesheet();
 
         let mut rule = QualifiedRule::new();
-        rule.set_selector(Selector::TypeSelector("p".to_string()));
+        rule.set_selector(simple(SimpleSelector::Type("p".to_string())));
         let mut declaration = Declaration::new();
         declaration.set_property("color".to_string());
         declaration.set_value(ComponentValue::Ident("red".to_string()));
@@ -313,7 +662,7 @@ mod tests {
         let cssom = CssParser::new(t).parse_stylesheet();
 
         let mut rule = QualifiedRule::new();
-        rule.set_selector(Selector::IdSelector("id".to_string()));
+        rule.set_selector(simple(SimpleSelector::Id("id".to_string())));
         let mut declaration = Declaration::new();
         declaration.set_property("color".to_string());
         declaration.set_value(ComponentValue::Ident("red".to_string()));
@@ -336,7 +685,7 @@ mod tests {
         let cssom = CssParser::new(t).parse_stylesheet();
 
         let mut rule = QualifiedRule::new();
-        rule.set_selector(Selector::ClassSelector("class".to_string()));
+        rule.set_selector(simple(SimpleSelector::Class("class".to_string())));
         let mut declaration = Declaration::new();
         declaration.set_property("color".to_string());
         declaration.set_value(ComponentValue::Ident("red".to_string()));
@@ -359,14 +708,14 @@ mod tests {
         let cssom = CssParser::new(t).parse_stylesheet();
 
         let mut rule1 = QualifiedRule::new();
-        rule1.set_selector(Selector::TypeSelector("p".to_string()));
+        rule1.set_selector(simple(SimpleSelector::Type("p".to_string())));
         let mut declaration1 = Declaration::new();
         declaration1.set_property("content".to_string());
         declaration1.set_value(ComponentValue::StringToken("Hey".to_string()));
         rule1.set_declarations(vec![declaration1]);
 
         let mut rule2 = QualifiedRule::new();
-        rule2.set_selector(Selector::TypeSelector("h1".to_string()));
+        rule2.set_selector(simple(SimpleSelector::Type("h1".to_string())));
         let mut declaration2 = Declaration::new();
         declaration2.set_property("font-size".to_string());
         declaration2.set_value(ComponentValue::Number(40.0));
@@ -384,4 +733,223 @@ mod tests {
             i += 1;
         }
     }
+
+    #[test]
+    fn test_parse_selector() {
+        let parse = |s: &str| CssParser::new(CssTokenizer::new(s.to_string())).parse_selector();
+
+        assert_eq!(
+            Some(simple(SimpleSelector::Type("p".to_string()))),
+            parse("p")
+        );
+        assert_eq!(
+            Some(simple(SimpleSelector::Class("a".to_string()))),
+            parse(" .a ")
+        );
+        assert_eq!(
+            Some(simple(SimpleSelector::Id("b".to_string()))),
+            parse("#b")
+        );
+        assert_eq!(None, parse(""));
+        assert_eq!(None, parse("p {"));
+        assert_eq!(None, parse("@media"));
+    }
+
+    #[test]
+    fn test_complex_selectors() {
+        let parse = |s: &str| CssParser::new(CssTokenizer::new(s.to_string())).parse_selector();
+
+        let type_selector = |name: &str| SimpleSelector::Type(name.to_string());
+        let expected = SelectorList::new(vec![
+            ComplexSelector::new(
+                vec![
+                    CompoundSelector::new(vec![
+                        type_selector("p"),
+                        SimpleSelector::Class("note".to_string()),
+                        SimpleSelector::Id("x".to_string()),
+                    ]),
+                    CompoundSelector::new(vec![type_selector("a")]),
+                    CompoundSelector::new(vec![SimpleSelector::Universal]),
+                    CompoundSelector::new(vec![type_selector("h1")]),
+                    CompoundSelector::new(vec![SimpleSelector::Attribute(AttributeSelector::new(
+                        "lang",
+                        Some((AttributeOperator::DashMatch, "en".to_string())),
+                    ))]),
+                ],
+                vec![
+                    Combinator::Descendant,
+                    Combinator::Child,
+                    Combinator::NextSibling,
+                    Combinator::SubsequentSibling,
+                ],
+            ),
+            ComplexSelector::new(
+                vec![CompoundSelector::new(vec![
+                    type_selector("li"),
+                    SimpleSelector::PseudoClass(PseudoClass::NthChild(2, 1)),
+                    SimpleSelector::PseudoClass(PseudoClass::Not(simple(SimpleSelector::Class(
+                        "a".to_string(),
+                    )))),
+                ])],
+                Vec::new(),
+            ),
+        ]);
+        assert_eq!(
+            Some(expected),
+            parse("P.note#x a>*  +  h1 ~ [LANG|=\"en\"], li:nth-child(2n + 1):not(.a)")
+        );
+
+        assert_eq!(
+            Some(simple(SimpleSelector::PseudoClass(PseudoClass::FirstChild))),
+            parse(":first-child")
+        );
+        for invalid in [
+            "p..a",
+            "p . a",
+            "a >",
+            "p, ",
+            "p:unknown",
+            "p::before",
+            "p:nth-child(foo)",
+            "p:not(.a",
+            "[x=1]",
+            "*p",
+        ] {
+            assert_eq!(None, parse(invalid), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_declaration_list() {
+        let style = "color: red; ;display:block !important; width 1; height: 2";
+        let mut parser = CssParser::new(CssTokenizer::new(style.to_string()));
+        let declarations = parser.parse_declaration_list();
+
+        let values: Vec<(&str, &ComponentValue, bool)> = declarations
+            .iter()
+            .map(|d| (d.property.as_str(), &d.value, d.important))
+            .collect();
+        assert_eq!(
+            vec![
+                ("color", &ComponentValue::Ident("red".to_string()), false),
+                ("display", &ComponentValue::Ident("block".to_string()), true),
+                ("height", &ComponentValue::Number(2.0), false),
+            ],
+            values
+        );
+        assert_eq!(
+            "missing-colon-in-declaration",
+            parser.diagnostics().errors()[0].name()
+        );
+    }
+
+    #[test]
+    fn test_important() {
+        let style = "p { color: red !important; display: block ! IMPORTANT; width: 1 !foo; }";
+        let mut parser = CssParser::new(CssTokenizer::new(style.to_string()));
+        let cssom = parser.parse_stylesheet();
+
+        let declarations = &cssom.rules[0].declarations;
+        let important: Vec<(&str, bool)> = declarations
+            .iter()
+            .map(|d| (d.property.as_str(), d.important))
+            .collect();
+        assert_eq!(vec![("color", true), ("display", true)], important);
+        assert_eq!(
+            ComponentValue::Ident("red".to_string()),
+            declarations[0].value
+        );
+        assert_eq!("invalid-important", parser.diagnostics().errors()[0].name());
+    }
+
+    #[test]
+    fn test_important_with_unit() {
+        // `10px`は数値と識別子の2つのトークンになる
+        let style = "p { width: 10px !important; height: 20px; }";
+        let mut parser = CssParser::new(CssTokenizer::new(style.to_string()));
+        let cssom = parser.parse_stylesheet();
+
+        let declarations: Vec<(&str, &ComponentValue, bool)> = cssom.rules[0]
+            .declarations
+            .iter()
+            .map(|d| (d.property.as_str(), &d.value, d.important))
+            .collect();
+        assert_eq!(
+            vec![
+                ("width", &ComponentValue::Number(10.0), true),
+                ("height", &ComponentValue::Number(20.0), false),
+            ],
+            declarations
+        );
+        assert!(parser.diagnostics().errors().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_selector_list() {
+        // リストの中に不正なセレクタが1つでもあると、ルール全体が何にも一致しない
+        let style = "h1, p:hover, p::before { color: red; } p:first-child { color: blue; }";
+        let mut parser = CssParser::new(CssTokenizer::new(style.to_string()));
+        let cssom = parser.parse_stylesheet();
+
+        assert_eq!(2, cssom.rules.len());
+        assert_eq!(SelectorList::default(), cssom.rules[0].selector);
+        assert_eq!(1, cssom.rules[0].declarations.len());
+        assert_eq!(
+            simple(SimpleSelector::Type("p".to_string())).selectors()[0].compounds()[0].selectors()
+                [0],
+            cssom.rules[1].selector.selectors()[0].compounds()[0].selectors()[0]
+        );
+        assert_eq!("expected-ident", parser.diagnostics().errors()[0].name());
+    }
+
+    #[test]
+    fn test_unterminated_not() {
+        // 閉じ括弧のない:not(は、宣言ブロックを残したままセレクタだけが無効になる
+        let style = "p:not(.a { color: red; } h1 { color: blue; }";
+        let mut parser = CssParser::new(CssTokenizer::new(style.to_string()));
+        let cssom = parser.parse_stylesheet();
+
+        assert_eq!(2, cssom.rules.len());
+        assert_eq!(SelectorList::default(), cssom.rules[0].selector);
+        assert_eq!(1, cssom.rules[0].declarations.len());
+        assert_eq!(
+            simple(SimpleSelector::Type("h1".to_string())),
+            cssom.rules[1].selector
+        );
+        assert_eq!(
+            "unexpected-token-in-selector",
+            parser.diagnostics().errors()[0].name()
+        );
+
+        // 入力の終わりに達した場合もセレクタは無効になる
+        let mut parser = CssParser::new(CssTokenizer::new("p:not(.a".to_string()));
+        assert_eq!(None, parser.parse_selector());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let style = "p { color red; }\n% { color: blue; }\nh1 { color:".to_string();
+        let t = CssTokenizer::new(style);
+        let mut parser = CssParser::new(t);
+        let cssom = parser.parse_stylesheet();
+
+        assert_eq!(3, cssom.rules.len());
+        assert!(cssom.rules[0].declarations.is_empty());
+
+        let errors = parser.diagnostics().errors();
+        let names: Vec<String> = errors.iter().map(|e| e.name()).collect();
+        assert_eq!(
+            vec![
+                "missing-colon-in-declaration".to_string(),
+                "unexpected-token-in-selector".to_string(),
+                "missing-declaration-value".to_string(),
+                "eof-in-block".to_string(),
+            ],
+            names
+        );
+        assert_eq!(1, errors[0].position().line());
+        assert_eq!(11, errors[0].position().column());
+        assert_eq!(2, errors[1].position().line());
+        assert_eq!(1, errors[1].position().column());
+    }
 }
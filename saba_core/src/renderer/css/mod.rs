@@ -1,2 +1,5 @@
+pub mod cascade;
 pub mod cssom;
+pub mod selector;
 pub mod token;
+pub mod user_agent;
@@ -0,0 +1,606 @@
+//! https://www.w3.org/TR/selectors-3/
+//! https://drafts.csswg.org/selectors-4/
+
+use crate::alloc::string::ToString;
+use crate::renderer::dom::api::ancestors;
+use crate::renderer::dom::api::children;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::ElementKind;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::node::NodeKind;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// カンマで区切られたセレクタのリスト。いずれかのセレクタに一致すれば一致する
+/// https://drafts.csswg.org/selectors-4/#typedef-selector-list
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SelectorList {
+    selectors: Vec<ComplexSelector>,
+}
+
+impl SelectorList {
+    pub fn new(selectors: Vec<ComplexSelector>) -> Self {
+        Self { selectors }
+    }
+
+    pub fn selectors(&self) -> &[ComplexSelector] {
+        &self.selectors
+    }
+
+    /// `node`がいずれかのセレクタに一致する要素かどうかを返す。CSSのカスケードと
+    /// `querySelector`で共通して使う
+    /// https://drafts.csswg.org/selectors-4/#match-a-selector-against-an-element
+    pub fn matches(&self, document: &Document, node: NodeId) -> bool {
+        self.selectors.iter().any(|s| s.matches(document, node))
+    }
+
+    /// `node`に一致するセレクタのうち、最も大きい詳細度を返す。一致するセレクタが
+    /// ない場合はNoneを返す
+    /// https://drafts.csswg.org/selectors-4/#specificity-rules
+    pub fn matching_specificity(&self, document: &Document, node: NodeId) -> Option<Specificity> {
+        self.selectors
+            .iter()
+            .filter(|s| s.matches(document, node))
+            .map(|s| s.specificity())
+            .max()
+    }
+
+    /// リストの中で最も大きい詳細度。`:not()`の引数の詳細度として使う
+    fn max_specificity(&self) -> Specificity {
+        self.selectors
+            .iter()
+            .map(|s| s.specificity())
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// セレクタの詳細度。IDセレクタの数、クラスセレクタと属性セレクタと疑似クラスの
+/// 数、タイプセレクタの数の順に比べる
+/// https://drafts.csswg.org/selectors-4/#specificity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Specificity {
+    ids: u32,
+    classes: u32,
+    types: u32,
+}
+
+impl Specificity {
+    pub fn new(ids: u32, classes: u32, types: u32) -> Self {
+        Self {
+            ids,
+            classes,
+            types,
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self::new(
+            self.ids + other.ids,
+            self.classes + other.classes,
+            self.types + other.types,
+        )
+    }
+}
+
+/// 複合セレクタ同士の関係
+/// https://drafts.csswg.org/selectors-4/#combinators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combinator {
+    /// `A B`
+    Descendant,
+    /// `A > B`
+    Child,
+    /// `A + B`
+    NextSibling,
+    /// `A ~ B`
+    SubsequentSibling,
+}
+
+/// 複合セレクタを結合子でつないだセレクタ。最後の複合セレクタが、一致するかを
+/// 判定する要素(subject)を表す
+/// https://drafts.csswg.org/selectors-4/#complex
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComplexSelector {
+    compounds: Vec<CompoundSelector>,
+    /// `combinators[i]`は`compounds[i]`と`compounds[i + 1]`の関係を表す
+    combinators: Vec<Combinator>,
+}
+
+impl ComplexSelector {
+    /// `compounds`の数は`combinators`の数より1つ多くなければならない
+    pub fn new(compounds: Vec<CompoundSelector>, combinators: Vec<Combinator>) -> Self {
+        assert_eq!(compounds.len(), combinators.len() + 1);
+        Self {
+            compounds,
+            combinators,
+        }
+    }
+
+    pub fn compounds(&self) -> &[CompoundSelector] {
+        &self.compounds
+    }
+
+    pub fn combinators(&self) -> &[Combinator] {
+        &self.combinators
+    }
+
+    pub fn matches(&self, document: &Document, node: NodeId) -> bool {
+        self.matches_from(self.compounds.len() - 1, document, node)
+    }
+
+    /// 結合子は詳細度に影響しないので、すべての単純セレクタの詳細度を足す
+    pub fn specificity(&self) -> Specificity {
+        self.compounds
+            .iter()
+            .flat_map(|c| c.selectors.iter())
+            .fold(Specificity::default(), |sum, s| sum.add(s.specificity()))
+    }
+
+    /// `compounds[index]`が`node`に一致し、その左側の複合セレクタも結合子の
+    /// 関係にある要素に一致するかどうかを、右から左に向かって判定する
+    fn matches_from(&self, index: usize, document: &Document, node: NodeId) -> bool {
+        if !self.compounds[index].matches(document, node) {
+            return false;
+        }
+        if index == 0 {
+            return true;
+        }
+
+        let next = index - 1;
+        match self.combinators[next] {
+            Combinator::Descendant => {
+                ancestors(document, node).any(|a| self.matches_from(next, document, a))
+            }
+            Combinator::Child => document
+                .parent(node)
+                .is_some_and(|p| self.matches_from(next, document, p)),
+            Combinator::NextSibling => previous_element_sibling(document, node)
+                .is_some_and(|s| self.matches_from(next, document, s)),
+            Combinator::SubsequentSibling => {
+                let mut sibling = previous_element_sibling(document, node);
+                while let Some(s) = sibling {
+                    if self.matches_from(next, document, s) {
+                        return true;
+                    }
+                    sibling = previous_element_sibling(document, s);
+                }
+                false
+            }
+        }
+    }
+}
+
+/// 結合子を含まない単純セレクタの並び。すべての単純セレクタに一致すれば一致する
+/// https://drafts.csswg.org/selectors-4/#compound
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompoundSelector {
+    selectors: Vec<SimpleSelector>,
+}
+
+impl CompoundSelector {
+    pub fn new(selectors: Vec<SimpleSelector>) -> Self {
+        Self { selectors }
+    }
+
+    pub fn selectors(&self) -> &[SimpleSelector] {
+        &self.selectors
+    }
+
+    pub fn matches(&self, document: &Document, node: NodeId) -> bool {
+        document.element(node).is_some() && self.selectors.iter().all(|s| s.matches(document, node))
+    }
+}
+
+/// https://drafts.csswg.org/selectors-4/#simple
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimpleSelector {
+    /// https://drafts.csswg.org/selectors-4/#the-universal-selector
+    Universal,
+    /// https://drafts.csswg.org/selectors-4/#type-selectors
+    Type(String),
+    /// https://drafts.csswg.org/selectors-4/#class-html
+    Class(String),
+    /// https://drafts.csswg.org/selectors-4/#id-selectors
+    Id(String),
+    /// https://drafts.csswg.org/selectors-4/#attribute-selectors
+    Attribute(AttributeSelector),
+    /// https://drafts.csswg.org/selectors-4/#pseudo-classes
+    PseudoClass(PseudoClass),
+}
+
+impl SimpleSelector {
+    fn specificity(&self) -> Specificity {
+        match self {
+            SimpleSelector::Universal => Specificity::default(),
+            SimpleSelector::Type(_) => Specificity::new(0, 0, 1),
+            SimpleSelector::Id(_) => Specificity::new(1, 0, 0),
+            // `:not()`の詳細度は、引数の中で最も詳細度の大きいセレクタの詳細度
+            SimpleSelector::PseudoClass(PseudoClass::Not(list)) => list.max_specificity(),
+            SimpleSelector::Class(_)
+            | SimpleSelector::Attribute(_)
+            | SimpleSelector::PseudoClass(_) => Specificity::new(0, 1, 0),
+        }
+    }
+
+    fn matches(&self, document: &Document, node: NodeId) -> bool {
+        let element = match document.element(node) {
+            Some(e) => e,
+            None => return false,
+        };
+
+        match self {
+            SimpleSelector::Universal => true,
+            SimpleSelector::Type(type_name) => element.kind().to_string() == *type_name,
+            // class属性はスペース区切りのクラス名のリスト
+            SimpleSelector::Class(class_name) => element
+                .get_attribute("class")
+                .is_some_and(|classes| classes.split_ascii_whitespace().any(|c| c == class_name)),
+            SimpleSelector::Id(id_name) => element.get_attribute("id") == Some(id_name.as_str()),
+            SimpleSelector::Attribute(attribute) => {
+                attribute.matches(element.get_attribute(&attribute.name))
+            }
+            SimpleSelector::PseudoClass(pseudo_class) => pseudo_class.matches(document, node),
+        }
+    }
+}
+
+/// 属性の値の比べ方
+/// https://drafts.csswg.org/selectors-4/#attribute-representation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeOperator {
+    /// `[name=value]`
+    Equal,
+    /// `[name~=value]` 空白区切りの値のいずれかと等しい
+    Includes,
+    /// `[name|=value]` 値と等しいか、値にハイフンが続く
+    DashMatch,
+    /// `[name^=value]`
+    Prefix,
+    /// `[name$=value]`
+    Suffix,
+    /// `[name*=value]`
+    Substring,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeSelector {
+    /// 属性名。HTMLの属性名は小文字なので、小文字にして持つ
+    name: String,
+    /// 値を比べない`[name]`の場合はNone
+    value: Option<(AttributeOperator, String)>,
+}
+
+impl AttributeSelector {
+    pub fn new(name: &str, value: Option<(AttributeOperator, String)>) -> Self {
+        Self {
+            name: name.to_ascii_lowercase(),
+            value,
+        }
+    }
+
+    fn matches(&self, attribute: Option<&str>) -> bool {
+        let attribute = match attribute {
+            Some(a) => a,
+            None => return false,
+        };
+        let (operator, value) = match &self.value {
+            Some((operator, value)) => (operator, value.as_str()),
+            None => return true,
+        };
+
+        match operator {
+            AttributeOperator::Equal => attribute == value,
+            AttributeOperator::Includes => {
+                !value.is_empty() && attribute.split_ascii_whitespace().any(|v| v == value)
+            }
+            AttributeOperator::DashMatch => {
+                attribute == value
+                    || attribute
+                        .strip_prefix(value)
+                        .is_some_and(|rest| rest.starts_with('-'))
+            }
+            // 値が空の場合は何にも一致しない
+            AttributeOperator::Prefix => !value.is_empty() && attribute.starts_with(value),
+            AttributeOperator::Suffix => !value.is_empty() && attribute.ends_with(value),
+            AttributeOperator::Substring => !value.is_empty() && attribute.contains(value),
+        }
+    }
+}
+
+/// 本書のブラウザはマウスの位置を追跡しないので、`:hover`と`:active`は常に
+/// 一致しない。訪問履歴は`Browser`が持っているが、セレクタのマッチングは文書しか
+/// 参照しないので、仕様で許されているとおりすべてのリンクを未訪問として扱い、
+/// `:visited`も常に一致しない
+/// https://html.spec.whatwg.org/multipage/semantics-other.html#selector-visited
+/// https://drafts.csswg.org/selectors-4/#pseudo-classes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PseudoClass {
+    /// https://drafts.csswg.org/selectors-4/#root-pseudo
+    Root,
+    /// https://drafts.csswg.org/selectors-4/#empty-pseudo
+    Empty,
+    /// https://drafts.csswg.org/selectors-4/#first-child-pseudo
+    FirstChild,
+    /// https://drafts.csswg.org/selectors-4/#last-child-pseudo
+    LastChild,
+    /// https://drafts.csswg.org/selectors-4/#only-child-pseudo
+    OnlyChild,
+    /// `:nth-child(an+b)`の(a, b)
+    /// https://drafts.csswg.org/selectors-4/#nth-child-pseudo
+    NthChild(i64, i64),
+    /// https://drafts.csswg.org/selectors-4/#nth-last-child-pseudo
+    NthLastChild(i64, i64),
+    /// https://drafts.csswg.org/selectors-4/#negation
+    Not(SelectorList),
+    /// https://drafts.csswg.org/selectors-4/#link
+    Link,
+    /// https://drafts.csswg.org/selectors-4/#visited-pseudo
+    Visited,
+    /// https://drafts.csswg.org/selectors-4/#the-hover-pseudo
+    Hover,
+    /// https://drafts.csswg.org/selectors-4/#the-active-pseudo
+    Active,
+    /// https://drafts.csswg.org/selectors-4/#the-focus-pseudo
+    Focus,
+}
+
+impl PseudoClass {
+    fn matches(&self, document: &Document, node: NodeId) -> bool {
+        match self {
+            PseudoClass::Root => document
+                .parent(node)
+                .is_some_and(|p| matches!(document.kind(p), NodeKind::Document)),
+            PseudoClass::Empty => children(document, node).all(|c| match document.kind(c) {
+                NodeKind::Text(s) => s.is_empty(),
+                _ => false,
+            }),
+            PseudoClass::FirstChild => previous_element_sibling(document, node).is_none(),
+            PseudoClass::LastChild => next_element_sibling(document, node).is_none(),
+            PseudoClass::OnlyChild => {
+                previous_element_sibling(document, node).is_none()
+                    && next_element_sibling(document, node).is_none()
+            }
+            PseudoClass::NthChild(a, b) => {
+                let index = count_siblings(document, node, previous_element_sibling) + 1;
+                matches_an_plus_b(*a, *b, index)
+            }
+            PseudoClass::NthLastChild(a, b) => {
+                let index = count_siblings(document, node, next_element_sibling) + 1;
+                matches_an_plus_b(*a, *b, index)
+            }
+            PseudoClass::Not(list) => !list.matches(document, node),
+            // すべてのリンクを未訪問として扱うので、すべてのリンクに一致する
+            PseudoClass::Link => document
+                .element(node)
+                .is_some_and(|e| e.kind() == ElementKind::A && e.has_attribute("href")),
+            PseudoClass::Visited | PseudoClass::Hover | PseudoClass::Active => false,
+            PseudoClass::Focus => document.focused_element() == Some(node),
+        }
+    }
+}
+
+/// `an+b`の形の式を解釈して(a, b)を返す。`odd`と`even`にも対応する
+/// https://drafts.csswg.org/css-syntax-3/#anb-microsyntax
+pub fn parse_an_plus_b(s: &str) -> Option<(i64, i64)> {
+    let s = s.trim().to_ascii_lowercase();
+    match s.as_str() {
+        "odd" => return Some((2, 1)),
+        "even" => return Some((2, 0)),
+        _ => {}
+    }
+
+    let (a, b) = match s.find('n') {
+        Some(i) => {
+            let a = match &s[..i] {
+                "" | "+" => 1,
+                "-" => -1,
+                a => parse_integer(a)?,
+            };
+            let b = s[i + 1..].trim();
+            if b.is_empty() {
+                return Some((a, 0));
+            }
+            // nの後の符号は必須
+            if !b.starts_with('+') && !b.starts_with('-') {
+                return None;
+            }
+            let (sign, digits) = b.split_at(1);
+            let digits = digits.trim();
+            if digits.starts_with('+') || digits.starts_with('-') {
+                return None;
+            }
+            let b = parse_integer(digits)?;
+            (a, if sign == "-" { -b } else { b })
+        }
+        None => (0, parse_integer(&s)?),
+    };
+    Some((a, b))
+}
+
+/// 符号付きの整数を解釈する。空白文字を含む場合は失敗する
+fn parse_integer(s: &str) -> Option<i64> {
+    if s.chars().any(|c| c.is_ascii_whitespace()) {
+        return None;
+    }
+    s.parse::<i64>().ok()
+}
+
+/// 1から数えた`index`番目が、0以上の整数nについてan+bと表せるかどうかを返す
+fn matches_an_plus_b(a: i64, b: i64, index: i64) -> bool {
+    if a == 0 {
+        return index == b;
+    }
+    let diff = index - b;
+    diff % a == 0 && diff / a >= 0
+}
+
+/// `node`より前(または後)にある兄弟要素の数を返す
+fn count_siblings(
+    document: &Document,
+    node: NodeId,
+    next: fn(&Document, NodeId) -> Option<NodeId>,
+) -> i64 {
+    let mut count = 0;
+    let mut sibling = next(document, node);
+    while let Some(s) = sibling {
+        count += 1;
+        sibling = next(document, s);
+    }
+    count
+}
+
+/// https://dom.spec.whatwg.org/#dom-nondocumenttypechildnode-previouselementsibling
+fn previous_element_sibling(document: &Document, node: NodeId) -> Option<NodeId> {
+    let mut sibling = document.previous_sibling(node);
+    while let Some(s) = sibling {
+        if document.element(s).is_some() {
+            return Some(s);
+        }
+        sibling = document.previous_sibling(s);
+    }
+    None
+}
+
+/// https://dom.spec.whatwg.org/#dom-nondocumenttypechildnode-nextelementsibling
+fn next_element_sibling(document: &Document, node: NodeId) -> Option<NodeId> {
+    let mut sibling = document.next_sibling(node);
+    while let Some(s) = sibling {
+        if document.element(s).is_some() {
+            return Some(s);
+        }
+        sibling = document.next_sibling(s);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::css::cssom::CssParser;
+    use crate::renderer::css::token::CssTokenizer;
+    use crate::renderer::dom::api::descendants;
+    use crate::renderer::dom::api::get_element_by_id;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use alloc::vec;
+
+    /// `selector`に一致する要素のid属性を文書順に返す
+    fn select(html: &str, selector: &str) -> Vec<String> {
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let dom = window.borrow().document();
+        let document = dom.borrow();
+        let selector = CssParser::new(CssTokenizer::new(selector.to_string()))
+            .parse_selector()
+            .expect("failed to parse a selector");
+        descendants(&document, document.root())
+            .filter(|n| selector.matches(&document, *n))
+            .filter_map(|n| document.element(n)?.get_attribute("id").map(String::from))
+            .collect()
+    }
+
+    #[test]
+    fn test_matches() {
+        let html = r#"<html id=html><head></head><body id=body>
+<p id=p1 class="a b" lang=en-US>1 <a id=a1 href="/doc.pdf">x</a></p>
+<p id=p2 class=b></p>
+<h1 id=h1>h</h1>
+<p id=p3 data-x="foo bar"><a id=a2>y</a></p>
+</body></html>"#;
+        let ids = |selector: &str| select(html, selector);
+
+        assert_eq!(vec!["p1", "p2", "p3"], ids("p"));
+        assert_eq!(vec!["p1"], ids("p.a.b"));
+        assert_eq!(vec!["a1", "a2"], ids("body a"));
+        assert_eq!(vec!["a1", "a2"], ids("p > a"));
+        assert!(ids("body > a").is_empty());
+        assert_eq!(vec!["p2"], ids("p + p"));
+        assert_eq!(vec!["p2", "p3"], ids("p1 ~ p, #p1 ~ p"));
+        assert_eq!(
+            vec!["html", "body", "p1", "a1", "p2", "h1", "p3", "a2"],
+            ids("*")
+        );
+        assert_eq!(vec!["h1", "p3"], ids("h1, #p3"));
+
+        assert_eq!(vec!["a1"], ids("[href]"));
+        assert_eq!(vec!["a1"], ids("[href$=\".pdf\"]"));
+        assert_eq!(vec!["a1"], ids("[href^='/']"));
+        assert_eq!(vec!["p3"], ids("[data-x~=bar]"));
+        assert_eq!(vec!["p3"], ids("[data-x*=o]"));
+        assert_eq!(vec!["p1"], ids("[lang|=en]"));
+        assert!(ids("[lang|=e]").is_empty());
+
+        assert_eq!(vec!["html"], ids(":root"));
+        assert_eq!(vec!["p2", "a2"], ids(":empty, a:only-child:not(:link)"));
+        assert_eq!(vec!["html", "p1", "a1", "a2"], ids(":first-child"));
+        assert_eq!(vec!["a1", "p3", "a2"], ids("p:last-child, a:last-child"));
+        assert_eq!(vec!["p1", "h1"], ids("body > :nth-child(odd)"));
+        assert_eq!(
+            vec!["p2", "p3"],
+            ids("body > :nth-child(2n):not(h1), p:nth-last-child(1)")
+        );
+        assert_eq!(vec!["p1", "p2"], ids("body > :nth-child(-n+2)"));
+        assert_eq!(vec!["a1"], ids(":link"));
+        assert!(ids("a:hover, a:visited").is_empty());
+    }
+
+    #[test]
+    fn test_specificity() {
+        let specificity = |s: &str| {
+            CssParser::new(CssTokenizer::new(s.to_string()))
+                .parse_selector()
+                .expect("failed to parse a selector")
+                .max_specificity()
+        };
+
+        // https://drafts.csswg.org/selectors-4/#example-d97bd125
+        assert_eq!(Specificity::new(0, 0, 0), specificity("*"));
+        assert_eq!(Specificity::new(0, 0, 1), specificity("li"));
+        assert_eq!(Specificity::new(0, 0, 2), specificity("ul li"));
+        assert_eq!(Specificity::new(0, 0, 3), specificity("ul ol+li"));
+        assert_eq!(Specificity::new(0, 1, 1), specificity("h1 + *[rel=up]"));
+        assert_eq!(Specificity::new(0, 1, 3), specificity("ul ol li.red"));
+        assert_eq!(Specificity::new(0, 2, 1), specificity("li.red.level"));
+        assert_eq!(Specificity::new(1, 0, 0), specificity("#x34y"));
+        assert_eq!(Specificity::new(1, 0, 1), specificity("#s12:not(FOO)"));
+        assert_eq!(Specificity::new(1, 0, 0), specificity(":not(em, #foo)"));
+    }
+
+    #[test]
+    fn test_focus_pseudo_class() {
+        let html = "<html><head></head><body><a id=a href=x>a</a></body></html>";
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let dom = window.borrow().document();
+        let a = {
+            let document = dom.borrow();
+            get_element_by_id(&document, Some(document.root()), "a").expect("no a")
+        };
+        let selector = CssParser::new(CssTokenizer::new("a:focus".to_string()))
+            .parse_selector()
+            .expect("failed to parse a selector");
+
+        assert!(!selector.matches(&dom.borrow(), a));
+        dom.borrow_mut().set_focused_element(Some(a));
+        assert!(selector.matches(&dom.borrow(), a));
+    }
+
+    #[test]
+    fn test_parse_an_plus_b() {
+        assert_eq!(Some((2, 1)), parse_an_plus_b("odd"));
+        assert_eq!(Some((2, 0)), parse_an_plus_b("EVEN"));
+        assert_eq!(Some((0, 3)), parse_an_plus_b("3"));
+        assert_eq!(Some((2, 1)), parse_an_plus_b("2n+1"));
+        assert_eq!(Some((3, -2)), parse_an_plus_b("3n - 2"));
+        assert_eq!(Some((-1, 3)), parse_an_plus_b("-n+3"));
+        assert_eq!(Some((1, 0)), parse_an_plus_b("n"));
+        assert_eq!(None, parse_an_plus_b("n3"));
+        assert_eq!(None, parse_an_plus_b("2n+-1"));
+        assert_eq!(None, parse_an_plus_b("foo"));
+
+        assert!(matches_an_plus_b(2, 1, 5));
+        assert!(!matches_an_plus_b(2, 1, 4));
+        assert!(matches_an_plus_b(-1, 3, 3));
+        assert!(!matches_an_plus_b(-1, 3, 4));
+        assert!(matches_an_plus_b(0, 2, 2));
+    }
+}
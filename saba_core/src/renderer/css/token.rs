@@ -1,5 +1,10 @@
+use crate::renderer::diagnostics::LineIndex;
+use crate::renderer::diagnostics::ParseDiagnostics;
+use crate::renderer::diagnostics::ParseErrorSource;
+use crate::renderer::diagnostics::SourcePosition;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum CssToken {
@@ -29,40 +34,151 @@ pub enum CssToken {
     AtKeyword(String),
 }
 
+/// トークンをCSSのテキストに戻す。style属性を書き換えるときに使う
+/// https://drafts.csswg.org/cssom/#serialize-a-css-component-value
+impl fmt::Display for CssToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CssToken::HashToken(value) | CssToken::Ident(value) => write!(f, "{}", value),
+            CssToken::Delim(c) => write!(f, "{}", c),
+            CssToken::Number(n) => write!(f, "{}", n),
+            CssToken::Colon => write!(f, ":"),
+            CssToken::SemiColon => write!(f, ";"),
+            CssToken::OpenParenthesis => write!(f, "("),
+            CssToken::CloseParenthesis => write!(f, ")"),
+            CssToken::OpenCurly => write!(f, "{{"),
+            CssToken::CloseCurly => write!(f, "}}"),
+            // 本書のトークナイザはエスケープを解釈しないので、値に含まれない
+            // 引用符で囲む
+            CssToken::StringToken(value) => {
+                let quote = if value.contains('"') { '\'' } else { '"' };
+                write!(f, "{}{}{}", quote, value, quote)
+            }
+            CssToken::AtKeyword(value) => write!(f, "@{}", value),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct CssTokenizer {
     pos: usize,
     input: Vec<char>,
+    /// peek()で先読みしたトークン
+    peeked: Option<Option<CssToken>>,
+    /// 直前に作成したトークンの開始位置
+    token_start: usize,
+    /// 直前に作成したトークンの前に、空白文字があったかどうか
+    whitespace_before: bool,
+    line_index: LineIndex,
+    diagnostics: ParseDiagnostics,
 }
 
 impl CssTokenizer {
     pub fn new(css: String) -> Self {
+        let input: Vec<char> = css.chars().collect();
+        let line_index = LineIndex::new(&input);
         Self {
             pos: 0,
-            input: css.chars().collect(),
+            input,
+            peeked: None,
+            token_start: 0,
+            whitespace_before: false,
+            line_index,
+            diagnostics: ParseDiagnostics::new(),
         }
     }
 
+    /// 次のトークンを消費せずに返す
+    pub fn peek(&mut self) -> Option<&CssToken> {
+        if self.peeked.is_none() {
+            let token = self.consume_token();
+            self.peeked = Some(token);
+        }
+        match self.peeked {
+            Some(Some(ref t)) => Some(t),
+            _ => None,
+        }
+    }
+
+    /// 直前に作成したトークンの開始位置を返す
+    pub fn source_position(&self) -> SourcePosition {
+        self.line_index.position(self.token_start)
+    }
+
+    /// 直前に作成したトークンの前に空白文字があったかどうかを返す。本書では
+    /// <whitespace-token>を作らないので、セレクタの子孫結合子はこれを見て判断する
+    /// https://www.w3.org/TR/css-syntax-3/#whitespace-token-diagram
+    pub fn preceded_by_whitespace(&self) -> bool {
+        self.whitespace_before
+    }
+
+    /// 直前に作成したトークンの位置でパースエラーを記録する
+    pub fn parse_error(&mut self, name: &str) {
+        let position = self.source_position();
+        self.diagnostics
+            .report(ParseErrorSource::Css, name, position);
+    }
+
+    pub fn diagnostics(&self) -> ParseDiagnostics {
+        self.diagnostics.clone()
+    }
+
+    /// 現在読んでいる文字の位置でパースエラーを記録する
+    fn tokenize_error(&mut self, name: &str) {
+        let position = self.line_index.position(self.pos);
+        self.diagnostics
+            .report(ParseErrorSource::Css, name, position);
+    }
+
     /// https://www.w3.org/TR/css-syntax-3/#consume-a-string-token
     fn consume_string_token(&mut self) -> String {
         let mut s = String::new();
+        // 文字列は開始と同じ引用符で終わる
+        let ending = self.input[self.pos];
 
         loop {
+            self.pos += 1;
             if self.pos >= self.input.len() {
+                self.tokenize_error("eof-in-string");
                 return s;
             }
 
-            self.pos += 1;
             let c = self.input[self.pos];
-            match c {
-                '"' | '\'' => break,
-                _ => s.push(c),
+            if c == ending {
+                break;
             }
+            if c == '\n' {
+                // 仕様では<bad-string-token>を返すが、本書では改行までを
+                // 文字列として扱い、改行は次のトークンとして読み直す
+                self.tokenize_error("newline-in-string");
+                self.pos -= 1;
+                return s;
+            }
+            s.push(c);
         }
 
         s
     }
 
+    /// https://www.w3.org/TR/css-syntax-3/#consume-comments
+    fn consume_comments(&mut self) {
+        // skip '/*'
+        self.pos += 2;
+
+        loop {
+            if self.pos >= self.input.len() {
+                self.tokenize_error("eof-in-comment");
+                return;
+            }
+
+            if self.input[self.pos] == '*' && self.input.get(self.pos + 1) == Some(&'/') {
+                self.pos += 2;
+                return;
+            }
+            self.pos += 1;
+        }
+    }
+
     /// https://www.w3.org/TR/css-syntax-3/#consume-number
     fn consume_numeric_token(&mut self) -> f64 {
         let mut num = 0f64;
@@ -105,6 +221,9 @@ impl CssTokenizer {
 
         loop {
             self.pos += 1;
+            if self.pos >= self.input.len() {
+                break;
+            }
             let c = self.input[self.pos];
             match c {
                 'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => {
@@ -116,19 +235,18 @@ impl CssTokenizer {
 
         s
     }
-}
-
-impl Iterator for CssTokenizer {
-    type Item = CssToken;
 
     /// https://www.w3.org/TR/css-syntax-3/#consume-token
-    fn next(&mut self) -> Option<Self::Item> {
+    fn consume_token(&mut self) -> Option<CssToken> {
+        self.whitespace_before = false;
         loop {
             if self.pos >= self.input.len() {
+                self.token_start = self.input.len();
                 return None;
             }
 
             let c = self.input[self.pos];
+            self.token_start = self.pos;
 
             let token = match c {
                 '(' => CssToken::OpenParenthesis,
@@ -165,9 +283,14 @@ impl Iterator for CssTokenizer {
                     // 次の3文字が識別子として有効な文字の場合、<at-keyword-token>
                     // トークンを作成して返す。
                     // それ以外の場合、<delim-token>を返す。
-                    if self.input[self.pos + 1].is_ascii_alphabetic()
-                        && self.input[self.pos + 2].is_alphanumeric()
-                        && self.input[self.pos + 3].is_alphanumeric()
+                    let is_alphanumeric_at =
+                        |i: usize| self.input.get(i).is_some_and(|c| c.is_alphanumeric());
+                    if self
+                        .input
+                        .get(self.pos + 1)
+                        .is_some_and(|c| c.is_ascii_alphabetic())
+                        && is_alphanumeric_at(self.pos + 2)
+                        && is_alphanumeric_at(self.pos + 3)
                     {
                         // skip '@'
                         self.pos += 1;
@@ -183,13 +306,16 @@ impl Iterator for CssTokenizer {
                     self.pos -= 1;
                     t
                 }
-                ' ' | '\n' => {
-                    self.pos += 1;
+                '/' if self.input.get(self.pos + 1) == Some(&'*') => {
+                    self.consume_comments();
                     continue;
                 }
-                _ => {
-                    unimplemented!("char {} is not supported yet", c);
+                ' ' | '\n' | '\t' | '\r' => {
+                    self.pos += 1;
+                    self.whitespace_before = true;
+                    continue;
                 }
+                _ => CssToken::Delim(c),
             };
 
             self.pos += 1;
@@ -198,6 +324,17 @@ impl Iterator for CssTokenizer {
     }
 }
 
+impl Iterator for CssTokenizer {
+    type Item = CssToken;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.peeked.take() {
+            Some(token) => token,
+            None => self.consume_token(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -297,4 +434,43 @@ mod tests {
         }
         assert!(t.next().is_none());
     }
+
+    #[test]
+    fn test_comment_and_unknown_char() {
+        let style = "/* comment */ * { }".to_string();
+        let mut t = CssTokenizer::new(style);
+        let expected = [
+            CssToken::Delim('*'),
+            CssToken::OpenCurly,
+            CssToken::CloseCurly,
+        ];
+        for e in expected {
+            assert_eq!(Some(e.clone()), t.next());
+        }
+        assert!(t.next().is_none());
+        assert!(t.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let style = "p {\n  content: \"Hey'".to_string();
+        let mut t = CssTokenizer::new(style);
+        let expected = [
+            CssToken::Ident("p".to_string()),
+            CssToken::OpenCurly,
+            CssToken::Ident("content".to_string()),
+            CssToken::Colon,
+            CssToken::StringToken("Hey'".to_string()),
+        ];
+        for e in expected {
+            assert_eq!(Some(e.clone()), t.next());
+        }
+        assert!(t.next().is_none());
+
+        let errors = t.diagnostics().errors();
+        assert_eq!(1, errors.len());
+        assert_eq!("eof-in-string", errors[0].name());
+        assert_eq!(2, errors[0].position().line());
+        assert_eq!(17, errors[0].position().column());
+    }
 }
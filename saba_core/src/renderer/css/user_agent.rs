@@ -0,0 +1,31 @@
+//! https://drafts.csswg.org/css-cascade/#cascade-origin-ua
+
+use crate::renderer::css::cascade::Origin;
+use crate::renderer::css::cssom::CssParser;
+use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::css::token::CssTokenizer;
+use alloc::string::ToString;
+
+/// バイナリに埋め込んだ、ユーザーエージェントのスタイルシートのテキスト
+const USER_AGENT_CSS: &str = include_str!("user_agent.css");
+
+/// ユーザーエージェントのスタイルシートをパースする
+pub fn user_agent_style_sheet() -> StyleSheet {
+    let mut sheet =
+        CssParser::new(CssTokenizer::new(USER_AGENT_CSS.to_string())).parse_stylesheet();
+    sheet.set_origin(Origin::UserAgent);
+    sheet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_agent_style_sheet() {
+        let sheet = user_agent_style_sheet();
+        assert_eq!(Origin::UserAgent, sheet.origin);
+        assert_eq!(6, sheet.rules.len());
+        assert!(sheet.rules.iter().all(|rule| !rule.declarations.is_empty()));
+    }
+}
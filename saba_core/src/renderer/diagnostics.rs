@@ -0,0 +1,188 @@
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt::Display;
+use core::fmt::Formatter;
+
+/// ソースコード上の位置。行と列はどちらも1から数える
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SourcePosition {
+    line: usize,
+    column: usize,
+}
+
+impl SourcePosition {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+/// 入力文字列の各行の開始インデックスを保持し、文字のインデックスから行と列を求める
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(input: &[char]) -> Self {
+        let mut line_starts = Vec::new();
+        line_starts.push(0);
+        for (i, c) in input.iter().enumerate() {
+            if *c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+
+        Self { line_starts }
+    }
+
+    pub fn position(&self, index: usize) -> SourcePosition {
+        // `index`以下で最も大きい行の開始位置を二分探索で探す
+        let line = match self.line_starts.binary_search(&index) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+
+        SourcePosition::new(line + 1, index - self.line_starts[line] + 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorSource {
+    Html,
+    Css,
+    Js,
+}
+
+impl Display for ParseErrorSource {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        let s = match self {
+            ParseErrorSource::Html => "html",
+            ParseErrorSource::Css => "css",
+            ParseErrorSource::Js => "js",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// パースエラー。`name`には仕様で定められたエラー名を使う。仕様で名前が
+/// 定められていないエラーは、同じ形式 (小文字とハイフン) の名前を付ける
+/// https://html.spec.whatwg.org/multipage/parsing.html#parse-errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    source: ParseErrorSource,
+    name: String,
+    position: SourcePosition,
+}
+
+impl ParseError {
+    pub fn new(source: ParseErrorSource, name: &str, position: SourcePosition) -> Self {
+        Self {
+            source,
+            name: name.to_string(),
+            position,
+        }
+    }
+
+    pub fn source(&self) -> ParseErrorSource {
+        self.source
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn position(&self) -> SourcePosition {
+        self.position
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.source,
+            self.position.line(),
+            self.position.column(),
+            self.name
+        )
+    }
+}
+
+/// パース中に見つかったエラーを集める
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDiagnostics {
+    errors: Vec<ParseError>,
+}
+
+impl ParseDiagnostics {
+    pub fn new() -> Self {
+        Self { errors: Vec::new() }
+    }
+
+    pub fn report(&mut self, source: ParseErrorSource, name: &str, position: SourcePosition) {
+        self.errors.push(ParseError::new(source, name, position));
+    }
+
+    pub fn extend(&mut self, other: ParseDiagnostics) {
+        self.errors.extend(other.errors);
+    }
+
+    pub fn errors(&self) -> Vec<ParseError> {
+        self.errors.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl Default for ParseDiagnostics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_index() {
+        let input: Vec<char> = "ab\ncd\n\ne".chars().collect();
+        let index = LineIndex::new(&input);
+
+        assert_eq!(SourcePosition::new(1, 1), index.position(0));
+        assert_eq!(SourcePosition::new(1, 3), index.position(2));
+        assert_eq!(SourcePosition::new(2, 1), index.position(3));
+        assert_eq!(SourcePosition::new(2, 2), index.position(4));
+        assert_eq!(SourcePosition::new(3, 1), index.position(6));
+        assert_eq!(SourcePosition::new(4, 1), index.position(7));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut diagnostics = ParseDiagnostics::new();
+        diagnostics.report(
+            ParseErrorSource::Html,
+            "eof-in-tag",
+            SourcePosition::new(3, 5),
+        );
+
+        assert_eq!(1, diagnostics.len());
+        assert_eq!("html:3:5: eof-in-tag", diagnostics.errors()[0].to_string());
+    }
+}
@@ -1,75 +1,592 @@
-use crate::renderer::dom::node::Element;
+use crate::renderer::css::cssom::CssParser;
+use crate::renderer::css::cssom::Declaration;
+use crate::renderer::css::token::CssTokenizer;
+use crate::renderer::dom::exception::DomException;
+use crate::renderer::dom::mutation::set_text_content;
+use crate::renderer::dom::node::Document;
 use crate::renderer::dom::node::ElementKind;
-use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::NodeId;
 use crate::renderer::dom::node::NodeKind;
-use alloc::rc::Rc;
+use alloc::format;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
-use core::cell::RefCell;
 
+/// `node`の子ノードを順にたどるイテレータ
+/// https://dom.spec.whatwg.org/#concept-tree-child
+pub struct Children<'a> {
+    document: &'a Document,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Children<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+        self.next = self.document.next_sibling(current);
+        Some(current)
+    }
+}
+
+pub fn children(document: &Document, node: NodeId) -> Children<'_> {
+    Children {
+        document,
+        next: document.first_child(node),
+    }
+}
+
+/// `node`の親ノードから根に向かって祖先をたどるイテレータ。`node`自身は含まない
+/// https://dom.spec.whatwg.org/#concept-tree-ancestor
+pub struct Ancestors<'a> {
+    document: &'a Document,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Ancestors<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+        self.next = self.document.parent(current);
+        Some(current)
+    }
+}
+
+pub fn ancestors(document: &Document, node: NodeId) -> Ancestors<'_> {
+    Ancestors {
+        document,
+        next: document.parent(node),
+    }
+}
+
+/// `root`の子孫を文書順にたどるイテレータ。再帰を使わないので、兄弟ノードや
+/// ツリーの深さが大きくてもスタックを消費しない
+/// https://dom.spec.whatwg.org/#concept-tree-descendant
+pub struct Descendants<'a> {
+    document: &'a Document,
+    root: NodeId,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current = self.next?;
+        self.next = following(self.document, current, self.root);
+        Some(current)
+    }
+}
+
+/// `root`の子孫を文書順に返す。`root`自身は含まない
+pub fn descendants(document: &Document, root: NodeId) -> Descendants<'_> {
+    Descendants {
+        document,
+        root,
+        next: document.first_child(root),
+    }
+}
+
+/// `root`自身と`root`の子孫を文書順に返す
+/// https://dom.spec.whatwg.org/#concept-tree-inclusive-descendant
+pub fn inclusive_descendants(document: &Document, root: NodeId) -> Descendants<'_> {
+    Descendants {
+        document,
+        root,
+        next: Some(root),
+    }
+}
+
+/// `root`を根とする部分木の中で、文書順で`node`の次にあるノードを返す
+/// https://dom.spec.whatwg.org/#concept-tree-following
+pub fn following(document: &Document, node: NodeId, root: NodeId) -> Option<NodeId> {
+    if let Some(child) = document.first_child(node) {
+        return Some(child);
+    }
+
+    // 子ノードがない場合は、兄弟ノードを持つ祖先までさかのぼる
+    let mut current = node;
+    while current != root {
+        if let Some(sibling) = document.next_sibling(current) {
+            return Some(sibling);
+        }
+        current = document.parent(current)?;
+    }
+    None
+}
+
+/// `root`を根とする部分木の中で、文書順で`node`の前にあるノードを返す
+/// https://dom.spec.whatwg.org/#concept-tree-preceding
+pub fn preceding(document: &Document, node: NodeId, root: NodeId) -> Option<NodeId> {
+    if node == root {
+        return None;
+    }
+
+    // 直前の兄弟ノードがある場合は、その最後の子孫が直前のノードになる
+    match document.previous_sibling(node) {
+        Some(sibling) => {
+            let mut current = sibling;
+            while let Some(child) = document.last_child(current) {
+                current = child;
+            }
+            Some(current)
+        }
+        None => document.parent(node),
+    }
+}
+
+/// `node`とその子孫のうち、`element_kind`の最初の要素を返す
 pub fn get_target_element_node(
-    node: Option<Rc<RefCell<Node>>>,
+    document: &Document,
+    node: Option<NodeId>,
     element_kind: ElementKind,
-) -> Option<Rc<RefCell<Node>>> {
-    match node {
-        Some(n) => {
-            if n.borrow().kind()
-                == NodeKind::Element(Element::new(&element_kind.to_string(), Vec::new()))
-            {
-                return Some(n.clone());
-            }
+) -> Option<NodeId> {
+    inclusive_descendants(document, node?).find(|n| document.element_kind(*n) == Some(element_kind))
+}
 
-            let result1 = get_target_element_node(n.borrow().first_child(), element_kind);
-            let result2 = get_target_element_node(n.borrow().next_sibling(), element_kind);
-            if result1.is_none() && result2.is_none() {
-                return None;
-            }
-            if result1.is_none() {
-                return result2;
+/// すべての<style>要素の内容を、文書順に連結して返す
+pub fn get_style_content(document: &Document) -> String {
+    get_style_sheet_nodes(document)
+        .iter()
+        .filter(|node| document.element_kind(**node) == Some(ElementKind::Style))
+        .map(|node| child_text_content(document, *node))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// スタイルシートを持つ<style>要素と<link rel="stylesheet">要素を文書順に返す
+/// https://html.spec.whatwg.org/multipage/semantics.html#link-type-stylesheet
+pub fn get_style_sheet_nodes(document: &Document) -> Vec<NodeId> {
+    inclusive_descendants(document, document.root())
+        .filter(|n| match document.element(*n) {
+            Some(e) => match e.kind() {
+                ElementKind::Style => true,
+                ElementKind::Link => {
+                    // rel属性はスペース区切りのキーワードで、大文字と小文字を区別しない。
+                    // 代替スタイルシートは既定では適用しない
+                    let rel = e.get_attribute("rel").unwrap_or_default();
+                    let keywords: Vec<&str> = rel.split_ascii_whitespace().collect();
+                    let is_style_sheet = keywords
+                        .iter()
+                        .any(|k| k.eq_ignore_ascii_case("stylesheet"))
+                        && !keywords.iter().any(|k| k.eq_ignore_ascii_case("alternate"));
+                    is_style_sheet && e.get_attribute("href").is_some()
+                }
+                _ => false,
+            },
+            None => false,
+        })
+        .collect()
+}
+
+/// `element_kind`の要素をすべて文書順に返す
+pub fn get_elements_by_kind(document: &Document, element_kind: ElementKind) -> Vec<NodeId> {
+    inclusive_descendants(document, document.root())
+        .filter(|n| document.element_kind(*n) == Some(element_kind))
+        .collect()
+}
+
+/// `node`とその子孫のうち、id属性が`id_name`の最初の要素を返す
+pub fn get_element_by_id(
+    document: &Document,
+    node: Option<NodeId>,
+    id_name: &str,
+) -> Option<NodeId> {
+    inclusive_descendants(document, node?).find(|n| {
+        document
+            .element(*n)
+            .is_some_and(|e| e.get_attribute("id") == Some(id_name))
+    })
+}
+
+/// `root`の子孫のうち、`f`を満たす要素を文書順に返す
+fn collect_elements(document: &Document, root: NodeId, f: &dyn Fn(NodeId) -> bool) -> Vec<NodeId> {
+    descendants(document, root)
+        .filter(|n| document.element(*n).is_some() && f(*n))
+        .collect()
+}
+
+/// `root`の子孫のうち、タグ名が`qualified_name`の要素を文書順に返す。"*"の場合は
+/// すべての要素を返す
+/// https://dom.spec.whatwg.org/#concept-getelementsbytagname
+pub fn get_elements_by_tag_name(
+    document: &Document,
+    root: NodeId,
+    qualified_name: &str,
+) -> Vec<NodeId> {
+    // HTML文書の要素のタグ名は小文字で比較する
+    let name = qualified_name.to_ascii_lowercase();
+    collect_elements(document, root, &|n| {
+        qualified_name == "*"
+            || document
+                .element_kind(n)
+                .is_some_and(|kind| kind.to_string() == name)
+    })
+}
+
+/// `root`の子孫のうち、スペース区切りの`class_names`のクラスをすべて持つ要素を
+/// 文書順に返す
+/// https://dom.spec.whatwg.org/#concept-getelementsbyclassname
+pub fn get_elements_by_class_name(
+    document: &Document,
+    root: NodeId,
+    class_names: &str,
+) -> Vec<NodeId> {
+    let names: Vec<&str> = class_names.split_ascii_whitespace().collect();
+    if names.is_empty() {
+        return Vec::new();
+    }
+
+    collect_elements(document, root, &|n| {
+        let classes = document
+            .element(n)
+            .and_then(|e| e.get_attribute("class"))
+            .unwrap_or_default();
+        names
+            .iter()
+            .all(|name| classes.split_ascii_whitespace().any(|c| c == *name))
+    })
+}
+
+/// `root`の子孫のうち、セレクタに一致する最初の要素を返す。セレクタをパース
+/// できない場合はSyntaxErrorを返す
+/// https://dom.spec.whatwg.org/#dom-parentnode-queryselector
+pub fn query_selector(
+    document: &Document,
+    root: NodeId,
+    selectors: &str,
+) -> Result<Option<NodeId>, DomException> {
+    Ok(query_selector_all(document, root, selectors)?
+        .first()
+        .copied())
+}
+
+/// `root`の子孫のうち、セレクタに一致するすべての要素を文書順に返す。セレクタを
+/// パースできない場合はSyntaxErrorを返す
+/// https://dom.spec.whatwg.org/#scope-match-a-selectors-string
+pub fn query_selector_all(
+    document: &Document,
+    root: NodeId,
+    selectors: &str,
+) -> Result<Vec<NodeId>, DomException> {
+    let selector = match CssParser::new(CssTokenizer::new(selectors.to_string())).parse_selector() {
+        Some(s) => s,
+        None => return Err(DomException::SyntaxError),
+    };
+
+    Ok(collect_elements(document, root, &|n| {
+        selector.matches(document, n)
+    }))
+}
+
+/// 要素のstyle属性に書かれた宣言のリストを返す。style属性がない場合は空になる
+/// https://drafts.csswg.org/cssom/#dom-elementcssinlinestyle-style
+pub fn get_inline_style(document: &Document, node: NodeId) -> Vec<Declaration> {
+    match document
+        .element(node)
+        .and_then(|e| e.get_attribute("style"))
+    {
+        Some(style) => {
+            CssParser::new(CssTokenizer::new(style.to_string())).parse_declaration_list()
+        }
+        None => Vec::new(),
+    }
+}
+
+/// style属性のテキストを、トップレベルの";"で宣言ごとに分ける。括弧や文字列の
+/// 中の";"では分けない
+/// https://drafts.csswg.org/css-syntax/#consume-a-list-of-declarations
+fn split_declarations(style: &str) -> Vec<&str> {
+    let mut declarations = Vec::new();
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in style.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (_, '\\') => escaped = true,
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') | (None, '[') | (None, '{') => depth += 1,
+            (None, ')') | (None, ']') | (None, '}') => depth = depth.saturating_sub(1),
+            (None, ';') if depth == 0 => {
+                declarations.push(&style[start..i]);
+                start = i + 1;
             }
-            result1
+            _ => {}
         }
-        None => None,
     }
+    declarations.push(&style[start..]);
+    declarations
 }
 
-pub fn get_style_content(root: Rc<RefCell<Node>>) -> String {
-    let style_node = match get_target_element_node(Some(root), ElementKind::Style) {
-        Some(node) => node,
-        None => return "".to_string(),
-    };
-    let text_node = match style_node.borrow().first_child() {
-        Some(node) => node,
-        None => return "".to_string(),
-    };
-    let content = match &text_node.borrow().kind() {
-        NodeKind::Text(ref s) => s.clone(),
-        _ => "".to_string(),
+/// 要素のstyle属性の`property`の値を`value`にする。`value`が空の場合は
+/// `property`の宣言を取り除く。値として解釈できない場合は何もしない。ほかの宣言は
+/// 書かれたテキストのまま残し、`property`の宣言だけを書き換えるか末尾に追加する
+/// https://drafts.csswg.org/cssom/#dom-cssstyledeclaration-setproperty
+/// https://drafts.csswg.org/cssom/#update-style-attribute-for
+pub fn set_inline_style_property(
+    document: &mut Document,
+    node: NodeId,
+    property: &str,
+    value: &str,
+) {
+    let value = value.trim();
+    let declaration = if value.is_empty() {
+        None
+    } else {
+        // スタイルシートやstyle属性と同じように宣言としてパースする。値が複数の
+        // 宣言を含む場合や、"!important"が付いている場合は無効になる
+        let text = format!("{}: {}", property, value);
+        let parsed = CssParser::new(CssTokenizer::new(text.clone())).parse_declaration_list();
+        match parsed.as_slice() {
+            [d] if d.property == property && !d.important => Some(text),
+            _ => return,
+        }
     };
+
+    let style = document
+        .element(node)
+        .and_then(|e| e.get_attribute("style"))
+        .unwrap_or_default();
+    let mut found = false;
+    let mut replaced = false;
+    let mut chunks: Vec<String> = Vec::new();
+    for chunk in split_declarations(style) {
+        let name = chunk.split(':').next().unwrap_or_default().trim();
+        if !name.eq_ignore_ascii_case(property) {
+            chunks.push(chunk.to_string());
+            continue;
+        }
+        found = true;
+        // 最初の宣言だけを書き換え、後ろにある同じプロパティの宣言は取り除く
+        if let (Some(declaration), false) = (&declaration, replaced) {
+            let indent = &chunk[..chunk.len() - chunk.trim_start().len()];
+            chunks.push(format!("{}{}", indent, declaration));
+            replaced = true;
+        }
+    }
+
+    match declaration {
+        // 取り除く宣言がない場合は、style属性を変えない
+        None if !found => return,
+        Some(declaration) if !replaced => {
+            // 末尾の";"の後ろの空白は、追加した宣言の後ろに残す
+            let trailing = match chunks.last() {
+                Some(last) if last.trim().is_empty() => chunks.pop(),
+                _ => None,
+            };
+            if chunks.is_empty() {
+                chunks.push(declaration);
+            } else {
+                chunks.push(format!(" {}", declaration));
+            }
+            chunks.extend(trailing);
+        }
+        _ => {}
+    }
+
+    if let Some(element) = document.element_mut(node) {
+        // "style"は正しい属性名なので失敗しない
+        let _ = element.set_attribute("style", chunks.join(";").trim_start());
+    }
+}
+
+/// https://infra.spec.whatwg.org/#strip-and-collapse-ascii-whitespace
+pub fn strip_and_collapse_ascii_whitespace(s: &str) -> String {
+    s.split(|c: char| c.is_ascii_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// https://dom.spec.whatwg.org/#concept-child-text-content
+pub fn child_text_content(document: &Document, node: NodeId) -> String {
+    let mut content = String::new();
+    for child in children(document, node) {
+        if let NodeKind::Text(s) = document.kind(child) {
+            content.push_str(s);
+        }
+    }
     content
 }
 
-pub fn get_element_by_id(
-    node: Option<Rc<RefCell<Node>>>,
-    id_name: &String,
-) -> Option<Rc<RefCell<Node>>> {
-    match node {
-        Some(n) => {
-            if let NodeKind::Element(e) = n.borrow().kind() {
-                for attr in &e.attributes() {
-                    if attr.name() == "id" && attr.value() == *id_name {
-                        return Some(n.clone());
-                    }
-                }
-            }
-            let result1 = get_element_by_id(n.borrow().first_child(), id_name);
-            let result2 = get_element_by_id(n.borrow().next_sibling(), id_name);
-            if result1.is_none() {
-                return result2;
-            }
-            result1
+/// https://html.spec.whatwg.org/multipage/dom.html#document.title
+pub fn get_document_title(document: &Document) -> String {
+    match get_target_element_node(document, Some(document.root()), ElementKind::Title) {
+        Some(title) => strip_and_collapse_ascii_whitespace(&child_text_content(document, title)),
+        None => "".to_string(),
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/dom.html#document.title
+pub fn set_document_title(document: &mut Document, title: &str) {
+    let root = document.root();
+    let title_element = match get_target_element_node(document, Some(root), ElementKind::Title) {
+        Some(element) => element,
+        None => {
+            // <title>がない場合は、<head>の最後の子要素として作成する。<head>も
+            // ない場合は何もしない
+            let head = match get_target_element_node(document, Some(root), ElementKind::Head) {
+                Some(head) => head,
+                None => return,
+            };
+            let element = document.create_element("title", Vec::new());
+            document.insert(element, head, None);
+            element
         }
-        None => None,
+    };
+
+    // 子ノードをすべて、新しいテキストノードに置き換える。空文字の場合は
+    // 子ノードを削除するだけにする
+    set_text_content(document, title_element, title);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::parser::parse_html;
+    use alloc::vec;
+
+    fn ids(document: &Document, nodes: &[NodeId]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|n| {
+                document
+                    .element(*n)
+                    .and_then(|e| e.get_attribute("id"))
+                    .unwrap_or_default()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_iterators() {
+        let document = parse_html(
+            "<html><head></head><body><p id=a><a id=b></a></p><p id=c></p></body></html>",
+        )
+        .borrow()
+        .document();
+        let document = document.borrow();
+        let body = get_target_element_node(&document, Some(document.root()), ElementKind::Body)
+            .expect("failed to get a body element");
+
+        let nodes: Vec<NodeId> = descendants(&document, body).collect();
+        assert_eq!(vec!["a", "b", "c"], ids(&document, &nodes));
+        let nodes: Vec<NodeId> = children(&document, body).collect();
+        assert_eq!(vec!["a", "c"], ids(&document, &nodes));
+        assert_eq!(4, inclusive_descendants(&document, body).count());
+
+        let b = get_element_by_id(&document, Some(body), "b").expect("failed to get b");
+        let nodes: Vec<NodeId> = ancestors(&document, b).take(2).collect();
+        assert_eq!(vec!["a", ""], ids(&document, &nodes));
+        assert_eq!(Some(document.root()), ancestors(&document, b).last());
+
+        // 部分木の外には出ない
+        let c = get_element_by_id(&document, Some(body), "c").expect("failed to get c");
+        assert_eq!(Some(c), following(&document, b, body));
+        assert_eq!(None, following(&document, b, nodes[0]));
+        assert_eq!(Some(b), preceding(&document, c, body));
+        assert_eq!(None, preceding(&document, body, body));
+    }
+
+    #[test]
+    fn test_many_siblings() {
+        // 兄弟ノードの数だけ再帰するとスタックが溢れる
+        let mut html = "<html><head></head><body>".to_string();
+        for _ in 0..50000 {
+            html.push_str("<p></p>");
+        }
+        html.push_str("<p id=last></p></body></html>");
+        let document = parse_html(&html).borrow().document();
+        let document = document.borrow();
+
+        assert!(get_element_by_id(&document, Some(document.root()), "last").is_some());
+        assert_eq!(50001, get_elements_by_kind(&document, ElementKind::P).len());
+    }
+
+    #[test]
+    fn test_get_elements_by_tag_name() {
+        let document = parse_html(
+            "<html><head></head><body><p id=a><a id=b></a></p><P id=c></P></body></html>",
+        )
+        .borrow()
+        .document();
+        let document = document.borrow();
+        let root = document.root();
+
+        let nodes = get_elements_by_tag_name(&document, root, "P");
+        assert_eq!(vec!["a", "c"], ids(&document, &nodes));
+        assert_eq!(6, get_elements_by_tag_name(&document, root, "*").len());
+
+        // 子孫だけを探し、`root`自身は含めない
+        let p = nodes[0];
+        assert!(get_elements_by_tag_name(&document, p, "p").is_empty());
+        assert_eq!(
+            vec!["b"],
+            ids(&document, &get_elements_by_tag_name(&document, p, "*"))
+        );
+    }
+
+    #[test]
+    fn test_get_elements_by_class_name() {
+        let document = parse_html(
+            "<html><head></head><body><p id=a class=\"x y\"></p><p id=b class=\"y\"></p>\
+<p id=c class=\"xy\"></p></body></html>",
+        )
+        .borrow()
+        .document();
+        let document = document.borrow();
+        let root = document.root();
+
+        let find = |names: &str| {
+            ids(
+                &document,
+                &get_elements_by_class_name(&document, root, names),
+            )
+        };
+        assert_eq!(vec!["a", "b"], find("y"));
+        assert_eq!(vec!["a"], find(" y  x "));
+        assert_eq!(vec!["c"], find("xy"));
+        assert!(find(" ").is_empty());
+    }
+
+    #[test]
+    fn test_query_selector() {
+        let document = parse_html(
+            "<html><head></head><body><p id=a class=\"x y\"></p><h1 id=b class=y></h1>\
+</body></html>",
+        )
+        .borrow()
+        .document();
+        let document = document.borrow();
+        let root = document.root();
+
+        let all = |s: &str| ids(&document, &query_selector_all(&document, root, s).unwrap());
+        assert_eq!(vec!["a", "b"], all(".y"));
+        assert_eq!(vec!["b"], all("h1"));
+        assert_eq!(vec!["b"], all("#b"));
+        assert!(all("pre").is_empty());
+
+        assert_eq!(
+            Ok(document.first_child(root)),
+            query_selector(&document, root, "html")
+        );
+        assert_eq!(Ok(None), query_selector(&document, root, ".z"));
+        assert_eq!(
+            Err(DomException::SyntaxError),
+            query_selector(&document, root, "")
+        );
+        assert_eq!(
+            Err(DomException::SyntaxError),
+            query_selector_all(&document, root, "p }")
+        );
     }
 }
@@ -0,0 +1,475 @@
+//! https://dom.spec.whatwg.org/#events
+
+use crate::renderer::dom::api::ancestors;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::NodeId;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::Debug;
+use core::fmt::Formatter;
+
+/// イベントを受け取るオブジェクト。ノードのほかに、ドキュメントの親として
+/// ウィンドウがある
+/// https://dom.spec.whatwg.org/#interface-eventtarget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventTarget {
+    Window,
+    Node(NodeId),
+}
+
+/// https://dom.spec.whatwg.org/#dom-event-eventphase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventPhase {
+    None,
+    Capturing,
+    AtTarget,
+    Bubbling,
+}
+
+/// イベントの種類ごとの情報
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventDetail {
+    None,
+    /// https://w3c.github.io/uievents/#interface-mouseevent
+    Mouse {
+        client_x: i64,
+        client_y: i64,
+    },
+    /// https://w3c.github.io/uievents/#interface-keyboardevent
+    Keyboard {
+        key: String,
+    },
+    /// 挿入された文字列。削除の場合はNoneになる
+    /// https://w3c.github.io/uievents/#interface-inputevent
+    Input {
+        data: Option<String>,
+    },
+}
+
+/// https://dom.spec.whatwg.org/#interface-event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    event_type: String,
+    detail: EventDetail,
+    bubbles: bool,
+    cancelable: bool,
+    target: Option<EventTarget>,
+    current_target: Option<EventTarget>,
+    phase: EventPhase,
+    stop_propagation: bool,
+    stop_immediate_propagation: bool,
+    canceled: bool,
+}
+
+impl Event {
+    pub fn new(event_type: &str, bubbles: bool, cancelable: bool) -> Self {
+        Self {
+            event_type: event_type.to_string(),
+            detail: EventDetail::None,
+            bubbles,
+            cancelable,
+            target: None,
+            current_target: None,
+            phase: EventPhase::None,
+            stop_propagation: false,
+            stop_immediate_propagation: false,
+            canceled: false,
+        }
+    }
+
+    /// `position`はコンテンツ領域の中の座標
+    pub fn new_mouse_event(event_type: &str, position: (i64, i64)) -> Self {
+        let mut event = Self::new(event_type, true, true);
+        event.detail = EventDetail::Mouse {
+            client_x: position.0,
+            client_y: position.1,
+        };
+        event
+    }
+
+    /// `key`は"a"のような文字か、"Enter"のようなキーの名前
+    /// https://w3c.github.io/uievents-key/
+    pub fn new_keyboard_event(event_type: &str, key: &str) -> Self {
+        let mut event = Self::new(event_type, true, true);
+        event.detail = EventDetail::Keyboard {
+            key: key.to_string(),
+        };
+        event
+    }
+
+    /// inputイベントは編集が終わった後に送られるので、キャンセルできない
+    /// https://w3c.github.io/uievents/#event-type-input
+    pub fn new_input_event(data: Option<String>) -> Self {
+        let mut event = Self::new("input", true, false);
+        event.detail = EventDetail::Input { data };
+        event
+    }
+
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+
+    pub fn detail(&self) -> &EventDetail {
+        &self.detail
+    }
+
+    pub fn bubbles(&self) -> bool {
+        self.bubbles
+    }
+
+    pub fn cancelable(&self) -> bool {
+        self.cancelable
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-event-target
+    pub fn target(&self) -> Option<EventTarget> {
+        self.target
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-event-currenttarget
+    pub fn current_target(&self) -> Option<EventTarget> {
+        self.current_target
+    }
+
+    pub fn event_phase(&self) -> EventPhase {
+        self.phase
+    }
+
+    /// 現在のターゲットのリスナを呼んだ後に、イベントの伝播を止める
+    /// https://dom.spec.whatwg.org/#dom-event-stoppropagation
+    pub fn stop_propagation(&mut self) {
+        self.stop_propagation = true;
+    }
+
+    /// 残りのリスナを呼ばずに、イベントの伝播を止める
+    /// https://dom.spec.whatwg.org/#dom-event-stopimmediatepropagation
+    pub fn stop_immediate_propagation(&mut self) {
+        self.stop_propagation = true;
+        self.stop_immediate_propagation = true;
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-event-preventdefault
+    pub fn prevent_default(&mut self) {
+        if self.cancelable {
+            self.canceled = true;
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-event-defaultprevented
+    pub fn default_prevented(&self) -> bool {
+        self.canceled
+    }
+}
+
+pub type EventCallback = Rc<dyn Fn(&mut Event)>;
+
+/// https://dom.spec.whatwg.org/#dictdef-addeventlisteneroptions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListenerOptions {
+    /// 捕獲フェーズで呼ばれるリスナ
+    pub capture: bool,
+    /// 一度呼ばれたら削除されるリスナ
+    pub once: bool,
+}
+
+/// https://dom.spec.whatwg.org/#concept-event-listener
+#[derive(Clone)]
+pub struct EventListener {
+    event_type: String,
+    callback: EventCallback,
+    options: ListenerOptions,
+}
+
+impl EventListener {
+    /// イベントの種類、コールバック、captureが同じリスナは同じリスナとして扱う
+    fn is_same(&self, event_type: &str, callback: &EventCallback, capture: bool) -> bool {
+        self.event_type == event_type
+            && Rc::ptr_eq(&self.callback, callback)
+            && self.options.capture == capture
+    }
+}
+
+impl Debug for EventListener {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        f.debug_struct("EventListener")
+            .field("event_type", &self.event_type)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+/// https://dom.spec.whatwg.org/#dom-eventtarget-addeventlistener
+pub fn add_event_listener(
+    document: &mut Document,
+    target: EventTarget,
+    event_type: &str,
+    callback: EventCallback,
+    options: ListenerOptions,
+) {
+    let listeners = document.event_listeners_mut(target);
+    // 同じリスナがすでに登録されている場合は何もしない
+    if listeners
+        .iter()
+        .any(|l| l.is_same(event_type, &callback, options.capture))
+    {
+        return;
+    }
+    listeners.push(EventListener {
+        event_type: event_type.to_string(),
+        callback,
+        options,
+    });
+}
+
+/// https://dom.spec.whatwg.org/#dom-eventtarget-removeeventlistener
+pub fn remove_event_listener(
+    document: &mut Document,
+    target: EventTarget,
+    event_type: &str,
+    callback: &EventCallback,
+    capture: bool,
+) {
+    document
+        .event_listeners_mut(target)
+        .retain(|l| !l.is_same(event_type, callback, capture));
+}
+
+/// `event`を`target`に送る。ターゲットから根までの経路を、捕獲、ターゲット、
+/// バブリングの順にたどってリスナを呼ぶ。リスナの中でDOMを変更できるように、
+/// リスナを呼んでいる間はドキュメントを借用しない。`prevent_default`が
+/// 呼ばれなかった場合はtrueを返し、呼び出し側は既定の動作を行う
+/// https://dom.spec.whatwg.org/#concept-event-dispatch
+pub fn dispatch_event(
+    document: &Rc<RefCell<Document>>,
+    target: EventTarget,
+    event: &mut Event,
+) -> bool {
+    event.target = Some(target);
+
+    // イベントの経路。ノードの場合は祖先をたどり、ドキュメントの親として
+    // ウィンドウを加える。loadイベントはウィンドウに伝播しない
+    let mut path = Vec::new();
+    path.push(target);
+    if let EventTarget::Node(node) = target {
+        let document = document.borrow();
+        path.extend(ancestors(&document, node).map(EventTarget::Node));
+        if document.window().is_some() && event.event_type != "load" {
+            path.push(EventTarget::Window);
+        }
+    }
+
+    for t in path.iter().skip(1).rev() {
+        invoke(document, *t, event, EventPhase::Capturing, true);
+    }
+    invoke(document, target, event, EventPhase::AtTarget, true);
+    invoke(document, target, event, EventPhase::AtTarget, false);
+    if event.bubbles {
+        for t in path.iter().skip(1) {
+            invoke(document, *t, event, EventPhase::Bubbling, false);
+        }
+    }
+
+    event.phase = EventPhase::None;
+    event.current_target = None;
+    event.stop_propagation = false;
+    event.stop_immediate_propagation = false;
+
+    !event.canceled
+}
+
+/// `target`のリスナのうち、イベントの種類とcaptureが一致するものを登録順に呼ぶ
+/// https://dom.spec.whatwg.org/#concept-event-listener-inner-invoke
+fn invoke(
+    document: &Rc<RefCell<Document>>,
+    target: EventTarget,
+    event: &mut Event,
+    phase: EventPhase,
+    capture: bool,
+) {
+    if event.stop_propagation {
+        return;
+    }
+    event.current_target = Some(target);
+    event.phase = phase;
+
+    // リスナの中でリスナが追加されても、そのリスナは今回は呼ばない
+    let listeners = document.borrow().event_listeners(target).to_vec();
+    for listener in listeners {
+        if listener.event_type != event.event_type || listener.options.capture != capture {
+            continue;
+        }
+
+        // 前のリスナによって削除されたリスナは呼ばない
+        {
+            let mut document = document.borrow_mut();
+            let listeners = document.event_listeners_mut(target);
+            let position = match listeners
+                .iter()
+                .position(|l| l.is_same(&event.event_type, &listener.callback, capture))
+            {
+                Some(p) => p,
+                None => continue,
+            };
+            if listener.options.once {
+                listeners.remove(position);
+            }
+        }
+
+        (listener.callback)(event);
+
+        if event.stop_immediate_propagation {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::dom::api::get_element_by_id;
+    use crate::renderer::html::parser::parse_html;
+    use alloc::format;
+    use alloc::vec;
+
+    /// 呼ばれたリスナの名前と、そのときのフェーズを`log`に記録するリスナ
+    fn logger(log: &Rc<RefCell<Vec<String>>>, name: &str) -> EventCallback {
+        let log = log.clone();
+        let name = name.to_string();
+        Rc::new(move |event: &mut Event| {
+            log.borrow_mut()
+                .push(format!("{}:{:?}", name, event.event_phase()));
+        })
+    }
+
+    #[test]
+    fn test_dispatch_phases() {
+        let window = parse_html("<html><head></head><body><p id=p><a id=a>x</a></p></body></html>");
+        let dom = window.borrow().document();
+        let (p, a) = {
+            let document = dom.borrow();
+            let root = Some(document.root());
+            (
+                get_element_by_id(&document, root, "p").expect("failed to get p"),
+                get_element_by_id(&document, root, "a").expect("failed to get a"),
+            )
+        };
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let capture = ListenerOptions {
+            capture: true,
+            once: false,
+        };
+        {
+            let mut document = dom.borrow_mut();
+            let p = EventTarget::Node(p);
+            let a = EventTarget::Node(a);
+            add_event_listener(&mut document, p, "click", logger(&log, "p"), capture);
+            add_event_listener(
+                &mut document,
+                p,
+                "click",
+                logger(&log, "p"),
+                Default::default(),
+            );
+            add_event_listener(
+                &mut document,
+                a,
+                "click",
+                logger(&log, "a"),
+                Default::default(),
+            );
+            add_event_listener(&mut document, a, "keydown", logger(&log, "key"), capture);
+            add_event_listener(
+                &mut document,
+                EventTarget::Window,
+                "click",
+                logger(&log, "window"),
+                Default::default(),
+            );
+        }
+
+        let mut event = Event::new_mouse_event("click", (1, 2));
+        assert!(dispatch_event(&dom, EventTarget::Node(a), &mut event));
+        assert_eq!(
+            vec!["p:Capturing", "a:AtTarget", "p:Bubbling", "window:Bubbling"],
+            *log.borrow()
+        );
+        assert_eq!(Some(EventTarget::Node(a)), event.target());
+        assert_eq!(None, event.current_target());
+        assert_eq!(EventPhase::None, event.event_phase());
+
+        // バブリングしないイベントは、捕獲フェーズとターゲットだけをたどる
+        log.borrow_mut().clear();
+        let mut event = Event::new("click", false, false);
+        dispatch_event(&dom, EventTarget::Node(a), &mut event);
+        assert_eq!(vec!["p:Capturing", "a:AtTarget"], *log.borrow());
+    }
+
+    #[test]
+    fn test_stop_propagation_and_prevent_default() {
+        let window = parse_html("<html><head></head><body><p id=p>x</p></body></html>");
+        let dom = window.borrow().document();
+        let p = {
+            let document = dom.borrow();
+            get_element_by_id(&document, Some(document.root()), "p").expect("failed to get p")
+        };
+        let body = dom.borrow().parent(p).expect("failed to get body");
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let stop: EventCallback = Rc::new(|event: &mut Event| {
+            event.stop_propagation();
+            event.prevent_default();
+        });
+        let once = ListenerOptions {
+            capture: false,
+            once: true,
+        };
+        {
+            let mut document = dom.borrow_mut();
+            add_event_listener(&mut document, EventTarget::Node(p), "click", stop, once);
+            add_event_listener(
+                &mut document,
+                EventTarget::Node(p),
+                "click",
+                logger(&log, "p"),
+                Default::default(),
+            );
+            add_event_listener(
+                &mut document,
+                EventTarget::Node(body),
+                "click",
+                logger(&log, "body"),
+                Default::default(),
+            );
+        }
+
+        // 同じターゲットの残りのリスナは呼ばれるが、親には伝播しない
+        let mut event = Event::new_mouse_event("click", (0, 0));
+        assert!(!dispatch_event(&dom, EventTarget::Node(p), &mut event));
+        assert_eq!(vec!["p:AtTarget"], *log.borrow());
+
+        // onceのリスナは削除されている
+        log.borrow_mut().clear();
+        let mut event = Event::new_mouse_event("click", (0, 0));
+        assert!(dispatch_event(&dom, EventTarget::Node(p), &mut event));
+        assert_eq!(vec!["p:AtTarget", "body:Bubbling"], *log.borrow());
+
+        // キャンセルできないイベントは、prevent_defaultを呼んでもキャンセルされない
+        let mut event = Event::new_input_event(None);
+        event.prevent_default();
+        assert!(!event.default_prevented());
+
+        // リスナを削除する
+        let callback = logger(&log, "removed");
+        {
+            let mut document = dom.borrow_mut();
+            let target = EventTarget::Node(p);
+            add_event_listener(&mut document, target, "click", callback.clone(), once);
+            remove_event_listener(&mut document, target, "click", &callback, false);
+            assert_eq!(1, document.event_listeners(target).len());
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use core::fmt::Display;
+use core::fmt::Formatter;
+
+/// DOMの操作が失敗したときに返すエラー
+/// https://webidl.spec.whatwg.org/#idl-DOMException
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomException {
+    /// ツリーの構造が正しくなくなる操作をしようとした
+    /// https://webidl.spec.whatwg.org/#hierarchyrequesterror
+    HierarchyRequestError,
+    /// 操作の対象のノードが見つからない
+    /// https://webidl.spec.whatwg.org/#notfounderror
+    NotFoundError,
+    /// 名前などに使えない文字が含まれている
+    /// https://webidl.spec.whatwg.org/#invalidcharactererror
+    InvalidCharacterError,
+    /// 文字列をセレクタなどとしてパースできない
+    /// https://webidl.spec.whatwg.org/#syntaxerror
+    SyntaxError,
+    /// 操作がサポートされていない
+    /// https://webidl.spec.whatwg.org/#notsupportederror
+    NotSupportedError,
+}
+
+impl DomException {
+    /// 仕様で定められた例外の名前を返す
+    pub fn name(&self) -> &'static str {
+        match self {
+            DomException::HierarchyRequestError => "HierarchyRequestError",
+            DomException::NotFoundError => "NotFoundError",
+            DomException::InvalidCharacterError => "InvalidCharacterError",
+            DomException::SyntaxError => "SyntaxError",
+            DomException::NotSupportedError => "NotSupportedError",
+        }
+    }
+}
+
+impl Display for DomException {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,227 @@
+//! https://html.spec.whatwg.org/multipage/interaction.html#focus
+
+use crate::renderer::dom::api::ancestors;
+use crate::renderer::dom::api::descendants;
+use crate::renderer::dom::api::get_target_element_node;
+use crate::renderer::dom::event::dispatch_event;
+use crate::renderer::dom::event::Event;
+use crate::renderer::dom::event::EventTarget;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::ElementKind;
+use crate::renderer::dom::node::NodeId;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+/// 順番にフォーカスを移す方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusDirection {
+    /// Tabキー
+    Forward,
+    /// Shift+Tabキー
+    Backward,
+}
+
+/// 要素がtabindex属性を持たなくてもフォーカスできるかどうかを返す。href属性を
+/// 持つ<a>要素と、編集可能な要素がフォーカスできる
+/// https://html.spec.whatwg.org/multipage/interaction.html#focusable-area
+fn is_focusable_by_default(document: &Document, node: NodeId) -> bool {
+    let element = match document.element(node) {
+        Some(e) => e,
+        None => return false,
+    };
+    if element.kind() == ElementKind::A && element.has_attribute("href") {
+        return true;
+    }
+    match element.get_attribute("contenteditable") {
+        Some(value) => !value.eq_ignore_ascii_case("false"),
+        None => false,
+    }
+}
+
+/// 要素のtabindexの値を返す。tabindex属性がないか、整数として解釈できない
+/// 場合は、既定でフォーカスできる要素なら0になる。フォーカスできない要素の場合は
+/// Noneを返す
+/// https://html.spec.whatwg.org/multipage/interaction.html#the-tabindex-attribute
+pub fn tab_index(document: &Document, node: NodeId) -> Option<i64> {
+    let element = document.element(node)?;
+    if let Some(value) = element.get_attribute("tabindex") {
+        if let Ok(index) = value.trim().parse::<i64>() {
+            return Some(index);
+        }
+    }
+    if is_focusable_by_default(document, node) {
+        Some(0)
+    } else {
+        None
+    }
+}
+
+/// 要素がドキュメントのツリーの中にあり、フォーカスできるかどうかを返す
+pub fn is_focusable(document: &Document, node: NodeId) -> bool {
+    tab_index(document, node).is_some() && ancestors(document, node).any(|n| n == document.root())
+}
+
+/// Tabキーでフォーカスを移す順番に要素を返す。tabindexが正の要素を値の小さい
+/// 順に並べ、その後にtabindexが0の要素を文書順に並べる。tabindexが負の要素は
+/// フォーカスできるが、この順番には含まれない
+/// https://html.spec.whatwg.org/multipage/interaction.html#sequential-focus-navigation-order
+pub fn sequential_focus_order(document: &Document) -> Vec<NodeId> {
+    let mut order: Vec<(i64, NodeId)> = descendants(document, document.root())
+        .filter_map(|n| tab_index(document, n).map(|index| (index, n)))
+        .filter(|(index, _)| *index >= 0)
+        .collect();
+    // 安定ソートなので、同じ値の要素は文書順のままになる
+    order.sort_by_key(|(index, _)| if *index > 0 { *index } else { i64::MAX });
+    order.into_iter().map(|(_, n)| n).collect()
+}
+
+/// フォーカスされている要素を返す。何もフォーカスされていない場合は<body>要素を
+/// 返す
+/// https://html.spec.whatwg.org/multipage/interaction.html#dom-document-activeelement
+pub fn active_element(document: &Document) -> Option<NodeId> {
+    document
+        .focused_element()
+        .or_else(|| get_target_element_node(document, Some(document.root()), ElementKind::Body))
+}
+
+/// `node`にフォーカスを移す。Noneの場合はフォーカスを外す。フォーカスを失う
+/// 要素にblurとfocusoutを、フォーカスを得る要素にfocusとfocusinを送る。
+/// フォーカスできない要素の場合や、すでにフォーカスされている場合は何もしない。
+/// フォーカスが移った場合はtrueを返す
+/// https://html.spec.whatwg.org/multipage/interaction.html#focusing-steps
+pub fn focus(document: &Rc<RefCell<Document>>, node: Option<NodeId>) -> bool {
+    let old = {
+        let document = document.borrow();
+        if let Some(n) = node {
+            if !is_focusable(&document, n) {
+                return false;
+            }
+        }
+        let old = document.focused_element();
+        if old == node {
+            return false;
+        }
+        old
+    };
+
+    // https://html.spec.whatwg.org/multipage/interaction.html#focus-update-steps
+    if let Some(old) = old {
+        let mut event = Event::new("blur", false, false);
+        dispatch_event(document, EventTarget::Node(old), &mut event);
+        let mut event = Event::new("focusout", true, false);
+        dispatch_event(document, EventTarget::Node(old), &mut event);
+    }
+
+    document.borrow_mut().set_focused_element(node);
+
+    if let Some(node) = node {
+        let mut event = Event::new("focus", false, false);
+        dispatch_event(document, EventTarget::Node(node), &mut event);
+        let mut event = Event::new("focusin", true, false);
+        dispatch_event(document, EventTarget::Node(node), &mut event);
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::dom::api::get_element_by_id;
+    use crate::renderer::dom::event::add_event_listener;
+    use crate::renderer::dom::event::EventCallback;
+    use crate::renderer::html::parser::parse_html;
+    use alloc::format;
+    use alloc::string::String;
+    use alloc::vec;
+
+    fn element(document: &Document, id: &str) -> NodeId {
+        get_element_by_id(document, Some(document.root()), id).expect("failed to get an element")
+    }
+
+    #[test]
+    fn test_sequential_focus_order() {
+        let window = parse_html(
+            "<html><head></head><body>\
+<a id=a href=x>a</a><a id=b>b</a><p id=c tabindex=2>c</p><p id=d tabindex=-1>d</p>\
+<p id=e contenteditable>e</p><p id=f tabindex=1>f</p><a id=g href=x tabindex=2>g</a>\
+</body></html>",
+        );
+        let dom = window.borrow().document();
+        let document = dom.borrow();
+        let ids: Vec<NodeId> = ["f", "c", "g", "a", "e"]
+            .iter()
+            .map(|id| element(&document, id))
+            .collect();
+        assert_eq!(ids, sequential_focus_order(&document));
+
+        // tabindexが負の要素はフォーカスできるが、順番には含まれない
+        assert!(is_focusable(&document, element(&document, "d")));
+        assert!(!is_focusable(&document, element(&document, "b")));
+    }
+
+    #[test]
+    fn test_focus_events() {
+        let window = parse_html(
+            "<html><head></head><body><a id=a href=x>a</a><a id=b href=x>b</a><p id=p>p</p>\
+</body></html>",
+        );
+        let dom = window.borrow().document();
+        let (a, b, p, body) = {
+            let document = dom.borrow();
+            (
+                element(&document, "a"),
+                element(&document, "b"),
+                element(&document, "p"),
+                active_element(&document).expect("failed to get body"),
+            )
+        };
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        for (target, name) in [(a, "a"), (b, "b"), (body, "body")] {
+            for event_type in ["focus", "blur", "focusin", "focusout"] {
+                let log = log.clone();
+                let callback: EventCallback = Rc::new(move |event: &mut Event| {
+                    log.borrow_mut()
+                        .push(format!("{}:{}", name, event.event_type()));
+                });
+                add_event_listener(
+                    &mut dom.borrow_mut(),
+                    EventTarget::Node(target),
+                    event_type,
+                    callback,
+                    Default::default(),
+                );
+            }
+        }
+
+        assert!(focus(&dom, Some(a)));
+        // フォーカスできない要素や、フォーカスされている要素には移らない
+        assert!(!focus(&dom, Some(p)));
+        assert!(!focus(&dom, Some(a)));
+        assert!(focus(&dom, Some(b)));
+        assert_eq!(Some(b), active_element(&dom.borrow()));
+        assert_eq!(
+            vec![
+                "a:focus",
+                "a:focusin",
+                "body:focusin",
+                "a:blur",
+                "a:focusout",
+                "body:focusout",
+                "b:focus",
+                "b:focusin",
+                "body:focusin",
+            ],
+            *log.borrow()
+        );
+
+        // フォーカスされている要素が取り除かれると、イベントを送らずに<body>要素
+        // に戻る
+        log.borrow_mut().clear();
+        dom.borrow_mut().remove(b);
+        assert_eq!(Some(body), active_element(&dom.borrow()));
+        assert_eq!(Vec::<String>::new(), *log.borrow());
+    }
+}
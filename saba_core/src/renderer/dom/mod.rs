@@ -1,2 +1,10 @@
 pub mod api;
+pub mod event;
+pub mod exception;
+pub mod focus;
+pub mod mutation;
 pub mod node;
+pub mod range;
+pub mod selection;
+pub mod serializer;
+pub mod traversal;
@@ -0,0 +1,412 @@
+use crate::renderer::dom::exception::DomException;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::node::NodeKind;
+use alloc::string::ToString;
+
+/// `ancestor`が`node`自身か、`node`の祖先かどうかを返す
+/// https://dom.spec.whatwg.org/#concept-tree-inclusive-ancestor
+fn is_inclusive_ancestor(document: &Document, ancestor: NodeId, node: NodeId) -> bool {
+    let mut current = Some(node);
+    while let Some(n) = current {
+        if n == ancestor {
+            return true;
+        }
+        current = document.parent(n);
+    }
+    false
+}
+
+/// `parent`がDocumentノードで、`node`が要素の場合に、`child`以外の子要素が
+/// すでにあるかどうかを返す。Documentノードが持てる子要素は1つだけである
+fn has_other_document_element(
+    document: &Document,
+    node: NodeId,
+    parent: NodeId,
+    child: Option<NodeId>,
+) -> bool {
+    if document.kind(parent) != &NodeKind::Document || document.element(node).is_none() {
+        return false;
+    }
+
+    let mut current = document.first_child(parent);
+    while let Some(c) = current {
+        if Some(c) != child && document.element(c).is_some() {
+            return true;
+        }
+        current = document.next_sibling(c);
+    }
+    false
+}
+
+/// `node`を`parent`の子として挿入できるかどうかを検査する。`replace_child`では
+/// `child`を取り除いた後のツリーを検査する
+/// https://dom.spec.whatwg.org/#concept-node-ensure-pre-insertion-validity
+fn ensure_validity(
+    document: &Document,
+    node: NodeId,
+    parent: NodeId,
+    child: Option<NodeId>,
+    is_replace: bool,
+) -> Result<(), DomException> {
+    // 子ノードを持てるのはDocumentノードと要素だけ
+    if let NodeKind::Text(_) = document.kind(parent) {
+        return Err(DomException::HierarchyRequestError);
+    }
+
+    // ノードを自身の子孫に挿入すると、ツリーが循環する
+    if is_inclusive_ancestor(document, node, parent) {
+        return Err(DomException::HierarchyRequestError);
+    }
+
+    if let Some(c) = child {
+        if document.parent(c) != Some(parent) {
+            return Err(DomException::NotFoundError);
+        }
+    }
+
+    match document.kind(node) {
+        NodeKind::Document => return Err(DomException::HierarchyRequestError),
+        NodeKind::Text(_) if document.kind(parent) == &NodeKind::Document => {
+            return Err(DomException::HierarchyRequestError);
+        }
+        _ => {}
+    }
+
+    let replaced = if is_replace { child } else { None };
+    if has_other_document_element(document, node, parent, replaced) {
+        return Err(DomException::HierarchyRequestError);
+    }
+
+    Ok(())
+}
+
+/// https://dom.spec.whatwg.org/#concept-node-pre-insert
+fn pre_insert(
+    document: &mut Document,
+    node: NodeId,
+    parent: NodeId,
+    child: Option<NodeId>,
+) -> Result<NodeId, DomException> {
+    ensure_validity(document, node, parent, child, /*is_replace*/ false)?;
+
+    // `node`自身の直前に挿入する場合は、`node`を取り除いた後の次の兄弟ノードの
+    // 直前に挿入する
+    let reference_child = if child == Some(node) {
+        document.next_sibling(node)
+    } else {
+        child
+    };
+
+    document.insert(node, parent, reference_child);
+    Ok(node)
+}
+
+/// `node`を`parent`の最後の子ノードとして追加し、`node`を返す。`node`がすでに
+/// ツリーの中にある場合は、元の位置から移動する
+/// https://dom.spec.whatwg.org/#dom-node-appendchild
+pub fn append_child(
+    document: &mut Document,
+    parent: NodeId,
+    node: NodeId,
+) -> Result<NodeId, DomException> {
+    pre_insert(document, node, parent, None)
+}
+
+/// `node`を`parent`の子ノード`child`の直前に挿入し、`node`を返す。`child`が
+/// Noneの場合は`append_child`と同じになる
+/// https://dom.spec.whatwg.org/#dom-node-insertbefore
+pub fn insert_before(
+    document: &mut Document,
+    parent: NodeId,
+    node: NodeId,
+    child: Option<NodeId>,
+) -> Result<NodeId, DomException> {
+    pre_insert(document, node, parent, child)
+}
+
+/// `parent`の子ノード`child`を取り除き、`child`を返す
+/// https://dom.spec.whatwg.org/#dom-node-removechild
+pub fn remove_child(
+    document: &mut Document,
+    parent: NodeId,
+    child: NodeId,
+) -> Result<NodeId, DomException> {
+    if document.parent(child) != Some(parent) {
+        return Err(DomException::NotFoundError);
+    }
+
+    document.remove(child);
+    Ok(child)
+}
+
+/// `parent`の子ノード`child`を`node`に置き換え、取り除いた`child`を返す
+/// https://dom.spec.whatwg.org/#dom-node-replacechild
+/// https://dom.spec.whatwg.org/#concept-node-replace
+pub fn replace_child(
+    document: &mut Document,
+    parent: NodeId,
+    node: NodeId,
+    child: NodeId,
+) -> Result<NodeId, DomException> {
+    ensure_validity(
+        document,
+        node,
+        parent,
+        Some(child),
+        /*is_replace*/ true,
+    )?;
+
+    if node == child {
+        return Ok(child);
+    }
+
+    let mut reference_child = document.next_sibling(child);
+    if reference_child == Some(node) {
+        reference_child = document.next_sibling(node);
+    }
+
+    document.remove(child);
+    document.insert(node, parent, reference_child);
+    Ok(child)
+}
+
+/// `node`の複製を作成する。`deep`がtrueの場合は子孫もすべて複製する。複製した
+/// ノードはどのノードの子でもない。Documentノードは同じアリーナに複製できない
+/// ので、NotSupportedErrorを返す
+/// https://dom.spec.whatwg.org/#concept-node-clone
+pub fn clone_node(
+    document: &mut Document,
+    node: NodeId,
+    deep: bool,
+) -> Result<NodeId, DomException> {
+    if document.kind(node) == &NodeKind::Document {
+        return Err(DomException::NotSupportedError);
+    }
+
+    let copy = document.create_node(document.kind(node).clone());
+
+    if deep {
+        let mut child = document.first_child(node);
+        while let Some(c) = child {
+            let child_copy = clone_node(document, c, deep)?;
+            document.insert(child_copy, copy, None);
+            child = document.next_sibling(c);
+        }
+    }
+
+    Ok(copy)
+}
+
+/// `textContent`のセッター。要素の場合は子ノードをすべて`text`を持つテキスト
+/// ノードに置き換え、テキストノードの場合は内容を書き換える。Documentノードの
+/// 場合は何もしない
+/// https://dom.spec.whatwg.org/#dom-node-textcontent
+pub fn set_text_content(document: &mut Document, node: NodeId, text: &str) {
+    match document.kind_mut(node) {
+        NodeKind::Document => {}
+        NodeKind::Text(data) => *data = text.to_string(),
+        NodeKind::Element(_) => {
+            // https://dom.spec.whatwg.org/#string-replace-all
+            let text_node = if text.is_empty() {
+                None
+            } else {
+                Some(document.create_text(text))
+            };
+            document.replace_all(text_node, node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::dom::api::child_text_content;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn children(document: &Document, parent: NodeId) -> Vec<NodeId> {
+        let mut result = Vec::new();
+        let mut child = document.first_child(parent);
+        while let Some(c) = child {
+            assert_eq!(Some(parent), document.parent(c));
+            assert_eq!(result.last().copied(), document.previous_sibling(c));
+            result.push(c);
+            child = document.next_sibling(c);
+        }
+        assert_eq!(result.last().copied(), document.last_child(parent));
+        result
+    }
+
+    #[test]
+    fn test_append_and_insert() {
+        let mut document = Document::new();
+        let p = document.create_element("p", Vec::new());
+        let a = document.create_text("a");
+        let b = document.create_text("b");
+        let c = document.create_text("c");
+
+        assert_eq!(Ok(a), append_child(&mut document, p, a));
+        assert_eq!(Ok(c), append_child(&mut document, p, c));
+        assert_eq!(Ok(b), insert_before(&mut document, p, b, Some(c)));
+        assert_eq!(vec![a, b, c], children(&document, p));
+
+        // すでにツリーの中にあるノードは移動する
+        assert_eq!(Ok(a), insert_before(&mut document, p, a, None));
+        assert_eq!(vec![b, c, a], children(&document, p));
+        assert_eq!(Ok(c), insert_before(&mut document, p, c, Some(c)));
+        assert_eq!(vec![b, c, a], children(&document, p));
+    }
+
+    #[test]
+    fn test_hierarchy_request_error() {
+        let mut document = Document::new();
+        let root = document.root();
+        let html = document.create_element("html", Vec::new());
+        let body = document.create_element("body", Vec::new());
+        let text = document.create_text("a");
+        append_child(&mut document, root, html).expect("failed to append html");
+        append_child(&mut document, html, body).expect("failed to append body");
+
+        // 祖先を子孫に挿入することはできない
+        assert_eq!(
+            Err(DomException::HierarchyRequestError),
+            append_child(&mut document, body, html)
+        );
+        assert_eq!(
+            Err(DomException::HierarchyRequestError),
+            append_child(&mut document, body, body)
+        );
+        // テキストノードは子ノードを持てない
+        assert_eq!(
+            Err(DomException::HierarchyRequestError),
+            append_child(&mut document, text, body)
+        );
+        // Documentノードは、テキストノードと2つ目の要素を子に持てない
+        assert_eq!(
+            Err(DomException::HierarchyRequestError),
+            append_child(&mut document, root, text)
+        );
+        let p = document.create_element("p", Vec::new());
+        assert_eq!(
+            Err(DomException::HierarchyRequestError),
+            append_child(&mut document, root, p)
+        );
+        assert_eq!(
+            Err(DomException::HierarchyRequestError),
+            append_child(&mut document, body, root)
+        );
+        // 失敗した場合はツリーを変更しない
+        assert_eq!(vec![body], children(&document, html));
+    }
+
+    #[test]
+    fn test_remove_child() {
+        let mut document = Document::new();
+        let p = document.create_element("p", Vec::new());
+        let a = document.create_text("a");
+        let b = document.create_text("b");
+        append_child(&mut document, p, a).expect("failed to append a");
+        append_child(&mut document, p, b).expect("failed to append b");
+
+        assert_eq!(
+            Err(DomException::NotFoundError),
+            remove_child(&mut document, b, a)
+        );
+        assert_eq!(Ok(a), remove_child(&mut document, p, a));
+        assert_eq!(vec![b], children(&document, p));
+        assert!(document.parent(a).is_none());
+        assert_eq!(
+            Err(DomException::NotFoundError),
+            remove_child(&mut document, p, a)
+        );
+        assert_eq!(
+            Err(DomException::NotFoundError),
+            insert_before(&mut document, p, b, Some(a))
+        );
+    }
+
+    #[test]
+    fn test_replace_child() {
+        let mut document = Document::new();
+        let root = document.root();
+        let html = document.create_element("html", Vec::new());
+        append_child(&mut document, root, html).expect("failed to append html");
+        let p = document.create_element("p", Vec::new());
+        let a = document.create_text("a");
+        let b = document.create_text("b");
+        let c = document.create_text("c");
+        append_child(&mut document, p, a).expect("failed to append a");
+        append_child(&mut document, p, b).expect("failed to append b");
+
+        assert_eq!(Ok(a), replace_child(&mut document, p, c, a));
+        assert_eq!(vec![c, b], children(&document, p));
+        assert!(document.parent(a).is_none());
+
+        // 置き換えるノードが次の兄弟ノードの場合
+        assert_eq!(Ok(c), replace_child(&mut document, p, b, c));
+        assert_eq!(vec![b], children(&document, p));
+
+        // Documentノードの唯一の要素は、別の要素に置き換えられる
+        let body = document.create_element("body", Vec::new());
+        assert_eq!(Ok(html), replace_child(&mut document, root, body, html));
+        assert_eq!(vec![body], children(&document, root));
+        assert_eq!(
+            Err(DomException::NotFoundError),
+            replace_child(&mut document, p, c, a)
+        );
+    }
+
+    #[test]
+    fn test_clone_node() {
+        let mut document = Document::new();
+        let p = document.create_element("p", Vec::new());
+        let a = document.create_element("a", Vec::new());
+        let text = document.create_text("text");
+        append_child(&mut document, p, a).expect("failed to append a");
+        append_child(&mut document, a, text).expect("failed to append text");
+
+        let shallow = clone_node(&mut document, p, false).expect("failed to clone p");
+        assert_eq!(document.kind(p), document.kind(shallow));
+        assert!(document.first_child(shallow).is_none());
+
+        let deep = clone_node(&mut document, p, true).expect("failed to clone p");
+        assert!(document.parent(deep).is_none());
+        let a_copy = document
+            .first_child(deep)
+            .expect("failed to get a copy of a");
+        assert_ne!(a, a_copy);
+        assert_eq!("text", child_text_content(&document, a_copy));
+
+        // 複製を変更しても元のノードは変わらない
+        set_text_content(&mut document, a_copy, "copy");
+        assert_eq!("text", child_text_content(&document, a));
+
+        let root = document.root();
+        assert_eq!(
+            Err(DomException::NotSupportedError),
+            clone_node(&mut document, root, true)
+        );
+    }
+
+    #[test]
+    fn test_set_text_content() {
+        let mut document = Document::new();
+        let p = document.create_element("p", Vec::new());
+        let a = document.create_element("a", Vec::new());
+        let text = document.create_text("a");
+        append_child(&mut document, p, a).expect("failed to append a");
+        append_child(&mut document, p, text).expect("failed to append text");
+
+        set_text_content(&mut document, p, "b");
+        assert_eq!(1, children(&document, p).len());
+        assert_eq!("b", child_text_content(&document, p));
+        assert!(document.parent(a).is_none());
+
+        set_text_content(&mut document, text, "c");
+        assert_eq!(&NodeKind::Text("c".to_string()), document.kind(text));
+
+        set_text_content(&mut document, p, "");
+        assert!(document.first_child(p).is_none());
+    }
+}
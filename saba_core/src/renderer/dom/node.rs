@@ -1,103 +1,341 @@
+use crate::image::Image;
+use crate::renderer::dom::event::EventListener;
+use crate::renderer::dom::event::EventTarget;
+use crate::renderer::dom::exception::DomException;
+use crate::renderer::dom::selection::Selection;
 use crate::renderer::html::attribute::Attribute;
 use alloc::format;
 use alloc::rc::Rc;
 use alloc::rc::Weak;
 use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::fmt::Display;
 use core::fmt::Formatter;
 use core::str::FromStr;
 
+/// ドキュメントの中のノードを指すID。ノードを所有する`Document`のアリーナの
+/// インデックスなので、同じドキュメントの中でだけ意味を持つ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+/// アリーナに格納されるノード。親子と兄弟の関係はすべて`NodeId`で表す
+/// https://dom.spec.whatwg.org/#interface-node
 #[derive(Debug, Clone)]
-pub struct Node {
-    pub kind: NodeKind,
-    window: Weak<RefCell<Window>>,
-    parent: Weak<RefCell<Node>>,
-    first_child: Option<Rc<RefCell<Node>>>,
-    last_child: Weak<RefCell<Node>>,
-    previous_sibling: Weak<RefCell<Node>>,
-    next_sibling: Option<Rc<RefCell<Node>>>,
+struct NodeData {
+    kind: NodeKind,
+    parent: Option<NodeId>,
+    first_child: Option<NodeId>,
+    last_child: Option<NodeId>,
+    previous_sibling: Option<NodeId>,
+    next_sibling: Option<NodeId>,
+    /// ノードに登録されたイベントリスナ
+    listeners: Vec<EventListener>,
 }
 
-impl PartialEq for Node {
-    fn eq(&self, other: &Self) -> bool {
-        self.kind == other.kind
+impl NodeData {
+    fn new(kind: NodeKind) -> Self {
+        Self {
+            kind,
+            parent: None,
+            first_child: None,
+            last_child: None,
+            previous_sibling: None,
+            next_sibling: None,
+            listeners: Vec::new(),
+        }
     }
 }
 
-impl Node {
-    pub fn new(kind: NodeKind) -> Self {
+/// 文書の読み込みの状態
+/// https://html.spec.whatwg.org/multipage/dom.html#current-document-readiness
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentReadyState {
+    /// パース中
+    Loading,
+    /// パースが終わり、サブリソースを読み込んでいる
+    Interactive,
+    /// サブリソースの読み込みも終わった
+    Complete,
+}
+
+impl Display for DocumentReadyState {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        let s = match self {
+            DocumentReadyState::Loading => "loading",
+            DocumentReadyState::Interactive => "interactive",
+            DocumentReadyState::Complete => "complete",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// ドキュメントと、そのドキュメントに属するすべてのノードを所有するアリーナ。
+/// ツリーから取り除かれたノードもアリーナに残るので、`NodeId`はドキュメントが
+/// 存在する限り有効である
+/// https://dom.spec.whatwg.org/#interface-document
+#[derive(Debug, Clone)]
+pub struct Document {
+    nodes: Vec<NodeData>,
+    window: Weak<RefCell<Window>>,
+    /// ウィンドウに登録されたイベントリスナ。イベントの経路はドキュメントの中で
+    /// 決まるので、ウィンドウの分もドキュメントが持つ
+    window_listeners: Vec<EventListener>,
+    /// 前回の描画の後に、ツリーの構造や要素の属性、テキストが変更されたかどうか
+    needs_style_recalc: bool,
+    ready_state: DocumentReadyState,
+    /// フォーカスされている要素
+    focused: Option<NodeId>,
+    selection: Option<Selection>,
+}
+
+impl Document {
+    pub fn new() -> Self {
         Self {
-            kind,
+            nodes: vec![NodeData::new(NodeKind::Document)],
             window: Weak::new(),
-            parent: Weak::new(),
-            first_child: None,
-            last_child: Weak::new(),
-            previous_sibling: Weak::new(),
-            next_sibling: None,
+            window_listeners: Vec::new(),
+            needs_style_recalc: false,
+            // パーサが作成するドキュメント以外は、最初から読み込みが終わっている
+            ready_state: DocumentReadyState::Complete,
+            focused: None,
+            selection: None,
         }
     }
 
-    pub fn set_parent(&mut self, parent: Weak<RefCell<Node>>) {
-        self.parent = parent;
+    /// Documentノード自身のID
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
     }
 
-    pub fn parent(&self) -> Weak<RefCell<Node>> {
-        self.parent.clone()
+    /// ドキュメントが属するウィンドウを返す。フラグメントのパースなどで作られた、
+    /// ウィンドウを持たないドキュメントの場合はNoneを返す
+    /// https://html.spec.whatwg.org/multipage/nav-history-apis.html#dom-document-defaultview
+    pub fn window(&self) -> Option<Rc<RefCell<Window>>> {
+        self.window.upgrade()
     }
 
-    pub fn set_first_child(&mut self, first_child: Option<Rc<RefCell<Node>>>) {
-        self.first_child = first_child
+    /// どのノードの子でもないノードを作成する。ツリーに挿入するには
+    /// `mutation::append_child`などを使う
+    pub fn create_node(&mut self, kind: NodeKind) -> NodeId {
+        self.nodes.push(NodeData::new(kind));
+        NodeId(self.nodes.len() - 1)
     }
 
-    pub fn first_child(&self) -> Option<Rc<RefCell<Node>>> {
-        self.first_child.as_ref().cloned()
+    /// https://dom.spec.whatwg.org/#dom-document-createelement
+    pub fn create_element(&mut self, tag: &str, attributes: Vec<Attribute>) -> NodeId {
+        self.create_node(NodeKind::Element(Element::new(tag, attributes)))
     }
 
-    pub fn set_last_child(&mut self, last_child: Weak<RefCell<Node>>) {
-        self.last_child = last_child;
+    /// https://dom.spec.whatwg.org/#dom-document-createtextnode
+    pub fn create_text(&mut self, data: &str) -> NodeId {
+        self.create_node(NodeKind::Text(data.to_string()))
     }
 
-    pub fn last_child(&self) -> Weak<RefCell<Node>> {
-        self.last_child.clone()
+    pub fn kind(&self, id: NodeId) -> &NodeKind {
+        &self.nodes[id.0].kind
     }
 
-    pub fn set_previous_sibling(&mut self, previous_sibling: Weak<RefCell<Node>>) {
-        self.previous_sibling = previous_sibling
+    /// ノードを変更するとスタイルを計算し直す必要があるので、
+    /// `needs_style_recalc`がtrueになる
+    pub fn kind_mut(&mut self, id: NodeId) -> &mut NodeKind {
+        self.needs_style_recalc = true;
+        &mut self.nodes[id.0].kind
     }
 
-    pub fn previous_sibling(&self) -> Weak<RefCell<Node>> {
-        self.previous_sibling.clone()
+    pub fn element(&self, id: NodeId) -> Option<&Element> {
+        match self.kind(id) {
+            NodeKind::Element(e) => Some(e),
+            NodeKind::Document | NodeKind::Text(_) => None,
+        }
     }
 
-    pub fn set_next_sibling(&mut self, next_sibling: Option<Rc<RefCell<Node>>>) {
-        self.next_sibling = next_sibling
+    pub fn element_mut(&mut self, id: NodeId) -> Option<&mut Element> {
+        match self.kind_mut(id) {
+            NodeKind::Element(e) => Some(e),
+            NodeKind::Document | NodeKind::Text(_) => None,
+        }
     }
 
-    pub fn next_sibling(&self) -> Option<Rc<RefCell<Node>>> {
-        self.next_sibling.as_ref().cloned()
+    pub fn element_kind(&self, id: NodeId) -> Option<ElementKind> {
+        self.element(id).map(|e| e.kind())
     }
 
-    pub fn set_window(&mut self, window: Weak<RefCell<Window>>) {
-        self.window = window;
+    /// https://html.spec.whatwg.org/multipage/dom.html#dom-document-readystate
+    pub fn ready_state(&self) -> DocumentReadyState {
+        self.ready_state
     }
 
-    pub fn kind(&self) -> NodeKind {
-        self.kind.clone()
+    /// 状態を変更するだけで、readystatechangeイベントは送らない
+    pub fn set_ready_state(&mut self, ready_state: DocumentReadyState) {
+        self.ready_state = ready_state;
     }
 
-    pub fn get_element(&self) -> Option<Element> {
-        match self.kind {
-            NodeKind::Document | NodeKind::Text(_) => None,
-            NodeKind::Element(ref e) => Some(e.clone()),
+    /// フォーカスされている要素を返す。何もフォーカスされていない場合はNone。
+    /// activeElementは`focus::active_element`で取得する
+    pub fn focused_element(&self) -> Option<NodeId> {
+        self.focused
+    }
+
+    /// フォーカスされている要素を変更するだけで、イベントは送らない
+    pub fn set_focused_element(&mut self, node: Option<NodeId>) {
+        self.focused = node;
+    }
+
+    /// 文書の選択範囲を返す。何も選択されていない場合はNone
+    /// https://html.spec.whatwg.org/multipage/interaction.html#dom-document-getselection
+    pub fn selection(&self) -> Option<Selection> {
+        self.selection
+    }
+
+    pub fn set_selection(&mut self, selection: Option<Selection>) {
+        self.selection = selection;
+    }
+
+    /// `ancestor`が`node`自身か、その祖先かどうかを返す
+    fn is_inclusive_ancestor(&self, ancestor: NodeId, node: NodeId) -> bool {
+        let mut current = Some(node);
+        while let Some(n) = current {
+            if n == ancestor {
+                return true;
+            }
+            current = self.parent(n);
         }
+        false
     }
 
-    pub fn element_kind(&self) -> Option<ElementKind> {
-        match self.kind {
-            NodeKind::Document | NodeKind::Text(_) => None,
-            NodeKind::Element(ref e) => Some(e.kind()),
+    /// 前回の描画の後にDOMが変更され、スタイルとレイアウトを計算し直す必要が
+    /// あるかどうかを返す
+    pub fn needs_style_recalc(&self) -> bool {
+        self.needs_style_recalc
+    }
+
+    /// 描画が終わったときに呼ぶ
+    pub fn clear_needs_style_recalc(&mut self) {
+        self.needs_style_recalc = false;
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].parent
+    }
+
+    pub fn first_child(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].first_child
+    }
+
+    pub fn last_child(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].last_child
+    }
+
+    pub fn previous_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].previous_sibling
+    }
+
+    pub fn next_sibling(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0].next_sibling
+    }
+
+    /// `target`に登録されたイベントリスナを登録順に返す
+    pub fn event_listeners(&self, target: EventTarget) -> &[EventListener] {
+        match target {
+            EventTarget::Window => &self.window_listeners,
+            EventTarget::Node(id) => &self.nodes[id.0].listeners,
+        }
+    }
+
+    pub fn event_listeners_mut(&mut self, target: EventTarget) -> &mut Vec<EventListener> {
+        match target {
+            EventTarget::Window => &mut self.window_listeners,
+            EventTarget::Node(id) => &mut self.nodes[id.0].listeners,
+        }
+    }
+
+    /// `node`を`parent`の子ノードとして、`child`の直前に挿入する。`child`が
+    /// Noneの場合は最後の子ノードになる。`node`が他のノードの子である場合は、
+    /// 先に取り除く。ツリーの構造が正しいかどうかは検査しないので、検査が必要な
+    /// 場合は`mutation`モジュールの関数を使う
+    /// https://dom.spec.whatwg.org/#concept-node-insert
+    pub fn insert(&mut self, node: NodeId, parent: NodeId, child: Option<NodeId>) {
+        if self.parent(node).is_some() {
+            self.remove(node);
+        }
+
+        let previous = match child {
+            Some(c) => self.previous_sibling(c),
+            None => self.last_child(parent),
+        };
+
+        match previous {
+            Some(p) => self.nodes[p.0].next_sibling = Some(node),
+            None => self.nodes[parent.0].first_child = Some(node),
+        }
+        match child {
+            Some(c) => self.nodes[c.0].previous_sibling = Some(node),
+            None => self.nodes[parent.0].last_child = Some(node),
+        }
+
+        let data = &mut self.nodes[node.0];
+        data.parent = Some(parent);
+        data.previous_sibling = previous;
+        data.next_sibling = child;
+        self.needs_style_recalc = true;
+    }
+
+    /// `node`を親ノードから取り除く。親ノードがない場合は何もしない
+    /// https://dom.spec.whatwg.org/#concept-node-remove
+    pub fn remove(&mut self, node: NodeId) {
+        let parent = match self.parent(node) {
+            Some(p) => p,
+            None => return,
+        };
+        let previous = self.previous_sibling(node);
+        let next = self.next_sibling(node);
+
+        // フォーカスされている要素が取り除かれる場合は、イベントを送らずにフォーカス
+        // を外す
+        // https://html.spec.whatwg.org/multipage/interaction.html#focus-fixup-rule
+        if let Some(focused) = self.focused {
+            if self.is_inclusive_ancestor(node, focused) {
+                self.focused = None;
+            }
+        }
+        // 選択範囲の端が取り除かれる場合は、選択を解除する
+        if let Some(selection) = self.selection {
+            if self.is_inclusive_ancestor(node, selection.anchor().node())
+                || self.is_inclusive_ancestor(node, selection.focus().node())
+            {
+                self.selection = None;
+            }
+        }
+
+        match previous {
+            Some(p) => self.nodes[p.0].next_sibling = next,
+            None => self.nodes[parent.0].first_child = next,
+        }
+        match next {
+            Some(n) => self.nodes[n.0].previous_sibling = previous,
+            None => self.nodes[parent.0].last_child = previous,
+        }
+
+        let data = &mut self.nodes[node.0];
+        data.parent = None;
+        data.previous_sibling = None;
+        data.next_sibling = None;
+        self.needs_style_recalc = true;
+    }
+
+    /// `parent`の子ノードをすべて取り除き、`node`があれば唯一の子ノードとして
+    /// 追加する
+    /// https://dom.spec.whatwg.org/#concept-node-replace-all
+    pub fn replace_all(&mut self, node: Option<NodeId>, parent: NodeId) {
+        while let Some(child) = self.first_child(parent) {
+            self.remove(child);
+        }
+
+        if let Some(node) = node {
+            self.insert(node, parent, None);
         }
     }
 }
@@ -125,27 +363,32 @@ impl PartialEq for NodeKind {
     }
 }
 
+impl Default for Document {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// https://html.spec.whatwg.org/multipage/nav-history-apis.html#window
 #[derive(Debug, Clone)]
 pub struct Window {
-    document: Rc<RefCell<Node>>,
+    document: Rc<RefCell<Document>>,
 }
 
 impl Window {
-    pub fn new() -> Self {
-        let window = Self {
-            document: Rc::new(RefCell::new(Node::new(NodeKind::Document))),
-        };
+    /// 空のドキュメントを持つウィンドウを作成する。ドキュメントはウィンドウへの
+    /// 弱い参照を持つので、ウィンドウは`Rc`で共有する
+    pub fn new() -> Rc<RefCell<Self>> {
+        let window = Rc::new(RefCell::new(Self {
+            document: Rc::new(RefCell::new(Document::new())),
+        }));
 
-        window
-            .document
-            .borrow_mut()
-            .set_window(Rc::downgrade(&Rc::new(RefCell::new(window.clone()))));
+        window.borrow().document.borrow_mut().window = Rc::downgrade(&window);
 
         window
     }
 
-    pub fn document(&self) -> Rc<RefCell<Node>> {
+    pub fn document(&self) -> Rc<RefCell<Document>> {
         self.document.clone()
     }
 }
@@ -155,6 +398,8 @@ impl Window {
 pub struct Element {
     kind: ElementKind,
     attributes: Vec<Attribute>,
+    /// `<img>`要素の、デコード済みの画像
+    image: Option<Rc<Image>>,
 }
 
 impl Element {
@@ -163,6 +408,7 @@ impl Element {
             kind: ElementKind::from_str(element_name)
                 .expect("failed to convert string to ElementKind"),
             attributes,
+            image: None,
         }
     }
 
@@ -170,24 +416,230 @@ impl Element {
         self.kind
     }
 
-    pub fn is_block_element(&self) -> bool {
-        match self.kind {
-            ElementKind::Body | ElementKind::H1 | ElementKind::H2 | ElementKind::P => true,
-            _ => false,
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
+    /// HTML文書の要素の属性名は、大文字と小文字を区別しない
+    /// https://dom.spec.whatwg.org/#concept-element-attributes-get-by-name
+    fn find_attribute(&self, name: &str) -> Option<usize> {
+        self.attributes
+            .iter()
+            .position(|attr| attr.name().eq_ignore_ascii_case(name))
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-element-getattribute
+    pub fn get_attribute(&self, name: &str) -> Option<&str> {
+        self.find_attribute(name)
+            .map(|i| self.attributes[i].value())
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-element-hasattribute
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.find_attribute(name).is_some()
+    }
+
+    /// 属性の値を変更する。属性がない場合は最後に追加する
+    /// https://dom.spec.whatwg.org/#concept-element-attributes-set-value
+    fn write_attribute(&mut self, name: &str, value: &str) {
+        match self.find_attribute(name) {
+            Some(i) => self.attributes[i].set_value(value),
+            None => self.attributes.push(Attribute::from_name_value(
+                &name.to_ascii_lowercase(),
+                value,
+            )),
         }
     }
 
-    pub fn attributes(&self) -> Vec<Attribute> {
-        self.attributes.clone()
+    /// 属性名として使えない文字列の場合はInvalidCharacterErrorを返す
+    /// https://dom.spec.whatwg.org/#dom-element-setattribute
+    pub fn set_attribute(&mut self, name: &str, value: &str) -> Result<(), DomException> {
+        if !is_valid_attribute_name(name) {
+            return Err(DomException::InvalidCharacterError);
+        }
+        self.write_attribute(name, value);
+        Ok(())
     }
 
-    pub fn get_attribute(&self, name: &str) -> Option<String> {
-        for attr in &self.attributes {
-            if attr.name() == name {
-                return Some(attr.value());
+    /// https://dom.spec.whatwg.org/#dom-element-removeattribute
+    pub fn remove_attribute(&mut self, name: &str) {
+        if let Some(i) = self.find_attribute(name) {
+            self.attributes.remove(i);
+        }
+    }
+
+    /// 属性がなければ空の値で追加し、あれば削除する。`force`が指定された場合は、
+    /// trueなら追加だけを、falseなら削除だけを行う。最後に属性があるかどうかを返す
+    /// https://dom.spec.whatwg.org/#dom-element-toggleattribute
+    pub fn toggle_attribute(
+        &mut self,
+        name: &str,
+        force: Option<bool>,
+    ) -> Result<bool, DomException> {
+        if !is_valid_attribute_name(name) {
+            return Err(DomException::InvalidCharacterError);
+        }
+
+        if self.has_attribute(name) {
+            if force == Some(true) {
+                return Ok(true);
+            }
+            self.remove_attribute(name);
+            return Ok(false);
+        }
+
+        if force == Some(false) {
+            return Ok(false);
+        }
+        self.write_attribute(name, "");
+        Ok(true)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-element-id
+    pub fn id(&self) -> &str {
+        self.get_attribute("id").unwrap_or_default()
+    }
+
+    pub fn set_id(&mut self, id: &str) {
+        self.write_attribute("id", id);
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-element-classname
+    pub fn class_name(&self) -> &str {
+        self.get_attribute("class").unwrap_or_default()
+    }
+
+    pub fn set_class_name(&mut self, class_name: &str) {
+        self.write_attribute("class", class_name);
+    }
+
+    /// class属性をスペース区切りのトークンの集合として操作する
+    /// https://dom.spec.whatwg.org/#dom-element-classlist
+    pub fn class_list(&mut self) -> ClassList<'_> {
+        ClassList { element: self }
+    }
+
+    pub fn set_image(&mut self, image: Option<Rc<Image>>) {
+        self.image = image;
+    }
+
+    pub fn image(&self) -> Option<Rc<Image>> {
+        self.image.clone()
+    }
+}
+
+/// 属性名として使える文字列かどうかを返す
+/// https://dom.spec.whatwg.org/#valid-attribute-local-name
+fn is_valid_attribute_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_ascii_whitespace() || matches!(c, '\0' | '/' | '>' | '='))
+}
+
+/// 要素のclass属性のトークンの集合。変更するとclass属性を書き換える
+/// https://dom.spec.whatwg.org/#interface-domtokenlist
+#[derive(Debug)]
+pub struct ClassList<'a> {
+    element: &'a mut Element,
+}
+
+impl ClassList<'_> {
+    /// 重複を取り除いたトークンを順に返す
+    /// https://dom.spec.whatwg.org/#concept-ordered-set-parser
+    fn tokens(&self) -> Vec<String> {
+        let mut tokens: Vec<String> = Vec::new();
+        for token in self.element.class_name().split_ascii_whitespace() {
+            if !tokens.iter().any(|t| t == token) {
+                tokens.push(token.to_string());
+            }
+        }
+        tokens
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-dtl-update
+    fn update(&mut self, tokens: &[String]) {
+        if !self.element.has_attribute("class") && tokens.is_empty() {
+            return;
+        }
+        self.element.set_class_name(&tokens.join(" "));
+    }
+
+    /// 空のトークンはSyntaxError、空白文字を含むトークンはInvalidCharacterErrorになる
+    fn validate(token: &str) -> Result<(), DomException> {
+        if token.is_empty() {
+            return Err(DomException::SyntaxError);
+        }
+        if token.chars().any(|c| c.is_ascii_whitespace()) {
+            return Err(DomException::InvalidCharacterError);
+        }
+        Ok(())
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-length
+    pub fn len(&self) -> usize {
+        self.tokens().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-contains
+    pub fn contains(&self, token: &str) -> bool {
+        self.tokens().iter().any(|t| t == token)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-add
+    pub fn add(&mut self, tokens: &[&str]) -> Result<(), DomException> {
+        for token in tokens {
+            Self::validate(token)?;
+        }
+        let mut set = self.tokens();
+        for token in tokens {
+            if !set.iter().any(|t| t == token) {
+                set.push(token.to_string());
+            }
+        }
+        self.update(&set);
+        Ok(())
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-remove
+    pub fn remove(&mut self, tokens: &[&str]) -> Result<(), DomException> {
+        for token in tokens {
+            Self::validate(token)?;
+        }
+        let mut set = self.tokens();
+        set.retain(|t| !tokens.contains(&t.as_str()));
+        self.update(&set);
+        Ok(())
+    }
+
+    /// トークンがなければ追加し、あれば削除する。`force`の意味は
+    /// `Element::toggle_attribute`と同じ。最後にトークンがあるかどうかを返す
+    /// https://dom.spec.whatwg.org/#dom-domtokenlist-toggle
+    pub fn toggle(&mut self, token: &str, force: Option<bool>) -> Result<bool, DomException> {
+        Self::validate(token)?;
+        let mut set = self.tokens();
+        match set.iter().position(|t| t == token) {
+            Some(i) => {
+                if force == Some(true) {
+                    return Ok(true);
+                }
+                set.remove(i);
+                self.update(&set);
+                Ok(false)
+            }
+            None => {
+                if force == Some(false) {
+                    return Ok(false);
+                }
+                set.push(token.to_string());
+                self.update(&set);
+                Ok(true)
             }
         }
-        None
     }
 }
 
@@ -198,6 +650,12 @@ pub enum ElementKind {
     Html,
     /// https://html.spec.whatwg.org/multipage/semantics.html#the-head-element
     Head,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#the-title-element
+    Title,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#the-link-element
+    Link,
+    /// https://html.spec.whatwg.org/multipage/semantics.html#the-meta-element
+    Meta,
     /// https://html.spec.whatwg.org/multipage/semantics.html#the-style-element
     Style,
     /// https://html.spec.whatwg.org/multipage/semantics.html#the-script-element
@@ -206,11 +664,17 @@ pub enum ElementKind {
     Body,
     /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-p-element
     P,
+    /// https://html.spec.whatwg.org/multipage/grouping-content.html#the-pre-element
+    Pre,
     /// https://html.spec.whatwg.org/multipage/sections.html#the-h1,-h2,-h3,-h4,-h5,-and-h6-elements
     H1,
     H2,
     /// https://html.spec.whatwg.org/multipage/text-level-semantics.html#the-a-element
     A,
+    /// https://html.spec.whatwg.org/multipage/embedded-content.html#the-img-element
+    Img,
+    /// https://html.spec.whatwg.org/multipage/iframe-embed-object.html#the-iframe-element
+    Iframe,
 }
 
 impl FromStr for ElementKind {
@@ -220,13 +684,19 @@ impl FromStr for ElementKind {
         match s {
             "html" => Ok(ElementKind::Html),
             "head" => Ok(ElementKind::Head),
+            "title" => Ok(ElementKind::Title),
+            "link" => Ok(ElementKind::Link),
+            "meta" => Ok(ElementKind::Meta),
             "style" => Ok(ElementKind::Style),
             "script" => Ok(ElementKind::Script),
             "body" => Ok(ElementKind::Body),
             "p" => Ok(ElementKind::P),
+            "pre" => Ok(ElementKind::Pre),
             "h1" => Ok(ElementKind::H1),
             "h2" => Ok(ElementKind::H2),
             "a" => Ok(ElementKind::A),
+            "img" => Ok(ElementKind::Img),
+            "iframe" => Ok(ElementKind::Iframe),
             _ => Err(format!("unimplemented element name {:?}", s)),
         }
     }
@@ -237,14 +707,91 @@ impl Display for ElementKind {
         let s = match self {
             ElementKind::Html => "html",
             ElementKind::Head => "head",
+            ElementKind::Title => "title",
+            ElementKind::Link => "link",
+            ElementKind::Meta => "meta",
             ElementKind::Style => "style",
             ElementKind::Script => "script",
             ElementKind::Body => "body",
             ElementKind::H1 => "h1",
             ElementKind::H2 => "h2",
             ElementKind::P => "p",
+            ElementKind::Pre => "pre",
             ElementKind::A => "a",
+            ElementKind::Img => "img",
+            ElementKind::Iframe => "iframe",
         };
         write!(f, "{}", s)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(attributes: &[(&str, &str)]) -> Element {
+        let attributes = attributes
+            .iter()
+            .map(|(name, value)| Attribute::from_name_value(name, value))
+            .collect();
+        Element::new("p", attributes)
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut e = element(&[("id", "a")]);
+        assert!(e.has_attribute("ID"));
+        assert_eq!("a", e.id());
+
+        assert_eq!(Ok(()), e.set_attribute("Title", "x"));
+        assert_eq!(Some("x"), e.get_attribute("title"));
+        assert_eq!(Ok(()), e.set_attribute("title", "y"));
+        assert_eq!(2, e.attributes().len());
+        assert_eq!("title", e.attributes()[1].name());
+        assert_eq!(
+            Err(DomException::InvalidCharacterError),
+            e.set_attribute("a b", "")
+        );
+
+        e.remove_attribute("TITLE");
+        assert!(!e.has_attribute("title"));
+
+        assert_eq!(Ok(true), e.toggle_attribute("hidden", None));
+        assert_eq!(Some(""), e.get_attribute("hidden"));
+        assert_eq!(Ok(true), e.toggle_attribute("hidden", Some(true)));
+        assert_eq!(Ok(false), e.toggle_attribute("hidden", None));
+        assert_eq!(Ok(false), e.toggle_attribute("hidden", Some(false)));
+        assert!(!e.has_attribute("hidden"));
+
+        e.set_id("b");
+        e.set_class_name("c");
+        assert_eq!("b", e.id());
+        assert_eq!("c", e.class_name());
+    }
+
+    #[test]
+    fn test_class_list() {
+        let mut e = element(&[("class", " a  b a ")]);
+        assert_eq!(2, e.class_list().len());
+        assert!(e.class_list().contains("b"));
+
+        assert_eq!(Ok(()), e.class_list().add(&["c", "a"]));
+        assert_eq!("a b c", e.class_name());
+        assert_eq!(Ok(()), e.class_list().remove(&["a", "z"]));
+        assert_eq!("b c", e.class_name());
+        assert_eq!(Ok(false), e.class_list().toggle("b", None));
+        assert_eq!(Ok(true), e.class_list().toggle("d", Some(true)));
+        assert_eq!("c d", e.class_name());
+
+        assert_eq!(Err(DomException::SyntaxError), e.class_list().add(&[""]));
+        assert_eq!(
+            Err(DomException::InvalidCharacterError),
+            e.class_list().toggle("x y", None)
+        );
+
+        // class属性がない場合は、空の集合を書き込まない
+        let mut e = element(&[]);
+        assert_eq!(Ok(()), e.class_list().remove(&["a"]));
+        assert!(!e.has_attribute("class"));
+    }
+}
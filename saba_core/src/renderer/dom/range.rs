@@ -0,0 +1,311 @@
+//! https://dom.spec.whatwg.org/#ranges
+
+use crate::renderer::dom::api::ancestors;
+use crate::renderer::dom::api::following;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::node::NodeKind;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::iter;
+
+/// ノードとその中の位置の組。位置は、テキストの場合は文字の位置、それ以外の
+/// 場合は子ノードのインデックスになる。DOMの仕様ではテキストの位置をUTF-16の
+/// 単位で数えるが、ここでは文字単位で数える
+/// https://dom.spec.whatwg.org/#concept-range-bp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoundaryPoint {
+    node: NodeId,
+    offset: usize,
+}
+
+impl BoundaryPoint {
+    pub fn new(node: NodeId, offset: usize) -> Self {
+        Self { node, offset }
+    }
+
+    pub fn node(&self) -> NodeId {
+        self.node
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+/// テキストの場合は文字数、それ以外の場合は子ノードの数を返す
+/// https://dom.spec.whatwg.org/#concept-node-length
+pub fn node_length(document: &Document, node: NodeId) -> usize {
+    match document.kind(node) {
+        NodeKind::Text(s) => s.chars().count(),
+        _ => {
+            let mut count = 0;
+            let mut child = document.first_child(node);
+            while let Some(c) = child {
+                count += 1;
+                child = document.next_sibling(c);
+            }
+            count
+        }
+    }
+}
+
+/// 兄弟ノードの中での`node`のインデックス
+/// https://dom.spec.whatwg.org/#concept-tree-index
+fn index(document: &Document, node: NodeId) -> usize {
+    let mut index = 0;
+    let mut sibling = document.previous_sibling(node);
+    while let Some(s) = sibling {
+        index += 1;
+        sibling = document.previous_sibling(s);
+    }
+    index
+}
+
+/// 2つのノードの文書順での前後を返す。祖先は子孫より前になる。異なる木に
+/// 属するノードの場合は、根のIDで決める
+/// https://dom.spec.whatwg.org/#concept-tree-order
+pub fn compare_tree_order(document: &Document, a: NodeId, b: NodeId) -> Ordering {
+    let chain = |node: NodeId| -> Vec<NodeId> {
+        let mut chain: Vec<NodeId> = iter::once(node).chain(ancestors(document, node)).collect();
+        chain.reverse();
+        chain
+    };
+    let chain_a = chain(a);
+    let chain_b = chain(b);
+    if chain_a[0] != chain_b[0] {
+        return chain_a[0].cmp(&chain_b[0]);
+    }
+
+    let mut i = 0;
+    while i < chain_a.len() && i < chain_b.len() && chain_a[i] == chain_b[i] {
+        i += 1;
+    }
+    match (i == chain_a.len(), i == chain_b.len()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => index(document, chain_a[i]).cmp(&index(document, chain_b[i])),
+    }
+}
+
+/// 2つの境界点の文書順での前後を返す
+/// https://dom.spec.whatwg.org/#concept-range-bp-position
+pub fn compare_boundary_points(
+    document: &Document,
+    a: BoundaryPoint,
+    b: BoundaryPoint,
+) -> Ordering {
+    if a.node == b.node {
+        return a.offset.cmp(&b.offset);
+    }
+
+    if compare_tree_order(document, a.node, b.node) == Ordering::Greater {
+        return compare_boundary_points(document, b, a).reverse();
+    }
+
+    // `a`のノードが`b`のノードの祖先の場合は、`b`を含む子ノードの位置と比べる
+    let mut child = b.node;
+    while let Some(parent) = document.parent(child) {
+        if parent == a.node {
+            return if index(document, child) < a.offset {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+        child = parent;
+    }
+
+    Ordering::Less
+}
+
+/// テキストの`start`文字目から`end`文字目の手前までを返す
+fn substring(text: &str, start: usize, end: usize) -> String {
+    text.chars()
+        .skip(start)
+        .take(end.saturating_sub(start))
+        .collect()
+}
+
+/// 文書の中の連続した範囲。開始点は終了点より後ろにならない
+/// https://dom.spec.whatwg.org/#concept-range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    start: BoundaryPoint,
+    end: BoundaryPoint,
+}
+
+impl Range {
+    /// 2つの境界点の間の範囲を作成する。`a`が`b`より後ろにある場合は入れ替える
+    pub fn new(document: &Document, a: BoundaryPoint, b: BoundaryPoint) -> Self {
+        if compare_boundary_points(document, a, b) == Ordering::Greater {
+            Self { start: b, end: a }
+        } else {
+            Self { start: a, end: b }
+        }
+    }
+
+    pub fn start(&self) -> BoundaryPoint {
+        self.start
+    }
+
+    pub fn end(&self) -> BoundaryPoint {
+        self.end
+    }
+
+    /// https://dom.spec.whatwg.org/#range-collapsed
+    pub fn collapsed(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// `node`全体が範囲に含まれるかどうかを返す
+    /// https://dom.spec.whatwg.org/#contained
+    pub fn contains_node(&self, document: &Document, node: NodeId) -> bool {
+        let length = node_length(document, node);
+        compare_boundary_points(document, BoundaryPoint::new(node, 0), self.start)
+            == Ordering::Greater
+            && compare_boundary_points(document, BoundaryPoint::new(node, length), self.end)
+                == Ordering::Less
+    }
+
+    /// テキストノード`node`のうち、範囲に含まれる部分を文字の位置で
+    /// (開始, 終了) として返す。含まれる文字がない場合はNoneを返す
+    pub fn text_offsets(&self, document: &Document, node: NodeId) -> Option<(usize, usize)> {
+        let length = node_length(document, node);
+        let start = if self.start.node == node {
+            self.start.offset.min(length)
+        } else if compare_boundary_points(document, self.start, BoundaryPoint::new(node, 0))
+            == Ordering::Greater
+        {
+            return None;
+        } else {
+            0
+        };
+        let end = if self.end.node == node {
+            self.end.offset.min(length)
+        } else if compare_boundary_points(document, self.end, BoundaryPoint::new(node, length))
+            == Ordering::Less
+        {
+            return None;
+        } else {
+            length
+        };
+
+        if start < end {
+            Some((start, end))
+        } else {
+            None
+        }
+    }
+
+    /// 範囲に含まれるテキストを、文書順に連結して返す
+    /// https://dom.spec.whatwg.org/#dom-range-stringifier
+    pub fn text(&self, document: &Document) -> String {
+        let text_of = |node: NodeId| match document.kind(node) {
+            NodeKind::Text(s) => Some(s.as_str()),
+            _ => None,
+        };
+
+        if self.start.node == self.end.node {
+            return text_of(self.start.node)
+                .map(|s| substring(s, self.start.offset, self.end.offset))
+                .unwrap_or_default();
+        }
+
+        let mut result = String::new();
+        if let Some(s) = text_of(self.start.node) {
+            result.push_str(&substring(s, self.start.offset, usize::MAX));
+        }
+
+        let root = ancestors(document, self.start.node)
+            .last()
+            .unwrap_or(self.start.node);
+        let mut current = following(document, self.start.node, root);
+        while let Some(node) = current {
+            if node == self.end.node
+                || compare_boundary_points(document, BoundaryPoint::new(node, 0), self.end)
+                    == Ordering::Greater
+            {
+                break;
+            }
+            if let Some(s) = text_of(node) {
+                if self.contains_node(document, node) {
+                    result.push_str(s);
+                }
+            }
+            current = following(document, node, root);
+        }
+
+        if let Some(s) = text_of(self.end.node) {
+            result.push_str(&substring(s, 0, self.end.offset));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::dom::api::get_element_by_id;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_range_text() {
+        let html = "<html><head></head><body><p id=a>hello</p><p id=b>big <a>wide</a></p>\
+<p id=c>world</p></body></html>";
+        let window = HtmlParser::new(HtmlTokenizer::new(html.to_string())).construct_tree();
+        let dom = window.borrow().document();
+        let document = dom.borrow();
+        let text = |id: &str| {
+            let element = get_element_by_id(&document, Some(document.root()), id)
+                .expect("failed to get an element");
+            document.first_child(element).expect("failed to get a text")
+        };
+        let (a, c) = (text("a"), text("c"));
+
+        // 開始点と終了点は文書順に並べ替える
+        let range = Range::new(
+            &document,
+            BoundaryPoint::new(c, 3),
+            BoundaryPoint::new(a, 1),
+        );
+        assert_eq!(BoundaryPoint::new(a, 1), range.start());
+        assert_eq!("ellobig widewor", range.text(&document));
+        assert_eq!(Some((1, 5)), range.text_offsets(&document, a));
+        assert_eq!(Some((0, 4)), range.text_offsets(&document, text("b")));
+        assert_eq!(Some((0, 3)), range.text_offsets(&document, c));
+
+        let range = Range::new(
+            &document,
+            BoundaryPoint::new(a, 1),
+            BoundaryPoint::new(a, 3),
+        );
+        assert_eq!("el", range.text(&document));
+        assert_eq!(None, range.text_offsets(&document, c));
+
+        // 要素の境界点は子ノードの位置で比べる
+        let body = document
+            .parent(document.parent(a).expect("no p"))
+            .expect("no body");
+        assert_eq!(
+            Ordering::Greater,
+            compare_boundary_points(
+                &document,
+                BoundaryPoint::new(body, 1),
+                BoundaryPoint::new(a, 5)
+            )
+        );
+        assert_eq!(
+            Ordering::Less,
+            compare_boundary_points(
+                &document,
+                BoundaryPoint::new(body, 0),
+                BoundaryPoint::new(a, 0)
+            )
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! https://w3c.github.io/selection-api/
+
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::node::NodeKind;
+use crate::renderer::dom::range::BoundaryPoint;
+use crate::renderer::dom::range::Range;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// 文書の選択範囲。選択を始めた位置(anchor)と、選択を終えた位置(focus)を持つ。
+/// 後ろから前に向かって選択した場合は、focusがanchorより前になる
+/// https://w3c.github.io/selection-api/#selection-interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    anchor: BoundaryPoint,
+    focus: BoundaryPoint,
+}
+
+impl Selection {
+    pub fn new(anchor: BoundaryPoint, focus: BoundaryPoint) -> Self {
+        Self { anchor, focus }
+    }
+
+    /// `point`の位置に折りたたまれた、何も選択していない選択範囲を作成する
+    /// https://w3c.github.io/selection-api/#dom-selection-collapse
+    pub fn collapsed(point: BoundaryPoint) -> Self {
+        Self::new(point, point)
+    }
+
+    pub fn anchor(&self) -> BoundaryPoint {
+        self.anchor
+    }
+
+    pub fn focus(&self) -> BoundaryPoint {
+        self.focus
+    }
+
+    /// anchorはそのままで、focusを`point`に移す
+    /// https://w3c.github.io/selection-api/#dom-selection-extend
+    pub fn extend(&mut self, point: BoundaryPoint) {
+        self.focus = point;
+    }
+
+    /// https://w3c.github.io/selection-api/#dom-selection-iscollapsed
+    pub fn is_collapsed(&self) -> bool {
+        self.anchor == self.focus
+    }
+
+    /// anchorとfocusを文書順に並べた範囲を返す
+    pub fn range(&self, document: &Document) -> Range {
+        Range::new(document, self.anchor, self.focus)
+    }
+
+    /// 選択されているテキスト。コピーするときに使う
+    /// https://w3c.github.io/selection-api/#dom-selection-stringifier
+    pub fn text(&self, document: &Document) -> String {
+        self.range(document).text(document)
+    }
+}
+
+/// 単語の区切りを決めるための文字の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Word,
+    Space,
+    Other,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else if c.is_whitespace() {
+        CharClass::Space
+    } else {
+        CharClass::Other
+    }
+}
+
+/// テキストノード`node`の`offset`文字目を含む単語を選択する。英数字の並びを
+/// 単語とし、空白文字の場合は連続する空白文字を、記号の場合はその1文字を選択する。
+/// テキストの末尾の位置の場合は、直前の文字を含む単語を選択する
+pub fn select_word(document: &Document, node: NodeId, offset: usize) -> Option<Selection> {
+    let chars: Vec<char> = match document.kind(node) {
+        NodeKind::Text(s) => s.chars().collect(),
+        _ => return None,
+    };
+    if chars.is_empty() {
+        return None;
+    }
+
+    let offset = offset.min(chars.len() - 1);
+    let class = char_class(chars[offset]);
+    let (mut start, mut end) = (offset, offset + 1);
+    if class != CharClass::Other {
+        while start > 0 && char_class(chars[start - 1]) == class {
+            start -= 1;
+        }
+        while end < chars.len() && char_class(chars[end]) == class {
+            end += 1;
+        }
+    }
+
+    Some(Selection::new(
+        BoundaryPoint::new(node, start),
+        BoundaryPoint::new(node, end),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_select_word() {
+        let mut document = Document::new();
+        let text = document.create_text("foo_bar,  baz");
+        let root = document.root();
+        document.insert(text, root, None);
+
+        let word = |offset: usize| {
+            select_word(&document, text, offset)
+                .map(|s| s.text(&document))
+                .unwrap_or_default()
+        };
+        assert_eq!("foo_bar".to_string(), word(2));
+        assert_eq!(",".to_string(), word(7));
+        assert_eq!("  ".to_string(), word(9));
+        assert_eq!("baz".to_string(), word(13));
+
+        // 後ろから前に選択した場合も、テキストは文書順になる
+        let mut selection = Selection::collapsed(BoundaryPoint::new(text, 7));
+        assert!(selection.is_collapsed());
+        selection.extend(BoundaryPoint::new(text, 4));
+        assert_eq!("bar".to_string(), selection.text(&document));
+        assert_eq!(
+            BoundaryPoint::new(text, 4),
+            selection.range(&document).start()
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use crate::renderer::dom::api::children;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::ElementKind;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::node::NodeKind;
+use alloc::string::String;
+use alloc::string::ToString;
+
+/// https://html.spec.whatwg.org/multipage/syntax.html#void-elements
+static VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// 子孫のテキストをエスケープせずにそのまま出力する要素
+/// https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments
+static RAW_TEXT_ELEMENTS: [&str; 7] = [
+    "style",
+    "script",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "plaintext",
+];
+
+/// ノードの子孫をHTMLの文字列に変換する。`innerHTML`のゲッターに相当する
+/// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-innerhtml
+pub fn inner_html(document: &Document, node: NodeId) -> String {
+    let mut result = String::new();
+    serialize_html_fragment(document, node, &mut result);
+    result
+}
+
+/// ノード自身を含めてHTMLの文字列に変換する。`outerHTML`のゲッターに相当する
+/// https://html.spec.whatwg.org/multipage/dynamic-markup-insertion.html#dom-element-outerhtml
+pub fn outer_html(document: &Document, node: NodeId) -> String {
+    let mut result = String::new();
+    // 仕様では、ノードを唯一の子として持つ架空の親ノードをシリアライズする
+    let parent_kind = document.parent(node).and_then(|p| document.element_kind(p));
+    serialize_node(document, node, parent_kind, &mut result);
+    result
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#html-fragment-serialisation-algorithm
+fn serialize_html_fragment(document: &Document, node: NodeId, result: &mut String) {
+    let parent_kind = document.element_kind(node);
+    for child in children(document, node) {
+        serialize_node(document, child, parent_kind, result);
+    }
+}
+
+fn serialize_node(
+    document: &Document,
+    node: NodeId,
+    parent_kind: Option<ElementKind>,
+    result: &mut String,
+) {
+    match document.kind(node) {
+        NodeKind::Document => serialize_html_fragment(document, node, result),
+        NodeKind::Element(e) => {
+            let tag_name = e.kind().to_string();
+
+            result.push('<');
+            result.push_str(&tag_name);
+            for attr in e.attributes() {
+                result.push(' ');
+                result.push_str(attr.name());
+                result.push_str("=\"");
+                result.push_str(&escape_string(attr.value(), /*attribute_mode*/ true));
+                result.push('"');
+            }
+            result.push('>');
+
+            // 空要素は子ノードも終了タグも持たない
+            if is_void_element(&tag_name) {
+                return;
+            }
+
+            serialize_html_fragment(document, node, result);
+
+            result.push_str("</");
+            result.push_str(&tag_name);
+            result.push('>');
+        }
+        NodeKind::Text(s) => {
+            let is_raw_text = match parent_kind {
+                Some(kind) => RAW_TEXT_ELEMENTS.contains(&kind.to_string().as_str()),
+                None => false,
+            };
+
+            if is_raw_text {
+                result.push_str(s);
+            } else {
+                result.push_str(&escape_string(s, /*attribute_mode*/ false));
+            }
+        }
+    }
+}
+
+/// https://html.spec.whatwg.org/multipage/syntax.html#void-elements
+pub fn is_void_element(tag_name: &str) -> bool {
+    VOID_ELEMENTS.contains(&tag_name)
+}
+
+/// https://html.spec.whatwg.org/multipage/parsing.html#escapingString
+fn escape_string(s: &str, attribute_mode: bool) -> String {
+    let mut result = String::new();
+
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '\u{00a0}' => result.push_str("&nbsp;"),
+            '"' if attribute_mode => result.push_str("&quot;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::dom::mutation::append_child;
+    use crate::renderer::html::attribute::Attribute;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+
+    fn create_attribute(name: &str, value: &str) -> Attribute {
+        let mut attr = Attribute::new();
+        for c in name.chars() {
+            attr.add_char(c, true);
+        }
+        for c in value.chars() {
+            attr.add_char(c, false);
+        }
+        attr
+    }
+
+    #[test]
+    fn test_empty() {
+        let document = Document::new();
+        assert_eq!("", inner_html(&document, document.root()));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let html =
+            "<html><head></head><body><p id=\"x\">text</p><h1>title</h1></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html.clone())).construct_tree();
+        let document = RefCell::borrow(&window).document();
+        let document = document.borrow();
+
+        assert_eq!(html, inner_html(&document, document.root()));
+    }
+
+    #[test]
+    fn test_outer_html() {
+        let html = "<html><head></head><body><p>text</p></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let document = RefCell::borrow(&window).document();
+        let document = document.borrow();
+        let html = document
+            .first_child(document.root())
+            .expect("failed to get a first child of document");
+        let body = document
+            .last_child(html)
+            .expect("failed to get a last child of html");
+
+        assert_eq!("<body><p>text</p></body>", outer_html(&document, body));
+        assert_eq!("<p>text</p>", inner_html(&document, body));
+    }
+
+    #[test]
+    fn test_escape_text() {
+        let mut document = Document::new();
+        let p = document.create_element("p", Vec::new());
+        let text = document.create_text("a < b && c > \"d\"\u{00a0}");
+        append_child(&mut document, p, text).expect("failed to append a text");
+
+        assert_eq!(
+            "<p>a &lt; b &amp;&amp; c &gt; \"d\"&nbsp;</p>",
+            outer_html(&document, p)
+        );
+    }
+
+    #[test]
+    fn test_escape_attribute() {
+        let mut document = Document::new();
+        let a = document.create_element("a", vec![create_attribute("href", "/?a=1&b=\"<2>\"")]);
+
+        assert_eq!(
+            "<a href=\"/?a=1&amp;b=&quot;&lt;2&gt;&quot;\"></a>",
+            outer_html(&document, a)
+        );
+    }
+
+    #[test]
+    fn test_raw_text_elements() {
+        let html = "<html><head><script>if (1 > 0 && x) {}</script></head></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let document = RefCell::borrow(&window).document();
+        let document = document.borrow();
+
+        assert_eq!(
+            "<html><head><script>if (1 > 0 && x) {}</script></head><body></body></html>",
+            inner_html(&document, document.root())
+        );
+    }
+
+    #[test]
+    fn test_void_element() {
+        assert!(is_void_element("img"));
+        assert!(is_void_element("br"));
+        assert!(!is_void_element("p"));
+    }
+}
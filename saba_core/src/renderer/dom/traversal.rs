@@ -0,0 +1,464 @@
+//! https://dom.spec.whatwg.org/#traversal
+
+use crate::renderer::dom::api::following;
+use crate::renderer::dom::api::preceding;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::node::NodeKind;
+use alloc::boxed::Box;
+
+/// whatToShowに指定する、たどる対象のノードの種類を表すビット
+/// https://dom.spec.whatwg.org/#interface-nodefilter
+pub const SHOW_ALL: u32 = 0xFFFF_FFFF;
+pub const SHOW_ELEMENT: u32 = 0x1;
+pub const SHOW_TEXT: u32 = 0x4;
+pub const SHOW_DOCUMENT: u32 = 0x100;
+
+/// フィルタの結果
+/// https://dom.spec.whatwg.org/#interface-nodefilter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterResult {
+    /// ノードを返す
+    Accept,
+    /// ノードとその子孫を飛ばす。NodeIteratorではSkipと同じになる
+    Reject,
+    /// ノードを飛ばし、子孫はたどる
+    Skip,
+}
+
+pub type NodeFilter = Box<dyn Fn(&Document, NodeId) -> FilterResult>;
+
+/// https://dom.spec.whatwg.org/#concept-node-filter
+fn filter_node(
+    document: &Document,
+    what_to_show: u32,
+    filter: &Option<NodeFilter>,
+    node: NodeId,
+) -> FilterResult {
+    // ノードの種類はnodeTypeの値から1を引いたビットで表される
+    let node_type = match document.kind(node) {
+        NodeKind::Element(_) => 1,
+        NodeKind::Text(_) => 3,
+        NodeKind::Document => 9,
+    };
+    if what_to_show & (1 << (node_type - 1)) == 0 {
+        return FilterResult::Skip;
+    }
+
+    match filter {
+        Some(f) => f(document, node),
+        None => FilterResult::Accept,
+    }
+}
+
+/// `root`の部分木を、現在のノードから親、子、兄弟の方向に移動しながらたどる
+/// https://dom.spec.whatwg.org/#interface-treewalker
+pub struct TreeWalker {
+    root: NodeId,
+    what_to_show: u32,
+    filter: Option<NodeFilter>,
+    current: NodeId,
+}
+
+impl TreeWalker {
+    pub fn new(root: NodeId, what_to_show: u32, filter: Option<NodeFilter>) -> Self {
+        Self {
+            root,
+            what_to_show,
+            filter,
+            current: root,
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    pub fn current_node(&self) -> NodeId {
+        self.current
+    }
+
+    pub fn set_current_node(&mut self, node: NodeId) {
+        self.current = node;
+    }
+
+    fn filter(&self, document: &Document, node: NodeId) -> FilterResult {
+        filter_node(document, self.what_to_show, &self.filter, node)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-parentnode
+    pub fn parent_node(&mut self, document: &Document) -> Option<NodeId> {
+        let mut node = self.current;
+        while node != self.root {
+            node = document.parent(node)?;
+            if self.filter(document, node) == FilterResult::Accept {
+                self.current = node;
+                return Some(node);
+            }
+        }
+        None
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-firstchild
+    pub fn first_child(&mut self, document: &Document) -> Option<NodeId> {
+        self.traverse_children(document, true)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-lastchild
+    pub fn last_child(&mut self, document: &Document) -> Option<NodeId> {
+        self.traverse_children(document, false)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-previoussibling
+    pub fn previous_sibling(&mut self, document: &Document) -> Option<NodeId> {
+        self.traverse_siblings(document, false)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-nextsibling
+    pub fn next_sibling(&mut self, document: &Document) -> Option<NodeId> {
+        self.traverse_siblings(document, true)
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-traverse-children
+    fn traverse_children(&mut self, document: &Document, first: bool) -> Option<NodeId> {
+        let child = |n: NodeId| {
+            if first {
+                document.first_child(n)
+            } else {
+                document.last_child(n)
+            }
+        };
+        let sibling = |n: NodeId| {
+            if first {
+                document.next_sibling(n)
+            } else {
+                document.previous_sibling(n)
+            }
+        };
+
+        let mut node = child(self.current)?;
+        loop {
+            match self.filter(document, node) {
+                FilterResult::Accept => {
+                    self.current = node;
+                    return Some(node);
+                }
+                FilterResult::Skip => {
+                    if let Some(c) = child(node) {
+                        node = c;
+                        continue;
+                    }
+                }
+                FilterResult::Reject => {}
+            }
+
+            // 子ノードをたどれない場合は、兄弟ノードか、現在のノードに達するまでの
+            // 祖先の兄弟ノードに移動する
+            loop {
+                if let Some(s) = sibling(node) {
+                    node = s;
+                    break;
+                }
+                let parent = document.parent(node)?;
+                if parent == self.root || parent == self.current {
+                    return None;
+                }
+                node = parent;
+            }
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-traverse-siblings
+    fn traverse_siblings(&mut self, document: &Document, next: bool) -> Option<NodeId> {
+        let sibling_of = |n: NodeId| {
+            if next {
+                document.next_sibling(n)
+            } else {
+                document.previous_sibling(n)
+            }
+        };
+        let child_of = |n: NodeId| {
+            if next {
+                document.first_child(n)
+            } else {
+                document.last_child(n)
+            }
+        };
+
+        let mut node = self.current;
+        if node == self.root {
+            return None;
+        }
+        loop {
+            let mut sibling = sibling_of(node);
+            while let Some(s) = sibling {
+                node = s;
+                let result = self.filter(document, node);
+                if result == FilterResult::Accept {
+                    self.current = node;
+                    return Some(node);
+                }
+                sibling = child_of(node);
+                if result == FilterResult::Reject || sibling.is_none() {
+                    sibling = sibling_of(node);
+                }
+            }
+
+            node = document.parent(node)?;
+            if node == self.root || self.filter(document, node) == FilterResult::Accept {
+                return None;
+            }
+        }
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-previousnode
+    pub fn previous_node(&mut self, document: &Document) -> Option<NodeId> {
+        let mut node = self.current;
+        while node != self.root {
+            let mut sibling = document.previous_sibling(node);
+            while let Some(s) = sibling {
+                // 直前の兄弟ノードの最後の子孫から順にさかのぼる
+                node = s;
+                let mut result = self.filter(document, node);
+                while result != FilterResult::Reject {
+                    match document.last_child(node) {
+                        Some(child) => {
+                            node = child;
+                            result = self.filter(document, node);
+                        }
+                        None => break,
+                    }
+                }
+                if result == FilterResult::Accept {
+                    self.current = node;
+                    return Some(node);
+                }
+                sibling = document.previous_sibling(node);
+            }
+
+            node = document.parent(node)?;
+            if self.filter(document, node) == FilterResult::Accept {
+                self.current = node;
+                return Some(node);
+            }
+        }
+        None
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-treewalker-nextnode
+    pub fn next_node(&mut self, document: &Document) -> Option<NodeId> {
+        let mut node = self.current;
+        let mut result = FilterResult::Accept;
+        loop {
+            while result != FilterResult::Reject {
+                match document.first_child(node) {
+                    Some(child) => {
+                        node = child;
+                        result = self.filter(document, node);
+                        if result == FilterResult::Accept {
+                            self.current = node;
+                            return Some(node);
+                        }
+                    }
+                    None => break,
+                }
+            }
+
+            // 子ノードをたどれない場合は、兄弟ノードを持つ祖先までさかのぼる
+            let mut temporary = node;
+            loop {
+                if temporary == self.root {
+                    return None;
+                }
+                if let Some(sibling) = document.next_sibling(temporary) {
+                    node = sibling;
+                    break;
+                }
+                temporary = document.parent(temporary)?;
+            }
+
+            result = self.filter(document, node);
+            if result == FilterResult::Accept {
+                self.current = node;
+                return Some(node);
+            }
+        }
+    }
+}
+
+/// `root`の部分木のノードを文書順に前後へたどる
+/// https://dom.spec.whatwg.org/#interface-nodeiterator
+///
+/// ノードが削除されたときに参照ノードを移動する処理 (pre-removing steps) は
+/// サポートしていないので、たどっている間はDOMツリーを変更しないこと
+pub struct NodeIterator {
+    root: NodeId,
+    what_to_show: u32,
+    filter: Option<NodeFilter>,
+    reference: NodeId,
+    pointer_before_reference: bool,
+}
+
+impl NodeIterator {
+    pub fn new(root: NodeId, what_to_show: u32, filter: Option<NodeFilter>) -> Self {
+        Self {
+            root,
+            what_to_show,
+            filter,
+            reference: root,
+            pointer_before_reference: true,
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    pub fn reference_node(&self) -> NodeId {
+        self.reference
+    }
+
+    pub fn pointer_before_reference_node(&self) -> bool {
+        self.pointer_before_reference
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-nodeiterator-nextnode
+    pub fn next_node(&mut self, document: &Document) -> Option<NodeId> {
+        self.traverse(document, true)
+    }
+
+    /// https://dom.spec.whatwg.org/#dom-nodeiterator-previousnode
+    pub fn previous_node(&mut self, document: &Document) -> Option<NodeId> {
+        self.traverse(document, false)
+    }
+
+    /// https://dom.spec.whatwg.org/#concept-nodeiterator-traverse
+    fn traverse(&mut self, document: &Document, next: bool) -> Option<NodeId> {
+        let mut node = self.reference;
+        let mut before_node = self.pointer_before_reference;
+        loop {
+            if next {
+                if before_node {
+                    before_node = false;
+                } else {
+                    node = following(document, node, self.root)?;
+                }
+            } else if before_node {
+                node = preceding(document, node, self.root)?;
+            } else {
+                before_node = true;
+            }
+
+            if filter_node(document, self.what_to_show, &self.filter, node) == FilterResult::Accept
+            {
+                break;
+            }
+        }
+
+        self.reference = node;
+        self.pointer_before_reference = before_node;
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::html::parser::parse_html;
+    use alloc::string::String;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn name(document: &Document, node: Option<NodeId>) -> String {
+        match node.map(|n| document.kind(n)) {
+            Some(NodeKind::Element(e)) => e.kind().to_string(),
+            Some(NodeKind::Text(s)) => s.clone(),
+            Some(NodeKind::Document) => "#document".to_string(),
+            None => "".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_tree_walker() {
+        let document = parse_html(
+            "<html><head></head><body><p>a<a>b</a></p><h1>c</h1><h2>d</h2></body></html>",
+        )
+        .borrow()
+        .document();
+        let document = document.borrow();
+
+        // <a>とその子孫を飛ばし、<p>は飛ばして子孫だけをたどる
+        let filter: NodeFilter = Box::new(|document, node| match document.element(node) {
+            Some(e) if e.kind().to_string() == "a" => FilterResult::Reject,
+            Some(e) if e.kind().to_string() == "p" => FilterResult::Skip,
+            _ => FilterResult::Accept,
+        });
+        let mut walker = TreeWalker::new(document.root(), SHOW_ALL, Some(filter));
+
+        let mut nodes = Vec::new();
+        while let Some(n) = walker.next_node(&document) {
+            nodes.push(name(&document, Some(n)));
+        }
+        assert_eq!(
+            vec!["html", "head", "body", "a", "h1", "c", "h2", "d"],
+            nodes
+        );
+
+        let mut nodes = Vec::new();
+        while let Some(n) = walker.previous_node(&document) {
+            nodes.push(name(&document, Some(n)));
+        }
+        assert_eq!(
+            vec!["h2", "c", "h1", "a", "body", "head", "html", "#document"],
+            nodes
+        );
+
+        // 飛ばした<p>の子ノードは、<body>の子ノードとして扱われる
+        walker.set_current_node(document.root());
+        let html = walker.first_child(&document);
+        assert_eq!("html", name(&document, html));
+        assert_eq!("body", name(&document, walker.last_child(&document)));
+        assert_eq!("a", name(&document, walker.first_child(&document)));
+        assert_eq!("h1", name(&document, walker.next_sibling(&document)));
+        assert_eq!("a", name(&document, walker.previous_sibling(&document)));
+        assert_eq!(None, walker.previous_sibling(&document));
+        assert_eq!("body", name(&document, walker.parent_node(&document)));
+        assert_eq!("html", name(&document, walker.parent_node(&document)));
+    }
+
+    #[test]
+    fn test_node_iterator() {
+        let document =
+            parse_html("<html><head></head><body><p>a<a>b</a></p><h1>c</h1></body></html>")
+                .borrow()
+                .document();
+        let document = document.borrow();
+
+        let mut iterator = NodeIterator::new(document.root(), SHOW_TEXT, None);
+        let mut nodes = Vec::new();
+        while let Some(n) = iterator.next_node(&document) {
+            nodes.push(name(&document, Some(n)));
+        }
+        assert_eq!(vec!["a", "b", "c"], nodes);
+        assert!(!iterator.pointer_before_reference_node());
+
+        // 向きを変えると、参照ノードをもう一度返す
+        assert_eq!("c", name(&document, iterator.previous_node(&document)));
+        assert_eq!("b", name(&document, iterator.previous_node(&document)));
+        assert!(iterator.pointer_before_reference_node());
+        assert_eq!("b", name(&document, iterator.next_node(&document)));
+
+        // NodeIteratorではRejectはSkipと同じで、子孫をたどる
+        let filter: NodeFilter = Box::new(|document, node| match document.element(node) {
+            Some(e) if e.kind().to_string() == "p" => FilterResult::Reject,
+            _ => FilterResult::Accept,
+        });
+        let mut iterator = NodeIterator::new(document.root(), SHOW_ELEMENT, Some(filter));
+        let mut nodes = Vec::new();
+        while let Some(n) = iterator.next_node(&document) {
+            nodes.push(name(&document, Some(n)));
+        }
+        assert_eq!(vec!["html", "head", "body", "a", "h1"], nodes);
+    }
+}
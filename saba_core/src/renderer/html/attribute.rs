@@ -1,4 +1,5 @@
 use alloc::string::String;
+use alloc::string::ToString;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Attribute {
@@ -14,6 +15,13 @@ impl Attribute {
         }
     }
 
+    pub fn from_name_value(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
     pub fn add_char(&mut self, c: char, is_name: bool) {
         if is_name {
             self.name.push(c);
@@ -22,11 +30,15 @@ impl Attribute {
         }
     }
 
-    pub fn name(&self) -> String {
-        self.name.clone()
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
     }
 
-    pub fn value(&self) -> String {
-        self.value.clone()
+    pub fn set_value(&mut self, value: &str) {
+        self.value = value.to_string();
     }
 }
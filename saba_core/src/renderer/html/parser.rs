@@ -1,35 +1,38 @@
+use crate::renderer::diagnostics::ParseDiagnostics;
+use crate::renderer::dom::node::Document;
 use crate::renderer::dom::node::ElementKind;
-use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::NodeId;
 use crate::renderer::dom::node::Window;
 use crate::renderer::html::token::HtmlTokenizer;
+use crate::renderer::html::token::State;
 use alloc::rc::Rc;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefCell;
 use core::str::FromStr;
 
 use super::token::HtmlToken;
 
-use crate::renderer::dom::node::Element;
 use crate::renderer::dom::node::NodeKind;
 use crate::renderer::html::attribute::Attribute;
 
-use alloc::string::String;
-
 #[derive(Debug, Clone)]
 pub struct HtmlParser {
-    window: Rc<RefCell<Window>>,
+    /// 構築中のDOMツリーを持つドキュメント。フラグメントのパースでは、
+    /// コンテキスト要素が属するドキュメントになる
+    document: Rc<RefCell<Document>>,
     mode: InsertionMode,
     /// https://html.spec.whatwg.org/multipage/parsing.html#original-insertion-mode
     original_insertion_mode: InsertionMode,
     /// https://html.spec.whatwg.org/multipage/parsing.html#the-stack-of-open-elements
-    stack_of_open_elements: Vec<Rc<RefCell<Node>>>,
+    stack_of_open_elements: Vec<NodeId>,
     t: HtmlTokenizer,
 }
 
 impl HtmlParser {
     pub fn new(t: HtmlTokenizer) -> Self {
         Self {
-            window: Rc::new(RefCell::new(Window::new())),
+            document: Rc::new(RefCell::new(Document::new())),
             mode: InsertionMode::Initial,
             original_insertion_mode: InsertionMode::Initial,
             stack_of_open_elements: Vec::new(),
@@ -37,7 +40,83 @@ impl HtmlParser {
         }
     }
 
+    pub fn diagnostics(&self) -> ParseDiagnostics {
+        self.t.diagnostics()
+    }
+
+    /// ツリー構築段階のパースエラーを記録する。仕様ではツリー構築段階のエラーに
+    /// 名前が付けられていないため、独自の名前を使う
+    fn parse_error(&mut self, name: &str) {
+        self.t.parse_error(name);
+    }
+
     pub fn construct_tree(&mut self) -> Rc<RefCell<Window>> {
+        let window = self.create_window();
+        while self.parse_until_script().is_some() {}
+        window
+    }
+
+    /// パースの結果を格納するウィンドウを作成する。パースは
+    /// `parse_until_script`で進める
+    pub fn create_window(&mut self) -> Rc<RefCell<Window>> {
+        let window = Window::new();
+        self.document = window.borrow().document();
+        window
+    }
+
+    /// 次の<script>要素の終了タグまでパースし、その<script>要素を返す。呼び出し
+    /// 側はスクリプトを実行した後に、もう一度このメソッドを呼んでパースを再開する。
+    /// 文書の終わりまでパースした場合はNoneを返す
+    pub fn parse_until_script(&mut self) -> Option<NodeId> {
+        self.run()
+    }
+
+    /// `document`に属するコンテキスト要素の子としてHTMLの断片をパースし、作成
+    /// されたノードのリストを返す。ノードはどのノードの子でもないので、呼び出し側が
+    /// ツリーに挿入する。`innerHTML`への代入などで使う。新しいウィンドウは作成しない
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-html-fragments
+    pub fn parse_fragment(
+        &mut self,
+        document: Rc<RefCell<Document>>,
+        context: NodeId,
+    ) -> Vec<NodeId> {
+        let context_kind = document.borrow().element_kind(context);
+
+        // ルートとなる<html>要素を、ツリーに挿入せずに作成する
+        let root = document.borrow_mut().create_element("html", Vec::new());
+        self.document = document;
+        self.stack_of_open_elements = vec![root];
+
+        // コンテキスト要素によって、トークナイザの状態を決める
+        match context_kind {
+            Some(ElementKind::Title) => self.t.switch_to(State::Rcdata),
+            Some(ElementKind::Style) => self.t.switch_to(State::RawText),
+            Some(ElementKind::Script) => self.t.switch_to(State::ScriptData),
+            _ => self.t.switch_to(State::Data),
+        }
+
+        // https://html.spec.whatwg.org/multipage/parsing.html#reset-the-insertion-mode-appropriately
+        self.mode = match context_kind {
+            Some(ElementKind::Html) => InsertionMode::BeforeHead,
+            _ => InsertionMode::InBody,
+        };
+        self.original_insertion_mode = self.mode;
+
+        // フラグメントの<script>要素は実行しない
+        while self.run().is_some() {}
+
+        let mut document = self.document.borrow_mut();
+        let mut children = Vec::new();
+        let mut child = document.first_child(root);
+        while let Some(c) = child {
+            children.push(c);
+            child = document.next_sibling(c);
+        }
+        document.replace_all(None, root);
+        children
+    }
+
+    fn run(&mut self) -> Option<NodeId> {
         let mut token = self.t.next();
 
         while token.is_some() {
@@ -76,7 +155,7 @@ impl HtmlParser {
                             }
                         }
                         Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                            return None;
                         }
                         _ => {}
                     }
@@ -105,7 +184,7 @@ impl HtmlParser {
                             }
                         }
                         Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                            return None;
                         }
                         _ => {}
                     }
@@ -127,8 +206,32 @@ impl HtmlParser {
                             self_closing: _,
                             ref attributes,
                         }) => {
+                            if tag == "link" || tag == "meta" {
+                                // <link>と<meta>は空要素なので、挿入した直後に
+                                // スタックから取り除く
+                                self.insert_element(tag, attributes.to_vec());
+                                self.stack_of_open_elements.pop();
+                                token = self.t.next();
+                                continue;
+                            }
+                            if tag == "title" {
+                                self.insert_element(tag, attributes.to_vec());
+                                // <title>の内容はタグとして解釈しない
+                                // https://html.spec.whatwg.org/multipage/parsing.html#generic-rcdata-element-parsing-algorithm
+                                self.t.switch_to(State::Rcdata);
+                                self.original_insertion_mode = self.mode;
+                                self.mode = InsertionMode::Text;
+                                token = self.t.next();
+                                continue;
+                            }
                             if tag == "style" || tag == "script" {
                                 self.insert_element(tag, attributes.to_vec());
+                                // <style>と<script>の内容はタグとして解釈しない
+                                if tag == "style" {
+                                    self.t.switch_to(State::RawText);
+                                } else {
+                                    self.t.switch_to(State::ScriptData);
+                                }
                                 self.original_insertion_mode = self.mode;
                                 self.mode = InsertionMode::Text;
                                 token = self.t.next();
@@ -158,10 +261,10 @@ impl HtmlParser {
                             }
                         }
                         Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                            return None;
                         }
                     }
-                    // <meta>や<title>などのサポートされていないタグは無視する
+                    // サポートされていないタグは無視する
                     token = self.t.next();
                     continue;
                 }
@@ -187,7 +290,7 @@ impl HtmlParser {
                             }
                         }
                         Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                            return None;
                         }
                         _ => {}
                     }
@@ -212,11 +315,67 @@ impl HtmlParser {
                                 token = self.t.next();
                                 continue;
                             }
+                            "pre" => {
+                                self.insert_element(tag, attributes.to_vec());
+                                token = self.t.next();
+                                // 開始タグの直後の改行は無視する
+                                if let Some(HtmlToken::Char('\n')) = token {
+                                    token = self.t.next();
+                                }
+                                continue;
+                            }
                             "a" => {
                                 self.insert_element(tag, attributes.to_vec());
                                 token = self.t.next();
                                 continue;
                             }
+                            "style" | "script" => {
+                                // <head>の中と同じ規則で処理する
+                                self.insert_element(tag, attributes.to_vec());
+                                if tag == "style" {
+                                    self.t.switch_to(State::RawText);
+                                } else {
+                                    self.t.switch_to(State::ScriptData);
+                                }
+                                self.original_insertion_mode = self.mode;
+                                self.mode = InsertionMode::Text;
+                                token = self.t.next();
+                                continue;
+                            }
+                            "link" | "meta" => {
+                                // <head>の中と同じ規則で処理する
+                                self.insert_element(tag, attributes.to_vec());
+                                self.stack_of_open_elements.pop();
+                                token = self.t.next();
+                                continue;
+                            }
+                            "iframe" => {
+                                // <iframe>の内容はタグとして解釈しない
+                                // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+                                self.insert_element(tag, attributes.to_vec());
+                                self.t.switch_to(State::RawText);
+                                self.original_insertion_mode = self.mode;
+                                self.mode = InsertionMode::Text;
+                                token = self.t.next();
+                                continue;
+                            }
+                            "img" => {
+                                // 空要素なので、挿入したらすぐにスタックから取り除く
+                                // https://html.spec.whatwg.org/multipage/parsing.html#parsing-main-inbody
+                                self.insert_element(tag, attributes.to_vec());
+                                self.stack_of_open_elements.pop();
+                                token = self.t.next();
+                                continue;
+                            }
+                            "title" => {
+                                // <head>の中と同じ規則で処理する
+                                self.insert_element(tag, attributes.to_vec());
+                                self.t.switch_to(State::Rcdata);
+                                self.original_insertion_mode = self.mode;
+                                self.mode = InsertionMode::Text;
+                                token = self.t.next();
+                                continue;
+                            }
                             _ => {
                                 token = self.t.next();
                             }
@@ -225,11 +384,13 @@ impl HtmlParser {
                             match tag.as_str() {
                                 "body" => {
                                     self.mode = InsertionMode::AfterBody;
-                                    token = self.t.next();
                                     if !self.contain_in_stack(ElementKind::Body) {
                                         // パースの失敗。トークンを無視する
+                                        self.parse_error("unexpected-end-tag");
+                                        token = self.t.next();
                                         continue;
                                     }
+                                    token = self.t.next();
                                     self.pop_until(ElementKind::Body);
                                     continue;
                                 }
@@ -238,6 +399,8 @@ impl HtmlParser {
                                         self.mode = InsertionMode::AfterBody;
                                         assert!(self.pop_current_node(ElementKind::Html));
                                     } else {
+                                        // パースの失敗。トークンを無視する
+                                        self.parse_error("unexpected-end-tag");
                                         token = self.t.next();
                                     }
                                     continue;
@@ -246,14 +409,28 @@ impl HtmlParser {
                                     let element_kind = ElementKind::from_str(tag)
                                         .expect("failed to convert string to ElementKind");
 
+                                    if !self.contain_in_stack(element_kind) {
+                                        // 対応する開始タグがないため、パースの失敗。
+                                        // トークンを無視する
+                                        self.parse_error("unexpected-end-tag");
+                                        token = self.t.next();
+                                        continue;
+                                    }
                                     token = self.t.next();
                                     self.pop_until(element_kind);
                                     continue;
                                 }
-                                "h1" | "h2" => {
+                                "h1" | "h2" | "pre" => {
                                     let element_kind = ElementKind::from_str(tag)
                                         .expect("failed to convert string to ElementKind");
 
+                                    if !self.contain_in_stack(element_kind) {
+                                        // 対応する開始タグがないため、パースの失敗。
+                                        // トークンを無視する
+                                        self.parse_error("unexpected-end-tag");
+                                        token = self.t.next();
+                                        continue;
+                                    }
                                     token = self.t.next();
                                     self.pop_until(element_kind);
                                     continue;
@@ -262,6 +439,13 @@ impl HtmlParser {
                                     let element_kind = ElementKind::from_str(tag)
                                         .expect("failed to convert string to ElementKind");
 
+                                    if !self.contain_in_stack(element_kind) {
+                                        // 対応する開始タグがないため、パースの失敗。
+                                        // トークンを無視する
+                                        self.parse_error("unexpected-end-tag");
+                                        token = self.t.next();
+                                        continue;
+                                    }
                                     token = self.t.next();
                                     self.pop_until(element_kind);
                                     continue;
@@ -277,14 +461,16 @@ impl HtmlParser {
                             continue;
                         }
                         Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                            return None;
                         }
                     }
                 }
                 InsertionMode::Text => {
                     match token {
                         Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                            // 終了タグが現れる前に入力が終わったため、パースの失敗
+                            self.parse_error("unexpected-eof-in-text");
+                            return None;
                         }
                         Some(HtmlToken::EndTag { ref tag }) => {
                             if tag == "style" {
@@ -293,12 +479,27 @@ impl HtmlParser {
                                 token = self.t.next();
                                 continue;
                             }
-                            if tag == "script" {
-                                self.pop_until(ElementKind::Script);
+                            if tag == "iframe" {
+                                self.pop_until(ElementKind::Iframe);
                                 self.mode = self.original_insertion_mode;
                                 token = self.t.next();
                                 continue;
                             }
+                            if tag == "title" {
+                                self.pop_until(ElementKind::Title);
+                                self.mode = self.original_insertion_mode;
+                                token = self.t.next();
+                                continue;
+                            }
+                            if tag == "script" {
+                                let script = self.stack_of_open_elements.last().copied();
+                                self.pop_until(ElementKind::Script);
+                                self.mode = self.original_insertion_mode;
+                                // スクリプトを実行できるように、次のトークンを
+                                // 読む前にパースを中断する
+                                // https://html.spec.whatwg.org/multipage/parsing.html#scriptEndTag
+                                return script;
+                            }
                         }
                         Some(HtmlToken::Char(c)) => {
                             self.insert_char(c);
@@ -312,7 +513,10 @@ impl HtmlParser {
                 }
                 InsertionMode::AfterBody => {
                     match token {
-                        Some(HtmlToken::Char(_c)) => {
+                        Some(HtmlToken::Char(c)) => {
+                            if c != ' ' && c != '\n' {
+                                self.parse_error("unexpected-token-after-body");
+                            }
                             token = self.t.next();
                             continue;
                         }
@@ -324,88 +528,60 @@ impl HtmlParser {
                             }
                         }
                         Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                            return None;
                         }
                         _ => {}
                     }
 
+                    // パースの失敗
+                    self.parse_error("unexpected-token-after-body");
                     self.mode = InsertionMode::InBody;
                 }
                 InsertionMode::AfterAfterBody => {
                     match token {
-                        Some(HtmlToken::Char(_c)) => {
+                        Some(HtmlToken::Char(c)) => {
+                            if c != ' ' && c != '\n' {
+                                self.parse_error("unexpected-token-after-body");
+                            }
                             token = self.t.next();
                             continue;
                         }
                         Some(HtmlToken::Eof) | None => {
-                            return self.window.clone();
+                            return None;
                         }
                         _ => {}
                     }
 
                     // パースの失敗
+                    self.parse_error("unexpected-token-after-body");
                     self.mode = InsertionMode::InBody;
                 }
             }
         }
 
-        self.window.clone()
-    }
-
-    fn create_element(&self, tag: &str, attributes: Vec<Attribute>) -> Node {
-        Node::new(NodeKind::Element(Element::new(tag, attributes)))
+        None
     }
 
     fn insert_element(&mut self, tag: &str, attributes: Vec<Attribute>) {
-        let window = self.window.borrow();
+        let mut document = self.document.borrow_mut();
         let current = match self.stack_of_open_elements.last() {
-            Some(n) => n.clone(),
-            None => window.document(),
+            Some(n) => *n,
+            None => document.root(),
         };
-        let node = Rc::new(RefCell::new(self.create_element(tag, attributes)));
-
-        if current.borrow().first_child().is_some() {
-            let mut last_sibling = current.borrow().first_child();
-            loop {
-                last_sibling = match last_sibling {
-                    Some(ref node) => {
-                        if node.borrow().next_sibling().is_some() {
-                            node.borrow().next_sibling()
-                        } else {
-                            break;
-                        }
-                    }
-                    None => unimplemented!("last_sibling should be Some"),
-                }
-            }
+        let node = document.create_element(tag, attributes);
 
-            last_sibling
-                .unwrap()
-                .borrow_mut()
-                .set_next_sibling(Some(Rc::clone(&node)));
-            node.borrow_mut().set_previous_sibling(Rc::downgrade(
-                &current
-                    .borrow()
-                    .first_child()
-                    .expect("failed to get a first child"),
-            ))
-        } else {
-            current.borrow_mut().set_first_child(Some(Rc::clone(&node)));
-        }
-
-        current.borrow_mut().set_last_child(Rc::downgrade(&node));
-        node.borrow_mut().set_parent(Rc::downgrade(&current));
+        document.insert(node, current, None);
 
         self.stack_of_open_elements.push(node);
     }
 
     fn pop_current_node(&mut self, element_kind: ElementKind) -> bool {
         let current = match self.stack_of_open_elements.last() {
-            Some(n) => n,
+            Some(n) => *n,
             None => return false,
         };
 
-        if current.borrow().element_kind() == Some(element_kind) {
+        if self.document.borrow().element_kind(current) == Some(element_kind) {
             self.stack_of_open_elements.pop();
             return true;
         }
@@ -426,15 +602,16 @@ impl HtmlParser {
                 None => return,
             };
 
-            if current.borrow().element_kind() == Some(element_kind) {
+            if self.document.borrow().element_kind(current) == Some(element_kind) {
                 return;
             }
         }
     }
 
     fn contain_in_stack(&mut self, element_kind: ElementKind) -> bool {
+        let document = self.document.borrow();
         for i in 0..self.stack_of_open_elements.len() {
-            if self.stack_of_open_elements[i].borrow().element_kind() == Some(element_kind) {
+            if document.element_kind(self.stack_of_open_elements[i]) == Some(element_kind) {
                 return true;
             }
         }
@@ -442,56 +619,35 @@ impl HtmlParser {
         false
     }
 
-    fn create_char(&mut self, c: char) -> Node {
-        let mut s = String::new();
-        s.push(c);
-        Node::new(NodeKind::Text(s))
-    }
-
+    /// https://html.spec.whatwg.org/multipage/parsing.html#insert-a-character
     fn insert_char(&mut self, c: char) {
         let current = match self.stack_of_open_elements.last() {
-            Some(n) => n.clone(),
+            Some(n) => *n,
             None => return,
         };
+        let mut document = self.document.borrow_mut();
 
-        // 現在参照しているノードがテキストノードの場合、そのノードに文字を
-        // 追加する
-        if let NodeKind::Text(ref mut s) = current.borrow_mut().kind {
-            s.push(c);
-            return;
-        }
-
-        // 改行文字や空白文字のときはテキストノードを追加しない
-        if c == '\n' || c == ' ' {
-            return;
-        }
-
-        let node = Rc::new(RefCell::new(self.create_char(c)));
-
-        if current.borrow().first_child().is_some() {
-            current
-                .borrow()
-                .first_child()
-                .unwrap()
-                .borrow_mut()
-                .set_next_sibling(Some(Rc::clone(&node)));
-            node.borrow_mut().set_previous_sibling(Rc::downgrade(
-                &current
-                    .borrow()
-                    .first_child()
-                    .expect("failed to get a first child"),
-            ));
-        } else {
-            current.borrow_mut().set_first_child(Some(Rc::clone(&node)));
+        // 現在のノードの最後の子ノードがテキストノードの場合、新しいノードを
+        // 作らずに、そのノードに文字を追加する
+        if let Some(last) = document.last_child(current) {
+            if let NodeKind::Text(s) = document.kind_mut(last) {
+                s.push(c);
+                return;
+            }
         }
 
-        current.borrow_mut().set_last_child(Rc::downgrade(&node));
-        node.borrow_mut().set_parent(Rc::downgrade(&current));
-
-        self.stack_of_open_elements.push(node);
+        let mut s = [0; 4];
+        let node = document.create_text(c.encode_utf8(&mut s));
+        document.insert(node, current, None);
     }
 }
 
+/// `html`をパースしてDOMツリーを構築し、Windowを返す。各モジュールのテストで使う
+#[cfg(test)]
+pub fn parse_html(html: &str) -> Rc<RefCell<Window>> {
+    HtmlParser::new(HtmlTokenizer::new(html.into())).construct_tree()
+}
+
 /// https://html.spec.whatwg.org/multipage/parsing.html#the-insertion-mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InsertionMode {
@@ -510,151 +666,101 @@ pub enum InsertionMode {
 mod tests {
     use super::*;
     use crate::alloc::string::ToString;
-    use alloc::vec;
+    use crate::renderer::dom::api::get_document_title;
+    use crate::renderer::dom::api::get_target_element_node;
+    use crate::renderer::dom::node::Element;
+
+    fn find(document: &Document, element_kind: ElementKind) -> NodeId {
+        get_target_element_node(document, Some(document.root()), element_kind)
+            .expect("failed to find an element")
+    }
 
     #[test]
     fn test_empty() {
-        let html = "".to_string();
-        let t = HtmlTokenizer::new(html);
-        let window = HtmlParser::new(t).construct_tree();
-        let expected = Rc::new(RefCell::new(Node::new(NodeKind::Document)));
+        let document = parse_html("").borrow().document();
+        let document = document.borrow();
 
-        assert_eq!(expected, window.borrow().document());
+        assert_eq!(&NodeKind::Document, document.kind(document.root()));
+        assert!(document.first_child(document.root()).is_none());
     }
 
     #[test]
-    fn test_body() {
-        let html = "<html><head></head><body></body></html>".to_string();
-        let t = HtmlTokenizer::new(html);
+    fn test_window() {
+        let t = HtmlTokenizer::new("".to_string());
         let window = HtmlParser::new(t).construct_tree();
         let document = window.borrow().document();
 
-        assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Document))),
-            document
-        );
+        // ドキュメントから、それを持つウィンドウをたどれる
+        let owner = document
+            .borrow()
+            .window()
+            .expect("failed to get a window of document");
+        assert!(Rc::ptr_eq(&window, &owner));
+    }
 
-        let html = document
+    #[test]
+    fn test_body() {
+        let document = parse_html("<html><head></head><body></body></html>")
             .borrow()
-            .first_child()
+            .document();
+        let document = document.borrow();
+
+        let html = document
+            .first_child(document.root())
             .expect("failed to get a first child of document");
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "html",
-                Vec::new()
-            ))))),
-            html
+            &NodeKind::Element(Element::new("html", Vec::new())),
+            document.kind(html)
         );
+        assert_eq!(Some(document.root()), document.parent(html));
 
-        let head = html
-            .borrow()
-            .first_child()
+        let head = document
+            .first_child(html)
             .expect("failed to get a first child of html");
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "head",
-                Vec::new()
-            ))))),
-            head
+            &NodeKind::Element(Element::new("head", Vec::new())),
+            document.kind(head)
         );
 
-        let body = head
-            .borrow()
-            .next_sibling()
+        let body = document
+            .next_sibling(head)
             .expect("failed to get a next sibling of head");
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "body",
-                Vec::new()
-            ))))),
-            body
+            &NodeKind::Element(Element::new("body", Vec::new())),
+            document.kind(body)
         );
+        assert_eq!(Some(head), document.previous_sibling(body));
+        assert_eq!(Some(body), document.last_child(html));
     }
 
     #[test]
     fn test_text() {
-        let html = "<html><head></head><body>text</body></html>".to_string();
-        let t = HtmlTokenizer::new(html);
-        let window = HtmlParser::new(t).construct_tree();
-        let document = window.borrow().document();
-        assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Document))),
-            document
-        );
-
-        let html = document
+        let document = parse_html("<html><head></head><body>text</body></html>")
             .borrow()
-            .first_child()
-            .expect("failed to get a first child of document");
-        assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "html",
-                Vec::new()
-            ))))),
-            html
-        );
-
-        let body = html
-            .borrow()
-            .first_child()
-            .expect("failed to get a first child of html")
-            .borrow()
-            .next_sibling()
-            .expect("failed to get a next sibling of head");
-        assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "body",
-                Vec::new()
-            ))))),
-            body
-        );
+            .document();
+        let document = document.borrow();
+        let body = find(&document, ElementKind::Body);
 
-        let text = body
-            .borrow()
-            .first_child()
+        let text = document
+            .first_child(body)
             .expect("failed to get a first child of body");
-        assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Text("text".to_string())))),
-            text
-        );
+        assert_eq!(&NodeKind::Text("text".to_string()), document.kind(text));
+        assert_eq!(Some(body), document.parent(text));
     }
 
     #[test]
     fn test_multiple_nodes() {
-        let html = "<html><head></head><body><p><a foo=bar>text</a></p></body></html>".to_string();
-        let t = HtmlTokenizer::new(html);
-        let window = HtmlParser::new(t).construct_tree();
-        let document = window.borrow().document();
-
-        let body = document
-            .borrow()
-            .first_child()
-            .expect("failed to get a first child of document")
-            .borrow()
-            .first_child()
-            .expect("failed to get a first child of html")
-            .borrow()
-            .next_sibling()
-            .expect("failed to get a next sibling of head");
-        assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "body",
-                Vec::new()
-            ))))),
-            body
-        );
+        let document =
+            parse_html("<html><head></head><body><p><a foo=bar>text</a></p></body></html>")
+                .borrow()
+                .document();
+        let document = document.borrow();
+        let body = find(&document, ElementKind::Body);
 
-        let p = body
-            .borrow()
-            .first_child()
+        let p = document
+            .first_child(body)
             .expect("failed to get a first child of body");
-        assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "p",
-                Vec::new()
-            ))))),
-            p
-        );
+        assert_eq!(Some(ElementKind::P), document.element_kind(p));
 
         let mut attr = Attribute::new();
         attr.add_char('f', true);
@@ -663,25 +769,167 @@ mod tests {
         attr.add_char('b', false);
         attr.add_char('a', false);
         attr.add_char('r', false);
-        let a = p
-            .borrow()
-            .first_child()
+        let a = document
+            .first_child(p)
             .expect("failed to get a first child of p");
-        assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Element(Element::new(
-                "a",
-                vec![attr]
-            ))))),
-            a
-        );
+        let element = document.element(a).expect("failed to get an a element");
+        assert_eq!(ElementKind::A, element.kind());
+        assert_eq!(&[attr], element.attributes());
+        assert_eq!(Some("bar"), element.get_attribute("foo"));
 
-        let text = a
-            .borrow()
-            .first_child()
+        let text = document
+            .first_child(a)
             .expect("failed to get a first child of a");
+        assert_eq!(&NodeKind::Text("text".to_string()), document.kind(text));
+    }
+
+    #[test]
+    fn test_unexpected_end_tag() {
+        let html = "<html><head></head><body></p>text</a></body></html>".to_string();
+        let t = HtmlTokenizer::new(html);
+        let mut parser = HtmlParser::new(t);
+        let window = parser.construct_tree();
+        let document = window.borrow().document();
+        let document = document.borrow();
+
+        let body = find(&document, ElementKind::Body);
+        let text = document
+            .first_child(body)
+            .expect("failed to get a first child of body");
+        assert_eq!(&NodeKind::Text("text".to_string()), document.kind(text));
+
+        let errors = parser.diagnostics().errors();
+        assert_eq!(2, errors.len());
+        assert_eq!("unexpected-end-tag", errors[0].name());
+        assert_eq!(1, errors[0].position().line());
+        assert_eq!(29, errors[0].position().column());
+        assert_eq!("unexpected-end-tag", errors[1].name());
+    }
+
+    fn parse_fragment(context_tag: &str, html: &str) -> (Rc<RefCell<Document>>, Vec<NodeId>) {
+        let document = Rc::new(RefCell::new(Document::new()));
+        let context = document
+            .borrow_mut()
+            .create_element(context_tag, Vec::new());
+        let t = HtmlTokenizer::new(html.to_string());
+        let nodes = HtmlParser::new(t).parse_fragment(document.clone(), context);
+        (document, nodes)
+    }
+
+    #[test]
+    fn test_parse_fragment() {
+        let (document, nodes) = parse_fragment("body", "<p>text</p><h1>title</h1>");
+        let document = document.borrow();
+
+        assert_eq!(2, nodes.len());
+        assert_eq!(Some(ElementKind::P), document.element_kind(nodes[0]));
+        let text = document
+            .first_child(nodes[0])
+            .expect("failed to get a first child of p");
+        assert_eq!(&NodeKind::Text("text".to_string()), document.kind(text));
+        assert_eq!(Some(ElementKind::H1), document.element_kind(nodes[1]));
+
+        // 作成されたノードはどのノードの子でもない
+        assert!(document.parent(nodes[0]).is_none());
+        assert!(document.next_sibling(nodes[0]).is_none());
+        assert!(document.previous_sibling(nodes[1]).is_none());
+    }
+
+    #[test]
+    fn test_parse_fragment_raw_text() {
+        let (document, nodes) = parse_fragment("style", "a < b</p>");
+        let document = document.borrow();
+
+        assert_eq!(1, nodes.len());
         assert_eq!(
-            Rc::new(RefCell::new(Node::new(NodeKind::Text("text".to_string())))),
-            text
+            &NodeKind::Text("a < b</p>".to_string()),
+            document.kind(nodes[0])
         );
     }
+
+    #[test]
+    fn test_parse_fragment_html_context() {
+        let (document, nodes) = parse_fragment("html", "<p>text</p>");
+        let document = document.borrow();
+
+        assert_eq!(2, nodes.len());
+        assert_eq!(Some(ElementKind::Head), document.element_kind(nodes[0]));
+        assert_eq!(Some(ElementKind::Body), document.element_kind(nodes[1]));
+    }
+
+    #[test]
+    fn test_coalesce_text() {
+        let document = parse_html("<html><head></head><body><p>a <a>b</a> c</p></body></html>")
+            .borrow()
+            .document();
+        let document = document.borrow();
+        let p = find(&document, ElementKind::P);
+
+        let text1 = document.first_child(p).expect("failed to get a text");
+        assert_eq!(&NodeKind::Text("a ".to_string()), document.kind(text1));
+        let a = document.next_sibling(text1).expect("failed to get an a");
+        assert_eq!(Some(ElementKind::A), document.element_kind(a));
+        let text2 = document.next_sibling(a).expect("failed to get a text");
+        assert_eq!(&NodeKind::Text(" c".to_string()), document.kind(text2));
+        assert!(document.next_sibling(text2).is_none());
+        assert_eq!(Some(a), document.previous_sibling(text2));
+    }
+
+    #[test]
+    fn test_pre() {
+        let document = parse_html("<html><head></head><body><pre>\n  a\n b</pre></body></html>")
+            .borrow()
+            .document();
+        let document = document.borrow();
+        let pre = find(&document, ElementKind::Pre);
+
+        let text = document.first_child(pre).expect("failed to get a text");
+        assert_eq!(&NodeKind::Text("  a\n b".to_string()), document.kind(text));
+    }
+
+    #[test]
+    fn test_title() {
+        let document =
+            parse_html("<html><head><title>\n  a <b>  c\n</title></head><body></body></html>")
+                .borrow()
+                .document();
+        let document = document.borrow();
+        let title = find(&document, ElementKind::Title);
+
+        // <title>の内容はタグとして解釈しない
+        let text = document.first_child(title).expect("failed to get a text");
+        assert!(document.next_sibling(text).is_none());
+        assert_eq!("a <b> c".to_string(), get_document_title(&document));
+    }
+
+    #[test]
+    fn test_img() {
+        let document = parse_html("<html><head></head><body><p><img src=a.png>b</p></body></html>")
+            .borrow()
+            .document();
+        let document = document.borrow();
+        let img = find(&document, ElementKind::Img);
+
+        // <img>は子を持たず、後続のテキストは兄弟になる
+        assert!(document.first_child(img).is_none());
+        let text = document.next_sibling(img).expect("failed to get a text");
+        assert_eq!(&NodeKind::Text("b".to_string()), document.kind(text));
+    }
+
+    #[test]
+    fn test_iframe() {
+        let document = parse_html(
+            "<html><head></head><body><iframe src=a.html><p>a</p></iframe>b</body></html>",
+        )
+        .borrow()
+        .document();
+        let document = document.borrow();
+        let iframe = find(&document, ElementKind::Iframe);
+
+        // <iframe>の内容はテキストとして扱う
+        let text = document.first_child(iframe).expect("failed to get a text");
+        assert_eq!(&NodeKind::Text("<p>a</p>".to_string()), document.kind(text));
+        let next = document.next_sibling(iframe).expect("failed to get a text");
+        assert_eq!(&NodeKind::Text("b".to_string()), document.kind(next));
+    }
 }
@@ -1,7 +1,14 @@
+use crate::renderer::diagnostics::LineIndex;
+use crate::renderer::diagnostics::ParseDiagnostics;
+use crate::renderer::diagnostics::ParseErrorSource;
+use crate::renderer::diagnostics::SourcePosition;
 use crate::renderer::html::attribute::Attribute;
 use alloc::string::String;
 use alloc::vec::Vec;
 
+/// 入力の終わりを超えて読み進めたときに返す文字。`is_eof`と組み合わせて使う
+const EOF_CHAR: char = '\0';
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HtmlTokenizer {
     state: State,
@@ -10,25 +17,76 @@ pub struct HtmlTokenizer {
     latest_token: Option<HtmlToken>,
     input: Vec<char>,
     buf: String,
+    /// 一時的なバッファを出力し終えたあとに戻る状態
+    return_state: State,
+    /// 最後に出力した開始タグの名前。適切な終了タグかどうかの判定に使う
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-end-tag-token
+    last_start_tag: Option<String>,
+    line_index: LineIndex,
+    diagnostics: ParseDiagnostics,
 }
 
 impl HtmlTokenizer {
     pub fn new(html: String) -> Self {
+        let input: Vec<char> = html.chars().collect();
         Self {
             state: State::Data,
             pos: 0,
             reconsume: false,
             latest_token: None,
-            input: html.chars().collect(),
+            line_index: LineIndex::new(&input),
+            input,
             buf: String::new(),
+            return_state: State::Data,
+            last_start_tag: None,
+            diagnostics: ParseDiagnostics::new(),
         }
     }
 
+    /// ツリー構築段階から、トークナイザの状態を切り替える。<style>や<script>の
+    /// 内容を読むときや、フラグメントのパースを始めるときに使う
+    /// https://html.spec.whatwg.org/multipage/parsing.html#parsing-html-fragments
+    pub fn switch_to(&mut self, state: State) {
+        self.state = state;
+    }
+
+    /// 作成中の終了タグが、最後に出力した開始タグと同じ名前かどうかを返す
+    /// https://html.spec.whatwg.org/multipage/parsing.html#appropriate-end-tag-token
+    fn is_appropriate_end_tag(&self) -> bool {
+        match (&self.latest_token, &self.last_start_tag) {
+            (Some(HtmlToken::EndTag { tag }), Some(last)) => tag == last,
+            _ => false,
+        }
+    }
+
+    /// 最後に消費した文字の位置を返す
+    pub fn source_position(&self) -> SourcePosition {
+        self.line_index.position(self.pos.saturating_sub(1))
+    }
+
+    /// 最後に消費した文字の位置でパースエラーを記録する
+    pub fn parse_error(&mut self, name: &str) {
+        let position = self.source_position();
+        self.diagnostics
+            .report(ParseErrorSource::Html, name, position);
+    }
+
+    pub fn diagnostics(&self) -> ParseDiagnostics {
+        self.diagnostics.clone()
+    }
+
     fn is_eof(&self) -> bool {
         self.pos > self.input.len()
     }
 
     fn consume_next_input(&mut self) -> char {
+        // 入力の終わりを超えて読もうとした場合、posを入力の長さより大きくして
+        // is_eofがtrueを返すようにする
+        if self.pos >= self.input.len() {
+            self.pos = self.input.len() + 1;
+            return EOF_CHAR;
+        }
+
         let c = self.input[self.pos];
         self.pos += 1;
         c
@@ -48,84 +106,100 @@ impl HtmlTokenizer {
 
     fn reconsume_input(&mut self) -> char {
         self.reconsume = false;
+        if self.is_eof() {
+            return EOF_CHAR;
+        }
         self.input[self.pos - 1]
     }
 
     fn append_tag_name(&mut self, c: char) {
-        assert!(self.latest_token.is_some());
-
-        if let Some(t) = self.latest_token.as_mut() {
-            match t {
-                HtmlToken::StartTag {
-                    ref mut tag,
-                    self_closing: _,
-                    attributes: _,
-                }
-                | HtmlToken::EndTag { ref mut tag } => tag.push(c),
-                _ => panic!("`latest_token` should be either StartTag or EndTag"),
-            }
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::StartTag {
+                ref mut tag,
+                self_closing: _,
+                attributes: _,
+            })
+            | Some(HtmlToken::EndTag { ref mut tag }) => tag.push(c),
+            // 作成中のタグがない場合、文字を無視する
+            _ => self.parse_error("unexpected-character-outside-tag"),
         }
     }
 
     fn take_latest_token(&mut self) -> Option<HtmlToken> {
-        assert!(self.latest_token.is_some());
+        let mut t = self.latest_token.take();
+
+        // 同じ名前の属性が複数ある場合、最初の属性以外を取り除く
+        // https://html.spec.whatwg.org/multipage/parsing.html#parse-error-duplicate-attribute
+        if let Some(HtmlToken::StartTag {
+            tag: _,
+            self_closing: _,
+            ref mut attributes,
+        }) = t
+        {
+            let mut unique: Vec<Attribute> = Vec::new();
+            let mut has_duplicate = false;
+            for attr in attributes.iter() {
+                if unique.iter().any(|a| a.name() == attr.name()) {
+                    has_duplicate = true;
+                    continue;
+                }
+                unique.push(attr.clone());
+            }
+            if has_duplicate {
+                self.parse_error("duplicate-attribute");
+                *attributes = unique;
+            }
+        }
 
-        let t = self.latest_token.as_ref().cloned();
-        self.latest_token = None;
-        assert!(self.latest_token.is_none());
+        if let Some(HtmlToken::StartTag {
+            ref tag,
+            self_closing: _,
+            attributes: _,
+        }) = t
+        {
+            self.last_start_tag = Some(tag.clone());
+        }
 
         t
     }
 
     fn start_new_attribute(&mut self) {
-        assert!(self.latest_token.is_some());
-
-        if let Some(t) = self.latest_token.as_mut() {
-            match t {
-                HtmlToken::StartTag {
-                    tag: _,
-                    self_closing: _,
-                    ref mut attributes,
-                } => {
-                    attributes.push(Attribute::new());
-                }
-                _ => panic!("`latest_token` should be either StartTag"),
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::StartTag {
+                tag: _,
+                self_closing: _,
+                ref mut attributes,
+            }) => {
+                attributes.push(Attribute::new());
             }
+            // 終了タグの属性は無視する
+            Some(HtmlToken::EndTag { tag: _ }) => self.parse_error("end-tag-with-attributes"),
+            _ => self.parse_error("unexpected-character-outside-tag"),
         }
     }
 
     fn append_attribute(&mut self, c: char, is_name: bool) {
-        assert!(self.latest_token.is_some());
-
-        if let Some(t) = self.latest_token.as_mut() {
-            match t {
-                HtmlToken::StartTag {
-                    tag: _,
-                    self_closing: _,
-                    ref mut attributes,
-                } => {
-                    let len = attributes.len();
-                    assert!(len > 0);
-
-                    attributes[len - 1].add_char(c, is_name);
-                }
-                _ => panic!("`latest_token` should be either StartTag"),
+        if let Some(HtmlToken::StartTag {
+            tag: _,
+            self_closing: _,
+            ref mut attributes,
+        }) = self.latest_token.as_mut()
+        {
+            if let Some(attr) = attributes.last_mut() {
+                attr.add_char(c, is_name);
             }
         }
     }
 
     fn set_self_closing_flag(&mut self) {
-        assert!(self.latest_token.is_some());
-
-        if let Some(t) = self.latest_token.as_mut() {
-            match t {
-                HtmlToken::StartTag {
-                    tag: _,
-                    ref mut self_closing,
-                    attributes: _,
-                } => *self_closing = true,
-                _ => panic!("`latest_token` should be either StartTag"),
-            }
+        match self.latest_token.as_mut() {
+            Some(HtmlToken::StartTag {
+                tag: _,
+                ref mut self_closing,
+                attributes: _,
+            }) => *self_closing = true,
+            Some(HtmlToken::EndTag { tag: _ }) => self.parse_error("end-tag-with-trailing-solidus"),
+            _ => self.parse_error("unexpected-character-outside-tag"),
         }
     }
 }
@@ -148,7 +222,7 @@ pub enum HtmlToken {
     Eof,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum State {
     /// https://html.spec.whatwg.org/multipage/parsing.html#data-state
     Data,
@@ -176,6 +250,22 @@ pub enum State {
     AfterAttributeValueQuoted,
     /// https://html.spec.whatwg.org/multipage/parsing.html#self-closing-start-tag-state
     SelfClosingStartTag,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-state
+    Rcdata,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-less-than-sign-state
+    RcdataLessThanSign,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-open-state
+    RcdataEndTagOpen,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rcdata-end-tag-name-state
+    RcdataEndTagName,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rawtext-state
+    RawText,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rawtext-less-than-sign-state
+    RawTextLessThanSign,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rawtext-end-tag-open-state
+    RawTextEndTagOpen,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#rawtext-end-tag-name-state
+    RawTextEndTagName,
     /// https://html.spec.whatwg.org/multipage/parsing.html#script-data-state
     ScriptData,
     /// https://html.spec.whatwg.org/multipage/parsing.html#script-data-less-then-sign-state
@@ -186,13 +276,17 @@ pub enum State {
     ScriptDataEndTagName,
     /// https://html.spec.whatwg.org/multipage/parsing.html#temporary-buffer
     TemporaryBuffer,
+    /// https://html.spec.whatwg.org/multipage/parsing.html#bogus-comment-state
+    BogusComment,
 }
 
 impl Iterator for HtmlTokenizer {
     type Item = HtmlToken;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos >= self.input.len() {
+        // 読み直す文字や一時的なバッファに残っている文字がある場合は、入力の
+        // 終わりに達していてもトークンを出力する
+        if self.pos >= self.input.len() && !self.reconsume && self.state != State::TemporaryBuffer {
             return None;
         }
 
@@ -213,6 +307,10 @@ impl Iterator for HtmlTokenizer {
                         return Some(HtmlToken::Eof);
                     }
 
+                    if c == '\0' {
+                        self.parse_error("unexpected-null-character");
+                    }
+
                     return Some(HtmlToken::Char(c));
                 }
                 State::TagOpen => {
@@ -229,14 +327,28 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-before-tag-name");
                         return Some(HtmlToken::Eof);
                     }
 
+                    if c == '?' {
+                        self.parse_error("unexpected-question-mark-instead-of-tag-name");
+                        self.state = State::BogusComment;
+                        continue;
+                    }
+
+                    // 本書では、DOCTYPEやコメントをサポートしていないため、"<!"は
+                    // パースエラーとして扱わない
+                    if c != '!' {
+                        self.parse_error("invalid-first-character-of-tag-name");
+                    }
+
                     self.reconsume = true;
                     self.state = State::Data;
                 }
                 State::EndTagOpen => {
                     if self.is_eof() {
+                        self.parse_error("eof-before-tag-name");
                         return Some(HtmlToken::Eof);
                     }
 
@@ -246,6 +358,16 @@ impl Iterator for HtmlTokenizer {
                         self.create_tag(false);
                         continue;
                     }
+
+                    if c == '>' {
+                        self.parse_error("missing-end-tag-name");
+                        self.state = State::Data;
+                        continue;
+                    }
+
+                    self.parse_error("invalid-first-character-of-tag-name");
+                    self.reconsume = true;
+                    self.state = State::BogusComment;
                 }
                 State::TagName => {
                     if c == ' ' {
@@ -269,6 +391,7 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
 
@@ -281,6 +404,10 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '=' {
+                        self.parse_error("unexpected-equals-sign-before-attribute-name");
+                    }
+
                     self.reconsume = true;
                     self.state = State::AttributeName;
                     self.start_new_attribute();
@@ -302,6 +429,10 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '"' || c == '\'' || c == '<' {
+                        self.parse_error("unexpected-character-in-attribute-name");
+                    }
+
                     self.append_attribute(c, /*is_name*/ true);
                 }
                 State::AfterAttributeName => {
@@ -325,6 +456,7 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
 
@@ -348,6 +480,10 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    if c == '>' {
+                        self.parse_error("missing-attribute-value");
+                    }
+
                     self.reconsume = true;
                     self.state = State::AttributeValueUnquoted;
                 }
@@ -358,6 +494,7 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
 
@@ -370,6 +507,7 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
 
@@ -387,9 +525,14 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
 
+                    if c == '"' || c == '\'' || c == '<' || c == '=' || c == '`' {
+                        self.parse_error("unexpected-character-in-unquoted-attribute-value");
+                    }
+
                     self.append_attribute(c, /*is_name*/ false);
                 }
                 State::AfterAttributeValueQuoted => {
@@ -409,11 +552,13 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
 
+                    self.parse_error("missing-whitespace-between-attributes");
                     self.reconsume = true;
-                    self.state = State::BeforeAttributeValue;
+                    self.state = State::BeforeAttributeName;
                 }
                 State::SelfClosingStartTag => {
                     if c == '>' {
@@ -423,9 +568,13 @@ impl Iterator for HtmlTokenizer {
                     }
 
                     if self.is_eof() {
-                        // invalid parse error.
+                        self.parse_error("eof-in-tag");
                         return Some(HtmlToken::Eof);
                     }
+
+                    self.parse_error("unexpected-solidus-in-tag");
+                    self.reconsume = true;
+                    self.state = State::BeforeAttributeName;
                 }
                 State::ScriptData => {
                     if c == '<' {
@@ -459,15 +608,17 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
-                    self.reconsume = true;
-                    self.state = State::ScriptData;
                     // 仕様では、"<"と"/"の2つの文字トークンを返すとなっているが、
-                    // 私達の実装では、nextメソッドからは一つのトークンしか返せないため、
-                    // "<"のトークンのみを返す
-                    return Some(HtmlToken::Char('<'));
+                    // nextメソッドからは一つのトークンしか返せないため、一時的な
+                    // バッファを使って1文字ずつ出力する
+                    self.reconsume = true;
+                    self.state = State::TemporaryBuffer;
+                    self.return_state = State::ScriptData;
+                    self.buf = String::from("</");
+                    continue;
                 }
                 State::ScriptDataEndTagName => {
-                    if c == '>' {
+                    if c == '>' && self.is_appropriate_end_tag() {
                         self.state = State::Data;
                         return self.take_latest_token();
                     }
@@ -478,16 +629,136 @@ impl Iterator for HtmlTokenizer {
                         continue;
                     }
 
+                    // 適切な終了タグでない場合、読んだ文字をそのまま文字トークンとして
+                    // 出力する
+                    self.latest_token = None;
+                    self.reconsume = true;
                     self.state = State::TemporaryBuffer;
+                    self.return_state = State::ScriptData;
+                    self.buf = String::from("</") + &self.buf;
+                    continue;
+                }
+                // 本書では文字参照をサポートしていないため、RCDATAの状態は
+                // RAWTEXTの状態と同じように扱う
+                State::Rcdata => {
+                    if c == '<' {
+                        self.state = State::RcdataLessThanSign;
+                        continue;
+                    }
+
+                    if self.is_eof() {
+                        return Some(HtmlToken::Eof);
+                    }
+
+                    return Some(HtmlToken::Char(c));
+                }
+                State::RcdataLessThanSign => {
+                    if c == '/' {
+                        // 一時的なバッファを空文字でリセットする
+                        self.buf = String::new();
+                        self.state = State::RcdataEndTagOpen;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::Rcdata;
+                    return Some(HtmlToken::Char('<'));
+                }
+                State::RcdataEndTagOpen => {
+                    if c.is_ascii_alphabetic() {
+                        self.reconsume = true;
+                        self.state = State::RcdataEndTagName;
+                        self.create_tag(false);
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::TemporaryBuffer;
+                    self.return_state = State::Rcdata;
+                    self.buf = String::from("</");
+                    continue;
+                }
+                State::RcdataEndTagName => {
+                    if c == '>' && self.is_appropriate_end_tag() {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c.is_ascii_alphabetic() {
+                        self.buf.push(c);
+                        self.append_tag_name(c.to_ascii_lowercase());
+                        continue;
+                    }
+
+                    self.latest_token = None;
+                    self.reconsume = true;
+                    self.state = State::TemporaryBuffer;
+                    self.return_state = State::Rcdata;
+                    self.buf = String::from("</") + &self.buf;
+                    continue;
+                }
+                State::RawText => {
+                    if c == '<' {
+                        self.state = State::RawTextLessThanSign;
+                        continue;
+                    }
+
+                    if self.is_eof() {
+                        return Some(HtmlToken::Eof);
+                    }
+
+                    return Some(HtmlToken::Char(c));
+                }
+                State::RawTextLessThanSign => {
+                    if c == '/' {
+                        // 一時的なバッファを空文字でリセットする
+                        self.buf = String::new();
+                        self.state = State::RawTextEndTagOpen;
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::RawText;
+                    return Some(HtmlToken::Char('<'));
+                }
+                State::RawTextEndTagOpen => {
+                    if c.is_ascii_alphabetic() {
+                        self.reconsume = true;
+                        self.state = State::RawTextEndTagName;
+                        self.create_tag(false);
+                        continue;
+                    }
+
+                    self.reconsume = true;
+                    self.state = State::TemporaryBuffer;
+                    self.return_state = State::RawText;
+                    self.buf = String::from("</");
+                    continue;
+                }
+                State::RawTextEndTagName => {
+                    if c == '>' && self.is_appropriate_end_tag() {
+                        self.state = State::Data;
+                        return self.take_latest_token();
+                    }
+
+                    if c.is_ascii_alphabetic() {
+                        self.buf.push(c);
+                        self.append_tag_name(c.to_ascii_lowercase());
+                        continue;
+                    }
+
+                    self.latest_token = None;
+                    self.reconsume = true;
+                    self.state = State::TemporaryBuffer;
+                    self.return_state = State::RawText;
                     self.buf = String::from("</") + &self.buf;
-                    self.buf.push(c);
                     continue;
                 }
                 State::TemporaryBuffer => {
                     self.reconsume = true;
 
                     if self.buf.chars().count() == 0 {
-                        self.state = State::ScriptData;
+                        self.state = self.return_state;
                         continue;
                     }
 
@@ -500,6 +771,18 @@ impl Iterator for HtmlTokenizer {
                     self.buf.remove(0);
                     return Some(HtmlToken::Char(c));
                 }
+                State::BogusComment => {
+                    // 本書では、コメントをサポートしていないため、">"までの文字を
+                    // 読み飛ばす
+                    if c == '>' {
+                        self.state = State::Data;
+                        continue;
+                    }
+
+                    if self.is_eof() {
+                        return Some(HtmlToken::Eof);
+                    }
+                }
             }
         }
     }
@@ -619,4 +902,122 @@ mod tests {
             assert_eq!(Some(e), tokenizer.next());
         }
     }
+
+    #[test]
+    fn test_eof_in_tag() {
+        let html = "<p\nclass=\"a".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        assert_eq!(Some(HtmlToken::Eof), tokenizer.next());
+        assert!(tokenizer.next().is_none());
+
+        let errors = tokenizer.diagnostics().errors();
+        assert_eq!(1, errors.len());
+        assert_eq!("eof-in-tag", errors[0].name());
+        assert_eq!(SourcePosition::new(2, 9), errors[0].position());
+    }
+
+    #[test]
+    fn test_end_tag_with_attributes() {
+        let html = "<p></p class=\"a\"/>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let expected = [
+            HtmlToken::StartTag {
+                tag: "p".to_string(),
+                self_closing: false,
+                attributes: Vec::new(),
+            },
+            HtmlToken::EndTag {
+                tag: "p".to_string(),
+            },
+        ];
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+
+        let names: Vec<String> = tokenizer
+            .diagnostics()
+            .errors()
+            .iter()
+            .map(|e| e.name())
+            .collect();
+        assert_eq!(
+            vec![
+                "end-tag-with-attributes".to_string(),
+                "end-tag-with-trailing-solidus".to_string()
+            ],
+            names
+        );
+    }
+
+    #[test]
+    fn test_duplicate_attribute() {
+        let html = "<p id=a id=b>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let mut attr = Attribute::new();
+        attr.add_char('i', true);
+        attr.add_char('d', true);
+        attr.add_char('a', false);
+
+        assert_eq!(
+            Some(HtmlToken::StartTag {
+                tag: "p".to_string(),
+                self_closing: false,
+                attributes: vec![attr],
+            }),
+            tokenizer.next()
+        );
+
+        let errors = tokenizer.diagnostics().errors();
+        assert_eq!(1, errors.len());
+        assert_eq!("duplicate-attribute", errors[0].name());
+        assert_eq!(SourcePosition::new(1, 13), errors[0].position());
+    }
+
+    #[test]
+    fn test_invalid_tag_open() {
+        let html = "a</>b<?x>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        let expected = [HtmlToken::Char('a'), HtmlToken::Char('b'), HtmlToken::Eof];
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+        assert!(tokenizer.next().is_none());
+
+        let errors = tokenizer.diagnostics().errors();
+        assert_eq!(2, errors.len());
+        assert_eq!("missing-end-tag-name", errors[0].name());
+        assert_eq!(SourcePosition::new(1, 4), errors[0].position());
+        assert_eq!(
+            "unexpected-question-mark-instead-of-tag-name",
+            errors[1].name()
+        );
+        assert_eq!(SourcePosition::new(1, 7), errors[1].position());
+    }
+
+    #[test]
+    fn test_raw_text() {
+        let html = "<style>a</b><</style>".to_string();
+        let mut tokenizer = HtmlTokenizer::new(html);
+        assert_eq!(
+            Some(HtmlToken::StartTag {
+                tag: "style".to_string(),
+                self_closing: false,
+                attributes: Vec::new(),
+            }),
+            tokenizer.next()
+        );
+        tokenizer.switch_to(State::RawText);
+
+        let mut expected = Vec::new();
+        for c in "a</b><".chars() {
+            expected.push(HtmlToken::Char(c));
+        }
+        expected.push(HtmlToken::EndTag {
+            tag: "style".to_string(),
+        });
+        for e in expected {
+            assert_eq!(Some(e), tokenizer.next());
+        }
+        assert!(tokenizer.next().is_none());
+    }
 }
@@ -1,9 +1,9 @@
+use crate::renderer::diagnostics::ParseDiagnostics;
 use crate::renderer::js::token::JsLexer;
 use crate::renderer::js::token::Token;
 use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::vec::Vec;
-use core::iter::Peekable;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Node {
@@ -133,12 +133,16 @@ impl Node {
 }
 
 pub struct JsParser {
-    t: Peekable<JsLexer>,
+    t: JsLexer,
 }
 
 impl JsParser {
     pub fn new(t: JsLexer) -> Self {
-        Self { t: t.peekable() }
+        Self { t }
+    }
+
+    pub fn diagnostics(&self) -> ParseDiagnostics {
+        self.t.diagnostics()
     }
 
     pub fn parse_ast(&mut self) -> Program {
@@ -302,25 +306,17 @@ impl JsParser {
     }
 
     fn member_expression(&mut self) -> Option<Rc<Node>> {
-        let expr = self.primary_expression();
-
-        let t = match self.t.peek() {
-            Some(token) => token,
-            None => return expr,
-        };
-
-        match t {
-            Token::Punctuator(c) => {
-                if c == &'.' {
-                    // '.'を消費する
-                    assert!(self.t.next().is_some());
-                    return Node::new_member_expression(expr, self.identifier());
-                }
-
-                expr
-            }
-            _ => expr,
+        let mut expr = self.primary_expression();
+
+        // a.b.cのように続くメンバアクセスは、左側を入れ子にしたMemberExpression
+        // ノードにする
+        while self.t.peek() == Some(&Token::Punctuator('.')) {
+            // '.'を消費する
+            assert!(self.t.next().is_some());
+            expr = Node::new_member_expression(expr, self.identifier());
         }
+
+        expr
     }
 
     fn primary_expression(&mut self) -> Option<Rc<Node>> {
@@ -385,12 +381,18 @@ impl JsParser {
         let mut params = Vec::new();
 
         // '('を消費する。もし次のトークンが'('でない場合、エラーになる
-        match self.t.next() {
-            Some(t) => match t {
-                Token::Punctuator(c) => assert!(c == '('),
-                _ => unimplemented!("function should have '(' but got {:?}", t),
-            },
-            None => unimplemented!("function should have '(' but got None"),
+        match self.t.peek() {
+            Some(Token::Punctuator('(')) => {
+                assert!(self.t.next().is_some());
+            }
+            Some(_) => {
+                self.t.parse_error("unexpected-token");
+                return params;
+            }
+            None => {
+                self.t.parse_error("unexpected-end-of-input");
+                return params;
+            }
         }
 
         loop {
@@ -406,25 +408,38 @@ impl JsParser {
                         if c == &',' {
                             // ','を消費する
                             assert!(self.t.next().is_some());
+                        } else {
+                            // 仮引数として不正な記号は読み飛ばす
+                            self.t.parse_error("unexpected-token");
+                            assert!(self.t.next().is_some());
                         }
                     }
                     _ => {
                         params.push(self.identifier());
                     }
                 },
-                None => return params,
+                None => {
+                    self.t.parse_error("unexpected-end-of-input");
+                    return params;
+                }
             }
         }
     }
 
     fn function_body(&mut self) -> Option<Rc<Node>> {
         // '{'を消費する
-        match self.t.next() {
-            Some(t) => match t {
-                Token::Punctuator(c) => assert!(c == '{'),
-                _ => unimplemented!("function should have open curly blacket but got {:?}", t),
-            },
-            None => unimplemented!("function should have open curly blacket but got None"),
+        match self.t.peek() {
+            Some(Token::Punctuator('{')) => {
+                assert!(self.t.next().is_some());
+            }
+            Some(_) => {
+                self.t.parse_error("unexpected-token");
+                return None;
+            }
+            None => {
+                self.t.parse_error("unexpected-end-of-input");
+                return None;
+            }
         }
 
         let mut body = Vec::new();
@@ -441,7 +456,11 @@ impl JsParser {
                     }
                     _ => {}
                 },
-                None => {}
+                None => {
+                    // '}'が現れる前に入力が終わったため、エラー
+                    self.t.parse_error("unexpected-end-of-input");
+                    return Node::new_block_statement(body);
+                }
             }
 
             body.push(self.source_element());
@@ -645,4 +664,31 @@ mod tests {
         expected.set_body(body);
         assert_eq!(expected, parser.parse_ast());
     }
+
+    #[test]
+    fn test_unterminated_function() {
+        let input = "function foo() {\n  return 42;".to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let mut expected = Program::new();
+        let body = [Rc::new(Node::FunctionDeclaration {
+            id: Some(Rc::new(Node::Identifier("foo".to_string()))),
+            params: [].to_vec(),
+            body: Some(Rc::new(Node::BlockStatement {
+                body: [Some(Rc::new(Node::ReturnStatement {
+                    argument: Some(Rc::new(Node::NumericLiteral(42))),
+                }))]
+                .to_vec(),
+            })),
+        })]
+        .to_vec();
+        expected.set_body(body);
+        assert_eq!(expected, parser.parse_ast());
+
+        let errors = parser.diagnostics().errors();
+        assert_eq!(1, errors.len());
+        assert_eq!("unexpected-end-of-input", errors[0].name());
+        assert_eq!(2, errors[0].position().line());
+        assert_eq!(13, errors[0].position().column());
+    }
 }
@@ -1,7 +1,22 @@
+use crate::renderer::dom::api::get_document_title;
 use crate::renderer::dom::api::get_element_by_id;
-use crate::renderer::dom::node::Node as DomNode;
-use crate::renderer::dom::node::NodeKind;
-use crate::renderer::dom::node::NodeKind as DomNodeKind;
+use crate::renderer::dom::api::get_elements_by_class_name;
+use crate::renderer::dom::api::get_elements_by_tag_name;
+use crate::renderer::dom::api::query_selector;
+use crate::renderer::dom::api::query_selector_all;
+use crate::renderer::dom::api::set_document_title;
+use crate::renderer::dom::api::set_inline_style_property;
+use crate::renderer::dom::event::add_event_listener;
+use crate::renderer::dom::event::remove_event_listener;
+use crate::renderer::dom::event::Event;
+use crate::renderer::dom::event::EventCallback;
+use crate::renderer::dom::event::EventTarget;
+use crate::renderer::dom::event::ListenerOptions;
+use crate::renderer::dom::focus::active_element;
+use crate::renderer::dom::focus::focus;
+use crate::renderer::dom::mutation::set_text_content;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::NodeId;
 use crate::renderer::js::ast::Node;
 use crate::renderer::js::ast::Program;
 use alloc::format;
@@ -11,6 +26,7 @@ use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::borrow::Borrow;
 use core::cell::RefCell;
+use core::fmt::Debug;
 use core::fmt::Display;
 use core::fmt::Formatter;
 use core::ops::Add;
@@ -18,19 +34,41 @@ use core::ops::Sub;
 
 type VariableMap = Vec<(String, Option<RuntimeValue>)>;
 
+/// スクリプトがイベントリスナとして登録した関数と、そのコールバック。
+/// removeEventListenerで同じリスナを見つけられるように、関数ごとに同じ
+/// コールバックを使い回す
+#[derive(Clone)]
+struct ScriptListener {
+    function: String,
+    callback: EventCallback,
+}
+
+impl Debug for ScriptListener {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        f.debug_struct("ScriptListener")
+            .field("function", &self.function)
+            .finish()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct JsRuntime {
-    dom_root: Rc<RefCell<DomNode>>,
+    document: Rc<RefCell<Document>>,
     functions: Vec<Function>,
     env: Rc<RefCell<Environment>>,
+    listeners: Vec<ScriptListener>,
+    /// イベントリスナとして呼ばれている間に処理しているイベント
+    event: Option<Event>,
 }
 
 impl JsRuntime {
-    pub fn new(dom_root: Rc<RefCell<DomNode>>) -> Self {
+    pub fn new(document: Rc<RefCell<Document>>) -> Self {
         Self {
-            dom_root,
+            document,
             functions: Vec::new(),
             env: Rc::new(RefCell::new(Environment::new(None))),
+            listeners: Vec::new(),
+            event: None,
         }
     }
 
@@ -92,6 +130,14 @@ impl JsRuntime {
                     }
                 }
 
+                // document.title = "foobar"; のようにドキュメントのタイトルを変更する
+                if Self::is_document_property(left, "title") {
+                    if let Some(value) = self.eval(right, env.clone()) {
+                        set_document_title(&mut self.document.borrow_mut(), &value.to_string());
+                    }
+                    return None;
+                }
+
                 if let Some(RuntimeValue::HtmlElement { object, property }) =
                     self.eval(left, env.clone())
                 {
@@ -101,13 +147,38 @@ impl JsRuntime {
                     };
 
                     if let Some(p) = property {
-                        // target.textContext = "foobar"; のようにノードのテキストを変更する
-                        if p == "textContext" {
-                            object
-                                .borrow_mut()
-                                .set_first_child(Some(Rc::new(RefCell::new(DomNode::new(
-                                    DomNodeKind::Text(right_value.to_string()),
-                                )))));
+                        let mut document = self.document.borrow_mut();
+                        match p.as_str() {
+                            // target.textContext = "foobar"; のようにノードのテキストを変更する
+                            "textContext" => {
+                                set_text_content(&mut document, object, &right_value.to_string())
+                            }
+                            "id" => {
+                                if let Some(e) = document.element_mut(object) {
+                                    e.set_id(&right_value.to_string());
+                                }
+                            }
+                            "className" => {
+                                if let Some(e) = document.element_mut(object) {
+                                    e.set_class_name(&right_value.to_string());
+                                }
+                            }
+                            // target.style.cssText = "color: red"; のようにstyle属性
+                            // 全体を変更する
+                            "style.cssText" => {
+                                if let Some(e) = document.element_mut(object) {
+                                    let _ = e.set_attribute("style", &right_value.to_string());
+                                }
+                            }
+                            // target.style.backgroundColor = "red"; のようにstyle属性の
+                            // プロパティを変更する
+                            _ if p.starts_with("style.") => set_inline_style_property(
+                                &mut document,
+                                object,
+                                &css_property_name(&p["style.".len()..]),
+                                &right_value.to_string(),
+                            ),
+                            _ => {}
                         }
                     }
                 }
@@ -115,6 +186,26 @@ impl JsRuntime {
                 None
             }
             Node::MemberExpression { object, property } => {
+                let node = Some(node.clone());
+                if Self::is_document_property(&node, "title") {
+                    return Some(RuntimeValue::StringLiteral(get_document_title(
+                        &RefCell::borrow(&self.document),
+                    )));
+                }
+                if Self::is_document_property(&node, "activeElement") {
+                    return active_element(&RefCell::borrow(&self.document)).map(|n| {
+                        RuntimeValue::HtmlElement {
+                            object: n,
+                            property: None,
+                        }
+                    });
+                }
+                if Self::is_document_property(&node, "readyState") {
+                    return Some(RuntimeValue::StringLiteral(
+                        RefCell::borrow(&self.document).ready_state().to_string(),
+                    ));
+                }
+
                 let object_value = match self.eval(object, env.clone()) {
                     Some(value) => value,
                     None => return None,
@@ -125,13 +216,34 @@ impl JsRuntime {
                     None => return Some(object_value),
                 };
 
+                // NodeListはlengthプロパティだけをサポートする
+                if let RuntimeValue::NodeList(ref nodes) = object_value {
+                    if property_value.to_string() == "length" {
+                        return Some(RuntimeValue::Number(nodes.len() as u64));
+                    }
+                }
+
                 // もしオブジェクトがDOMノードの場合、HtmlElementのpropertyを更新する
                 if let RuntimeValue::HtmlElement { object, property } = object_value {
-                    assert!(property.is_none());
+                    let property = match property {
+                        None => property_value.to_string(),
+                        // element.style.colorは、"style.color"というプロパティとして扱う
+                        Some(p) if p == "style" => format!("style.{}", property_value),
+                        // その他のプロパティのプロパティはサポートしない
+                        Some(_) => return None,
+                    };
                     // HtmlElement の property に property_value の文字列を
                     // セットする
                     return Some(RuntimeValue::HtmlElement {
                         object,
+                        property: Some(property),
+                    });
+                }
+
+                // event.preventDefaultのようなイベントのメソッドも、呼び出すときに
+                // propertyで区別する
+                if let RuntimeValue::Event { property: None } = object_value {
+                    return Some(RuntimeValue::Event {
                         property: Some(property_value.to_string()),
                     });
                 }
@@ -221,7 +333,9 @@ impl JsRuntime {
 
                     match f {
                         Some(f) => f,
-                        None => panic!("function {:?} doesn't exist", callee),
+                        // ページのスクリプトを実行するため、サポートしていない
+                        // 関数の呼び出しでパニックせずに無視する
+                        None => return None,
                     }
                 };
 
@@ -243,6 +357,20 @@ impl JsRuntime {
         }
     }
 
+    /// ノードが`document.<name>`を表すメンバー式かどうかを返す
+    fn is_document_property(node: &Option<Rc<Node>>, name: &str) -> bool {
+        match node.as_deref() {
+            Some(Node::MemberExpression {
+                object: Some(object),
+                property: Some(property),
+            }) => {
+                **object == Node::Identifier("document".to_string())
+                    && **property == Node::Identifier(name.to_string())
+            }
+            _ => false,
+        }
+    }
+
     /// (bool, Option<RuntimeValue>)のタプルを返す
     /// bool: ブラウザAPIが呼ばれたかどうか。trueなら何かしらのAPIが呼ばれた
     /// ことを示す
@@ -253,14 +381,58 @@ impl JsRuntime {
         arguments: &[Option<Rc<Node>>],
         env: Rc<RefCell<Environment>>,
     ) -> (bool, Option<RuntimeValue>) {
+        // リスナとして呼ばれている間だけ、イベントを操作できる
+        if let RuntimeValue::Event {
+            property: Some(method),
+        } = func
+        {
+            if let Some(event) = &mut self.event {
+                match method.as_str() {
+                    "preventDefault" => event.prevent_default(),
+                    "stopPropagation" => event.stop_propagation(),
+                    "stopImmediatePropagation" => event.stop_immediate_propagation(),
+                    _ => {}
+                }
+            }
+            return (true, None);
+        }
+
+        // イベントリスナを登録、削除するメソッド。`document`の場合は文書の
+        // ルートノードを、`window`の場合はウィンドウを対象にする
+        let listener_method = match func {
+            RuntimeValue::HtmlElement {
+                object,
+                property: Some(method),
+            } => Some((EventTarget::Node(*object), method.as_str())),
+            RuntimeValue::StringLiteral(name) => match name.split_once('.') {
+                Some(("document", method)) => Some((
+                    EventTarget::Node(RefCell::borrow(&self.document).root()),
+                    method,
+                )),
+                Some(("window", method)) => Some((EventTarget::Window, method)),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some((target, method)) = listener_method {
+            if matches!(method, "addEventListener" | "removeEventListener") {
+                let is_add = method == "addEventListener";
+                self.update_event_listener(target, is_add, arguments, env);
+                return (true, None);
+            }
+        }
+
         if func == &RuntimeValue::StringLiteral("document.getElementById".to_string()) {
             let arg = match self.eval(&arguments[0], env.clone()) {
                 Some(a) => a,
                 None => return (true, None),
             };
-            let target = match get_element_by_id(Some(self.dom_root.clone()), &arg.to_string()) {
-                Some(n) => n,
-                None => return (true, None),
+            let target = {
+                let document = RefCell::borrow(&self.document);
+                match get_element_by_id(&document, Some(document.root()), &arg.to_string()) {
+                    Some(n) => n,
+                    None => return (true, None),
+                }
             };
             return (
                 true,
@@ -271,17 +443,262 @@ impl JsRuntime {
             );
         }
 
-        (false, None)
+        // フォーカスを操作するメソッド。blurはフォーカスされている要素の場合だけ
+        // フォーカスを外す
+        if let RuntimeValue::HtmlElement {
+            object,
+            property: Some(method),
+        } = func
+        {
+            match method.as_str() {
+                "focus" => {
+                    focus(&self.document, Some(*object));
+                    return (true, None);
+                }
+                "blur" => {
+                    let is_focused =
+                        RefCell::borrow(&self.document).focused_element() == Some(*object);
+                    if is_focused {
+                        focus(&self.document, None);
+                    }
+                    return (true, None);
+                }
+                _ => {}
+            }
+        }
+
+        // 要素の属性を操作するメソッド
+        if let RuntimeValue::HtmlElement {
+            object,
+            property: Some(method),
+        } = func
+        {
+            if matches!(
+                method.as_str(),
+                "getAttribute" | "setAttribute" | "removeAttribute"
+            ) {
+                let mut args = Vec::new();
+                for argument in arguments {
+                    match self.eval(argument, env.clone()) {
+                        Some(a) => args.push(a.to_string()),
+                        None => return (true, None),
+                    }
+                }
+                let name = match args.first() {
+                    Some(name) => name,
+                    None => return (true, None),
+                };
+
+                if method == "getAttribute" {
+                    let document = RefCell::borrow(&self.document);
+                    return (
+                        true,
+                        document
+                            .element(*object)
+                            .and_then(|e| e.get_attribute(name))
+                            .map(|v| RuntimeValue::StringLiteral(v.to_string())),
+                    );
+                }
+
+                let mut document = self.document.borrow_mut();
+                let element = match document.element_mut(*object) {
+                    Some(e) => e,
+                    None => return (true, None),
+                };
+                return match method.as_str() {
+                    "setAttribute" => {
+                        // 不正な属性名の場合はInvalidCharacterErrorを投げるべきだが、
+                        // 例外はサポートしていないので何もしない
+                        let value = args.get(1).cloned().unwrap_or_default();
+                        let _ = element.set_attribute(name, &value);
+                        (true, None)
+                    }
+                    _ => {
+                        element.remove_attribute(name);
+                        (true, None)
+                    }
+                };
+            }
+        }
+
+        // 要素を探すメソッドは、`document`の場合は文書全体を、要素の場合はその
+        // 子孫を対象にする
+        let (root, method) = match func {
+            RuntimeValue::StringLiteral(name) => match name.strip_prefix("document.") {
+                Some(method) => (RefCell::borrow(&self.document).root(), method.to_string()),
+                None => return (false, None),
+            },
+            RuntimeValue::HtmlElement {
+                object,
+                property: Some(property),
+            } => (*object, property.to_string()),
+            _ => return (false, None),
+        };
+        if !matches!(
+            method.as_str(),
+            "querySelector"
+                | "querySelectorAll"
+                | "getElementsByTagName"
+                | "getElementsByClassName"
+        ) {
+            return (false, None);
+        }
+
+        let arg = match arguments.first() {
+            Some(argument) => match self.eval(argument, env.clone()) {
+                Some(a) => a.to_string(),
+                None => return (true, None),
+            },
+            None => return (true, None),
+        };
+
+        // セレクタをパースできない場合はSyntaxErrorを投げるべきだが、例外は
+        // サポートしていないので何も返さない
+        let document = RefCell::borrow(&self.document);
+        let result = match method.as_str() {
+            "querySelector" => match query_selector(&document, root, &arg) {
+                Ok(Some(node)) => Some(RuntimeValue::HtmlElement {
+                    object: node,
+                    property: None,
+                }),
+                _ => None,
+            },
+            "querySelectorAll" => query_selector_all(&document, root, &arg)
+                .ok()
+                .map(RuntimeValue::NodeList),
+            "getElementsByTagName" => Some(RuntimeValue::NodeList(get_elements_by_tag_name(
+                &document, root, &arg,
+            ))),
+            _ => Some(RuntimeValue::NodeList(get_elements_by_class_name(
+                &document, root, &arg,
+            ))),
+        };
+        (true, result)
+    }
+
+    /// `addEventListener(type, listener, capture)`または`removeEventListener`を
+    /// 実行する。`listener`には関数の名前を渡す
+    /// https://dom.spec.whatwg.org/#dom-eventtarget-addeventlistener
+    /// https://dom.spec.whatwg.org/#dom-eventtarget-removeeventlistener
+    fn update_event_listener(
+        &mut self,
+        target: EventTarget,
+        is_add: bool,
+        arguments: &[Option<Rc<Node>>],
+        env: Rc<RefCell<Environment>>,
+    ) {
+        let mut args = Vec::new();
+        for argument in arguments {
+            match self.eval(argument, env.clone()) {
+                Some(a) => args.push(a.to_string()),
+                None => return,
+            }
+        }
+        let (event_type, function) = match (args.first(), args.get(1)) {
+            (Some(event_type), Some(function)) => (event_type, function),
+            _ => return,
+        };
+        let capture = args.get(2).is_some_and(|c| c == "true");
+        let callback = match self.listener_callback(function) {
+            Some(callback) => callback,
+            None => return,
+        };
+
+        let mut document = self.document.borrow_mut();
+        if is_add {
+            let options = ListenerOptions {
+                capture,
+                once: false,
+            };
+            add_event_listener(&mut document, target, event_type, callback, options);
+        } else {
+            remove_event_listener(&mut document, target, event_type, &callback, capture);
+        }
+    }
+
+    /// `name`の関数を呼ぶイベントリスナのコールバックを返す。関数が定義されて
+    /// いない場合はNoneを返す
+    fn listener_callback(&mut self, name: &str) -> Option<EventCallback> {
+        if let Some(listener) = self.listeners.iter().find(|l| l.function == name) {
+            return Some(listener.callback.clone());
+        }
+        let function = self.functions.iter().rev().find(|f| f.id == name)?.clone();
+
+        // コールバックはドキュメントがリスナとして持つので、ドキュメントを弱い参照で
+        // 持って循環参照を避ける。関数は登録した時点で定義されているものを使う
+        let document = Rc::downgrade(&self.document);
+        let functions = self.functions.clone();
+        let env = self.env.clone();
+        let callback: EventCallback = Rc::new(move |event: &mut Event| {
+            let document = match document.upgrade() {
+                Some(document) => document,
+                None => return,
+            };
+            let mut runtime = JsRuntime {
+                document,
+                functions: functions.clone(),
+                env: env.clone(),
+                listeners: Vec::new(),
+                event: Some(event.clone()),
+            };
+            runtime.call_listener(&function);
+            // preventDefault()やstopPropagation()の結果を、送っているイベントに戻す
+            if let Some(e) = runtime.event {
+                *event = e;
+            }
+        });
+
+        self.listeners.push(ScriptListener {
+            function: name.to_string(),
+            callback: callback.clone(),
+        });
+        Some(callback)
+    }
+
+    /// 関数をイベントリスナとして呼ぶ。最初の仮引数には処理しているイベントを渡す
+    /// https://dom.spec.whatwg.org/#concept-event-listener-inner-invoke
+    fn call_listener(&mut self, function: &Function) {
+        let env = Rc::new(RefCell::new(Environment::new(Some(self.env.clone()))));
+        if let Some(Node::Identifier(name)) = function.params.first().and_then(|p| p.as_deref()) {
+            env.borrow_mut().add_variable(
+                name.to_string(),
+                Some(RuntimeValue::Event { property: None }),
+            );
+        }
+        self.eval(&function.body, env);
     }
 }
 
+/// `backgroundColor`のようなキャメルケースのプロパティ名を、`background-color`
+/// のようなCSSのプロパティ名にする
+/// https://drafts.csswg.org/cssom/#camel-cased-attribute
+fn css_property_name(name: &str) -> String {
+    let mut property = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            property.push('-');
+            property.push(c.to_ascii_lowercase());
+        } else {
+            property.push(c);
+        }
+    }
+    property
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RuntimeValue {
     /// https://262.ecma-international.org/#sec-numeric-types
     Number(u64),
     StringLiteral(String),
     HtmlElement {
-        object: Rc<RefCell<DomNode>>,
+        object: NodeId,
+        property: Option<String>,
+    },
+    /// https://dom.spec.whatwg.org/#interface-nodelist
+    NodeList(Vec<NodeId>),
+    /// イベントリスナに渡されたイベント
+    /// https://dom.spec.whatwg.org/#interface-event
+    Event {
         property: Option<String>,
     },
 }
@@ -322,6 +739,8 @@ impl Display for RuntimeValue {
             } => {
                 format!("HtmlElement: {:?}", object)
             }
+            RuntimeValue::NodeList(nodes) => format!("NodeList: {:?}", nodes),
+            RuntimeValue::Event { property: _ } => "Event".to_string(),
         };
         write!(f, "{}", s)
     }
@@ -390,6 +809,9 @@ mod tests {
     use alloc::string::ToString;
 
     use super::*;
+    use crate::renderer::dom::api::child_text_content;
+    use crate::renderer::html::parser::HtmlParser;
+    use crate::renderer::html::token::HtmlTokenizer;
     use crate::renderer::js::ast::JsParser;
     use crate::renderer::js::token::JsLexer;
 
@@ -399,7 +821,7 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let dom = Rc::new(RefCell::new(Document::new()));
         let mut runtime = JsRuntime::new(dom);
         let expected = [Some(RuntimeValue::Number(42))];
         let mut i = 0;
@@ -417,7 +839,7 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let dom = Rc::new(RefCell::new(Document::new()));
         let mut runtime = JsRuntime::new(dom);
         let expected = [Some(RuntimeValue::Number(3))];
         let mut i = 0;
@@ -435,7 +857,7 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let dom = Rc::new(RefCell::new(Document::new()));
         let mut runtime = JsRuntime::new(dom);
         let expected = [Some(RuntimeValue::Number(1))];
         let mut i = 0;
@@ -453,7 +875,7 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let dom = Rc::new(RefCell::new(Document::new()));
         let mut runtime = JsRuntime::new(dom);
         let expected = [None];
         let mut i = 0;
@@ -471,7 +893,7 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let dom = Rc::new(RefCell::new(Document::new()));
         let mut runtime = JsRuntime::new(dom);
         let expected = [None, Some(RuntimeValue::Number(43))];
         let mut i = 0;
@@ -489,7 +911,7 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let dom = Rc::new(RefCell::new(Document::new()));
         let mut runtime = JsRuntime::new(dom);
         let expected = [None, None, Some(RuntimeValue::Number(1))];
         let mut i = 0;
@@ -507,7 +929,7 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let dom = Rc::new(RefCell::new(Document::new()));
         let mut runtime = JsRuntime::new(dom);
         let expected = [None, Some(RuntimeValue::Number(43))];
         let mut i = 0;
@@ -525,7 +947,7 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let dom = Rc::new(RefCell::new(Document::new()));
         let mut runtime = JsRuntime::new(dom);
         let expected = [None, Some(RuntimeValue::Number(6))];
         let mut i = 0;
@@ -543,7 +965,7 @@ mod tests {
         let lexer = JsLexer::new(input);
         let mut parser = JsParser::new(lexer);
         let ast = parser.parse_ast();
-        let dom = Rc::new(RefCell::new(DomNode::new(DomNodeKind::Document)));
+        let dom = Rc::new(RefCell::new(Document::new()));
         let mut runtime = JsRuntime::new(dom);
         let expected = [None, None, Some(RuntimeValue::Number(43))];
         let mut i = 0;
@@ -554,4 +976,174 @@ mod tests {
             i += 1;
         }
     }
+
+    #[test]
+    fn test_document_title() {
+        let html = "<html><head><title>old</title></head><body></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).document();
+
+        let input = "var a=document.title; document.title=\"new\"; a".to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom.clone());
+        let expected = [
+            None,
+            None,
+            Some(RuntimeValue::StringLiteral("old".to_string())),
+        ];
+        for (i, node) in ast.body().iter().enumerate() {
+            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+            assert_eq!(expected[i], result);
+        }
+        assert_eq!(
+            "new".to_string(),
+            get_document_title(&RefCell::borrow(&dom))
+        );
+    }
+
+    #[test]
+    fn test_inline_style() {
+        let html =
+            "<html><head></head><body><p id=p style=\"color: red; width: 1; margin: 2px\"></p>\
+</body></html>"
+                .to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).document();
+
+        let input = "var p=document.getElementById(\"p\"); p.style.backgroundColor=\"blue\"; \
+p.style.color=\"green\"; p.style.width=\"\"; p.style.height=\"100px\"; \
+p.style.top=\"1; color: blue\"; p.style.left=\"1 !important\""
+            .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom.clone());
+        for node in ast.body() {
+            runtime.eval(&Some(node.clone()), runtime.env.clone());
+        }
+
+        // 書き換えていない宣言や設定した値は書かれたテキストのまま残り、不正な値は
+        // 無視される
+        let document = RefCell::borrow(&dom);
+        let p = get_element_by_id(&document, Some(document.root()), "p")
+            .expect("failed to get a p element");
+        assert_eq!(
+            Some("color: green; margin: 2px; background-color: blue; height: 100px"),
+            document.element(p).and_then(|e| e.get_attribute("style"))
+        );
+    }
+
+    #[test]
+    fn test_query_selector() {
+        let html = "<html><head></head><body><p class=x><a class=x id=a>old</a></p>\
+<p class=y></p></body></html>"
+            .to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).document();
+
+        let input = "var a=document.querySelectorAll(\".x\"); a.length; \
+var b=document.getElementsByTagName(\"p\"); b.length; \
+var p=document.querySelector(\"p\"); var c=p.getElementsByClassName(\"x\"); c.length; \
+var e=document.querySelector(\"#a\"); e.textContext=\"new\"; \
+document.querySelector(\"pre\")"
+            .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom.clone());
+        let expected = [
+            None,
+            Some(RuntimeValue::Number(2)),
+            None,
+            Some(RuntimeValue::Number(2)),
+            None,
+            None,
+            Some(RuntimeValue::Number(1)),
+            None,
+            None,
+            None,
+        ];
+        for (i, node) in ast.body().iter().enumerate() {
+            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+            assert_eq!(expected[i], result);
+        }
+
+        let document = RefCell::borrow(&dom);
+        let a = get_element_by_id(&document, Some(document.root()), "a")
+            .expect("failed to get an a element");
+        assert_eq!("new".to_string(), child_text_content(&document, a));
+    }
+
+    #[test]
+    fn test_attributes() {
+        let html = "<html><head></head><body><p id=a class=x>text</p></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).document();
+
+        let input = "var p=document.getElementById(\"a\"); p.getAttribute(\"class\"); \
+p.setAttribute(\"title\", \"t\"); p.removeAttribute(\"class\"); p.className=\"y\"; \
+p.id=\"b\"; p.getAttribute(\"TITLE\")"
+            .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom.clone());
+        let expected = [
+            None,
+            Some(RuntimeValue::StringLiteral("x".to_string())),
+            None,
+            None,
+            None,
+            None,
+            Some(RuntimeValue::StringLiteral("t".to_string())),
+        ];
+        for (i, node) in ast.body().iter().enumerate() {
+            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+            assert_eq!(expected[i], result);
+        }
+
+        let document = RefCell::borrow(&dom);
+        let p = get_element_by_id(&document, Some(document.root()), "b")
+            .expect("failed to get a p element");
+        let element = document.element(p).expect("failed to get a p element");
+        assert_eq!("y", element.class_name());
+        assert_eq!(3, element.attributes().len());
+    }
+
+    #[test]
+    fn test_focus() {
+        let html = "<html><head></head><body><a id=a href=x>a</a></body></html>".to_string();
+        let window = HtmlParser::new(HtmlTokenizer::new(html)).construct_tree();
+        let dom = RefCell::borrow(&window).document();
+        let (a, body) = {
+            let document = RefCell::borrow(&dom);
+            let a = get_element_by_id(&document, Some(document.root()), "a")
+                .expect("failed to get an a element");
+            (
+                a,
+                active_element(&document).expect("failed to get a body element"),
+            )
+        };
+
+        let input = "var a=document.getElementById(\"a\"); a.focus(); document.activeElement; \
+a.blur(); document.activeElement"
+            .to_string();
+        let lexer = JsLexer::new(input);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        let mut runtime = JsRuntime::new(dom.clone());
+        let element = |object| {
+            Some(RuntimeValue::HtmlElement {
+                object,
+                property: None,
+            })
+        };
+        let expected = [None, None, element(a), None, element(body)];
+        for (i, node) in ast.body().iter().enumerate() {
+            let result = runtime.eval(&Some(node.clone()), runtime.env.clone());
+            assert_eq!(expected[i], result);
+        }
+    }
 }
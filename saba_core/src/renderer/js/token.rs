@@ -1,3 +1,7 @@
+use crate::renderer::diagnostics::LineIndex;
+use crate::renderer::diagnostics::ParseDiagnostics;
+use crate::renderer::diagnostics::ParseErrorSource;
+use crate::renderer::diagnostics::SourcePosition;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 
@@ -6,16 +10,63 @@ static RESERVED_WORDS: [&str; 3] = ["var", "function", "return"];
 pub struct JsLexer {
     pos: usize,
     input: Vec<char>,
+    /// peek()で先読みしたトークン
+    peeked: Option<Option<Token>>,
+    /// 直前に作成したトークンの開始位置
+    token_start: usize,
+    line_index: LineIndex,
+    diagnostics: ParseDiagnostics,
 }
 
 impl JsLexer {
     pub fn new(js: String) -> Self {
+        let input: Vec<char> = js.chars().collect();
+        let line_index = LineIndex::new(&input);
         Self {
             pos: 0,
-            input: js.chars().collect(),
+            input,
+            peeked: None,
+            token_start: 0,
+            line_index,
+            diagnostics: ParseDiagnostics::new(),
         }
     }
 
+    /// 次のトークンを消費せずに返す
+    pub fn peek(&mut self) -> Option<&Token> {
+        if self.peeked.is_none() {
+            let token = self.consume_token();
+            self.peeked = Some(token);
+        }
+        match self.peeked {
+            Some(Some(ref t)) => Some(t),
+            _ => None,
+        }
+    }
+
+    /// 直前に作成したトークンの開始位置を返す
+    pub fn source_position(&self) -> SourcePosition {
+        self.line_index.position(self.token_start)
+    }
+
+    /// 直前に作成したトークンの位置でパースエラーを記録する
+    pub fn parse_error(&mut self, name: &str) {
+        let position = self.source_position();
+        self.diagnostics
+            .report(ParseErrorSource::Js, name, position);
+    }
+
+    pub fn diagnostics(&self) -> ParseDiagnostics {
+        self.diagnostics.clone()
+    }
+
+    /// 現在読んでいる文字の位置でパースエラーを記録する
+    fn tokenize_error(&mut self, name: &str) {
+        let position = self.line_index.position(self.pos);
+        self.diagnostics
+            .report(ParseErrorSource::Js, name, position);
+    }
+
     fn consume_number(&mut self) -> u64 {
         let mut num = 0;
         loop {
@@ -39,17 +90,23 @@ impl JsLexer {
 
     fn contains(&self, keyword: &str) -> bool {
         for i in 0..keyword.len() {
-            if keyword
-                .chars()
-                .nth(i)
-                .expect("failed to access to i-th char")
-                != self.input[self.pos + i]
+            if Some(
+                &keyword
+                    .chars()
+                    .nth(i)
+                    .expect("failed to access to i-th char"),
+            ) != self.input.get(self.pos + i)
             {
                 return false;
             }
         }
 
-        true
+        // "variable"のような識別子を予約語と区別するため、予約語の直後が
+        // 識別子の一部でないことを確認する
+        match self.input.get(self.pos + keyword.len()) {
+            Some(c) => !(c.is_ascii_alphanumeric() || *c == '_' || *c == '$'),
+            None => true,
+        }
     }
 
     fn check_reserved_word(&self) -> Option<String> {
@@ -70,7 +127,10 @@ impl JsLexer {
                 return result;
             }
 
-            if self.input[self.pos].is_ascii_alphanumeric() || self.input[self.pos] == '$' {
+            if self.input[self.pos].is_ascii_alphanumeric()
+                || self.input[self.pos] == '_'
+                || self.input[self.pos] == '$'
+            {
                 result.push(self.input[self.pos]);
                 self.pos += 1;
             } else {
@@ -85,6 +145,7 @@ impl JsLexer {
 
         loop {
             if self.pos >= self.input.len() {
+                self.tokenize_error("unterminated-string-literal");
                 return result;
             }
 
@@ -97,23 +158,30 @@ impl JsLexer {
             self.pos += 1;
         }
     }
-}
-
-impl Iterator for JsLexer {
-    type Item = Token;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.pos >= self.input.len() {
-            return None;
-        }
-
-        // ホワイトスペースまたは改行文字が続く限り、次の位置に進める
-        while self.input[self.pos] == ' ' || self.input[self.pos] == '\n' {
-            self.pos += 1;
 
+    fn consume_token(&mut self) -> Option<Token> {
+        loop {
             if self.pos >= self.input.len() {
+                self.token_start = self.input.len();
                 return None;
             }
+
+            // ホワイトスペースまたは改行文字が続く限り、次の位置に進める
+            if matches!(self.input[self.pos], ' ' | '\n' | '\t' | '\r') {
+                self.pos += 1;
+                continue;
+            }
+
+            self.token_start = self.pos;
+            match self.input[self.pos] {
+                '+' | '-' | ';' | '=' | '(' | ')' | '{' | '}' | ',' | '.' => break,
+                '0'..='9' | 'a'..='z' | 'A'..='Z' | '_' | '$' | '"' => break,
+                _ => {
+                    // サポートしていない文字は読み飛ばす
+                    self.tokenize_error("invalid-or-unexpected-token");
+                    self.pos += 1;
+                }
+            }
         }
 
         // 予約語が現れたら、Keywordトークンを返す
@@ -134,13 +202,24 @@ impl Iterator for JsLexer {
             '0'..='9' => Token::Number(self.consume_number()),
             'a'..='z' | 'A'..='Z' | '_' | '$' => Token::Identifier(self.consume_identifier()),
             '"' => Token::StringLiteral(self.consume_string()),
-            _ => unimplemented!("char {:?} is not supported yet", c),
+            _ => unreachable!("char {:?} should be skipped", c),
         };
 
         Some(token)
     }
 }
 
+impl Iterator for JsLexer {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.peeked.take() {
+            Some(token) => token,
+            None => self.consume_token(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
     /// https://262.ecma-international.org/#sec-punctuators
@@ -277,4 +356,52 @@ mod tests {
         }
         assert!(lexer.peek().is_none());
     }
+
+    #[test]
+    fn test_keyword_boundary() {
+        let input = "variable = 1".to_string();
+        let mut lexer = JsLexer::new(input);
+        let expected = [
+            Token::Identifier("variable".to_string()),
+            Token::Punctuator('='),
+            Token::Number(1),
+        ]
+        .to_vec();
+        for e in expected {
+            assert_eq!(Some(e), lexer.next());
+        }
+        assert!(lexer.peek().is_none());
+    }
+
+    #[test]
+    fn test_lexer_errors() {
+        let input = "var a = 1 * 2;\nvar b = \"str".to_string();
+        let mut lexer = JsLexer::new(input);
+        let expected = [
+            Token::Keyword("var".to_string()),
+            Token::Identifier("a".to_string()),
+            Token::Punctuator('='),
+            Token::Number(1),
+            Token::Number(2),
+            Token::Punctuator(';'),
+            Token::Keyword("var".to_string()),
+            Token::Identifier("b".to_string()),
+            Token::Punctuator('='),
+            Token::StringLiteral("str".to_string()),
+        ]
+        .to_vec();
+        for e in expected {
+            assert_eq!(Some(e), lexer.next());
+        }
+        assert!(lexer.next().is_none());
+
+        let errors = lexer.diagnostics().errors();
+        assert_eq!(2, errors.len());
+        assert_eq!("invalid-or-unexpected-token", errors[0].name());
+        assert_eq!(1, errors[0].position().line());
+        assert_eq!(11, errors[0].position().column());
+        assert_eq!("unterminated-string-literal", errors[1].name());
+        assert_eq!(2, errors[1].position().line());
+        assert_eq!(13, errors[1].position().column());
+    }
 }
@@ -1,11 +1,9 @@
-use core::cell::RefCell;
-
 use alloc::format;
-use alloc::rc::Rc;
 use alloc::string::{String, ToString};
+use core::str::FromStr;
 
 use crate::error::Error;
-use crate::renderer::dom::node::{ElementKind, Node, NodeKind};
+use crate::renderer::dom::node::NodeKind;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ComputedStyle {
@@ -14,6 +12,7 @@ pub struct ComputedStyle {
     display: Option<DisplayType>,
     font_size: Option<FontSize>,
     text_decoration: Option<TextDecoration>,
+    white_space: Option<WhiteSpace>,
     height: Option<f64>,
     width: Option<f64>,
 }
@@ -26,6 +25,7 @@ impl ComputedStyle {
             display: None,
             font_size: None,
             text_decoration: None,
+            white_space: None,
             height: None,
             width: None,
         }
@@ -60,16 +60,33 @@ impl ComputedStyle {
             .expect("failed to access CSS property: display")
     }
 
+    pub fn set_font_size(&mut self, font_size: FontSize) {
+        self.font_size = Some(font_size);
+    }
+
     pub fn font_size(&self) -> FontSize {
         self.font_size
             .expect("failed to access CSS property: font_size")
     }
 
+    pub fn set_text_decoration(&mut self, text_decoration: TextDecoration) {
+        self.text_decoration = Some(text_decoration);
+    }
+
     pub fn text_decoration(&self) -> TextDecoration {
         self.text_decoration
             .expect("failed to access CSS property: text_decoration")
     }
 
+    pub fn set_white_space(&mut self, white_space: WhiteSpace) {
+        self.white_space = Some(white_space);
+    }
+
+    pub fn white_space(&self) -> WhiteSpace {
+        self.white_space
+            .expect("failed to access CSS property: white_space")
+    }
+
     pub fn set_height(&mut self, height: f64) {
         self.height = Some(height);
     }
@@ -86,7 +103,7 @@ impl ComputedStyle {
         self.width.expect("failed to access CSS property: width")
     }
 
-    pub fn defaulting(&mut self, node: &Rc<RefCell<Node>>, parent_style: Option<ComputedStyle>) {
+    pub fn defaulting(&mut self, node: &NodeKind, parent_style: Option<ComputedStyle>) {
         // もし親ノードが存在し、親のCSSの値が初期値とは異なる場合、値を継承する
         if let Some(parent_style) = parent_style {
             if self.background_color.is_none() && parent_style.background_color() != Color::white()
@@ -104,9 +121,13 @@ impl ComputedStyle {
             {
                 self.text_decoration = Some(parent_style.text_decoration());
             }
+            if self.white_space.is_none() && parent_style.white_space() != WhiteSpace::Normal {
+                self.white_space = Some(parent_style.white_space());
+            }
         }
 
-        // 各プロパティに対して、初期値を設定する
+        // 各プロパティに対して、初期値を設定する。要素ごとの既定の見た目は
+        // ユーザーエージェントのスタイルシートで指定する
         if self.background_color.is_none() {
             self.background_color = Some(Color::white());
         }
@@ -114,13 +135,16 @@ impl ComputedStyle {
             self.color = Some(Color::black());
         }
         if self.display.is_none() {
-            self.display = Some(DisplayType::default(node));
+            self.display = Some(DisplayType::initial(node));
         }
         if self.font_size.is_none() {
-            self.font_size = Some(FontSize::default(node));
+            self.font_size = Some(FontSize::Medium);
         }
         if self.text_decoration.is_none() {
-            self.text_decoration = Some(TextDecoration::default(node));
+            self.text_decoration = Some(TextDecoration::None);
+        }
+        if self.white_space.is_none() {
+            self.white_space = Some(WhiteSpace::Normal);
         }
         if self.height.is_none() {
             self.height = Some(0.0);
@@ -239,15 +263,18 @@ pub enum FontSize {
     XXLarge,
 }
 
-impl FontSize {
-    fn default(node: &Rc<RefCell<Node>>) -> Self {
-        match &node.borrow().kind() {
-            NodeKind::Element(element) => match element.kind() {
-                ElementKind::H1 => FontSize::XXLarge,
-                ElementKind::H2 => FontSize::XLarge,
-                _ => FontSize::Medium,
-            },
-            _ => FontSize::Medium,
+impl FromStr for FontSize {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "medium" => Ok(Self::Medium),
+            "x-large" => Ok(Self::XLarge),
+            "xx-large" => Ok(Self::XXLarge),
+            _ => Err(Error::UnexpectedInput(format!(
+                "font-size {:?} is not supported yet",
+                s
+            ))),
         }
     }
 }
@@ -260,21 +287,20 @@ pub enum DisplayType {
 }
 
 impl DisplayType {
-    fn default(node: &Rc<RefCell<Node>>) -> Self {
-        match &node.borrow().kind() {
+    /// displayプロパティの初期値はinlineだが、文書のルートはブロックとして扱う
+    /// https://drafts.csswg.org/css-display/#the-display-properties
+    fn initial(node: &NodeKind) -> Self {
+        match node {
             NodeKind::Document => DisplayType::Block,
-            NodeKind::Element(e) => {
-                if e.is_block_element() {
-                    DisplayType::Block
-                } else {
-                    DisplayType::Inline
-                }
-            }
-            NodeKind::Text(_) => DisplayType::Inline,
+            _ => DisplayType::Inline,
         }
     }
+}
+
+impl FromStr for DisplayType {
+    type Err = Error;
 
-    pub fn from_str(s: &str) -> Result<Self, Error> {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "block" => Ok(Self::Block),
             "inline" => Ok(Self::Inline),
@@ -293,14 +319,71 @@ pub enum TextDecoration {
     Underline,
 }
 
-impl TextDecoration {
-    fn default(node: &Rc<RefCell<Node>>) -> Self {
-        match &node.borrow().kind() {
-            NodeKind::Element(element) => match element.kind() {
-                ElementKind::A => TextDecoration::Underline,
-                _ => TextDecoration::None,
-            },
-            _ => TextDecoration::None,
+impl FromStr for TextDecoration {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "underline" => Ok(Self::Underline),
+            _ => Err(Error::UnexpectedInput(format!(
+                "text-decoration {:?} is not supported yet",
+                s
+            ))),
         }
     }
 }
+
+/// https://drafts.csswg.org/css-text/#white-space-property
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WhiteSpace {
+    Normal,
+    Pre,
+    PreWrap,
+    NoWrap,
+    PreLine,
+}
+
+impl FromStr for WhiteSpace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(Self::Normal),
+            "pre" => Ok(Self::Pre),
+            "pre-wrap" => Ok(Self::PreWrap),
+            "nowrap" => Ok(Self::NoWrap),
+            "pre-line" => Ok(Self::PreLine),
+            _ => Err(Error::UnexpectedInput(format!(
+                "white-space {:?} is not supported yet",
+                s
+            ))),
+        }
+    }
+}
+
+impl WhiteSpace {
+    /// 連続する空白文字をまとめるかどうか
+    pub fn collapses_spaces(&self) -> bool {
+        matches!(
+            self,
+            WhiteSpace::Normal | WhiteSpace::NoWrap | WhiteSpace::PreLine
+        )
+    }
+
+    /// 改行文字をそのまま改行として扱うかどうか
+    pub fn preserves_newlines(&self) -> bool {
+        matches!(
+            self,
+            WhiteSpace::Pre | WhiteSpace::PreWrap | WhiteSpace::PreLine
+        )
+    }
+
+    /// 行の幅に合わせてテキストを折り返すかどうか
+    pub fn wraps(&self) -> bool {
+        matches!(
+            self,
+            WhiteSpace::Normal | WhiteSpace::PreWrap | WhiteSpace::PreLine
+        )
+    }
+}
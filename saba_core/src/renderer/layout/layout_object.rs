@@ -3,26 +3,32 @@ use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefCell;
+use core::str::FromStr;
 
 use crate::alloc::string::ToString;
 use crate::constants::CHAR_WIDTH;
 use crate::constants::CONTENT_AREA_WIDTH;
 use crate::constants::{CHAR_HEIGHT_WITH_PADDING, WINDOW_PADDING, WINDOW_WIDTH};
 use crate::display_item::DisplayItem;
+use crate::renderer::css::cascade::cascade;
 use crate::renderer::css::cssom::ComponentValue;
 use crate::renderer::css::cssom::Declaration;
-use crate::renderer::css::cssom::Selector;
 use crate::renderer::css::cssom::StyleSheet;
-use crate::renderer::dom::node::{Node, NodeKind};
+use crate::renderer::dom::node::ElementKind;
+use crate::renderer::dom::node::{Document, NodeId, NodeKind};
 use crate::renderer::layout::computed_style::Color;
 use crate::renderer::layout::computed_style::ComputedStyle;
 use crate::renderer::layout::computed_style::DisplayType;
 use crate::renderer::layout::computed_style::FontSize;
+use crate::renderer::layout::computed_style::TextDecoration;
+use crate::renderer::layout::computed_style::WhiteSpace;
 
 #[derive(Debug, Clone)]
 pub struct LayoutObject {
     kind: LayoutObjectKind,
-    node: Rc<RefCell<Node>>,
+    /// `node`を持つドキュメント
+    document: Rc<RefCell<Document>>,
+    node: NodeId,
     first_child: Option<Rc<RefCell<LayoutObject>>>,
     next_sibling: Option<Rc<RefCell<LayoutObject>>>,
     parent: Weak<RefCell<LayoutObject>>,
@@ -32,7 +38,11 @@ pub struct LayoutObject {
 }
 
 impl LayoutObject {
-    pub fn new(node: Rc<RefCell<Node>>, parent_obj: &Option<Rc<RefCell<LayoutObject>>>) -> Self {
+    pub fn new(
+        document: Rc<RefCell<Document>>,
+        node: NodeId,
+        parent_obj: &Option<Rc<RefCell<LayoutObject>>>,
+    ) -> Self {
         let parent = match parent_obj {
             Some(p) => Rc::downgrade(p),
             None => Weak::new(),
@@ -40,7 +50,8 @@ impl LayoutObject {
 
         Self {
             kind: LayoutObjectKind::Block,
-            node: node.clone(),
+            document,
+            node,
             first_child: None,
             next_sibling: None,
             parent,
@@ -54,8 +65,12 @@ impl LayoutObject {
         self.kind
     }
 
+    pub fn node(&self) -> NodeId {
+        self.node
+    }
+
     pub fn node_kind(&self) -> NodeKind {
-        self.node.borrow().kind().clone()
+        self.document.borrow().kind(self.node).clone()
     }
 
     pub fn set_first_child(&mut self, first_child: Option<Rc<RefCell<LayoutObject>>>) {
@@ -90,37 +105,6 @@ impl LayoutObject {
         self.size
     }
 
-    pub fn is_node_selected(&self, selector: &Selector) -> bool {
-        match &self.node_kind() {
-            NodeKind::Element(e) => match selector {
-                Selector::TypeSelector(type_name) => {
-                    if e.kind().to_string() == *type_name {
-                        return true;
-                    }
-                    false
-                }
-                Selector::ClassSelector(class_name) => {
-                    for attr in &e.attributes() {
-                        if attr.name() == "class" && attr.value() == *class_name {
-                            return true;
-                        }
-                    }
-                    false
-                }
-                Selector::IdSelector(id_name) => {
-                    for attr in &e.attributes() {
-                        if attr.name() == "id" && attr.value() == *id_name {
-                            return true;
-                        }
-                    }
-                    false
-                }
-                Selector::UnknownSelector => false,
-            },
-            _ => false,
-        }
-    }
-
     pub fn cascading_style(&mut self, declarations: Vec<Declaration>) {
         for declaration in declarations {
             match declaration.property.as_str() {
@@ -169,21 +153,41 @@ impl LayoutObject {
                         self.style.set_display(display_type)
                     }
                 }
+                "font-size" => {
+                    if let ComponentValue::Ident(value) = declaration.value {
+                        if let Ok(font_size) = FontSize::from_str(&value) {
+                            self.style.set_font_size(font_size);
+                        }
+                    }
+                }
+                "text-decoration" => {
+                    if let ComponentValue::Ident(value) = declaration.value {
+                        if let Ok(text_decoration) = TextDecoration::from_str(&value) {
+                            self.style.set_text_decoration(text_decoration);
+                        }
+                    }
+                }
+                "white-space" => {
+                    if let ComponentValue::Ident(value) = declaration.value {
+                        if let Ok(white_space) = WhiteSpace::from_str(&value) {
+                            self.style.set_white_space(white_space);
+                        }
+                    }
+                }
                 _ => {}
             }
         }
     }
 
-    pub fn defaulting_style(
-        &mut self,
-        node: &Rc<RefCell<Node>>,
-        parent_style: Option<ComputedStyle>,
-    ) {
-        self.style.defaulting(node, parent_style)
+    pub fn defaulting_style(&mut self, parent_style: Option<ComputedStyle>) {
+        let document = self.document.borrow();
+        self.style
+            .defaulting(document.kind(self.node), parent_style)
     }
 
     pub fn update_kind(&mut self) {
-        match self.node_kind() {
+        let document = self.document.borrow();
+        match document.kind(self.node) {
             NodeKind::Document => panic!("should not create a layout object for a Document node"),
             NodeKind::Element(_) => {
                 let display = self.style.display();
@@ -228,6 +232,12 @@ impl LayoutObject {
                 }
                 size.set_height(height);
             }
+            LayoutObjectKind::Inline if self.element_kind() == Some(ElementKind::Img) => {
+                size = self.compute_img_size();
+            }
+            LayoutObjectKind::Inline if self.element_kind() == Some(ElementKind::Iframe) => {
+                size = self.compute_iframe_size();
+            }
             LayoutObjectKind::Inline => {
                 // すべての子ノードの高さと横幅を足し合わせた結果が現在のノードの高さと横幅
                 // とになる
@@ -250,28 +260,26 @@ impl LayoutObject {
                 size.set_height(height);
             }
             LayoutObjectKind::Text => {
-                if let NodeKind::Text(t) = self.node_kind() {
+                if let NodeKind::Text(t) = self.document.borrow().kind(self.node) {
                     let ratio = match self.style.font_size() {
                         FontSize::Medium => 1,
                         FontSize::XLarge => 2,
                         FontSize::XXLarge => 3,
                     };
 
-                    let width = CHAR_WIDTH * ratio * t.len() as i64;
-                    if width > CONTENT_AREA_WIDTH {
-                        // テキストが複数行のとき
+                    let white_space = self.style.white_space();
+                    let lines = layout_text(t, white_space, CHAR_WIDTH * ratio);
+
+                    // 最も長い行の幅がテキストの横幅になる。折り返す場合は、
+                    // コンテンツエリアの幅を超えない
+                    let max_len = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+                    let width = CHAR_WIDTH * ratio * max_len as i64;
+                    if white_space.wraps() && width > CONTENT_AREA_WIDTH {
                         size.set_width(CONTENT_AREA_WIDTH);
-                        let line_num = if width.wrapping_rem(CONTENT_AREA_WIDTH) == 0 {
-                            width.wrapping_div(CONTENT_AREA_WIDTH)
-                        } else {
-                            width.wrapping_div(CONTENT_AREA_WIDTH) + 1
-                        };
-                        size.set_height(CHAR_HEIGHT_WITH_PADDING * ratio * line_num);
                     } else {
-                        // テキストが1行に収まるとき
                         size.set_width(width);
-                        size.set_height(CHAR_HEIGHT_WITH_PADDING * ratio);
                     }
+                    size.set_height(CHAR_HEIGHT_WITH_PADDING * ratio * lines.len() as i64);
                 }
             }
         }
@@ -279,6 +287,115 @@ impl LayoutObject {
         self.size = size;
     }
 
+    /// テキストの1文字の幅
+    pub fn char_width(&self) -> i64 {
+        CHAR_WIDTH * self.font_ratio()
+    }
+
+    /// テキストの1行の高さ
+    pub fn line_height(&self) -> i64 {
+        CHAR_HEIGHT_WITH_PADDING * self.font_ratio()
+    }
+
+    fn font_ratio(&self) -> i64 {
+        match self.style.font_size() {
+            FontSize::Medium => 1,
+            FontSize::XLarge => 2,
+            FontSize::XXLarge => 3,
+        }
+    }
+
+    /// テキストの場合に、描画する行ごとに、行の左上の位置と行のテキストを返す。
+    /// テキストでない場合は空になる
+    pub fn text_lines(&self) -> Vec<(LayoutPoint, TextLine)> {
+        let document = self.document.borrow();
+        let text = match document.kind(self.node) {
+            NodeKind::Text(t) => t,
+            _ => return vec![],
+        };
+        layout_text_with_offsets(text, self.style.white_space(), self.char_width())
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let point = LayoutPoint::new(
+                    self.point().x(),
+                    self.point().y() + CHAR_HEIGHT_WITH_PADDING * i as i64,
+                );
+                (point, line)
+            })
+            .collect()
+    }
+
+    fn element_kind(&self) -> Option<ElementKind> {
+        self.document.borrow().element_kind(self.node)
+    }
+
+    /// <img>要素の大きさを、width/height属性と画像の本来の大きさから決める
+    /// https://html.spec.whatwg.org/multipage/embedded-content-other.html#dimension-attributes
+    fn compute_img_size(&self) -> LayoutSize {
+        let document = self.document.borrow();
+        let element = match document.element(self.node) {
+            Some(e) => e,
+            None => return LayoutSize::new(0, 0),
+        };
+
+        let width = element.get_attribute("width").and_then(parse_dimension);
+        let height = element.get_attribute("height").and_then(parse_dimension);
+
+        let image = match element.image() {
+            Some(image) => image,
+            None => {
+                if let (Some(w), Some(h)) = (width, height) {
+                    return LayoutSize::new(w, h);
+                }
+                // 画像がない場合は、alt属性のテキストの大きさになる
+                let alt = element.get_attribute("alt").unwrap_or_default();
+                if alt.is_empty() {
+                    return LayoutSize::new(0, 0);
+                }
+                return LayoutSize::new(
+                    CHAR_WIDTH * alt.chars().count() as i64,
+                    CHAR_HEIGHT_WITH_PADDING,
+                );
+            }
+        };
+
+        let intrinsic_width = image.width() as i64;
+        let intrinsic_height = image.height() as i64;
+        match (width, height) {
+            (Some(w), Some(h)) => LayoutSize::new(w, h),
+            // 片方だけ指定されている場合は、縦横比を保つ
+            (Some(w), None) if intrinsic_width > 0 => {
+                LayoutSize::new(w, w * intrinsic_height / intrinsic_width)
+            }
+            (None, Some(h)) if intrinsic_height > 0 => {
+                LayoutSize::new(h * intrinsic_width / intrinsic_height, h)
+            }
+            _ => LayoutSize::new(intrinsic_width, intrinsic_height),
+        }
+    }
+
+    /// <iframe>要素の大きさを、width/height属性から決める。指定されていない場合は
+    /// 300x150になる
+    /// https://html.spec.whatwg.org/multipage/rendering.html#attributes-for-embedded-content-and-images
+    fn compute_iframe_size(&self) -> LayoutSize {
+        let document = self.document.borrow();
+        let element = match document.element(self.node) {
+            Some(e) => e,
+            None => return LayoutSize::new(0, 0),
+        };
+
+        let width = element
+            .get_attribute("width")
+            .and_then(parse_dimension)
+            .unwrap_or(300);
+        let height = element
+            .get_attribute("height")
+            .and_then(parse_dimension)
+            .unwrap_or(150);
+        LayoutSize::new(width, height)
+    }
+
     pub fn compute_position(
         &mut self,
         parent_point: LayoutPoint,
@@ -324,7 +441,7 @@ impl LayoutObject {
 
         match self.kind {
             LayoutObjectKind::Block => {
-                if let NodeKind::Element(_e) = self.node_kind() {
+                if let NodeKind::Element(_e) = self.document.borrow().kind(self.node) {
                     return vec![DisplayItem::Rect {
                         style: self.style(),
                         layout_point: self.point(),
@@ -333,66 +450,220 @@ impl LayoutObject {
                 }
             }
             LayoutObjectKind::Inline => {
-                // 本書のブラウザでは、描画するインライン要素はない。
-                // <img>タグなどをサポートした場合はこのアームの中で処理をする
-            }
-            LayoutObjectKind::Text => {
-                if let NodeKind::Text(t) = self.node_kind() {
-                    let mut v = vec![];
+                // 描画するインライン要素は<img>だけ
+                if let NodeKind::Element(e) = self.document.borrow().kind(self.node) {
+                    if e.kind() != ElementKind::Img {
+                        return vec![];
+                    }
 
-                    let ratio = match self.style.font_size() {
-                        FontSize::Medium => 1,
-                        FontSize::XLarge => 2,
-                        FontSize::XXLarge => 3,
-                    };
-                    let plain_text = t
-                        .replace("\n", " ")
-                        .split(' ')
-                        .filter(|s| !s.is_empty())
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    let lines = split_text(plain_text, CHAR_WIDTH * ratio);
-                    let mut i = 0;
-                    for line in lines {
-                        let item = DisplayItem::Text {
-                            text: line,
+                    if let Some(image) = e.image() {
+                        return vec![DisplayItem::Image {
+                            image,
                             style: self.style(),
-                            layout_point: LayoutPoint::new(
-                                self.point().x(),
-                                self.point().y() + CHAR_HEIGHT_WITH_PADDING * i,
-                            ),
-                        };
-                        v.push(item);
-                        i += 1;
+                            layout_point: self.point(),
+                            layout_size: self.size(),
+                        }];
                     }
 
-                    return v;
+                    // 画像がない場合は、代わりにalt属性のテキストを描画する
+                    match e.get_attribute("alt") {
+                        Some(alt) if !alt.is_empty() => {
+                            return vec![DisplayItem::Text {
+                                text: alt.to_string(),
+                                style: self.style(),
+                                layout_point: self.point(),
+                            }];
+                        }
+                        _ => {}
+                    }
                 }
             }
+            LayoutObjectKind::Text => {
+                // 選択されている文字の背景を塗ってから、テキストを描画する
+                let selected = {
+                    let document = self.document.borrow();
+                    document
+                        .selection()
+                        .and_then(|s| s.range(&document).text_offsets(&document, self.node))
+                };
+                let char_width = self.char_width();
+
+                let mut v = vec![];
+                for (point, line) in self.text_lines() {
+                    if let Some((start, end)) = selected {
+                        let first = line.iter().position(|(_, i)| start <= *i && *i < end);
+                        let last = line.iter().rposition(|(_, i)| start <= *i && *i < end);
+                        if let (Some(first), Some(last)) = (first, last) {
+                            v.push(DisplayItem::Highlight {
+                                layout_point: LayoutPoint::new(
+                                    point.x() + char_width * first as i64,
+                                    point.y(),
+                                ),
+                                layout_size: LayoutSize::new(
+                                    char_width * (last - first + 1) as i64,
+                                    self.line_height(),
+                                ),
+                            });
+                        }
+                    }
+                    v.push(DisplayItem::Text {
+                        text: line.iter().map(|(c, _)| *c).collect(),
+                        style: self.style(),
+                        layout_point: point,
+                    });
+                }
+                return v;
+            }
         }
 
         vec![]
     }
 }
 
-fn find_index_for_line_break(line: String, max_index: usize) -> usize {
+/// 負でない整数として属性の値をパースする
+/// https://html.spec.whatwg.org/multipage/common-microsyntaxes.html#rules-for-parsing-non-negative-integers
+fn parse_dimension(value: &str) -> Option<i64> {
+    let digits: String = value
+        .trim_start_matches(|c: char| c.is_ascii_whitespace())
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse::<i64>().ok()
+}
+
+/// 描画する1行のテキスト。各文字と、元のテキストでのその文字の位置の組を持つ
+pub type TextLine = Vec<(char, usize)>;
+
+/// white-spaceプロパティに従って空白文字を処理し、描画する行のリストを返す
+/// https://drafts.csswg.org/css-text/#white-space-processing
+fn layout_text(text: &str, white_space: WhiteSpace, char_width: i64) -> Vec<String> {
+    layout_text_with_offsets(text, white_space, char_width)
+        .iter()
+        .map(|line| line.iter().map(|(c, _)| *c).collect())
+        .collect()
+}
+
+/// `layout_text`と同じように行に分け、各文字の元のテキストでの位置も返す。
+/// マウスで指した位置の文字を探すときに使う
+fn layout_text_with_offsets(text: &str, white_space: WhiteSpace, char_width: i64) -> Vec<TextLine> {
+    let chars: TextLine = text
+        .chars()
+        .enumerate()
+        .map(|(i, c)| (c, i))
+        .filter(|(c, _)| *c != '\r')
+        .collect();
+
+    // 改行を保持しない場合、改行文字は空白文字として扱う
+    let segments: Vec<TextLine> = if white_space.preserves_newlines() {
+        chars
+            .split(|(c, _)| *c == '\n')
+            .map(|s| s.to_vec())
+            .collect()
+    } else {
+        vec![chars
+            .iter()
+            .map(|&(c, i)| if c == '\n' { (' ', i) } else { (c, i) })
+            .collect()]
+    };
+
+    let mut lines = Vec::new();
+    for segment in segments {
+        let segment = if white_space.collapses_spaces() {
+            collapse_spaces(&segment)
+        } else {
+            expand_tabs(&segment)
+        };
+
+        if white_space.wraps() {
+            lines.extend(split_text(segment, char_width));
+        } else {
+            lines.push(segment);
+        }
+    }
+
+    lines
+}
+
+/// 連続する空白文字とタブ文字を1つの空白文字にまとめ、先頭と末尾の空白文字を
+/// 取り除く。まとめた空白文字の位置は、最初の空白文字の位置になる
+fn collapse_spaces(text: &[(char, usize)]) -> TextLine {
+    let mut result = Vec::new();
+    let mut space = None;
+    for &(c, i) in text {
+        if c == ' ' || c == '\t' {
+            space.get_or_insert(i);
+            continue;
+        }
+        if let Some(s) = space.take() {
+            if !result.is_empty() {
+                result.push((' ', s));
+            }
+        }
+        result.push((c, i));
+    }
+    result
+}
+
+/// タブ文字を、8文字ごとのタブストップまでの空白文字に置き換える
+/// https://drafts.csswg.org/css-text/#tab-size-property
+fn expand_tabs(text: &[(char, usize)]) -> TextLine {
+    let mut result = Vec::new();
+    let mut column = 0;
+    for &(c, i) in text {
+        if c == '\t' {
+            let n = 8 - column % 8;
+            for _ in 0..n {
+                result.push((' ', i));
+            }
+            column += n;
+        } else {
+            result.push((c, i));
+            column += 1;
+        }
+    }
+    result
+}
+
+/// 空白文字をまとめる場合、空白文字だけのテキストは描画しない
+fn is_collapsible_white_space(node: &NodeKind, white_space: WhiteSpace) -> bool {
+    match node {
+        NodeKind::Text(t) => {
+            white_space.collapses_spaces() && t.chars().all(|c| c.is_ascii_whitespace())
+        }
+        _ => false,
+    }
+}
+
+fn find_index_for_line_break(line: &[(char, usize)], max_index: usize) -> usize {
     for i in (0..max_index).rev() {
-        if line.chars().collect::<Vec<char>>()[i] == ' ' {
+        if line[i].0 == ' ' {
             return i;
         }
     }
     max_index
 }
 
-fn split_text(line: String, char_width: i64) -> Vec<String> {
-    let mut result: Vec<String> = vec![];
+fn split_text(line: TextLine, char_width: i64) -> Vec<TextLine> {
+    let mut result: Vec<TextLine> = vec![];
+    // 日本語などのマルチバイト文字を扱うため、バイト数ではなく文字数で数える
     if line.len() as i64 * char_width > (WINDOW_WIDTH + WINDOW_PADDING) {
-        let s = line.split_at(find_index_for_line_break(
-            line.clone(),
+        let index = find_index_for_line_break(
+            &line,
             ((WINDOW_WIDTH + WINDOW_PADDING) / char_width) as usize,
-        ));
-        result.push(s.0.to_string());
-        result.extend(split_text(s.1.trim().to_string(), char_width))
+        );
+        let first = line[..index].to_vec();
+        // 次の行の先頭と末尾の空白文字は取り除く
+        let rest = &line[index..];
+        let begin = rest
+            .iter()
+            .position(|(c, _)| !c.is_whitespace())
+            .unwrap_or(rest.len());
+        let end = rest
+            .iter()
+            .rposition(|(c, _)| !c.is_whitespace())
+            .map_or(begin, |i| i + 1);
+        result.push(first);
+        result.extend(split_text(rest[begin..end].to_vec(), char_width))
     } else {
         result.push(line)
     }
@@ -405,6 +676,25 @@ impl PartialEq for LayoutObject {
     }
 }
 
+/// 子ノードと兄弟ノードは`Rc`でつながっているので、そのまま解放すると兄弟ノードの
+/// 数だけ再帰してスタックが溢れる。つながりを外しながら順に解放する
+impl Drop for LayoutObject {
+    fn drop(&mut self) {
+        let mut pending: Vec<Rc<RefCell<LayoutObject>>> = Vec::new();
+        pending.extend(self.first_child.take());
+        pending.extend(self.next_sibling.take());
+
+        while let Some(object) = pending.pop() {
+            // 他から参照されているノードは、最後の参照がなくなったときに解放される
+            if let Ok(cell) = Rc::try_unwrap(object) {
+                let mut object = cell.into_inner();
+                pending.extend(object.first_child.take());
+                pending.extend(object.next_sibling.take());
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LayoutObjectKind {
     Block,
@@ -469,22 +759,23 @@ impl LayoutSize {
 }
 
 pub fn create_layout_object(
-    node: &Option<Rc<RefCell<Node>>>,
+    document: &Rc<RefCell<Document>>,
+    node: Option<NodeId>,
     parent_obj: &Option<Rc<RefCell<LayoutObject>>>,
-    cssom: &StyleSheet,
+    style_sheets: &[&StyleSheet],
 ) -> Option<Rc<RefCell<LayoutObject>>> {
     if let Some(n) = node {
         // LayoutObjectを作成する
-        let layout_object = Rc::new(RefCell::new(LayoutObject::new(n.clone(), parent_obj)));
-
-        // CSSのルールをセレクタで選択されたノードに適用する
-        for rule in &cssom.rules {
-            if layout_object.borrow().is_node_selected(&rule.selector) {
-                layout_object
-                    .borrow_mut()
-                    .cascading_style(rule.declarations.clone());
-            }
-        }
+        let layout_object = Rc::new(RefCell::new(LayoutObject::new(
+            document.clone(),
+            n,
+            parent_obj,
+        )));
+
+        // ノードに一致するルールの宣言から、プロパティごとに最も優先される値を
+        // 選んで適用する
+        let declarations = cascade(&document.borrow(), n, style_sheets);
+        layout_object.borrow_mut().cascading_style(declarations);
 
         // CSSでスタイルが指定されていない場合、デフォルトの値または親のノードから継承した値を使用する
         let parent_style = if let Some(parent) = parent_obj {
@@ -492,13 +783,21 @@ pub fn create_layout_object(
         } else {
             None
         };
-        layout_object.borrow_mut().defaulting_style(n, parent_style);
+        layout_object.borrow_mut().defaulting_style(parent_style);
 
         // displayプロパティがnoneの場合、ノードを作成しない
         if layout_object.borrow().style().display() == DisplayType::DisplayNone {
             return None;
         }
 
+        // 空白文字だけのテキストで、空白文字がまとめられる場合、ノードを作成しない
+        if is_collapsible_white_space(
+            document.borrow().kind(n),
+            layout_object.borrow().style().white_space(),
+        ) {
+            return None;
+        }
+
         // displayプロパティの最終的な値を使用してノードの種類を決定する
         layout_object.borrow_mut().update_kind();
         return Some(layout_object);
@@ -1,9 +1,12 @@
 use crate::constants::CONTENT_AREA_WIDTH;
 use crate::display_item::DisplayItem;
 use crate::renderer::css::cssom::StyleSheet;
+use crate::renderer::dom::api::children;
 use crate::renderer::dom::api::get_target_element_node;
+use crate::renderer::dom::node::Document;
 use crate::renderer::dom::node::ElementKind;
-use crate::renderer::dom::node::Node;
+use crate::renderer::dom::node::NodeId;
+use crate::renderer::dom::range::BoundaryPoint;
 use crate::renderer::layout::layout_object::create_layout_object;
 use crate::renderer::layout::layout_object::LayoutObject;
 use crate::renderer::layout::layout_object::LayoutObjectKind;
@@ -19,13 +22,18 @@ pub struct LayoutView {
 }
 
 impl LayoutView {
-    pub fn new(root: Rc<RefCell<Node>>, cssom: &StyleSheet) -> Self {
+    /// `style_sheets`は、カスケードでの出現順に並べたスタイルシート
+    pub fn new(document: Rc<RefCell<Document>>, style_sheets: &[&StyleSheet]) -> Self {
         // レイアウトツリーは描画される要素だけを持つツリーなので、<body>タグを取得
         // し、その子要素以下をレイアウトツリーのノードに変換する。
-        let body_root = get_target_element_node(Some(root), ElementKind::Body);
+        let body_root = {
+            let document = document.borrow();
+            get_target_element_node(&document, Some(document.root()), ElementKind::Body)
+        };
 
         let mut tree = Self {
-            root: build_layout_tree(&body_root, &None, cssom),
+            root: body_root
+                .and_then(|body| build_layout_tree(&document, body, &None, style_sheets)),
         };
 
         tree.update_layout();
@@ -50,7 +58,9 @@ impl LayoutView {
     }
 
     fn calculate_node_size(node: &Option<Rc<RefCell<LayoutObject>>>, parent_size: LayoutSize) {
-        if let Some(n) = node {
+        // 兄弟ノードは再帰を使わずに順にたどる
+        let mut current = node.clone();
+        while let Some(n) = current {
             // ノードがブロック要素の場合、子ノードのレイアウトを計算する前に
             // 横幅を決める
             if n.borrow().kind() == LayoutObjectKind::Block {
@@ -60,24 +70,24 @@ impl LayoutView {
             let first_child = n.borrow().first_child();
             Self::calculate_node_size(&first_child, n.borrow().size());
 
-            let next_sibling = n.borrow().next_sibling();
-            Self::calculate_node_size(&next_sibling, parent_size);
-
             // 子ノードのサイズが決まったあとにサイズを計算する
             // ブロック要素のとき、高さは子ノードの高さに依存する
             // インライン要素のとき、高さも横幅も子ノードに依存する
             n.borrow_mut().compute_size(parent_size);
+
+            current = n.borrow().next_sibling();
         }
     }
 
     fn calculate_node_position(
         node: &Option<Rc<RefCell<LayoutObject>>>,
         parent_point: LayoutPoint,
-        previous_sibling_kind: LayoutObjectKind,
-        previous_sibling_point: Option<LayoutPoint>,
-        previous_sibling_size: Option<LayoutSize>,
+        mut previous_sibling_kind: LayoutObjectKind,
+        mut previous_sibling_point: Option<LayoutPoint>,
+        mut previous_sibling_size: Option<LayoutSize>,
     ) {
-        if let Some(n) = node {
+        let mut current = node.clone();
+        while let Some(n) = current {
             n.borrow_mut().compute_position(
                 parent_point,
                 previous_sibling_kind,
@@ -95,30 +105,23 @@ impl LayoutView {
                 None,
             );
 
-            // ノード (node) の兄弟ノードの位置を計算する
-            let next_sibling = n.borrow().next_sibling();
-            Self::calculate_node_position(
-                &next_sibling,
-                parent_point,
-                n.borrow().kind(),
-                Some(n.borrow().point()),
-                Some(n.borrow().size()),
-            )
+            // ノード (node) の兄弟ノードの位置は、ノードの位置とサイズをもとに計算する
+            previous_sibling_kind = n.borrow().kind();
+            previous_sibling_point = Some(n.borrow().point());
+            previous_sibling_size = Some(n.borrow().size());
+            current = n.borrow().next_sibling();
         }
     }
 
     fn paint_node(node: &Option<Rc<RefCell<LayoutObject>>>, display_items: &mut Vec<DisplayItem>) {
-        match node {
-            Some(n) => {
-                display_items.extend(n.borrow_mut().paint());
+        let mut current = node.clone();
+        while let Some(n) = current {
+            display_items.extend(n.borrow_mut().paint());
 
-                let first_child = n.borrow().first_child();
-                Self::paint_node(&first_child, display_items);
+            let first_child = n.borrow().first_child();
+            Self::paint_node(&first_child, display_items);
 
-                let next_sibling = n.borrow().next_sibling();
-                Self::paint_node(&next_sibling, display_items);
-            }
-            None => (),
+            current = n.borrow().next_sibling();
         }
     }
 
@@ -130,138 +133,184 @@ impl LayoutView {
         display_items
     }
 
-    pub fn find_node_by_position(&self, position: (i64, i64)) -> Option<Rc<RefCell<LayoutObject>>> {
-        Self::find_node_by_position_internal(&self.root(), position)
+    /// DOMノード`node`に対応するLayoutObjectを返す
+    pub fn find_layout_object(&self, node: NodeId) -> Option<Rc<RefCell<LayoutObject>>> {
+        Self::find_layout_object_internal(&self.root(), node)
     }
 
-    fn find_node_by_position_internal(
-        node: &Option<Rc<RefCell<LayoutObject>>>,
-        position: (i64, i64),
+    fn find_layout_object_internal(
+        layout_object: &Option<Rc<RefCell<LayoutObject>>>,
+        node: NodeId,
     ) -> Option<Rc<RefCell<LayoutObject>>> {
-        match node {
-            Some(n) => {
-                let first_child = n.borrow().first_child();
-                let result1 = Self::find_node_by_position_internal(&first_child, position);
-                if result1.is_some() {
-                    return result1;
-                }
+        let mut current = layout_object.clone();
+        while let Some(o) = current {
+            if o.borrow().node() == node {
+                return Some(o);
+            }
+            let result = Self::find_layout_object_internal(&o.borrow().first_child(), node);
+            if result.is_some() {
+                return result;
+            }
+            current = o.borrow().next_sibling();
+        }
+        None
+    }
 
-                let next_sibling = n.borrow().next_sibling();
-                let result2 = Self::find_node_by_position_internal(&next_sibling, position);
-                if result2.is_some() {
-                    return result2;
+    /// `position`にある文字の位置を、テキストノードとその中での文字の位置で返す。
+    /// 文字の右半分を指している場合は次の文字の位置になる。同じ高さの行が複数ある
+    /// 場合は、横方向に最も近い行を選ぶ。`position`の高さにテキストがない場合は
+    /// Noneを返す
+    pub fn find_text_position(&self, position: (i64, i64)) -> Option<BoundaryPoint> {
+        let mut nearest: Option<(i64, BoundaryPoint)> = None;
+        for o in self.layout_objects() {
+            let o = o.borrow();
+            if o.kind() != LayoutObjectKind::Text {
+                continue;
+            }
+            let char_width = o.char_width();
+            for (point, line) in o.text_lines() {
+                if line.is_empty()
+                    || position.1 < point.y()
+                    || point.y() + o.line_height() <= position.1
+                {
+                    continue;
                 }
 
-                if n.borrow().point().x() <= position.0
-                    && position.0 <= (n.borrow().point().x() + n.borrow().size().width())
-                    && n.borrow().point().y() <= position.1
-                    && position.1 <= (n.borrow().point().y() + n.borrow().size().height())
-                {
-                    return Some(n.clone());
+                let width = char_width * line.len() as i64;
+                let distance = if position.0 < point.x() {
+                    point.x() - position.0
+                } else {
+                    (position.0 - point.x() - width).max(0)
+                };
+                if nearest.is_some_and(|(d, _)| d <= distance) {
+                    continue;
                 }
-                None
+
+                let column = ((position.0 - point.x() + char_width / 2) / char_width)
+                    .clamp(0, line.len() as i64) as usize;
+                let offset = match line.get(column) {
+                    Some((_, i)) => *i,
+                    None => line[line.len() - 1].1 + 1,
+                };
+                nearest = Some((distance, BoundaryPoint::new(o.node(), offset)));
             }
-            None => None,
         }
+        nearest.map(|(_, point)| point)
     }
-}
 
-fn build_layout_tree(
-    node: &Option<Rc<RefCell<Node>>>,
-    parent_obj: &Option<Rc<RefCell<LayoutObject>>>,
-    cssom: &StyleSheet,
-) -> Option<Rc<RefCell<LayoutObject>>> {
-    // create_layout_object関数によって、ノードとなるLayoutObjectの作成を試みる。
-    // CSSによって"display:none"が指定されていた場合、ノードは作成されない
-    let mut target_node = node.clone();
-    let mut layout_object = create_layout_object(node, parent_obj, cssom);
-    // もしノードが作成されなかった場合、DOMノードの兄弟ノードを使用してLayoutObjectの
-    // 作成を試みる。LayoutObjectが作成されるまで、兄弟ノードをたどり続ける
-    while layout_object.is_none() {
-        if let Some(n) = target_node {
-            target_node = n.borrow().next_sibling().clone();
-            layout_object = create_layout_object(&target_node, parent_obj, cssom);
-        } else {
-            // もし兄弟ノードがない場合、処理するべきDOMツリーは終了したので、今まで
-            // 作成したレイアウトツリーを返す
-            return layout_object;
-        }
+    /// すべてのLayoutObjectを、レイアウトツリーの順に返す
+    pub fn layout_objects(&self) -> Vec<Rc<RefCell<LayoutObject>>> {
+        let mut layout_objects = Vec::new();
+        Self::collect_layout_objects(&self.root, &mut layout_objects);
+        layout_objects
     }
 
-    if let Some(n) = target_node {
-        let original_first_child = n.borrow().first_child();
-        let original_next_sibling = n.borrow().next_sibling();
-        let mut first_child = build_layout_tree(&original_first_child, &layout_object, cssom);
-        let mut next_sibling = build_layout_tree(&original_next_sibling, &None, cssom);
-
-        // もし子ノードに"display:none"が指定されていた場合、LayoutObjectは作成され
-        // ないため、子ノードの兄弟ノードを使用してLayoutObjectの作成を試みる。
-        // LayoutObjectが作成されるか、たどるべき兄弟ノードがなくなるまで処理を繰り返す
-        if first_child.is_none() && original_first_child.is_some() {
-            let mut original_dom_node = original_first_child
-                .expect("first child should exist")
-                .borrow()
-                .next_sibling();
+    fn collect_layout_objects(
+        layout_object: &Option<Rc<RefCell<LayoutObject>>>,
+        layout_objects: &mut Vec<Rc<RefCell<LayoutObject>>>,
+    ) {
+        let mut current = layout_object.clone();
+        while let Some(o) = current {
+            layout_objects.push(o.clone());
+            let first_child = o.borrow().first_child();
+            Self::collect_layout_objects(&first_child, layout_objects);
+            current = o.borrow().next_sibling();
+        }
+    }
 
-            loop {
-                first_child = build_layout_tree(&original_dom_node, &layout_object, cssom);
+    pub fn find_node_by_position(&self, position: (i64, i64)) -> Option<Rc<RefCell<LayoutObject>>> {
+        Self::find_node_by_position_internal(&self.root(), position)
+    }
 
-                if first_child.is_none() && original_dom_node.is_some() {
-                    original_dom_node = original_dom_node
-                        .expect("next sibling should exist")
-                        .borrow()
-                        .next_sibling();
-                    continue;
-                }
+    fn find_node_by_position_internal(
+        node: &Option<Rc<RefCell<LayoutObject>>>,
+        position: (i64, i64),
+    ) -> Option<Rc<RefCell<LayoutObject>>> {
+        let mut siblings = Vec::new();
+        let mut current = node.clone();
+        while let Some(n) = current {
+            current = n.borrow().next_sibling();
+            siblings.push(n);
+        }
 
-                break;
+        // 子孫のノードを優先し、兄弟ノードどうしでは後ろにあるノードを優先する
+        for n in &siblings {
+            let first_child = n.borrow().first_child();
+            let result = Self::find_node_by_position_internal(&first_child, position);
+            if result.is_some() {
+                return result;
             }
         }
 
-        // もし兄弟ノードに"display:none"が指定されていた場合、LayoutObjectは作成され
-        // ないため、兄弟ノードの兄弟ノードを使用してLayoutObjectの作成を試みる。
-        // LayoutObjectが作成されるか、たどるべき兄弟ノードがなくなるまで処理を繰り返す
-        if next_sibling.is_none() && n.borrow().next_sibling().is_some() {
-            let mut original_dom_node = original_next_sibling
-                .expect("next sibling should exist")
-                .borrow()
-                .next_sibling();
-
-            loop {
-                next_sibling = build_layout_tree(&original_dom_node, &None, cssom);
-
-                if next_sibling.is_none() && original_dom_node.is_some() {
-                    original_dom_node = original_dom_node
-                        .expect("next sibling should exist")
-                        .borrow()
-                        .next_sibling();
-                    continue;
-                }
+        siblings
+            .iter()
+            .rev()
+            .find(|n| {
+                let n = n.borrow();
+                n.point().x() <= position.0
+                    && position.0 <= (n.point().x() + n.size().width())
+                    && n.point().y() <= position.1
+                    && position.1 <= (n.point().y() + n.size().height())
+            })
+            .cloned()
+    }
+}
 
-                break;
-            }
+/// DOMノード`node`とその子孫からレイアウトツリーを作成する。CSSによって
+/// "display:none"が指定されていた場合はNoneを返す。兄弟ノードは再帰を使わずに
+/// たどるので、再帰の深さはDOMツリーの深さまでになる
+fn build_layout_tree(
+    document: &Rc<RefCell<Document>>,
+    node: NodeId,
+    parent_obj: &Option<Rc<RefCell<LayoutObject>>>,
+    style_sheets: &[&StyleSheet],
+) -> Option<Rc<RefCell<LayoutObject>>> {
+    let layout_object = create_layout_object(document, Some(node), parent_obj, style_sheets)?;
+
+    // <iframe>の子ノードは入れ子の文書を表示できない場合の代替コンテンツなので、
+    // レイアウトツリーに含めない
+    let child_nodes: Vec<NodeId> = {
+        let document = document.borrow();
+        if document.element_kind(node) == Some(ElementKind::Iframe) {
+            Vec::new()
+        } else {
+            children(&document, node).collect()
         }
-
-        let obj = match layout_object {
-            Some(ref obj) => obj,
-            None => panic!("render object should exist here"),
+    };
+
+    // LayoutObjectが作成されなかった子ノードは飛ばして、作成されたものを兄弟として
+    // つなげる
+    let parent = Some(layout_object.clone());
+    let mut previous: Option<Rc<RefCell<LayoutObject>>> = None;
+    for child in child_nodes {
+        let child_obj = match build_layout_tree(document, child, &parent, style_sheets) {
+            Some(obj) => obj,
+            None => continue,
         };
-        obj.borrow_mut().set_first_child(first_child);
-        obj.borrow_mut().set_next_sibling(next_sibling);
+        match previous {
+            Some(ref p) => p.borrow_mut().set_next_sibling(Some(child_obj.clone())),
+            None => layout_object
+                .borrow_mut()
+                .set_first_child(Some(child_obj.clone())),
+        }
+        previous = Some(child_obj);
     }
 
-    layout_object
+    Some(layout_object)
 }
 
 #[cfg(test)]
 mod tests {
     use alloc::string::String;
+    use alloc::vec;
     use alloc::vec::Vec;
 
     use super::*;
     use crate::alloc::string::ToString;
+    use crate::constants::CHAR_HEIGHT_WITH_PADDING;
     use crate::renderer::css::cssom::CssParser;
     use crate::renderer::css::token::CssTokenizer;
+    use crate::renderer::css::user_agent::user_agent_style_sheet;
     use crate::renderer::dom::api::get_style_content;
     use crate::renderer::dom::node::{Element, NodeKind};
     use crate::renderer::html::parser::HtmlParser;
@@ -271,10 +320,10 @@ mod tests {
         let t = HtmlTokenizer::new(html);
         let window = HtmlParser::new(t).construct_tree();
         let dom = window.borrow().document();
-        let style = get_style_content(dom.clone());
+        let style = get_style_content(&dom.borrow());
         let css_tokenizer = CssTokenizer::new(style);
         let cssom = CssParser::new(css_tokenizer).parse_stylesheet();
-        LayoutView::new(dom, &cssom)
+        LayoutView::new(dom, &[&user_agent_style_sheet(), &cssom])
     }
 
     #[test]
@@ -405,4 +454,115 @@ mod tests {
             .next_sibling()
             .is_none());
     }
+
+    fn text_lines(layout_view: &LayoutView) -> Vec<String> {
+        let mut lines = Vec::new();
+        for item in layout_view.paint() {
+            if let DisplayItem::Text { text, .. } = item {
+                lines.push(text);
+            }
+        }
+        lines
+    }
+
+    #[test]
+    fn test_white_space_normal() {
+        let html = "<html><head></head><body><p>\n  a   b\n  c </p>\n</body></html>".to_string();
+        let layout_view = create_layout_view(html);
+
+        assert_eq!(vec!["a b c".to_string()], text_lines(&layout_view));
+    }
+
+    #[test]
+    fn test_white_space_pre() {
+        let html = "<html><head></head><body><pre>a  b\n  c</pre></body></html>".to_string();
+        let layout_view = create_layout_view(html);
+
+        assert_eq!(
+            vec!["a  b".to_string(), "  c".to_string()],
+            text_lines(&layout_view)
+        );
+
+        let pre = layout_view
+            .root()
+            .expect("root should exist")
+            .borrow()
+            .first_child();
+        let text = pre.expect("pre should exist").borrow().first_child();
+        assert_eq!(
+            CHAR_HEIGHT_WITH_PADDING * 2,
+            text.expect("text should exist").borrow().size().height()
+        );
+    }
+
+    #[test]
+    fn test_white_space_property() {
+        let html = r#"<html>
+<head>
+<style>
+  .line { white-space: pre-line; }
+</style>
+</head>
+<body>
+  <p class="line">a   b
+  c</p>
+</body>
+</html>"#
+            .to_string();
+        let layout_view = create_layout_view(html);
+
+        assert_eq!(
+            vec!["a b".to_string(), "c".to_string()],
+            text_lines(&layout_view)
+        );
+    }
+
+    #[test]
+    fn test_many_siblings() {
+        // 兄弟ノードの数だけ再帰すると、構築や解放でスタックが溢れる
+        let mut html = "<html><head></head><body>".to_string();
+        for _ in 0..50000 {
+            html.push_str("<p>a</p>");
+        }
+        html.push_str("</body></html>");
+        let layout_view = create_layout_view(html);
+
+        let root = layout_view.root().expect("root should exist");
+        let mut count = 0;
+        let mut child = root.borrow().first_child();
+        while let Some(c) = child {
+            count += 1;
+            child = c.borrow().next_sibling();
+        }
+        assert_eq!(50000, count);
+
+        drop(root);
+        drop(layout_view);
+    }
+
+    #[test]
+    fn test_cascade_order() {
+        // 詳細度の大きいルールは、後に書かれたルールより優先される
+        let html = r#"<html>
+<head>
+<style>
+  .pre { white-space: pre; }
+  p { white-space: normal; }
+  #x { white-space: pre-line !important; }
+  p#x.pre { white-space: pre; }
+</style>
+</head>
+<body>
+  <p class="pre">a  b</p>
+  <p id="x" class="pre">c  d</p>
+</body>
+</html>"#
+            .to_string();
+        let layout_view = create_layout_view(html);
+
+        assert_eq!(
+            vec!["a  b".to_string(), "c d".to_string()],
+            text_lines(&layout_view)
+        );
+    }
 }
@@ -1,6 +1,9 @@
+pub mod accessibility;
 pub mod css;
+pub mod diagnostics;
 pub mod dom;
 pub mod html;
 pub mod js;
 pub mod layout;
 pub mod page;
+pub mod refresh;
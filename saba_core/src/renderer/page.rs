@@ -1,39 +1,204 @@
 use crate::browser::Browser;
 use crate::display_item::DisplayItem;
+use crate::encoding::sniffer::decode_html;
+use crate::error::Error;
 use crate::http::HttpResponse;
+use crate::image::decode_image;
+use crate::renderer::accessibility::AccessibilityTree;
 use crate::renderer::css::cssom::CssParser;
 use crate::renderer::css::cssom::StyleSheet;
 use crate::renderer::css::token::CssTokenizer;
-use crate::renderer::dom::api::get_style_content;
+use crate::renderer::diagnostics::ParseDiagnostics;
+use crate::renderer::dom::api::ancestors;
+use crate::renderer::dom::api::child_text_content;
+use crate::renderer::dom::api::get_document_title;
+use crate::renderer::dom::api::get_elements_by_kind;
+use crate::renderer::dom::api::get_style_sheet_nodes;
+use crate::renderer::dom::event::dispatch_event;
+use crate::renderer::dom::event::Event;
+use crate::renderer::dom::event::EventTarget;
+use crate::renderer::dom::focus::active_element;
+use crate::renderer::dom::focus::focus;
+use crate::renderer::dom::focus::is_focusable;
+use crate::renderer::dom::focus::sequential_focus_order;
+use crate::renderer::dom::focus::FocusDirection;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::DocumentReadyState;
 use crate::renderer::dom::node::ElementKind;
+use crate::renderer::dom::node::NodeId;
 use crate::renderer::dom::node::NodeKind;
 use crate::renderer::dom::node::Window;
+use crate::renderer::dom::selection::select_word;
+use crate::renderer::dom::selection::Selection;
 use crate::renderer::html::parser::HtmlParser;
 use crate::renderer::html::token::HtmlTokenizer;
+use crate::renderer::js::ast::JsParser;
+use crate::renderer::js::runtime::JsRuntime;
+use crate::renderer::js::token::JsLexer;
 use crate::renderer::layout::layout_view::LayoutView;
+use crate::renderer::refresh::parse_refresh;
+use crate::renderer::refresh::Refresh;
+use crate::url::Url;
 use alloc::rc::Rc;
 use alloc::rc::Weak;
 use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::cell::RefCell;
+use core::iter;
+use core::mem;
+
+/// ページが取得を必要としているサブリソース。UIは`url`を取得し、結果をこの要求と
+/// 一緒に`Page::receive_subresource`に渡す。要求のIDはページごとに一意なので、
+/// 同じURLのサブリソースがいくつあっても取り違えない
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubresourceRequest {
+    id: usize,
+    url: String,
+}
+
+impl SubresourceRequest {
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// 文書順に並んだスタイルシート。<link>で指定されたスタイルシートは、
+/// 読み込みが終わるまでPendingになる
+#[derive(Debug, Clone)]
+enum StyleSheetEntry {
+    Loaded(StyleSheet),
+    /// 読み込み中のスタイルシートの要求のID
+    Pending(usize),
+}
+
+/// 外部スクリプトを実行するタイミング
+/// https://html.spec.whatwg.org/multipage/scripting.html#prepare-the-script-element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScriptKind {
+    /// 取得して実行するまでパーサをブロックする
+    ParserBlocking,
+    /// パースが終わった後に、文書順に実行する
+    Defer,
+    /// 取得できしだい実行する
+    Async,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ScriptSource {
+    Loading,
+    Loaded(String),
+    /// 取得に失敗したスクリプトは実行しない
+    Failed,
+}
+
+/// 読み込み中、または実行を待っている外部スクリプト
+#[derive(Debug, Clone)]
+struct PendingScript {
+    /// 取得の要求のID。スタイルシートを待っているインラインのスクリプトはNone
+    request: Option<usize>,
+    kind: ScriptKind,
+    source: ScriptSource,
+}
+
+/// 読み込み中の<img>要素の画像
+#[derive(Debug, Clone)]
+struct PendingImage {
+    url: String,
+    /// 取得の要求のID。同じURLの画像は同じ要求を共有する
+    request: usize,
+    node: NodeId,
+}
+
+/// 入れ子の文書の深さの上限。自分自身を<iframe>で読み込むページなどで、
+/// 無限に入れ子にならないようにする
+const MAX_FRAME_DEPTH: usize = 3;
+
+/// <iframe>要素が持つ、入れ子の文書
+/// https://html.spec.whatwg.org/multipage/document-sequences.html#nested-navigable
+#[derive(Debug, Clone)]
+struct ChildFrame {
+    node: NodeId,
+    page: Rc<RefCell<Page>>,
+    /// 入れ子の文書そのものを取得中の場合は、その要求のID
+    request: Option<usize>,
+}
+
+/// キー入力を処理した結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyPressResult {
+    /// 表示は変わらなかった
+    Unchanged,
+    /// 表示が変わったので、UIは`display_items`を描画し直す
+    Repaint,
+    /// フォーカスされたリンクが選択されたので、このURLへ移動する
+    Navigate(String),
+}
 
 #[derive(Debug, Clone)]
 pub struct Page {
     browser: Weak<RefCell<Browser>>,
+    url: Option<Url>,
     frame: Option<Rc<RefCell<Window>>>,
+    /// パース中のHTMLパーサ。パースが終わるとNoneになる
+    html_parser: Option<HtmlParser>,
+    js_runtime: Option<JsRuntime>,
+    scripts: Vec<PendingScript>,
+    /// <style>要素または<link rel="stylesheet">要素と、そのスタイルシート
+    style_sheets: Vec<(NodeId, StyleSheetEntry)>,
+    images: Vec<PendingImage>,
+    frames: Vec<ChildFrame>,
+    /// 入れ子の文書の深さ。トップレベルのページは0になる
+    frame_depth: usize,
+    /// 入れ子の文書のために取得中のサブリソース。このページでの要求のID、
+    /// `frames`のインデックス、入れ子の文書での要求の組になる
+    frame_requests: Vec<(usize, usize, SubresourceRequest)>,
+    /// 起動時にパースした、ユーザーエージェントのスタイルシート
+    user_agent_style: Rc<StyleSheet>,
     style: Option<StyleSheet>,
     layout_view: Option<LayoutView>,
     display_items: Vec<DisplayItem>,
+    diagnostics: ParseDiagnostics,
+    /// 予約されたナビゲーション。最初に現れたものだけが有効になる
+    refresh: Option<Refresh>,
+    /// まだ取得を始めていないサブリソースの要求
+    subresource_requests: Vec<SubresourceRequest>,
+    /// 次の要求に割り当てるID。古いページへの応答と取り違えないように、
+    /// 別のページを読み込んでも戻さない
+    next_request_id: usize,
+    /// DOMContentLoadedイベントを送ったかどうか
+    dom_content_loaded: bool,
+    /// スタイルシートが揃うのを待たずに描画するかどうか
+    stopped_waiting_for_style_sheets: bool,
 }
 
 impl Page {
-    pub fn new() -> Self {
+    /// `user_agent_style`は、起動時に一度だけパースしたユーザーエージェントの
+    /// スタイルシート。入れ子の文書とも共有する
+    pub fn new(user_agent_style: Rc<StyleSheet>) -> Self {
         Self {
             browser: Weak::new(),
+            url: None,
             frame: None,
+            html_parser: None,
+            js_runtime: None,
+            scripts: Vec::new(),
+            style_sheets: Vec::new(),
+            images: Vec::new(),
+            frames: Vec::new(),
+            frame_depth: 0,
+            frame_requests: Vec::new(),
+            user_agent_style,
             style: None,
             layout_view: None,
             display_items: Vec::new(),
+            diagnostics: ParseDiagnostics::new(),
+            refresh: None,
+            subresource_requests: Vec::new(),
+            next_request_id: 0,
+            dom_content_loaded: false,
+            stopped_waiting_for_style_sheets: false,
         }
     }
 
@@ -41,45 +206,695 @@ impl Page {
         self.browser = browser;
     }
 
-    pub fn receive_response(&mut self, response: HttpResponse) {
-        self.create_frame(response.body());
-
-        self.set_layout_view();
-        self.paint_tree();
+    /// `url`から取得したレスポンスを受け取り、ページを作成する。
+    /// 外部のスタイルシートやスクリプトがある場合は、`next_subresource_request`で
+    /// 取得する要求を返す。スタイルシートがすべて揃うまで描画しない。開いたURLは
+    /// 訪問済みとして記録する
+    pub fn receive_response(&mut self, url: String, response: HttpResponse) {
+        if let Some(browser) = self.browser.upgrade() {
+            browser.borrow_mut().add_visited_url(url.clone());
+        }
+        self.load_response(url, response);
     }
 
-    fn create_frame(&mut self, html: String) {
+    /// `receive_response`と同じようにページを作成するが、訪問済みとして記録しない。
+    /// 入れ子の文書を読み込むときに使う
+    fn load_response(&mut self, url: String, response: HttpResponse) {
+        self.url = Url::new(url).parse().ok();
+        self.scripts = Vec::new();
+        self.style_sheets = Vec::new();
+        self.images = Vec::new();
+        self.frames = Vec::new();
+        self.frame_requests = Vec::new();
+        self.style = None;
+        self.layout_view = None;
+        self.display_items = Vec::new();
+        self.diagnostics = ParseDiagnostics::new();
+        self.refresh = None;
+        self.subresource_requests = Vec::new();
+        self.dom_content_loaded = false;
+        self.stopped_waiting_for_style_sheets = false;
+
+        // Refreshヘッダは、<head>の先頭にある<meta http-equiv="refresh">と同じように
+        // 扱う
+        if let Ok(value) = response.header_value("Refresh") {
+            self.declare_refresh(&value);
+        }
+
+        // 文字コードを判定して、ボディを文字列にデコードする
+        let content_type = response.header_value("Content-Type").ok();
+        let html = decode_html(&response.body_bytes(), content_type);
+
         let html_tokenizer = HtmlTokenizer::new(html);
-        let frame = HtmlParser::new(html_tokenizer).construct_tree();
+        let mut html_parser = HtmlParser::new(html_tokenizer);
+        let frame = html_parser.create_window();
         let dom = frame.borrow().document();
-
-        let style = get_style_content(dom);
-        let css_tokenizer = CssTokenizer::new(style);
-        let cssom = CssParser::new(css_tokenizer).parse_stylesheet();
+        dom.borrow_mut()
+            .set_ready_state(DocumentReadyState::Loading);
 
         self.frame = Some(frame);
-        self.style = Some(cssom);
+        self.js_runtime = Some(JsRuntime::new(dom));
+        self.html_parser = Some(html_parser);
+
+        self.continue_parsing();
+    }
+
+    /// 次に取得するサブリソースの要求を返す。取得した結果は要求と一緒に
+    /// `receive_subresource`に渡す
+    pub fn next_subresource_request(&mut self) -> Option<SubresourceRequest> {
+        if !self.subresource_requests.is_empty() {
+            return Some(self.subresource_requests.remove(0));
+        }
+
+        // 入れ子の文書が必要とするサブリソースも、このページが代わりに取得する。
+        // 入れ子の文書の要求のIDはこのページのものと重なりうるので、このページの
+        // IDを割り当て直す
+        let frame_request =
+            self.frames.iter().enumerate().find_map(|(i, frame)| {
+                Some((i, frame.page.borrow_mut().next_subresource_request()?))
+            });
+        if let Some((i, frame_request)) = frame_request {
+            let request = self.create_request(frame_request.url());
+            self.frame_requests.push((request.id, i, frame_request));
+            return Some(request);
+        }
+
+        None
+    }
+
+    /// サブリソースの取得結果を受け取る。ネットワークのエラーで取得できなかった
+    /// 場合は`response`にエラーを渡す
+    pub fn receive_subresource(
+        &mut self,
+        request: SubresourceRequest,
+        response: Result<HttpResponse, Error>,
+    ) {
+        self.dispatch_subresource(request, response);
+        self.update_ready_state();
+    }
+
+    /// 読み込み中のスタイルシートを待たずに、揃っているスタイルシートだけで描画する。
+    /// UIは取得に時間がかかりすぎたときにこれを呼ぶ。後から届いたスタイルシートは、
+    /// 届いたときに反映する。入れ子の文書も同じように扱う
+    pub fn stop_waiting_for_style_sheets(&mut self) {
+        for frame in &self.frames {
+            frame.page.borrow_mut().stop_waiting_for_style_sheets();
+        }
+        if self.stopped_waiting_for_style_sheets {
+            return;
+        }
+        self.stopped_waiting_for_style_sheets = true;
+        self.render();
+    }
+
+    /// 文書の読み込みの状態を返す。UIはこれを見て読み込み中の表示をする。
+    /// 文書がない場合はComplete
+    pub fn ready_state(&self) -> DocumentReadyState {
+        self.document().map_or(DocumentReadyState::Complete, |dom| {
+            dom.borrow().ready_state()
+        })
+    }
+
+    /// 取得したサブリソースを、要求のIDで要求した<iframe>や<img>、スタイルシート、
+    /// スクリプトに渡す。どれも要求していない場合は捨てる
+    fn dispatch_subresource(
+        &mut self,
+        request: SubresourceRequest,
+        response: Result<HttpResponse, Error>,
+    ) {
+        let id = request.id;
+
+        if let Some(index) = self.frame_requests.iter().position(|(r, _, _)| *r == id) {
+            let (_, i, frame_request) = self.frame_requests.remove(index);
+            self.frames[i]
+                .page
+                .borrow_mut()
+                .receive_subresource(frame_request, response);
+            self.render();
+            return;
+        }
+
+        if let Some(i) = self
+            .frames
+            .iter()
+            .position(|frame| frame.request == Some(id))
+        {
+            // 取得できなかった場合は、空の文書のままにする
+            self.frames[i].request = None;
+            if let Ok(response) = response {
+                self.frames[i]
+                    .page
+                    .borrow_mut()
+                    .load_response(request.url, response);
+            }
+            self.render();
+            return;
+        }
+
+        let response = match response {
+            Ok(response) if (200..300).contains(&response.status_code()) => Some(response),
+            _ => None,
+        };
+
+        if self.images.iter().any(|image| image.request == id) {
+            self.receive_image(id, response);
+            return;
+        }
+
+        let body = response.map(|r| r.body());
+
+        let index = self
+            .style_sheets
+            .iter()
+            .position(|(_, entry)| matches!(entry, StyleSheetEntry::Pending(r) if *r == id));
+        if let Some(i) = index {
+            // 取得できなかったスタイルシートは、空のスタイルシートとして扱う
+            let sheet = self.parse_style_sheet(body.unwrap_or_default());
+            self.style_sheets[i].1 = StyleSheetEntry::Loaded(sheet);
+            self.render();
+            self.run_parser_blocking_script();
+            return;
+        }
+
+        let index = self
+            .scripts
+            .iter()
+            .position(|s| s.request == Some(id) && s.source == ScriptSource::Loading);
+        if let Some(i) = index {
+            self.scripts[i].source = match body {
+                Some(source) => ScriptSource::Loaded(source),
+                None => ScriptSource::Failed,
+            };
+
+            match self.scripts[i].kind {
+                ScriptKind::ParserBlocking => self.run_parser_blocking_script(),
+                ScriptKind::Async => {
+                    let script = self.scripts.remove(i);
+                    self.execute_script(script.source);
+                    self.render();
+                }
+                ScriptKind::Defer => {
+                    if self.html_parser.is_none() {
+                        self.run_deferred_scripts();
+                    }
+                }
+            }
+        }
+    }
+
+    /// パース中か、読み込み中のスタイルシートやスクリプト、画像があるかどうかを返す
+    pub fn is_loading(&self) -> bool {
+        self.html_parser.is_some()
+            || !self.scripts.is_empty()
+            || self.is_loading_style_sheets()
+            || !self.images.is_empty()
+            || self
+                .frames
+                .iter()
+                .any(|frame| frame.request.is_some() || frame.page.borrow().is_loading())
+    }
+
+    fn is_loading_style_sheets(&self) -> bool {
+        self.style_sheets
+            .iter()
+            .any(|(_, entry)| matches!(entry, StyleSheetEntry::Pending(_)))
+    }
+
+    /// パーサをブロックするスクリプトが現れるか、文書の終わりまでパースを進める
+    fn continue_parsing(&mut self) {
+        loop {
+            let parser = match &mut self.html_parser {
+                Some(parser) => parser,
+                None => return,
+            };
+
+            match parser.parse_until_script() {
+                Some(script) => {
+                    // ここまでに挿入された<link>要素のスタイルシートの取得を、
+                    // スクリプトより先に始める
+                    self.load_style_sheets();
+                    if self.prepare_script(script) {
+                        // スクリプトを取得するまでパースを中断する
+                        return;
+                    }
+                }
+                None => break,
+            }
+        }
+
+        // パースの終了
+        // https://html.spec.whatwg.org/multipage/parsing.html#the-end
+        if let Some(parser) = self.html_parser.take() {
+            self.diagnostics.extend(parser.diagnostics());
+        }
+        self.load_refresh();
+        self.load_style_sheets();
+        self.load_images();
+        self.load_frames();
+        self.run_deferred_scripts();
+        self.render();
+        self.update_ready_state();
+    }
+
+    /// パースの終了後、遅延スクリプトをすべて実行したらDOMContentLoadedイベントを、
+    /// サブリソースをすべて読み込んだらloadイベントを送る
+    /// https://html.spec.whatwg.org/multipage/parsing.html#the-end
+    fn update_ready_state(&mut self) {
+        if self.html_parser.is_some() {
+            return;
+        }
+        let dom = match self.document() {
+            Some(dom) => dom,
+            None => return,
+        };
+
+        if dom.borrow().ready_state() == DocumentReadyState::Loading {
+            Self::set_ready_state(&dom, DocumentReadyState::Interactive);
+        }
+
+        if !self.dom_content_loaded {
+            if self.scripts.iter().any(|s| s.kind == ScriptKind::Defer) {
+                return;
+            }
+            self.dom_content_loaded = true;
+            let root = dom.borrow().root();
+            let mut event = Event::new("DOMContentLoaded", true, false);
+            dispatch_event(&dom, EventTarget::Node(root), &mut event);
+            self.update_rendering();
+        }
+
+        if dom.borrow().ready_state() != DocumentReadyState::Complete && !self.is_loading() {
+            Self::set_ready_state(&dom, DocumentReadyState::Complete);
+            let mut event = Event::new("load", false, false);
+            dispatch_event(&dom, EventTarget::Window, &mut event);
+            self.update_rendering();
+        }
+    }
+
+    /// 文書の読み込みの状態を変更し、readystatechangeイベントを送る
+    /// https://html.spec.whatwg.org/multipage/dom.html#update-the-current-document-readiness
+    fn set_ready_state(dom: &Rc<RefCell<Document>>, ready_state: DocumentReadyState) {
+        dom.borrow_mut().set_ready_state(ready_state);
+        let root = dom.borrow().root();
+        let mut event = Event::new("readystatechange", false, false);
+        dispatch_event(dom, EventTarget::Node(root), &mut event);
+    }
+
+    /// <script>要素を実行する、または外部スクリプトの取得を始める。
+    /// パーサをブロックする場合はtrueを返す
+    /// https://html.spec.whatwg.org/multipage/scripting.html#prepare-the-script-element
+    fn prepare_script(&mut self, script: NodeId) -> bool {
+        let dom = match self.document() {
+            Some(dom) => dom,
+            None => return false,
+        };
+
+        let (src, kind) = {
+            let dom = dom.borrow();
+            let element = match dom.element(script) {
+                Some(e) => e,
+                None => return false,
+            };
+
+            let kind = if element.get_attribute("async").is_some() {
+                ScriptKind::Async
+            } else if element.get_attribute("defer").is_some() {
+                ScriptKind::Defer
+            } else {
+                ScriptKind::ParserBlocking
+            };
+            (element.get_attribute("src").map(String::from), kind)
+        };
+
+        let src = match src {
+            Some(src) => src,
+            None => {
+                // インラインのスクリプトは、defer属性やasync属性に関わらず
+                // すぐに実行する。ただし、読み込み中のスタイルシートがあれば
+                // 揃うまでパーサと一緒に待つ
+                let source = ScriptSource::Loaded(child_text_content(&dom.borrow(), script));
+                if !self.is_loading_style_sheets() {
+                    self.execute_script(source);
+                    return false;
+                }
+                self.scripts.push(PendingScript {
+                    request: None,
+                    kind: ScriptKind::ParserBlocking,
+                    source,
+                });
+                return true;
+            }
+        };
+
+        let url = self.resolve_url(&src);
+        let request = self.request_subresource(url);
+        self.scripts.push(PendingScript {
+            request: Some(request),
+            kind,
+            source: ScriptSource::Loading,
+        });
+
+        kind == ScriptKind::ParserBlocking
+    }
+
+    /// パーサをブロックしているスクリプトを、取得が終わり、それより前に現れた
+    /// スタイルシートがすべて揃ってから実行し、パースを再開する
+    /// https://html.spec.whatwg.org/multipage/parsing.html#scriptEndTag
+    /// https://html.spec.whatwg.org/multipage/semantics.html#has-no-style-sheet-that-is-blocking-scripts
+    fn run_parser_blocking_script(&mut self) {
+        if self.is_loading_style_sheets() {
+            return;
+        }
+        let index = self.scripts.iter().position(|s| {
+            s.kind == ScriptKind::ParserBlocking && s.source != ScriptSource::Loading
+        });
+        if let Some(i) = index {
+            let script = self.scripts.remove(i);
+            self.execute_script(script.source);
+            self.continue_parsing();
+        }
+    }
+
+    /// 遅延スクリプトを文書順に実行する。まだ取得できていないスクリプトがあれば、
+    /// そこで止める
+    fn run_deferred_scripts(&mut self) {
+        while let Some(i) = self
+            .scripts
+            .iter()
+            .position(|s| s.kind == ScriptKind::Defer)
+        {
+            if self.scripts[i].source == ScriptSource::Loading {
+                return;
+            }
+            let script = self.scripts.remove(i);
+            self.execute_script(script.source);
+            self.render();
+        }
+    }
+
+    fn execute_script(&mut self, source: ScriptSource) {
+        let source = match source {
+            ScriptSource::Loaded(source) => source,
+            _ => return,
+        };
+
+        let lexer = JsLexer::new(source);
+        let mut parser = JsParser::new(lexer);
+        let ast = parser.parse_ast();
+        self.diagnostics.extend(parser.diagnostics());
+
+        if let Some(runtime) = &mut self.js_runtime {
+            runtime.execute(&ast);
+        }
+    }
+
+    fn document(&self) -> Option<Rc<RefCell<Document>>> {
+        self.frame.as_ref().map(|frame| frame.borrow().document())
+    }
+
+    /// 新しいIDを割り当てたサブリソースの要求を作る
+    fn create_request(&mut self, url: String) -> SubresourceRequest {
+        let id = self.next_request_id;
+        self.next_request_id += 1;
+        SubresourceRequest { id, url }
+    }
+
+    /// `url`の取得を要求し、要求のIDを返す
+    fn request_subresource(&mut self, url: String) -> usize {
+        let request = self.create_request(url);
+        let id = request.id;
+        self.subresource_requests.push(request);
+        id
+    }
+
+    fn resolve_url(&self, url: &str) -> String {
+        match &self.url {
+            Some(base) => base.resolve(url),
+            None => url.to_string(),
+        }
+    }
+
+    /// <style>要素と<link rel="stylesheet">要素から、文書順にスタイルシートを
+    /// 集める。パース中も、パーサが止まるたびに呼んで、新しく挿入された要素の
+    /// スタイルシートをパースするか取得を始める。前に集めた要素のスタイルシートは
+    /// そのまま使い、文書から取り除かれた要素のものは捨てる
+    /// https://html.spec.whatwg.org/multipage/semantics.html#update-a-style-block
+    /// https://html.spec.whatwg.org/multipage/semantics.html#link-type-stylesheet
+    fn load_style_sheets(&mut self) {
+        let dom = match self.document() {
+            Some(dom) => dom,
+            None => return,
+        };
+        let dom = dom.borrow();
+
+        let mut previous = mem::take(&mut self.style_sheets);
+
+        for node in get_style_sheet_nodes(&dom) {
+            if let Some(i) = previous.iter().position(|(n, _)| *n == node) {
+                self.style_sheets.push(previous.remove(i));
+                continue;
+            }
+
+            let element = match dom.element(node) {
+                Some(e) => e,
+                None => continue,
+            };
+
+            if element.kind() == ElementKind::Style {
+                // CSSのエラーの位置は<style>要素の内容の先頭からの位置になる
+                let sheet = self.parse_style_sheet(child_text_content(&dom, node));
+                self.style_sheets
+                    .push((node, StyleSheetEntry::Loaded(sheet)));
+                continue;
+            }
+
+            let href = element.get_attribute("href").unwrap_or_default();
+            let url = self.resolve_url(href);
+            let request = self.request_subresource(url);
+            self.style_sheets
+                .push((node, StyleSheetEntry::Pending(request)));
+        }
+    }
+
+    /// <meta http-equiv="refresh">要素から、予約するナビゲーションを探す
+    /// https://html.spec.whatwg.org/multipage/semantics.html#attr-meta-http-equiv-refresh
+    fn load_refresh(&mut self) {
+        let dom = match self.document() {
+            Some(dom) => dom,
+            None => return,
+        };
+        let dom = dom.borrow();
+
+        for node in get_elements_by_kind(&dom, ElementKind::Meta) {
+            let element = match dom.element(node) {
+                Some(e) => e,
+                None => continue,
+            };
+            let http_equiv = element.get_attribute("http-equiv").unwrap_or_default();
+            if !http_equiv.eq_ignore_ascii_case("refresh") {
+                continue;
+            }
+            if let Some(content) = element.get_attribute("content") {
+                self.declare_refresh(content);
+            }
+        }
+    }
+
+    /// ナビゲーションを予約する。すでに予約されている場合は何もしない
+    /// https://html.spec.whatwg.org/multipage/document-lifecycle.html#shared-declarative-refresh-steps
+    fn declare_refresh(&mut self, input: &str) {
+        if self.refresh.is_some() {
+            return;
+        }
+
+        if let Some((delay, url)) = parse_refresh(input) {
+            let url = match url {
+                Some(url) => self.resolve_url(&url),
+                None => self.resolve_url(""),
+            };
+            self.refresh = Some(Refresh::new(delay, url));
+        }
+    }
+
+    /// src属性を持つ<img>要素の画像の取得を始める。画像は描画をブロックしないので、
+    /// 取得するまではalt属性のテキストを代わりに描画する
+    /// https://html.spec.whatwg.org/multipage/images.html#update-the-image-data
+    fn load_images(&mut self) {
+        let dom = match self.document() {
+            Some(dom) => dom,
+            None => return,
+        };
+        let dom = dom.borrow();
+
+        for node in get_elements_by_kind(&dom, ElementKind::Img) {
+            let src = match dom.element(node) {
+                Some(e) => e.get_attribute("src").unwrap_or_default(),
+                None => continue,
+            };
+            if src.is_empty() {
+                continue;
+            }
+
+            let url = self.resolve_url(src);
+            // 同じURLの画像は一度だけ取得する
+            let request = match self.images.iter().find(|image| image.url == url) {
+                Some(image) => image.request,
+                None => self.request_subresource(url.clone()),
+            };
+            self.images.push(PendingImage { url, request, node });
+        }
+    }
+
+    /// <iframe>要素ごとに入れ子の文書を作り、src属性の文書の取得を始める
+    /// https://html.spec.whatwg.org/multipage/iframe-embed-object.html#process-the-iframe-attributes
+    fn load_frames(&mut self) {
+        let dom = match self.document() {
+            Some(dom) => dom,
+            None => return,
+        };
+        let dom = dom.borrow();
+
+        for node in get_elements_by_kind(&dom, ElementKind::Iframe) {
+            let src = match dom.element(node) {
+                Some(e) => e.get_attribute("src").unwrap_or_default(),
+                None => continue,
+            };
+
+            let mut page = Page::new(self.user_agent_style.clone());
+            page.set_browser(self.browser.clone());
+            page.frame_depth = self.frame_depth + 1;
+
+            // src属性がない場合や深すぎる場合は、空の文書を表示する
+            let request = if !src.is_empty() && page.frame_depth <= MAX_FRAME_DEPTH {
+                Some(self.request_subresource(self.resolve_url(src)))
+            } else {
+                None
+            };
+
+            self.frames.push(ChildFrame {
+                node,
+                page: Rc::new(RefCell::new(page)),
+                request,
+            });
+        }
+    }
+
+    /// 取得した画像をデコードして、要求を共有するすべての<img>要素に設定する。
+    /// 取得やデコードに失敗した場合は、alt属性のテキストのままにする
+    fn receive_image(&mut self, request: usize, response: Option<HttpResponse>) {
+        let image = response.and_then(|r| decode_image(&r.body_bytes()).ok().map(Rc::new));
+
+        let (loaded, pending): (Vec<PendingImage>, Vec<PendingImage>) = self
+            .images
+            .drain(..)
+            .partition(|pending_image| pending_image.request == request);
+        self.images = pending;
+
+        if let Some(dom) = self.document() {
+            let mut dom = dom.borrow_mut();
+            for pending_image in loaded {
+                if let Some(e) = dom.element_mut(pending_image.node) {
+                    e.set_image(image.clone());
+                }
+            }
+        }
+
+        self.render();
+    }
+
+    fn parse_style_sheet(&mut self, css: String) -> StyleSheet {
+        let css_tokenizer = CssTokenizer::new(css);
+        let mut css_parser = CssParser::new(css_tokenizer);
+        let sheet = css_parser.parse_stylesheet();
+        self.diagnostics.extend(css_parser.diagnostics());
+        sheet
+    }
+
+    /// パースが終わり、すべてのスタイルシートが揃っていれば、レイアウトと描画を
+    /// 行う。スタイルシートは文書順に連結するので、後に現れるルールが優先される。
+    /// スタイルシートを待つのをやめた後は、揃っているものだけを使う
+    fn render(&mut self) {
+        if self.html_parser.is_some()
+            || (self.is_loading_style_sheets() && !self.stopped_waiting_for_style_sheets)
+        {
+            return;
+        }
+
+        let mut style = StyleSheet::new();
+        for (_, entry) in &self.style_sheets {
+            if let StyleSheetEntry::Loaded(sheet) = entry {
+                style.rules.extend(sheet.rules.clone());
+            }
+        }
+        self.style = Some(style);
+
+        self.set_layout_view();
+        self.paint_tree();
+
+        if let Some(dom) = self.document() {
+            dom.borrow_mut().clear_needs_style_recalc();
+        }
     }
 
     fn set_layout_view(&mut self) {
-        let dom = match &self.frame {
-            Some(frame) => frame.borrow().document(),
+        let dom = match self.document() {
+            Some(dom) => dom,
             None => return,
         };
 
-        let style = match self.style.clone() {
+        let style = match &self.style {
             Some(style) => style,
             None => return,
         };
 
-        let layout_view = LayoutView::new(dom, &style);
+        // ユーザーエージェント、ユーザー、作者のスタイルシートの順に渡す
+        let user_style = self
+            .browser
+            .upgrade()
+            .and_then(|browser| browser.borrow().user_style_sheet());
+        let mut style_sheets: Vec<&StyleSheet> = vec![&self.user_agent_style];
+        if let Some(user_style) = &user_style {
+            style_sheets.push(user_style);
+        }
+        style_sheets.push(style);
+
+        let layout_view = LayoutView::new(dom, &style_sheets);
 
         self.layout_view = Some(layout_view);
     }
 
     fn paint_tree(&mut self) {
-        if let Some(layout_view) = &self.layout_view {
-            self.display_items = layout_view.paint();
+        let layout_view = match &self.layout_view {
+            Some(v) => v,
+            None => return,
+        };
+
+        self.display_items = layout_view.paint();
+
+        // 入れ子の文書は、<iframe>要素の範囲に切り取って描画する
+        for frame in &self.frames {
+            let layout_object = match layout_view.find_layout_object(frame.node) {
+                Some(o) => o,
+                None => continue,
+            };
+            let point = layout_object.borrow().point();
+            let size = layout_object.borrow().size();
+            for item in frame.page.borrow().display_items() {
+                if let Some(clipped) = item.clip(point, size) {
+                    self.display_items.push(clipped);
+                }
+            }
+        }
+
+        // フォーカスされている要素の周りに枠線を描く
+        let focused = self
+            .document()
+            .and_then(|dom| dom.borrow().focused_element());
+        if let Some(o) = focused.and_then(|n| layout_view.find_layout_object(n)) {
+            self.display_items.push(DisplayItem::FocusRing {
+                layout_point: o.borrow().point(),
+                layout_size: o.borrow().size(),
+            });
         }
     }
 
@@ -87,26 +902,1321 @@ impl Page {
         self.display_items.clone()
     }
 
+    /// ドキュメントのタイトルを返す。スクリプトによって変更された場合は、
+    /// 変更後のタイトルを返す
+    pub fn title(&self) -> String {
+        match self.document() {
+            Some(dom) => get_document_title(&dom.borrow()),
+            None => String::new(),
+        }
+    }
+
+    /// 現在の文書とレイアウトから、アクセシビリティツリーを作成する。まだ
+    /// レイアウトされていない場合はNone
+    pub fn accessibility_tree(&self) -> Option<AccessibilityTree> {
+        let dom = self.document()?;
+        let layout_view = self.layout_view.as_ref()?;
+        let browser = self.browser.upgrade();
+        let is_visited = |href: &str| {
+            browser
+                .as_ref()
+                .is_some_and(|b| b.borrow().is_visited(&self.resolve_url(href)))
+        };
+        let document = dom.borrow();
+        Some(AccessibilityTree::new(&document, layout_view, &is_visited))
+    }
+
+    /// 予約されたナビゲーションを返す。UIはページの読み込みが終わってから
+    /// `delay`秒後に`url`へ移動する
+    pub fn refresh(&self) -> Option<Refresh> {
+        self.refresh.clone()
+    }
+
+    /// 直前に読み込んだページのパースエラーを返す
+    pub fn diagnostics(&self) -> ParseDiagnostics {
+        self.diagnostics.clone()
+    }
+
     pub fn clear_display_items(&mut self) {
         self.display_items = Vec::new();
     }
 
-    pub fn clicked(&self, position: (i64, i64)) -> Option<String> {
-        let view = match &self.layout_view {
-            Some(v) => v,
-            None => return None,
+    /// `position`にある<iframe>のインデックスと、入れ子の文書の中での座標を返す
+    fn frame_at(&self, position: (i64, i64)) -> Option<(usize, (i64, i64))> {
+        let view = self.layout_view.as_ref()?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let layout_object = match view.find_layout_object(frame.node) {
+                Some(o) => o,
+                None => continue,
+            };
+            let point = layout_object.borrow().point();
+            let size = layout_object.borrow().size();
+            if position.0 < point.x()
+                || point.x() + size.width() < position.0
+                || position.1 < point.y()
+                || point.y() + size.height() < position.1
+            {
+                continue;
+            }
+            return Some((i, (position.0 - point.x(), position.1 - point.y())));
+        }
+        None
+    }
+
+    /// `position`にある要素を返す。テキストの場合は、テキストを持つ要素を返す
+    fn hit_test(&self, position: (i64, i64)) -> Option<NodeId> {
+        let dom = self.document()?;
+        let dom = dom.borrow();
+        let node = self
+            .layout_view
+            .as_ref()?
+            .find_node_by_position(position)?
+            .borrow()
+            .node();
+        if dom.element(node).is_some() {
+            Some(node)
+        } else {
+            dom.parent(node)
+        }
+    }
+
+    /// `position`がクリックされたときに移動するURLを返す。<iframe>の中のリンクが
+    /// クリックされた場合は、入れ子の文書だけを移動してNoneを返す。その文書は
+    /// `next_subresource_request`で取得する
+    ///
+    /// clickイベントを送り、キャンセルされなかった場合に既定の動作として
+    /// リンクをたどる。リスナがDOMを変更した場合は描画し直すので、UIは
+    /// `display_items`を描画し直す
+    /// https://html.spec.whatwg.org/multipage/links.html#following-hyperlinks-2
+    pub fn clicked(&mut self, position: (i64, i64)) -> Option<String> {
+        if let Some((i, frame_position)) = self.frame_at(position) {
+            let href = self.frames[i].page.borrow_mut().clicked(frame_position);
+            if let Some(href) = href {
+                let url = self.frames[i].page.borrow().resolve_url(&href);
+                self.frames[i].request = Some(self.request_subresource(url));
+            }
+            self.paint_tree();
+            return None;
+        }
+
+        let dom = self.document()?;
+        let target = self.hit_test(position)?;
+
+        // クリックされた要素か、その祖先のうちフォーカスできる要素にフォーカスを
+        // 移す。なければフォーカスを外す
+        let focus_target = {
+            let dom = dom.borrow();
+            iter::once(target)
+                .chain(ancestors(&dom, target))
+                .find(|n| is_focusable(&dom, *n))
         };
+        if focus(&dom, focus_target) {
+            self.paint_tree();
+        }
 
-        if let Some(n) = view.find_node_by_position(position) {
-            if let Some(parent) = n.borrow().parent().upgrade() {
-                if let NodeKind::Element(e) = parent.borrow().node_kind() {
-                    if e.kind() == ElementKind::A {
-                        return e.get_attribute("href");
-                    }
+        self.fire_click(&dom, target, position)
+    }
+
+    /// `target`にclickイベントを送る。キャンセルされなかった場合は、`target`か
+    /// その祖先の<a>要素のリンクを返す
+    fn fire_click(
+        &mut self,
+        dom: &Rc<RefCell<Document>>,
+        target: NodeId,
+        position: (i64, i64),
+    ) -> Option<String> {
+        let mut event = Event::new_mouse_event("click", position);
+        let is_not_canceled = dispatch_event(dom, EventTarget::Node(target), &mut event);
+        self.update_rendering();
+        if !is_not_canceled {
+            return None;
+        }
+
+        let dom = dom.borrow();
+        iter::once(target)
+            .chain(ancestors(&dom, target))
+            .filter_map(|n| dom.element(n))
+            .find(|e| e.kind() == ElementKind::A && e.get_attribute("href").is_some())
+            .and_then(|e| e.get_attribute("href").map(String::from))
+    }
+
+    /// マウスのボタンが押されたときに、`position`の文字の位置に選択範囲を折り
+    /// たたむ。文字がない位置の場合は選択を解除する。入れ子の文書の中の選択は
+    /// サポートしていない。表示が変わった場合はtrueを返す
+    pub fn mouse_pressed(&mut self, position: (i64, i64)) -> bool {
+        if self.frame_at(position).is_some() {
+            return self.set_selection(None);
+        }
+        let point = self
+            .layout_view
+            .as_ref()
+            .and_then(|v| v.find_text_position(position));
+        self.set_selection(point.map(Selection::collapsed))
+    }
+
+    /// マウスのボタンを押したまま`position`に移動したときに、選択範囲の終わりを
+    /// その位置の文字に移す。表示が変わった場合はtrueを返す
+    pub fn mouse_dragged(&mut self, position: (i64, i64)) -> bool {
+        let mut selection = match self.document().and_then(|dom| dom.borrow().selection()) {
+            Some(selection) => selection,
+            None => return false,
+        };
+        let point = match self
+            .layout_view
+            .as_ref()
+            .and_then(|v| v.find_text_position(position))
+        {
+            Some(point) => point,
+            None => return false,
+        };
+        selection.extend(point);
+        self.set_selection(Some(selection))
+    }
+
+    /// ダブルクリックされたときに、`position`の文字を含む単語を選択する。表示が
+    /// 変わった場合はtrueを返す
+    pub fn double_clicked(&mut self, position: (i64, i64)) -> bool {
+        let dom = match self.document() {
+            Some(dom) => dom,
+            None => return false,
+        };
+        let selection = self
+            .layout_view
+            .as_ref()
+            .and_then(|v| v.find_text_position(position))
+            .and_then(|point| select_word(&dom.borrow(), point.node(), point.offset()));
+        match selection {
+            Some(selection) => self.set_selection(Some(selection)),
+            None => false,
+        }
+    }
+
+    /// 選択されているテキストを返す。コピーするときに使う
+    pub fn selected_text(&self) -> String {
+        match self.document() {
+            Some(dom) => {
+                let dom = dom.borrow();
+                dom.selection().map(|s| s.text(&dom)).unwrap_or_default()
+            }
+            None => String::new(),
+        }
+    }
+
+    /// 文書の選択範囲を変更し、選択されている文字が変わった場合は描画し直して
+    /// trueを返す
+    fn set_selection(&mut self, selection: Option<Selection>) -> bool {
+        let dom = match self.document() {
+            Some(dom) => dom,
+            None => return false,
+        };
+        let old = dom.borrow().selection();
+        dom.borrow_mut().set_selection(selection);
+
+        let is_visible = |s: Option<Selection>| s.is_some_and(|s| !s.is_collapsed());
+        if old == selection || (!is_visible(old) && !is_visible(selection)) {
+            return false;
+        }
+        self.paint_tree();
+        true
+    }
+
+    /// マウスカーソルが`position`に移動したときに、その位置の要素に
+    /// mousemoveイベントを送る。表示が変わった場合はtrueを返す
+    /// https://w3c.github.io/uievents/#event-type-mousemove
+    pub fn mouse_moved(&mut self, position: (i64, i64)) -> bool {
+        if let Some((i, frame_position)) = self.frame_at(position) {
+            let is_changed = self.frames[i].page.borrow_mut().mouse_moved(frame_position);
+            if is_changed {
+                self.paint_tree();
+            }
+            return is_changed;
+        }
+
+        let dom = match self.document() {
+            Some(dom) => dom,
+            None => return false,
+        };
+        if let Some(target) = self.hit_test(position) {
+            let mut event = Event::new_mouse_event("mousemove", position);
+            dispatch_event(&dom, EventTarget::Node(target), &mut event);
+        }
+
+        self.update_rendering()
+    }
+
+    /// キーが押されたときに、keydownイベントをフォーカスされている要素に送る。
+    /// フォーカスされている要素がない場合は<body>要素に送る。キャンセルされな
+    /// かった場合は、既定の動作として次のことを行う
+    /// - Tabキー: 次の要素にフォーカスを移す。`shift`がtrueなら前の要素に移す
+    /// - Enterキー: フォーカスされているリンクにclickイベントを送り、リンクをたどる
+    /// - その他: 編集可能な要素のテキストを編集し、inputイベントを送る
+    ///
+    /// https://w3c.github.io/uievents/#event-type-keydown
+    pub fn key_pressed(&mut self, key: char, shift: bool) -> KeyPressResult {
+        let dom = match self.document() {
+            Some(dom) => dom,
+            None => return KeyPressResult::Unchanged,
+        };
+        let target = match active_element(&dom.borrow()) {
+            Some(target) => target,
+            None => return KeyPressResult::Unchanged,
+        };
+
+        let key_name = match key {
+            '\n' => "Enter".to_string(),
+            '\t' => "Tab".to_string(),
+            '\x08' | '\x7f' => "Backspace".to_string(),
+            c => c.to_string(),
+        };
+        let mut event = Event::new_keyboard_event("keydown", &key_name);
+        let mut is_focus_changed = false;
+        if dispatch_event(&dom, EventTarget::Node(target), &mut event) {
+            let is_link = dom
+                .borrow()
+                .element(target)
+                .is_some_and(|e| e.kind() == ElementKind::A && e.get_attribute("href").is_some());
+            if key_name == "Tab" {
+                let direction = if shift {
+                    FocusDirection::Backward
+                } else {
+                    FocusDirection::Forward
+                };
+                is_focus_changed = self.move_focus(&dom, direction);
+            } else if key_name == "Enter" && is_link {
+                // キーボードから選択された場合は、座標が(0, 0)のclickイベントを送る
+                // https://html.spec.whatwg.org/multipage/interaction.html#fire-a-synthetic-pointer-event
+                if let Some(href) = self.fire_click(&dom, target, (0, 0)) {
+                    return KeyPressResult::Navigate(href);
+                }
+            } else {
+                let edited = edit_text(&mut dom.borrow_mut(), target, key, &key_name);
+                if let Some((host, data)) = edited {
+                    let mut event = Event::new_input_event(data);
+                    dispatch_event(&dom, EventTarget::Node(host), &mut event);
                 }
             }
         }
 
-        None
+        if self.update_rendering() {
+            KeyPressResult::Repaint
+        } else if is_focus_changed {
+            self.paint_tree();
+            KeyPressResult::Repaint
+        } else {
+            KeyPressResult::Unchanged
+        }
+    }
+
+    /// Tabキーで移す順番に従って、フォーカスを次の要素へ移す。最後の要素の次は
+    /// 最初の要素に戻る。描画されていない要素は飛ばす。フォーカスが移った場合は
+    /// trueを返す
+    /// https://html.spec.whatwg.org/multipage/interaction.html#sequential-focus-navigation
+    fn move_focus(&mut self, dom: &Rc<RefCell<Document>>, direction: FocusDirection) -> bool {
+        let (order, current) = {
+            let dom = dom.borrow();
+            let order: Vec<NodeId> = sequential_focus_order(&dom)
+                .into_iter()
+                .filter(|n| {
+                    self.layout_view
+                        .as_ref()
+                        .is_some_and(|v| v.find_layout_object(*n).is_some())
+                })
+                .collect();
+            (order, dom.focused_element())
+        };
+        if order.is_empty() {
+            return false;
+        }
+
+        let len = order.len();
+        let index = current.and_then(|c| order.iter().position(|n| *n == c));
+        let next = match (index, direction) {
+            (Some(i), FocusDirection::Forward) => (i + 1) % len,
+            (Some(i), FocusDirection::Backward) => (i + len - 1) % len,
+            (None, FocusDirection::Forward) => 0,
+            (None, FocusDirection::Backward) => len - 1,
+        };
+        focus(dom, Some(order[next]))
+    }
+
+    /// ユーザーのスタイルシートが変わったときなどに、入れ子の文書も含めて
+    /// スタイルを計算し直して描画する
+    pub fn restyle(&mut self) {
+        for frame in &self.frames {
+            frame.page.borrow_mut().restyle();
+        }
+        self.render();
+    }
+
+    /// イベントのリスナなどによってDOMが変更されていれば、描画し直す。表示が
+    /// 変わった場合はtrueを返す
+    fn update_rendering(&mut self) -> bool {
+        let needs_style_recalc = self
+            .document()
+            .is_some_and(|dom| dom.borrow().needs_style_recalc());
+        if !needs_style_recalc {
+            return false;
+        }
+        self.render();
+        true
+    }
+}
+
+/// `key`によって`target`を含む編集可能な要素のテキストを編集し、その要素と
+/// 挿入した文字列を返す。編集しなかった場合はNoneを返す
+fn edit_text(
+    dom: &mut Document,
+    target: NodeId,
+    key: char,
+    key_name: &str,
+) -> Option<(NodeId, Option<String>)> {
+    let host = editing_host(dom, target)?;
+    if key_name == "Backspace" {
+        if !delete_last_char(dom, host) {
+            return None;
+        }
+        return Some((host, None));
+    }
+    if key.is_control() {
+        return None;
+    }
+    insert_text(dom, host, key_name);
+    Some((host, Some(key_name.to_string())))
+}
+
+/// `node`を含む編集可能な要素を返す。contenteditable属性を持つ最も近い祖先の
+/// 値が"false"の場合は編集できない
+/// https://html.spec.whatwg.org/multipage/interaction.html#editing-host
+fn editing_host(dom: &Document, node: NodeId) -> Option<NodeId> {
+    let host = iter::once(node).chain(ancestors(dom, node)).find(|n| {
+        dom.element(*n)
+            .is_some_and(|e| e.get_attribute("contenteditable").is_some())
+    })?;
+    match dom.element(host)?.get_attribute("contenteditable") {
+        Some(value) if value.eq_ignore_ascii_case("false") => None,
+        _ => Some(host),
+    }
+}
+
+/// 編集可能な要素の末尾に文字列を挿入する
+fn insert_text(dom: &mut Document, host: NodeId, data: &str) {
+    if let Some(last) = dom.last_child(host) {
+        if let NodeKind::Text(s) = dom.kind_mut(last) {
+            s.push_str(data);
+            return;
+        }
+    }
+    let text = dom.create_text(data);
+    dom.insert(text, host, None);
+}
+
+/// 編集可能な要素の末尾の文字を削除する。削除する文字がない場合はfalseを返す
+fn delete_last_char(dom: &mut Document, host: NodeId) -> bool {
+    match dom.last_child(host) {
+        Some(last) => match dom.kind_mut(last) {
+            NodeKind::Text(s) => s.pop().is_some(),
+            _ => false,
+        },
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::CHAR_HEIGHT_WITH_PADDING;
+    use crate::constants::CHAR_WIDTH;
+    use crate::renderer::accessibility::Role;
+    use crate::renderer::css::user_agent::user_agent_style_sheet;
+    use crate::renderer::dom::event::add_event_listener;
+    use crate::renderer::dom::event::EventCallback;
+    use crate::renderer::dom::event::EventDetail;
+    use crate::renderer::layout::computed_style::Color;
+    use crate::renderer::layout::layout_object::LayoutPoint;
+    use alloc::vec;
+
+    fn create_response(body: &str) -> HttpResponse {
+        HttpResponse::new("HTTP/1.1 200 OK\n\n".to_string() + body)
+            .expect("failed to parse http response")
+    }
+
+    fn create_page() -> Page {
+        Page::new(Rc::new(user_agent_style_sheet()))
+    }
+
+    fn background_colors(page: &Page) -> Vec<Color> {
+        page.display_items()
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Rect { style, .. } => Some(style.background_color()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_inline_style() {
+        let html = "<html><head><style>#a{background-color:green;}p{background-color:green;}\
+</style></head><body><p id=a style=\"background-color: blue\">a</p><p id=b>b</p>\
+<script>var b=document.getElementById(\"b\"); b.style.backgroundColor=\"red\";</script>\
+</body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+
+        // style属性の宣言はIDセレクタより優先され、スクリプトでの変更も反映される
+        assert_eq!(
+            vec![
+                Color::from_name("white").unwrap(),
+                Color::from_name("blue").unwrap(),
+                Color::from_name("red").unwrap(),
+            ],
+            background_colors(&page)
+        );
+    }
+
+    #[test]
+    fn test_hidden_elements_in_body() {
+        let html = "<html><head><title>t</title></head><body><p>hi</p>\
+<script>var a=1;</script><style>p{color:red;}</style></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+
+        // <script>と<style>の内容は、<body>の中にあっても描画しない
+        let texts: Vec<String> = page
+            .display_items()
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { text, .. } => Some(text.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec!["hi".to_string()], texts);
+    }
+
+    #[test]
+    fn test_user_style_sheet() {
+        let browser = Browser::new();
+        browser.borrow_mut().set_user_style_sheet(
+            "p{background-color:yellow !important;}h1{background-color:blue;}".to_string(),
+        );
+        let html = "<html><head><style>p{background-color:green;}h1{background-color:red;}\
+</style></head><body><p>a</p><h1>b</h1></body></html>";
+        let page = browser.borrow().current_page();
+        page.borrow_mut()
+            .receive_response("http://example.com/".to_string(), create_response(html));
+
+        // ユーザーの重要な宣言は作者の宣言より優先され、通常の宣言は作者の宣言に負ける
+        assert_eq!(
+            vec![
+                Color::from_name("white").unwrap(),
+                Color::from_name("yellow").unwrap(),
+                Color::from_name("red").unwrap(),
+            ],
+            background_colors(&page.borrow())
+        );
+
+        // 表示中のページに新しいユーザーのスタイルシートを反映する
+        browser
+            .borrow_mut()
+            .set_user_style_sheet("h1{background-color:lime !important;}".to_string());
+        page.borrow_mut().restyle();
+        assert_eq!(
+            vec![
+                Color::from_name("white").unwrap(),
+                Color::from_name("green").unwrap(),
+                Color::from_name("lime").unwrap(),
+            ],
+            background_colors(&page.borrow())
+        );
+    }
+
+    #[test]
+    fn test_accessibility_tree() {
+        let browser = Browser::new();
+        let page = browser.borrow().current_page();
+        assert!(page.borrow().accessibility_tree().is_none());
+
+        let html = "<html><head><title>Top</title></head><body><h1>Hello</h1>\
+<p><a href=\"/\">home</a> <a href=\"/other\">other</a></p></body></html>";
+        page.borrow_mut()
+            .receive_response("http://example.com/".to_string(), create_response(html));
+
+        let tree = page
+            .borrow()
+            .accessibility_tree()
+            .expect("failed to create an accessibility tree");
+        let root = tree.root();
+        assert_eq!((Role::Document, "Top"), (root.role(), root.name()));
+
+        let heading = &root.children()[0];
+        assert_eq!((Role::Heading, "Hello"), (heading.role(), heading.name()));
+        assert_eq!(Some(1), heading.level());
+        let (_, size) = heading.bounds().expect("heading has no bounds");
+        assert!(size.width() > 0 && size.height() > 0);
+
+        // 開いたページへのリンクは訪問済みになる
+        let links: Vec<(&str, bool, bool)> = root.children()[1]
+            .children()
+            .iter()
+            .filter(|n| n.role() == Role::Link)
+            .map(|n| (n.name(), n.states().focusable, n.states().visited))
+            .collect();
+        assert_eq!(vec![("home", true, true), ("other", true, false)], links);
+    }
+
+    #[test]
+    fn test_external_style_sheet() {
+        let html = "<html><head><style>p{background-color:red;}</style>\
+<link rel=\"stylesheet\" href=\"css/a.css\"></head><body><p>a</p></body></html>";
+        let mut page = create_page();
+        page.receive_response(
+            "http://example.com/dir/index.html".to_string(),
+            create_response(html),
+        );
+
+        // スタイルシートが揃うまで描画しない
+        assert!(page.is_loading());
+        assert!(page.display_items().is_empty());
+
+        let request = page
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        assert_eq!(
+            "http://example.com/dir/css/a.css".to_string(),
+            request.url()
+        );
+        assert_eq!(None, page.next_subresource_request());
+
+        page.receive_subresource(request, Ok(create_response("p{background-color:blue;}")));
+        assert!(!page.is_loading());
+        // 後に現れるスタイルシートのルールが優先される
+        assert_eq!(
+            vec![
+                Color::from_name("white").unwrap(),
+                Color::from_name("blue").unwrap()
+            ],
+            background_colors(&page)
+        );
+    }
+
+    #[test]
+    fn test_failed_style_sheet() {
+        let html = "<html><head><link rel=\"stylesheet\" href=\"/a.css\">\
+<style>p{background-color:red;}</style></head><body><p>a</p></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+
+        let request = page
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        page.receive_subresource(
+            request,
+            Err(Error::Network("failed to get http response".to_string())),
+        );
+
+        assert!(!page.is_loading());
+        assert_eq!(
+            vec![
+                Color::from_name("white").unwrap(),
+                Color::from_name("red").unwrap()
+            ],
+            background_colors(&page)
+        );
+    }
+
+    #[test]
+    fn test_stop_waiting_for_style_sheets() {
+        let html = "<html><head><link rel=\"stylesheet\" href=\"/a.css\">\
+<style>p{background-color:red;}</style></head><body><p>a</p></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+        assert!(page.display_items().is_empty());
+
+        // 待つのをやめると、揃っているスタイルシートだけで描画する
+        page.stop_waiting_for_style_sheets();
+        assert!(page.is_loading());
+        assert_eq!(
+            vec![
+                Color::from_name("white").unwrap(),
+                Color::from_name("red").unwrap()
+            ],
+            background_colors(&page)
+        );
+
+        // 後から届いたスタイルシートも文書順に反映する
+        let request = page
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        page.receive_subresource(request, Ok(create_response("body{background-color:blue;}")));
+        assert!(!page.is_loading());
+        assert_eq!(
+            vec![
+                Color::from_name("blue").unwrap(),
+                Color::from_name("red").unwrap()
+            ],
+            background_colors(&page)
+        );
+    }
+
+    #[test]
+    fn test_script_order() {
+        let html = "<html><head><title></title>\
+<script src=\"defer.js\" defer></script>\
+<script src=\"async.js\" async></script>\
+<script>document.title=document.title+\"1\";</script>\
+<script src=\"block.js\"></script>\
+<script>document.title=document.title+\"3\";</script>\
+</head><body></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+
+        // パーサをブロックするスクリプトでパースが中断している
+        assert_eq!("1".to_string(), page.title());
+        let mut requests = Vec::new();
+        while let Some(request) = page.next_subresource_request() {
+            requests.push(request);
+        }
+        assert_eq!(
+            vec![
+                "http://example.com/defer.js".to_string(),
+                "http://example.com/async.js".to_string(),
+                "http://example.com/block.js".to_string(),
+            ],
+            requests.iter().map(|r| r.url()).collect::<Vec<String>>()
+        );
+
+        // 遅延スクリプトはパースが終わるまで実行しない
+        page.receive_subresource(
+            requests[0].clone(),
+            Ok(create_response("document.title=document.title+\"D\";")),
+        );
+        assert_eq!("1".to_string(), page.title());
+
+        // 非同期スクリプトは取得できしだい実行する
+        page.receive_subresource(
+            requests[1].clone(),
+            Ok(create_response("document.title=document.title+\"A\";")),
+        );
+        assert_eq!("1A".to_string(), page.title());
+        assert!(page.is_loading());
+
+        page.receive_subresource(
+            requests[2].clone(),
+            Ok(create_response("document.title=document.title+\"2\";")),
+        );
+        assert_eq!("1A23D".to_string(), page.title());
+        assert!(!page.is_loading());
+    }
+
+    #[test]
+    fn test_script_blocking_style_sheet() {
+        let html = "<html><head><title></title>\
+<link rel=\"stylesheet\" href=\"a.css\">\
+<script src=\"block.js\"></script>\
+<link rel=\"stylesheet\" href=\"b.css\">\
+<script>document.title=document.title+\"2\";</script>\
+</head><body></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+
+        // <link>要素のスタイルシートは、後に続くスクリプトより先に取得を始める
+        let mut requests = Vec::new();
+        while let Some(request) = page.next_subresource_request() {
+            requests.push(request);
+        }
+        assert_eq!(
+            vec![
+                "http://example.com/a.css".to_string(),
+                "http://example.com/block.js".to_string(),
+            ],
+            requests.iter().map(|r| r.url()).collect::<Vec<String>>()
+        );
+
+        // 前にあるスタイルシートが揃うまで、スクリプトを実行しない
+        page.receive_subresource(
+            requests[1].clone(),
+            Ok(create_response("document.title=document.title+\"1\";")),
+        );
+        assert_eq!("".to_string(), page.title());
+        page.receive_subresource(requests[0].clone(), Ok(create_response("")));
+        assert_eq!("1".to_string(), page.title());
+
+        // インラインのスクリプトも同じように待つ
+        let request = page
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        assert_eq!("http://example.com/b.css".to_string(), request.url());
+        assert_eq!("1".to_string(), page.title());
+        page.receive_subresource(request, Ok(create_response("")));
+        assert_eq!("12".to_string(), page.title());
+        assert!(!page.is_loading());
+    }
+
+    #[test]
+    fn test_failed_script() {
+        let html = "<html><head><title>a</title><script src=\"/a.js\"></script>\
+<script>document.title=document.title+\"b\";</script></head><body></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+        assert!(page.is_loading());
+
+        let request = page
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        page.receive_subresource(
+            request,
+            Err(Error::Network("failed to get http response".to_string())),
+        );
+
+        // 取得できなかったスクリプトは実行せずに、パースを再開する
+        assert_eq!("ab".to_string(), page.title());
+        assert!(!page.is_loading());
+    }
+
+    #[test]
+    fn test_image() {
+        let html = "<html><head></head><body>\
+<img src=\"a.bmp\" alt=\"alt\"><img src=\"a.bmp\" width=\"4\"></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+
+        // 画像を取得するまでは、alt属性のテキストを描画する
+        assert!(page.is_loading());
+        assert!(page
+            .display_items()
+            .iter()
+            .any(|item| matches!(item, DisplayItem::Text { text, .. } if text == "alt")));
+
+        let request = page
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        assert_eq!("http://example.com/a.bmp".to_string(), request.url());
+        assert_eq!(None, page.next_subresource_request());
+
+        // 2x1ピクセルの24ビットBMP
+        let mut bmp = vec![
+            b'B', b'M', 62, 0, 0, 0, 0, 0, 0, 0, 54, 0, 0, 0, 40, 0, 0, 0,
+        ];
+        bmp.extend_from_slice(&[2, 0, 0, 0, 1, 0, 0, 0, 1, 0, 24, 0]);
+        bmp.extend_from_slice(&[0; 24]);
+        bmp.extend_from_slice(&[0, 0, 255, 0, 255, 0, 0, 0]);
+        let mut raw_response = b"HTTP/1.1 200 OK\n\n".to_vec();
+        raw_response.extend_from_slice(&bmp);
+        let response =
+            HttpResponse::from_bytes(raw_response).expect("failed to parse http response");
+        page.receive_subresource(request, Ok(response));
+        assert!(!page.is_loading());
+
+        let sizes: Vec<(i64, i64)> = page
+            .display_items()
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Image { layout_size, .. } => {
+                    Some((layout_size.width(), layout_size.height()))
+                }
+                _ => None,
+            })
+            .collect();
+        // width属性だけが指定されている場合は、縦横比を保つ
+        assert_eq!(vec![(2, 1), (4, 2)], sizes);
+    }
+
+    #[test]
+    fn test_refresh() {
+        let html = "<html><head><meta http-equiv=\"Refresh\" content=\"3; url=next.html\">\
+<meta http-equiv=\"refresh\" content=\"1\"></head><body></body></html>";
+        let mut page = create_page();
+        page.receive_response(
+            "http://example.com/dir/index.html".to_string(),
+            create_response(html),
+        );
+        // 最初に現れた<meta>だけが有効になる
+        assert_eq!(
+            Some(Refresh::new(
+                3,
+                "http://example.com/dir/next.html".to_string()
+            )),
+            page.refresh()
+        );
+
+        // Refreshヘッダは<meta>よりも優先される
+        let response = HttpResponse::new("HTTP/1.1 200 OK\nRefresh: 10\n\n".to_string() + html)
+            .expect("failed to parse http response");
+        page.receive_response("http://example.com/dir/index.html".to_string(), response);
+        assert_eq!(
+            Some(Refresh::new(
+                10,
+                "http://example.com/dir/index.html".to_string()
+            )),
+            page.refresh()
+        );
+
+        // 新しいページを読み込むと予約は取り消される
+        page.receive_response(
+            "http://example.com/".to_string(),
+            create_response("<html><head></head><body></body></html>"),
+        );
+        assert_eq!(None, page.refresh());
+    }
+
+    #[test]
+    fn test_iframe() {
+        let html = "<html><head></head><body>\
+<iframe src=\"frame.html\" width=\"100\" height=\"50\">fallback</iframe></body></html>";
+        let mut page = create_page();
+        page.receive_response(
+            "http://example.com/dir/index.html".to_string(),
+            create_response(html),
+        );
+
+        let request = page
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        assert_eq!(
+            "http://example.com/dir/frame.html".to_string(),
+            request.url()
+        );
+        page.receive_subresource(
+            request,
+            Ok(create_response(
+                "<html><head><link rel=\"stylesheet\" href=\"a.css\"></head>\
+<body><p><a href=\"next.html\">link</a></p></body></html>",
+            )),
+        );
+
+        // 入れ子の文書のサブリソースも、親のページから取得する
+        assert!(page.is_loading());
+        let request = page
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        assert_eq!("http://example.com/dir/a.css".to_string(), request.url());
+        page.receive_subresource(request, Ok(create_response("p{background-color:red;}")));
+        assert!(!page.is_loading());
+
+        // 入れ子の文書は<iframe>の大きさに切り取られ、代替コンテンツは描画しない
+        let items = page.display_items();
+        let red = Color::from_name("red").unwrap();
+        let red_sizes: Vec<(i64, i64)> = items
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Rect {
+                    style, layout_size, ..
+                } if style.background_color() == red => {
+                    Some((layout_size.width(), layout_size.height()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(vec![(100, CHAR_HEIGHT_WITH_PADDING)], red_sizes);
+        assert!(items.iter().all(|item| match item {
+            DisplayItem::Text { text, .. } => text == "link",
+            _ => true,
+        }));
+
+        // <iframe>の中のリンクをクリックすると、入れ子の文書だけが移動する
+        let text_point = items
+            .iter()
+            .find_map(|item| match item {
+                DisplayItem::Text { layout_point, .. } => Some(*layout_point),
+                _ => None,
+            })
+            .expect("failed to find a link");
+        assert_eq!(None, page.clicked((text_point.x() + 1, text_point.y() + 1)));
+        assert_eq!(
+            Some("http://example.com/dir/next.html".to_string()),
+            page.next_subresource_request().map(|r| r.url())
+        );
+    }
+
+    #[test]
+    fn test_iframe_history() {
+        let browser = Browser::new();
+        let page = browser.borrow().current_page();
+        let html = "<html><head></head><body><iframe src=\"frame.html\"></iframe></body></html>";
+        page.borrow_mut()
+            .receive_response("http://example.com/".to_string(), create_response(html));
+
+        let request = page
+            .borrow_mut()
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        page.borrow_mut().receive_subresource(
+            request,
+            Ok(create_response("<html><head></head><body></body></html>")),
+        );
+
+        // 入れ子の文書のURLは訪問済みとして記録しない
+        assert!(browser.borrow().is_visited("http://example.com/"));
+        assert!(!browser.borrow().is_visited("http://example.com/frame.html"));
+    }
+
+    #[test]
+    fn test_same_url_subresources() {
+        let html = "<html><head><link rel=\"stylesheet\" href=\"a.css\"></head><body><p>a</p>\
+<iframe src=\"frame.html\" width=\"50\" height=\"50\"></iframe></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+
+        let parent_sheet = page
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        let frame = page
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        page.receive_subresource(
+            frame,
+            Ok(create_response(
+                "<html><head><link rel=\"stylesheet\" href=\"a.css\"></head>\
+<body><p>b</p></body></html>",
+            )),
+        );
+        let frame_sheet = page
+            .next_subresource_request()
+            .expect("failed to get a subresource request");
+        assert_eq!(parent_sheet.url(), frame_sheet.url());
+
+        // 同じURLでも、要求した文書にそれぞれの結果が届く
+        page.receive_subresource(
+            parent_sheet,
+            Ok(create_response("p{background-color:blue;}")),
+        );
+        page.receive_subresource(frame_sheet, Ok(create_response("p{background-color:red;}")));
+        assert!(!page.is_loading());
+
+        let widths = |color: &str| -> Vec<i64> {
+            let color = Color::from_name(color).unwrap();
+            page.display_items()
+                .iter()
+                .filter_map(|item| match item {
+                    DisplayItem::Rect {
+                        style, layout_size, ..
+                    } if style.background_color() == color => Some(layout_size.width()),
+                    _ => None,
+                })
+                .collect()
+        };
+        // 入れ子の文書の<p>要素だけが<iframe>の幅に切り取られる
+        assert_eq!(vec![50], widths("red"));
+        assert!(widths("blue").iter().all(|width| *width > 50));
+        assert_eq!(1, widths("blue").len());
+    }
+
+    #[test]
+    fn test_ready_state() {
+        let html = "<html><head><title></title>\
+<script src=\"defer.js\" defer></script>\
+<script>document.title=document.readyState;</script>\
+<link rel=\"stylesheet\" href=\"a.css\">\
+</head><body></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+        assert_eq!("loading".to_string(), page.title());
+        assert_eq!(DocumentReadyState::Interactive, page.ready_state());
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let dom = page.document().expect("failed to get a document");
+        let root = dom.borrow().root();
+        for (target, event_type) in [
+            (EventTarget::Window, "DOMContentLoaded"),
+            (EventTarget::Window, "load"),
+            (EventTarget::Node(root), "readystatechange"),
+        ] {
+            let log = events.clone();
+            let callback: EventCallback = Rc::new(move |event: &mut Event| {
+                log.borrow_mut().push(event.event_type().to_string());
+            });
+            add_event_listener(
+                &mut dom.borrow_mut(),
+                target,
+                event_type,
+                callback,
+                Default::default(),
+            );
+        }
+
+        let mut requests = Vec::new();
+        while let Some(request) = page.next_subresource_request() {
+            requests.push(request);
+        }
+        assert_eq!(
+            vec![
+                "http://example.com/defer.js".to_string(),
+                "http://example.com/a.css".to_string(),
+            ],
+            requests.iter().map(|r| r.url()).collect::<Vec<String>>()
+        );
+
+        // 遅延スクリプトを実行してからDOMContentLoadedイベントを送る
+        page.receive_subresource(
+            requests[0].clone(),
+            Ok(create_response(
+                "document.title=document.title+document.readyState;",
+            )),
+        );
+        assert_eq!("loadinginteractive".to_string(), page.title());
+        assert_eq!(vec!["DOMContentLoaded".to_string()], *events.borrow());
+        assert_eq!(DocumentReadyState::Interactive, page.ready_state());
+
+        // サブリソースをすべて読み込むとloadイベントを送る
+        page.receive_subresource(requests[1].clone(), Ok(create_response("")));
+        assert_eq!(DocumentReadyState::Complete, page.ready_state());
+        assert_eq!(
+            vec![
+                "DOMContentLoaded".to_string(),
+                "readystatechange".to_string(),
+                "load".to_string(),
+            ],
+            *events.borrow()
+        );
+    }
+
+    #[test]
+    fn test_click_event() {
+        let html = "<html><head></head><body><p><a href=\"next.html\">link</a></p></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+
+        let text_point = page
+            .display_items()
+            .iter()
+            .find_map(|item| match item {
+                DisplayItem::Text { layout_point, .. } => Some(*layout_point),
+                _ => None,
+            })
+            .expect("failed to find a link");
+        let position = (text_point.x() + 1, text_point.y() + 1);
+        assert_eq!(Some("next.html".to_string()), page.clicked(position));
+
+        // ウィンドウのリスナでキャンセルすると、リンクをたどらない
+        let clicks = Rc::new(RefCell::new(Vec::new()));
+        let log = clicks.clone();
+        let callback: EventCallback = Rc::new(move |event: &mut Event| {
+            log.borrow_mut().push(event.target());
+            event.prevent_default();
+        });
+        let dom = page.document().expect("failed to get a document");
+        add_event_listener(
+            &mut dom.borrow_mut(),
+            EventTarget::Window,
+            "click",
+            callback,
+            Default::default(),
+        );
+        assert_eq!(None, page.clicked(position));
+
+        let a = get_elements_by_kind(&dom.borrow(), ElementKind::A)[0];
+        assert_eq!(vec![Some(EventTarget::Node(a))], *clicks.borrow());
+    }
+
+    #[test]
+    fn test_script_event_listener() {
+        let html = "<html><head></head><body><p><a id=a href=\"next.html\">link</a></p>\
+<script>function cancel(e) { e.preventDefault(); }\
+function stop(e) { e.stopPropagation(); }\
+function log(e) { document.title = \"clicked\"; }\
+var a = document.getElementById(\"a\");\
+a.addEventListener(\"click\", cancel);\
+a.addEventListener(\"click\", stop);\
+document.addEventListener(\"click\", log);</script></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+
+        let text_point = page
+            .display_items()
+            .iter()
+            .find_map(|item| match item {
+                DisplayItem::Text { layout_point, .. } => Some(*layout_point),
+                _ => None,
+            })
+            .expect("failed to find a link");
+        let position = (text_point.x() + 1, text_point.y() + 1);
+
+        // スクリプトのリスナがキャンセルすると、リンクをたどらない。伝播も止まる
+        assert_eq!(None, page.clicked(position));
+        assert_eq!("".to_string(), page.title());
+
+        // 同じ関数を渡すとリスナを削除できる
+        page.execute_script(ScriptSource::Loaded(
+            "var a = document.getElementById(\"a\");\
+a.removeEventListener(\"click\", cancel);\
+a.removeEventListener(\"click\", stop);"
+                .to_string(),
+        ));
+        assert_eq!(Some("next.html".to_string()), page.clicked(position));
+        assert_eq!("clicked".to_string(), page.title());
+    }
+
+    #[test]
+    fn test_focus_navigation() {
+        let html = "<html><head></head><body>\
+<p><a href=\"a.html\">a</a></p><p tabindex=\"-1\">x</p><p><a href=\"b.html\">b</a></p>\
+</body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+        let dom = page.document().expect("failed to get a document");
+        let links = get_elements_by_kind(&dom.borrow(), ElementKind::A);
+        let focused = |dom: &Rc<RefCell<Document>>| dom.borrow().focused_element();
+        let focus_rings = |page: &Page| {
+            page.display_items()
+                .iter()
+                .filter(|item| matches!(item, DisplayItem::FocusRing { .. }))
+                .count()
+        };
+        assert_eq!(0, focus_rings(&page));
+
+        // Tabキーで順番にフォーカスを移し、最後の次は最初に戻る
+        assert_eq!(KeyPressResult::Repaint, page.key_pressed('\t', false));
+        assert_eq!(Some(links[0]), focused(&dom));
+        assert_eq!(1, focus_rings(&page));
+        page.key_pressed('\t', false);
+        assert_eq!(Some(links[1]), focused(&dom));
+        page.key_pressed('\t', false);
+        assert_eq!(Some(links[0]), focused(&dom));
+        page.key_pressed('\t', true);
+        assert_eq!(Some(links[1]), focused(&dom));
+
+        // Enterキーでフォーカスされているリンクをたどる
+        assert_eq!(
+            KeyPressResult::Navigate("b.html".to_string()),
+            page.key_pressed('\n', false)
+        );
+
+        // フォーカスできない場所をクリックするとフォーカスが外れる
+        assert_eq!(None, page.clicked((500, 5)));
+        assert_eq!(None, focused(&dom));
+        assert_eq!(0, focus_rings(&page));
+    }
+
+    #[test]
+    fn test_text_selection() {
+        let html = "<html><head></head><body><p>hello world</p><p>second</p></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+
+        let text_points: Vec<LayoutPoint> = page
+            .display_items()
+            .iter()
+            .filter_map(|item| match item {
+                DisplayItem::Text { layout_point, .. } => Some(*layout_point),
+                _ => None,
+            })
+            .collect();
+        let highlights = |page: &Page| {
+            page.display_items()
+                .iter()
+                .filter(|item| matches!(item, DisplayItem::Highlight { .. }))
+                .count()
+        };
+        let at = |point: LayoutPoint, column: i64| (point.x() + CHAR_WIDTH * column, point.y() + 1);
+
+        // ボタンを押しただけでは何も選択しない
+        assert!(!page.mouse_pressed(at(text_points[0], 6)));
+        assert_eq!(0, highlights(&page));
+        assert_eq!(String::new(), page.selected_text());
+
+        // 行をまたいで、後ろから前に向かって選択する
+        assert!(page.mouse_dragged(at(text_points[1], 3)));
+        assert_eq!("worldsec".to_string(), page.selected_text());
+        assert_eq!(2, highlights(&page));
+        assert!(page.mouse_dragged(at(text_points[0], 2)));
+        assert_eq!("llo ".to_string(), page.selected_text());
+
+        assert!(page.double_clicked(at(text_points[1], 2)));
+        assert_eq!("second".to_string(), page.selected_text());
+
+        // テキストのない位置でボタンを押すと選択を解除する
+        assert!(page.mouse_pressed((500, 100)));
+        assert_eq!(0, highlights(&page));
+    }
+
+    #[test]
+    fn test_key_event() {
+        let html = "<html><head></head><body contenteditable>ab</body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+
+        let inputs = Rc::new(RefCell::new(Vec::new()));
+        let log = inputs.clone();
+        let callback: EventCallback = Rc::new(move |event: &mut Event| {
+            log.borrow_mut().push(event.detail().clone());
+        });
+        let dom = page.document().expect("failed to get a document");
+        let root = dom.borrow().root();
+        add_event_listener(
+            &mut dom.borrow_mut(),
+            EventTarget::Node(root),
+            "input",
+            callback,
+            Default::default(),
+        );
+
+        let text = |page: &Page| -> Vec<String> {
+            page.display_items()
+                .iter()
+                .filter_map(|item| match item {
+                    DisplayItem::Text { text, .. } => Some(text.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        assert_eq!(KeyPressResult::Repaint, page.key_pressed('c', false));
+        assert_eq!(vec!["abc".to_string()], text(&page));
+        assert_eq!(
+            KeyPressResult::Repaint,
+            page.key_pressed(0x08 as char, false)
+        );
+        assert_eq!(vec!["ab".to_string()], text(&page));
+        assert_eq!(KeyPressResult::Unchanged, page.key_pressed('\n', false));
+        assert_eq!(
+            vec![
+                EventDetail::Input {
+                    data: Some("c".to_string())
+                },
+                EventDetail::Input { data: None },
+            ],
+            *inputs.borrow()
+        );
+    }
+
+    #[test]
+    fn test_style_invalidation() {
+        let html = "<html><head><style>.red{background-color:red;}</style></head>\
+<body><p id=p>text</p></body></html>";
+        let mut page = create_page();
+        page.receive_response("http://example.com/".to_string(), create_response(html));
+        let red = Color::from_name("red").unwrap();
+        assert!(!background_colors(&page).contains(&red));
+
+        // クリックされたら<p>要素のクラスを変更する
+        let dom = page.document().expect("failed to get a document");
+        let p = get_elements_by_kind(&dom.borrow(), ElementKind::P)[0];
+        let listener_dom = dom.clone();
+        let callback: EventCallback = Rc::new(move |_: &mut Event| {
+            let mut dom = listener_dom.borrow_mut();
+            let element = dom.element_mut(p).expect("failed to get a p element");
+            element
+                .class_list()
+                .add(&["red"])
+                .expect("failed to add a class");
+        });
+        add_event_listener(
+            &mut dom.borrow_mut(),
+            EventTarget::Node(p),
+            "click",
+            callback,
+            Default::default(),
+        );
+
+        let text_point = page
+            .display_items()
+            .iter()
+            .find_map(|item| match item {
+                DisplayItem::Text { layout_point, .. } => Some(*layout_point),
+                _ => None,
+            })
+            .expect("failed to find a text");
+        assert_eq!(None, page.clicked((text_point.x() + 1, text_point.y() + 1)));
+        assert!(background_colors(&page).contains(&red));
+        assert!(!dom.borrow().needs_style_recalc());
     }
 }
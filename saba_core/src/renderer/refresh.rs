@@ -0,0 +1,121 @@
+use alloc::string::String;
+use alloc::string::ToString;
+
+/// `<meta http-equiv="refresh">`または`Refresh`ヘッダによって予約された
+/// ナビゲーション
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Refresh {
+    /// ナビゲーションを始めるまでの秒数
+    delay: u64,
+    /// 移動先のURL。URLが指定されていない場合は、ページ自身のURLになる
+    url: String,
+}
+
+impl Refresh {
+    pub fn new(delay: u64, url: String) -> Self {
+        Self { delay, url }
+    }
+
+    pub fn delay(&self) -> u64 {
+        self.delay
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// `content`属性や`Refresh`ヘッダの値をパースして、秒数と移動先のURLを返す。
+/// URLが指定されていない場合はNoneになり、ページを再読み込みする
+/// https://html.spec.whatwg.org/multipage/document-lifecycle.html#shared-declarative-refresh-steps
+pub fn parse_refresh(input: &str) -> Option<(u64, Option<String>)> {
+    let is_whitespace = |c: char| c.is_ascii_whitespace();
+    let mut rest = input.trim_start_matches(is_whitespace);
+
+    // 秒数を取得する。小数部分は無視する
+    let digits_len = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    let delay = if digits_len == 0 {
+        if !rest.starts_with('.') {
+            return None;
+        }
+        0
+    } else {
+        rest[..digits_len].parse::<u64>().unwrap_or(u64::MAX)
+    };
+    rest = rest[digits_len..].trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+    if rest.is_empty() {
+        return Some((delay, None));
+    }
+
+    // 秒数とURLの区切り
+    match rest.chars().next() {
+        Some(c) if c == ';' || c == ',' || is_whitespace(c) => {}
+        _ => return None,
+    }
+    rest = rest.trim_start_matches(is_whitespace);
+    if rest.starts_with(';') || rest.starts_with(',') {
+        rest = &rest[1..];
+    }
+    rest = rest.trim_start_matches(is_whitespace);
+
+    if rest.is_empty() {
+        return Some((delay, None));
+    }
+
+    // "url="があれば取り除く
+    let url_string = rest;
+    if rest.get(..3).is_some_and(|s| s.eq_ignore_ascii_case("url")) {
+        let after_url = rest[3..].trim_start_matches(is_whitespace);
+        if let Some(value) = after_url.strip_prefix('=') {
+            rest = value.trim_start_matches(is_whitespace);
+        } else {
+            return Some((delay, Some(url_string.to_string())));
+        }
+    }
+
+    // 引用符で囲まれている場合は、閉じる引用符までをURLとする
+    let url = match rest.chars().next() {
+        Some(quote) if quote == '\'' || quote == '"' => {
+            let value = &rest[1..];
+            match value.find(quote) {
+                Some(end) => &value[..end],
+                None => value,
+            }
+        }
+        _ => rest,
+    };
+
+    Some((delay, Some(url.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reload() {
+        assert_eq!(Some((5, None)), parse_refresh("5"));
+        assert_eq!(Some((0, None)), parse_refresh(" .5; "));
+        assert_eq!(None, parse_refresh("a"));
+        assert_eq!(None, parse_refresh("5a"));
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            Some((3, Some("/next.html".to_string()))),
+            parse_refresh("3; URL = '/next.html'")
+        );
+        assert_eq!(
+            Some((0, Some("http://example.com/".to_string()))),
+            parse_refresh("0,http://example.com/")
+        );
+        assert_eq!(
+            Some((1, Some("a.html".to_string()))),
+            parse_refresh("1.5 url=\"a.html\"b")
+        );
+    }
+}
@@ -51,6 +51,81 @@ impl Url {
         Ok(self.clone())
     }
 
+    /// `input`をこのURLを基準として解決し、絶対URLの文字列を返す。
+    /// このURLはパース済みである必要がある
+    /// https://url.spec.whatwg.org/#concept-basic-url-parser
+    pub fn resolve(&self, input: &str) -> String {
+        let input = input.trim();
+
+        // スキームを含む場合は、すでに絶対URLである
+        if let Some(index) = input.find(':') {
+            let scheme = &input[..index];
+            if !scheme.is_empty()
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+            {
+                return input.to_string();
+            }
+        }
+
+        // スキームだけが省略されている場合
+        if let Some(rest) = input.strip_prefix("//") {
+            return "http://".to_string() + rest;
+        }
+
+        let origin = if self.port == "80" {
+            "http://".to_string() + &self.host
+        } else {
+            "http://".to_string() + &self.host + ":" + &self.port
+        };
+
+        if input.is_empty() || input.starts_with('#') {
+            let mut url = origin + "/" + &self.path;
+            if !self.searchpart.is_empty() {
+                url = url + "?" + &self.searchpart;
+            }
+            return url + input;
+        }
+
+        if input.starts_with('?') {
+            return origin + "/" + &self.path + input;
+        }
+
+        // パスの部分と、クエリやフラグメントの部分に分ける
+        let (path, rest) = match input.find(|c| c == '?' || c == '#') {
+            Some(index) => input.split_at(index),
+            None => (input, ""),
+        };
+
+        let mut segments: Vec<&str> = Vec::new();
+        if !path.starts_with('/') {
+            // 相対パスの場合は、基準となるURLのパスから最後のセグメントを
+            // 取り除いたものに続ける
+            segments = self.path.split('/').collect();
+            segments.pop();
+        }
+
+        let input_segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        let last = input_segments.len() - 1;
+        for (i, segment) in input_segments.iter().enumerate() {
+            match *segment {
+                "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                s => segments.push(s),
+            }
+            // "."や".."で終わる場合は、ディレクトリを表す
+            if i == last && (*segment == "." || *segment == "..") {
+                segments.push("");
+            }
+        }
+
+        origin + "/" + &segments.join("/") + rest
+    }
+
     fn is_http(&mut self) -> bool {
         if self.url.contains("http://") {
             return true;
@@ -212,4 +287,37 @@ mod tests {
 
         assert_eq!(expected, Url::new(url).parse());
     }
+
+    #[test]
+    fn test_resolve() {
+        let base = Url::new("http://example.com:8888/a/b/c.html?q=1".to_string())
+            .parse()
+            .expect("failed to parse a url");
+
+        assert_eq!(
+            "http://example.org/style.css".to_string(),
+            base.resolve("http://example.org/style.css")
+        );
+        assert_eq!(
+            "http://example.org/style.css".to_string(),
+            base.resolve("//example.org/style.css")
+        );
+        assert_eq!(
+            "http://example.com:8888/style.css".to_string(),
+            base.resolve("/style.css")
+        );
+        assert_eq!(
+            "http://example.com:8888/a/b/style.css".to_string(),
+            base.resolve("style.css")
+        );
+        assert_eq!(
+            "http://example.com:8888/a/d/style.css?v=2".to_string(),
+            base.resolve("./../d/style.css?v=2")
+        );
+        assert_eq!("http://example.com:8888/a/".to_string(), base.resolve(".."));
+        assert_eq!(
+            "http://example.com:8888/a/b/c.html?q=1#top".to_string(),
+            base.resolve("#top")
+        );
+    }
 }
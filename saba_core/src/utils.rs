@@ -1,29 +1,26 @@
-use crate::renderer::dom::node::Node;
+use crate::renderer::dom::api::ancestors;
+use crate::renderer::dom::api::inclusive_descendants;
+use crate::renderer::dom::node::Document;
+use crate::renderer::dom::node::NodeId;
 use alloc::format;
-use alloc::rc::Rc;
 use alloc::string::String;
-use core::cell::RefCell;
 
-pub fn convert_dom_to_string(root: &Option<Rc<RefCell<Node>>>) -> String {
+pub fn convert_dom_to_string(document: &Document, root: Option<NodeId>) -> String {
     let mut result = String::from("\n");
-    convert_dom_to_string_internal(root, 0, &mut result);
+    let root = match root {
+        Some(root) => root,
+        None => return result,
+    };
 
-    result
-}
-
-fn convert_dom_to_string_internal(
-    node: &Option<Rc<RefCell<Node>>>,
-    depth: usize,
-    result: &mut String,
-) {
-    match node {
-        Some(n) => {
-            result.push_str(&"  ".repeat(depth));
-            result.push_str(&format!("{:?}", n.borrow().kind()));
-            result.push('\n');
-            convert_dom_to_string_internal(&n.borrow().first_child(), depth + 1, result);
-            convert_dom_to_string_internal(&n.borrow().next_sibling(), depth, result);
-        }
-        None => (),
+    for node in inclusive_descendants(document, root) {
+        // `root`からの深さの分だけインデントする
+        let depth = ancestors(document, node)
+            .position(|a| a == root)
+            .map_or(0, |p| p + 1);
+        result.push_str(&"  ".repeat(depth));
+        result.push_str(&format!("{:?}", document.kind(node)));
+        result.push('\n');
     }
+
+    result
 }
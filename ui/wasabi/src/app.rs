@@ -1,7 +1,9 @@
 use crate::cursor::Cursor;
 use alloc::string::String;
 use alloc::{format, rc::Rc, string::ToString};
+use core::arch::x86_64::{__cpuid, _rdtsc};
 use core::cell::RefCell;
+use core::sync::atomic::{AtomicU64, Ordering};
 use noli::error::Result as OsResult;
 use noli::prelude::SystemApi;
 use noli::println;
@@ -15,7 +17,11 @@ use saba_core::constants::*;
 use saba_core::display_item::DisplayItem;
 use saba_core::error::Error;
 use saba_core::http::HttpResponse;
+use saba_core::image::Image;
+use saba_core::renderer::dom::node::DocumentReadyState;
 use saba_core::renderer::layout::computed_style::{FontSize, TextDecoration};
+use saba_core::renderer::layout::layout_object::{LayoutPoint, LayoutSize};
+use saba_core::renderer::page::KeyPressResult;
 
 fn convert_font_size(size: FontSize) -> StringSize {
     match size {
@@ -25,6 +31,67 @@ fn convert_font_size(size: FontSize) -> StringSize {
     }
 }
 
+/// CPUIDから周波数を得られないときに仮定する、タイムスタンプカウンタが1ミリ秒に
+/// 進む数 (2GHz)。時刻はナビゲーションの予約とスタイルシートの待ち時間にしか
+/// 使わないので、この仮定が外れても表示が早まるか遅れるだけで済む
+const FALLBACK_TSC_TICKS_PER_MILLISECOND: u64 = 2_000_000;
+
+/// 計測したタイムスタンプカウンタの周波数。0はまだ計測していないことを表す
+static TSC_TICKS_PER_MILLISECOND: AtomicU64 = AtomicU64::new(0);
+
+/// タイムスタンプカウンタが1ミリ秒に進む数をCPUIDから求める
+/// https://www.intel.com/content/www/us/en/developer/articles/technical/intel-sdm.html
+fn tsc_ticks_per_millisecond() -> u64 {
+    let cached = TSC_TICKS_PER_MILLISECOND.load(Ordering::Relaxed);
+    if cached != 0 {
+        return cached;
+    }
+
+    // CPUID命令はプロセッサの情報を読むだけで、副作用はない
+    let max_leaf = unsafe { __cpuid(0) }.eax;
+    let mut ticks = 0;
+    if max_leaf >= 0x15 {
+        // リーフ0x15: EBX/EAXがクリスタル周波数に対するTSC周波数の比、ECXがクリスタル周波数 (Hz)
+        let leaf = unsafe { __cpuid(0x15) };
+        if leaf.eax != 0 && leaf.ecx != 0 {
+            ticks = leaf.ecx as u64 * leaf.ebx as u64 / leaf.eax as u64 / 1000;
+        }
+    }
+    if ticks == 0 && max_leaf >= 0x16 {
+        // リーフ0x16: EAXがプロセッサのベース周波数 (MHz)
+        ticks = (unsafe { __cpuid(0x16) }.eax & 0xFFFF) as u64 * 1000;
+    }
+    if ticks == 0 {
+        ticks = FALLBACK_TSC_TICKS_PER_MILLISECOND;
+    }
+
+    TSC_TICKS_PER_MILLISECOND.store(ticks, Ordering::Relaxed);
+    ticks
+}
+
+/// 起動してからのおおよその経過時間をミリ秒で返す
+fn current_time_ms() -> u64 {
+    // RDTSC命令はタイムスタンプカウンタを読むだけで、副作用はない
+    unsafe { _rdtsc() / tsc_ticks_per_millisecond() }
+}
+
+/// スタイルシートが揃うのを待ってから最初に描画するまでの最大の時間 (ミリ秒)
+const STYLE_SHEET_TIMEOUT_MS: u64 = 3000;
+
+/// アドレスバーに入力すると、続くURLをユーザーのスタイルシートとして読み込む接頭辞
+const USER_STYLE_SHEET_PREFIX: &str = "userstyle:";
+
+/// 2回のクリックをダブルクリックとみなす時間 (ミリ秒)
+const DOUBLE_CLICK_INTERVAL_MS: u64 = 500;
+
+/// `<meta http-equiv="refresh">`などで予約されたナビゲーション
+#[derive(Debug, Clone)]
+struct RefreshTimer {
+    /// ナビゲーションを始める時刻 (ミリ秒)
+    deadline: u64,
+    url: String,
+}
+
 #[derive(Debug)]
 pub struct WasabiUI {
     browser: Rc<RefCell<Browser>>,
@@ -32,6 +99,15 @@ pub struct WasabiUI {
     input_mode: InputMode,
     window: Window,
     cursor: Cursor,
+    /// 直前のマウスカーソルの位置
+    mouse_position: (i64, i64),
+    /// 直前にマウスのボタンが押されていたかどうか
+    is_mouse_pressed: bool,
+    /// 直前にボタンが押された時刻と、コンテンツ領域の中の位置
+    last_press: Option<(u64, (i64, i64))>,
+    /// コピーしたテキスト
+    clipboard: String,
+    refresh_timer: Option<RefreshTimer>,
 }
 
 impl WasabiUI {
@@ -50,6 +126,11 @@ impl WasabiUI {
             )
             .unwrap(),
             cursor: Cursor::new(),
+            mouse_position: (0, 0),
+            is_mouse_pressed: false,
+            last_press: None,
+            clipboard: String::new(),
+            refresh_timer: None,
         }
     }
 
@@ -69,27 +150,50 @@ impl WasabiUI {
             TOOLBAR_HEIGHT + 1,
         )?;
 
+        // タブの背景の四角を描画
+        self.window
+            .fill_rect(WHITE, 2, 2, TAB_WIDTH, TAB_HEIGHT - 2)?;
+        self.window
+            .draw_line(GREY, 0, TAB_HEIGHT, WINDOW_WIDTH - 1, TAB_HEIGHT)?;
+
         // アドレスバーの横に"Address:"という文字を描画
         self.window.draw_string(
             BLACK,
             5,
-            5,
+            TAB_HEIGHT + 5,
             "Address",
             StringSize::Medium,
             /*underline=*/ false,
         )?;
 
         // アドレスバーの四角を描画
-        self.window
-            .fill_rect(WHITE, 70, 2, WINDOW_WIDTH - 74, 2 + ADDRESSBAR_HEIGHT)?;
+        self.window.fill_rect(
+            WHITE,
+            70,
+            TAB_HEIGHT + 2,
+            WINDOW_WIDTH - 74,
+            2 + ADDRESSBAR_HEIGHT,
+        )?;
 
         // アドレスバーの影の線を描画
-        self.window.draw_line(GREY, 70, 2, WINDOW_WIDTH - 4, 2)?;
         self.window
-            .draw_line(GREY, 70, 2, 70, 2 + ADDRESSBAR_HEIGHT)?;
-        self.window.draw_line(BLACK, 71, 3, WINDOW_WIDTH - 5, 3)?;
+            .draw_line(GREY, 70, TAB_HEIGHT + 2, WINDOW_WIDTH - 4, TAB_HEIGHT + 2)?;
+        self.window.draw_line(
+            GREY,
+            70,
+            TAB_HEIGHT + 2,
+            70,
+            TAB_HEIGHT + 2 + ADDRESSBAR_HEIGHT,
+        )?;
         self.window
-            .draw_line(GREY, 71, 3, 71, 1 + ADDRESSBAR_HEIGHT)?;
+            .draw_line(BLACK, 71, TAB_HEIGHT + 3, WINDOW_WIDTH - 5, TAB_HEIGHT + 3)?;
+        self.window.draw_line(
+            GREY,
+            71,
+            TAB_HEIGHT + 3,
+            71,
+            TAB_HEIGHT + 1 + ADDRESSBAR_HEIGHT,
+        )?;
 
         Ok(())
     }
@@ -125,6 +229,7 @@ impl WasabiUI {
         loop {
             self.handle_key_input(handle_url)?;
             self.handle_mouse_input(handle_url)?;
+            self.handle_refresh_timer(handle_url)?;
         }
     }
 
@@ -138,7 +243,37 @@ impl WasabiUI {
             self.window.flush_area(self.cursor.rect());
             self.cursor.flush();
 
-            if button.l() || button.c() || button.r() {
+            let is_pressed = button.l() || button.c() || button.r();
+            let was_pressed = self.is_mouse_pressed;
+            self.is_mouse_pressed = is_pressed;
+
+            // カーソルがコンテンツ領域の中で動いたときは、ページに知らせる。ボタンを
+            // 押したまま動いたときは、テキストの選択範囲を広げる
+            if self.mouse_position != (position.x, position.y) {
+                self.mouse_position = (position.x, position.y);
+                let position_in_content_area = (
+                    position.x - WINDOW_INIT_X_POS,
+                    position.y - WINDOW_INIT_Y_POS - TITLE_BAR_HEIGHT - TOOLBAR_HEIGHT,
+                );
+                if 0 <= position_in_content_area.0
+                    && position_in_content_area.0 <= CONTENT_AREA_WIDTH
+                    && 0 <= position_in_content_area.1
+                    && position_in_content_area.1 <= CONTENT_AREA_HEIGHT
+                {
+                    let page = self.browser.borrow().current_page();
+                    let mut is_changed = page.borrow_mut().mouse_moved(position_in_content_area);
+                    if is_pressed && was_pressed {
+                        is_changed |= page.borrow_mut().mouse_dragged(position_in_content_area);
+                    }
+                    if is_changed {
+                        self.clear_content_area()?;
+                        self.update_ui()?;
+                    }
+                }
+            }
+
+            // ボタンが押された瞬間だけを扱う
+            if is_pressed && !was_pressed {
                 // 相対位置を計算する
                 let relative_pos = (
                     position.x - WINDOW_INIT_X_POS,
@@ -156,9 +291,9 @@ impl WasabiUI {
                     return Ok(());
                 }
 
-                // ツールバーの範囲をクリックされたとき、InputModeをEditingに変更する
+                // アドレスバーの範囲をクリックされたとき、InputModeをEditingに変更する
                 if relative_pos.1 < TOOLBAR_HEIGHT + TITLE_BAR_HEIGHT
-                    && relative_pos.1 >= TITLE_BAR_HEIGHT
+                    && relative_pos.1 >= TITLE_BAR_HEIGHT + TAB_HEIGHT
                 {
                     self.clear_address_bar()?;
                     self.input_url = String::new();
@@ -174,12 +309,35 @@ impl WasabiUI {
                     relative_pos.1 - TITLE_BAR_HEIGHT - TOOLBAR_HEIGHT,
                 );
                 let page = self.browser.borrow().current_page();
+
+                // 選択範囲を押した位置に折りたたむ。同じ位置を続けて押したときは
+                // ダブルクリックとして単語を選択する
+                let now = current_time_ms();
+                let is_double_click = self.last_press.is_some_and(|(time, pos)| {
+                    now.saturating_sub(time) <= DOUBLE_CLICK_INTERVAL_MS
+                        && pos == position_in_content_area
+                });
+                self.last_press = Some((now, position_in_content_area));
+                if is_double_click {
+                    page.borrow_mut().double_clicked(position_in_content_area);
+                } else {
+                    page.borrow_mut().mouse_pressed(position_in_content_area);
+                }
+
                 let next_destination = page.borrow_mut().clicked(position_in_content_area);
 
                 if let Some(url) = next_destination {
                     self.input_url = url.clone();
                     self.update_address_bar()?;
                     self.start_navigation(handle_url, url)?;
+                } else {
+                    if page.borrow().is_loading() {
+                        // <iframe>の中のリンクがクリックされたので、入れ子の文書を取得する
+                        self.load_subresources(handle_url)?;
+                    }
+                    // イベントのリスナによって表示が変わった可能性があるので描画し直す
+                    self.clear_content_area()?;
+                    self.update_ui()?;
                 }
             }
         }
@@ -187,23 +345,81 @@ impl WasabiUI {
         Ok(())
     }
 
+    /// 予約されたナビゲーションの時刻になっていれば、ナビゲーションを始める
+    fn handle_refresh_timer(
+        &mut self,
+        handle_url: fn(String) -> Result<HttpResponse, Error>,
+    ) -> Result<(), Error> {
+        let is_expired = match &self.refresh_timer {
+            Some(timer) => current_time_ms() >= timer.deadline,
+            None => false,
+        };
+        if !is_expired {
+            return Ok(());
+        }
+
+        if let Some(timer) = self.refresh_timer.take() {
+            self.input_url = timer.url.clone();
+            self.update_address_bar()?;
+            self.start_navigation(handle_url, timer.url)?;
+        }
+
+        Ok(())
+    }
+
     fn handle_key_input(
         &mut self,
         handle_url: fn(String) -> Result<HttpResponse, Error>,
     ) -> Result<(), Error> {
         match self.input_mode {
             InputMode::Normal => {
-                // InputModeがNormalのとき、キー入力を無視する
-                let _ = Api::read_key();
+                // InputModeがNormalのとき、キー入力はページに送る。noliのキー入力は
+                // 修飾キーの状態を持たず、Shift+Tabを区別できないので、代わりに
+                // Ctrl+PをShift+Tabとして扱い、前の要素にフォーカスを移す
+                if let Some(c) = Api::read_key() {
+                    let page = self.browser.borrow().current_page();
+                    if c == 0x03 as char {
+                        // Ctrl+Cが押されたので、選択されているテキストをコピーする
+                        self.clipboard = page.borrow().selected_text();
+                        return Ok(());
+                    }
+                    let result = if c == 0x10 as char {
+                        page.borrow_mut().key_pressed('\t', /*shift=*/ true)
+                    } else {
+                        page.borrow_mut().key_pressed(c, /*shift=*/ false)
+                    };
+                    match result {
+                        KeyPressResult::Unchanged => {}
+                        KeyPressResult::Repaint => {
+                            self.clear_content_area()?;
+                            self.update_ui()?;
+                        }
+                        KeyPressResult::Navigate(url) => {
+                            self.input_url = url.clone();
+                            self.update_address_bar()?;
+                            self.start_navigation(handle_url, url)?;
+                        }
+                    }
+                }
             }
             InputMode::Editing => {
                 if let Some(c) = Api::read_key() {
                     if c == 0x0A as char {
-                        // Enterキーが押されたので、ナビゲーションを開始する
-                        self.start_navigation(handle_url, self.input_url.clone())?;
+                        // Enterキーが押されたので、ナビゲーションを開始する。
+                        // "userstyle:"で始まる場合は、続くURLのスタイルシートを
+                        // ユーザーのスタイルシートとして読み込む
+                        let input = self.input_url.clone();
+                        match input.strip_prefix(USER_STYLE_SHEET_PREFIX) {
+                            Some(url) => self.load_user_style_sheet(handle_url, url.to_string())?,
+                            None => self.start_navigation(handle_url, input)?,
+                        }
 
                         self.input_url = String::new();
                         self.input_mode = InputMode::Normal;
+                    } else if c == 0x16 as char {
+                        // Ctrl+Vが押されたので、コピーしたテキストを貼り付ける
+                        self.input_url.push_str(&self.clipboard);
+                        self.update_address_bar()?;
                     } else if c == 0x7F as char || c == 0x08 as char {
                         // デリートキーまたはバックスペースキーが押されたので、
                         // 最後の文字を削除する
@@ -224,7 +440,13 @@ impl WasabiUI {
         // アドレスバーを白く塗りつぶす
         if self
             .window
-            .fill_rect(WHITE, 72, 4, WINDOW_WIDTH - 76, ADDRESSBAR_HEIGHT - 2)
+            .fill_rect(
+                WHITE,
+                72,
+                TAB_HEIGHT + 4,
+                WINDOW_WIDTH - 76,
+                ADDRESSBAR_HEIGHT - 2,
+            )
             .is_err()
         {
             return Err(Error::InvalidUI(
@@ -238,7 +460,7 @@ impl WasabiUI {
             .draw_string(
                 BLACK,
                 74,
-                6,
+                TAB_HEIGHT + 6,
                 &self.input_url,
                 StringSize::Medium,
                 /*underline=*/ false,
@@ -264,11 +486,74 @@ impl WasabiUI {
         Ok(())
     }
 
+    fn update_tab(&mut self) -> Result<(), Error> {
+        let page = self.browser.borrow().current_page();
+        // 読み込みが終わるまでは、タイトルの代わりに読み込み中であることを表示する
+        let title = if page.borrow().ready_state() == DocumentReadyState::Complete {
+            page.borrow().title()
+        } else {
+            "Loading...".to_string()
+        };
+
+        // タブを白く塗りつぶす
+        if self
+            .window
+            .fill_rect(WHITE, 2, 2, TAB_WIDTH, TAB_HEIGHT - 2)
+            .is_err()
+        {
+            return Err(Error::InvalidUI("failed to clear a tab".to_string()));
+        }
+
+        // タブの幅に収まらないタイトルは、末尾を省略する
+        let max_chars = ((TAB_WIDTH - 8) / CHAR_WIDTH) as usize;
+        let text = if title.chars().count() > max_chars {
+            let mut s: String = title.chars().take(max_chars - 3).collect();
+            s.push_str("...");
+            s
+        } else {
+            title
+        };
+
+        if self
+            .window
+            .draw_string(
+                BLACK,
+                6,
+                4,
+                &text,
+                StringSize::Medium,
+                /*underline=*/ false,
+            )
+            .is_err()
+        {
+            return Err(Error::InvalidUI("failed to update a tab".to_string()));
+        }
+
+        // タブの部分の画面を更新する
+        self.window.flush_area(
+            Rect::new(
+                WINDOW_INIT_X_POS,
+                WINDOW_INIT_Y_POS + TITLE_BAR_HEIGHT,
+                WINDOW_WIDTH,
+                TAB_HEIGHT,
+            )
+            .expect("failed to create a rect for the tab"),
+        );
+
+        Ok(())
+    }
+
     fn clear_address_bar(&mut self) -> Result<(), Error> {
         // アドレスバーを白く塗りつぶす
         if self
             .window
-            .fill_rect(WHITE, 72, 4, WINDOW_WIDTH - 76, ADDRESSBAR_HEIGHT - 2)
+            .fill_rect(
+                WHITE,
+                72,
+                TAB_HEIGHT + 4,
+                WINDOW_WIDTH - 76,
+                ADDRESSBAR_HEIGHT - 2,
+            )
             .is_err()
         {
             return Err(Error::InvalidUI(
@@ -290,17 +575,54 @@ impl WasabiUI {
         Ok(())
     }
 
+    /// `url`のスタイルシートを取得してユーザーのスタイルシートにし、表示中の
+    /// ページを描画し直す。取得できなかった場合は何もしない
+    fn load_user_style_sheet(
+        &mut self,
+        handle_url: fn(String) -> Result<HttpResponse, Error>,
+        url: String,
+    ) -> Result<(), Error> {
+        let response = match handle_url(url) {
+            Ok(response) if (200..300).contains(&response.status_code()) => response,
+            _ => return Ok(()),
+        };
+        self.browser
+            .borrow_mut()
+            .set_user_style_sheet(response.body());
+
+        let page = self.browser.borrow().current_page();
+        page.borrow_mut().restyle();
+        self.clear_content_area()?;
+        self.update_ui()
+    }
+
     fn start_navigation(
         &mut self,
         handle_url: fn(String) -> Result<HttpResponse, Error>,
         destination: String,
     ) -> Result<(), Error> {
+        // 別のページへ移動するときは、予約されたナビゲーションを取り消す
+        self.refresh_timer = None;
+
         self.clear_content_area()?;
 
-        match handle_url(destination) {
+        match handle_url(destination.clone()) {
             Ok(response) => {
                 let page = self.browser.borrow().current_page();
-                page.borrow_mut().receive_response(response);
+                page.borrow_mut().receive_response(destination, response);
+
+                self.update_tab()?;
+                self.load_subresources(handle_url)?;
+
+                // ページの読み込みが終わってから、予約されたナビゲーションの
+                // 時間を計り始める
+                if let Some(refresh) = page.borrow().refresh() {
+                    self.refresh_timer = Some(RefreshTimer {
+                        deadline: current_time_ms()
+                            .saturating_add(refresh.delay().saturating_mul(1000)),
+                        url: refresh.url(),
+                    });
+                }
             }
             Err(e) => {
                 return Err(e);
@@ -312,6 +634,39 @@ impl WasabiUI {
         Ok(())
     }
 
+    /// ページが必要とするスタイルシートやスクリプト、画像、入れ子の文書を取得する。
+    /// パーサをブロックするスクリプトを取得すると、パースが再開して
+    /// 新しいサブリソースが追加されることがある。
+    /// `STYLE_SHEET_TIMEOUT_MS`を過ぎてもスタイルシートが揃わない場合は、待つのを
+    /// やめて揃っているものだけで描画する。`handle_url`は同期的に取得するので、
+    /// 時間は取得と取得の間でしか確かめられず、1つの取得が止まった場合は中断できない
+    fn load_subresources(
+        &mut self,
+        handle_url: fn(String) -> Result<HttpResponse, Error>,
+    ) -> Result<(), Error> {
+        let page = self.browser.borrow().current_page();
+        let deadline = current_time_ms().saturating_add(STYLE_SHEET_TIMEOUT_MS);
+        let mut is_timed_out = false;
+        loop {
+            if !is_timed_out && current_time_ms() >= deadline {
+                is_timed_out = true;
+                page.borrow_mut().stop_waiting_for_style_sheets();
+                self.clear_content_area()?;
+                self.update_ui()?;
+            }
+
+            let next = page.borrow_mut().next_subresource_request();
+            let request = match next {
+                Some(request) => request,
+                None => break,
+            };
+            let response = handle_url(request.url());
+            page.borrow_mut().receive_subresource(request, response);
+        }
+
+        Ok(())
+    }
+
     fn clear_content_area(&mut self) -> Result<(), Error> {
         // コンテンツエリアを白く塗りつぶす
         if self
@@ -335,7 +690,83 @@ impl WasabiUI {
         Ok(())
     }
 
+    /// フォーカスされている要素の周りに、1ピクセルの幅の枠線を描く
+    fn draw_focus_ring(&mut self, point: LayoutPoint, size: LayoutSize) -> Result<(), Error> {
+        let x = point.x() + WINDOW_PADDING;
+        let y = point.y() + WINDOW_PADDING + TOOLBAR_HEIGHT;
+        let width = size.width();
+        let height = size.height();
+
+        for (x, y, w, h) in [
+            (x, y, width, 1),
+            (x, y + height - 1, width, 1),
+            (x, y, 1, height),
+            (x + width - 1, y, 1, height),
+        ] {
+            if self.window.fill_rect(BLUE, x, y, w, h).is_err() {
+                return Err(Error::InvalidUI("failed to draw a focus ring".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 画像を`size`の大きさに拡大・縮小して描画する。同じ色が横に並ぶピクセルは
+    /// まとめて1つの矩形として描画する。半分以上透明なピクセルは描画しない
+    fn draw_image(
+        &mut self,
+        image: &Image,
+        point: LayoutPoint,
+        size: LayoutSize,
+    ) -> Result<(), Error> {
+        if image.width() == 0 || image.height() == 0 {
+            return Ok(());
+        }
+
+        let x0 = point.x() + WINDOW_PADDING;
+        let y0 = point.y() + WINDOW_PADDING + TOOLBAR_HEIGHT;
+        let width = size.width();
+
+        // コンテンツ領域からはみ出す部分は描画しない
+        let x_begin = (WINDOW_PADDING - x0).max(0);
+        let x_end = (WINDOW_PADDING + CONTENT_AREA_WIDTH - x0).min(width);
+        let y_begin = (WINDOW_PADDING + TOOLBAR_HEIGHT - y0).max(0);
+        let y_end = (WINDOW_PADDING + TOOLBAR_HEIGHT + CONTENT_AREA_HEIGHT - y0).min(size.height());
+
+        for y in y_begin..y_end {
+            // 最近傍のピクセルを使う
+            let image_y = (y * image.height() as i64 / size.height()) as usize;
+            let mut x = x_begin;
+            while x < x_end {
+                let pixel = image.pixel((x * image.width() as i64 / width) as usize, image_y);
+                let mut run = 1;
+                while x + run < x_end
+                    && image.pixel(((x + run) * image.width() as i64 / width) as usize, image_y)
+                        == pixel
+                {
+                    run += 1;
+                }
+
+                if pixel >> 24 >= 0x80
+                    && self
+                        .window
+                        .fill_rect(pixel & 0x00FF_FFFF, x0 + x, y0 + y, run, 1)
+                        .is_err()
+                {
+                    return Err(Error::InvalidUI("failed to draw an image".to_string()));
+                }
+                x += run;
+            }
+        }
+
+        Ok(())
+    }
+
     fn update_ui(&mut self) -> Result<(), Error> {
+        // スクリプトがdocument.titleを変更している場合があるので、描画のたびに
+        // タブのタイトルも描き直す
+        self.update_tab()?;
+
         let display_items = self
             .browser
             .borrow()
@@ -384,6 +815,38 @@ impl WasabiUI {
                         return Err(Error::InvalidUI("failed to draw a rectangular".to_string()));
                     }
                 }
+                DisplayItem::Image {
+                    image,
+                    style: _,
+                    layout_point,
+                    layout_size,
+                } => {
+                    self.draw_image(&image, layout_point, layout_size)?;
+                }
+                DisplayItem::FocusRing {
+                    layout_point,
+                    layout_size,
+                } => {
+                    self.draw_focus_ring(layout_point, layout_size)?;
+                }
+                DisplayItem::Highlight {
+                    layout_point,
+                    layout_size,
+                } => {
+                    if self
+                        .window
+                        .fill_rect(
+                            LIGHTBLUE,
+                            layout_point.x() + WINDOW_PADDING,
+                            layout_point.y() + WINDOW_PADDING + TOOLBAR_HEIGHT,
+                            layout_size.width(),
+                            layout_size.height(),
+                        )
+                        .is_err()
+                    {
+                        return Err(Error::InvalidUI("failed to draw a selection".to_string()));
+                    }
+                }
             }
         }
 